@@ -42,13 +42,38 @@ pub trait Actions {
     fn webidl_function_kind_constructor_default_new_target(
         &mut self,
     ) -> Self::WebidlFunctionKindConstructor;
+    fn webidl_function_kind_constructor_new_target(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+    ) -> Self::WebidlFunctionKindConstructor;
 
     type WebidlFunctionParams;
     fn webidl_function_params(
         &mut self,
-        tys: Vec<Self::WebidlTypeRef>,
+        required: Vec<Self::WebidlTypeRef>,
+        optional: Vec<Self::WebidlFunctionParamOptional>,
+        variadic: Option<Self::WebidlFunctionParamVariadic>,
     ) -> Self::WebidlFunctionParams;
 
+    type WebidlFunctionParamOptional;
+    fn webidl_function_param_optional(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+        default: Option<Self::WebidlDefaultValue>,
+    ) -> Self::WebidlFunctionParamOptional;
+
+    type WebidlFunctionParamVariadic;
+    fn webidl_function_param_variadic(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+    ) -> Self::WebidlFunctionParamVariadic;
+
+    type WebidlDefaultValue;
+    fn webidl_default_value_null(&mut self) -> Self::WebidlDefaultValue;
+    fn webidl_default_value_boolean(&mut self, value: bool) -> Self::WebidlDefaultValue;
+    fn webidl_default_value_integer(&mut self, value: i64) -> Self::WebidlDefaultValue;
+    fn webidl_default_value_string(&mut self, value: &str) -> Self::WebidlDefaultValue;
+
     type WebidlFunctionResult;
     fn webidl_function_result(&mut self, ty: Self::WebidlTypeRef) -> Self::WebidlFunctionResult;
 
@@ -131,7 +156,11 @@ pub trait Actions {
         + From<Self::OutgoingBindingExpressionView>
         + From<Self::OutgoingBindingExpressionCopy>
         + From<Self::OutgoingBindingExpressionDict>
-        + From<Self::OutgoingBindingExpressionBindExport>;
+        + From<Self::OutgoingBindingExpressionBindExport>
+        + From<Self::OutgoingBindingExpressionUndefined>
+        + From<Self::OutgoingBindingExpressionVariadic>
+        + From<Self::OutgoingBindingExpressionUtf16Str>
+        + From<Self::OutgoingBindingExpressionLatin1Str>;
 
     type OutgoingBindingExpressionAs;
     fn outgoing_binding_expression_as(
@@ -168,7 +197,8 @@ pub trait Actions {
         ty: Self::WebidlTypeRef,
         offset: u32,
         length: u32,
-    ) -> Self::OutgoingBindingExpressionView;
+        unit: Option<Self::BufferLengthUnit>,
+    ) -> Option<Self::OutgoingBindingExpressionView>;
 
     type OutgoingBindingExpressionCopy;
     fn outgoing_binding_expression_copy(
@@ -176,7 +206,12 @@ pub trait Actions {
         ty: Self::WebidlTypeRef,
         offset: u32,
         length: u32,
-    ) -> Self::OutgoingBindingExpressionCopy;
+        unit: Option<Self::BufferLengthUnit>,
+    ) -> Option<Self::OutgoingBindingExpressionCopy>;
+
+    type BufferLengthUnit;
+    fn buffer_length_unit_elements(&mut self) -> Self::BufferLengthUnit;
+    fn buffer_length_unit_bytes(&mut self) -> Self::BufferLengthUnit;
 
     type OutgoingBindingExpressionDict;
     fn outgoing_binding_expression_dict(
@@ -193,13 +228,47 @@ pub trait Actions {
         idx: u32,
     ) -> Self::OutgoingBindingExpressionBindExport;
 
+    type OutgoingBindingExpressionUndefined;
+    fn outgoing_binding_expression_undefined(&mut self)
+        -> Self::OutgoingBindingExpressionUndefined;
+
+    type OutgoingBindingExpressionVariadic;
+    fn outgoing_binding_expression_variadic(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+        elem_ty: Self::WasmValType,
+        offset: u32,
+        length: u32,
+    ) -> Self::OutgoingBindingExpressionVariadic;
+
+    type OutgoingBindingExpressionUtf16Str;
+    fn outgoing_binding_expression_utf16_str(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> Self::OutgoingBindingExpressionUtf16Str;
+
+    type OutgoingBindingExpressionLatin1Str;
+    fn outgoing_binding_expression_latin1_str(
+        &mut self,
+        ty: Self::WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> Self::OutgoingBindingExpressionLatin1Str;
+
     type IncomingBindingExpression: From<Self::IncomingBindingExpressionGet>
         + From<Self::IncomingBindingExpressionAs>
         + From<Self::IncomingBindingExpressionAllocUtf8Str>
         + From<Self::IncomingBindingExpressionAllocCopy>
         + From<Self::IncomingBindingExpressionEnumToI32>
         + From<Self::IncomingBindingExpressionField>
-        + From<Self::IncomingBindingExpressionBindImport>;
+        + From<Self::IncomingBindingExpressionBindImport>
+        + From<Self::IncomingBindingExpressionAllocUtf16Str>
+        + From<Self::IncomingBindingExpressionAllocLatin1Str>
+        + From<Self::IncomingBindingExpressionWriteUtf8Str>
+        + From<Self::IncomingBindingExpressionWriteUtf16Str>
+        + From<Self::IncomingBindingExpressionWriteCopy>;
 
     type IncomingBindingExpressionGet;
     fn incoming_binding_expression_get(&mut self, idx: u32) -> Self::IncomingBindingExpressionGet;
@@ -247,9 +316,48 @@ pub trait Actions {
         expr: Self::IncomingBindingExpression,
     ) -> Self::IncomingBindingExpressionBindImport;
 
+    type IncomingBindingExpressionAllocUtf16Str;
+    fn incoming_binding_expression_alloc_utf16_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: Self::IncomingBindingExpression,
+    ) -> Self::IncomingBindingExpressionAllocUtf16Str;
+
+    type IncomingBindingExpressionAllocLatin1Str;
+    fn incoming_binding_expression_alloc_latin1_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: Self::IncomingBindingExpression,
+    ) -> Self::IncomingBindingExpressionAllocLatin1Str;
+
+    type IncomingBindingExpressionWriteUtf8Str;
+    fn incoming_binding_expression_write_utf8_str(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: Self::IncomingBindingExpression,
+    ) -> Self::IncomingBindingExpressionWriteUtf8Str;
+
+    type IncomingBindingExpressionWriteUtf16Str;
+    fn incoming_binding_expression_write_utf16_str(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: Self::IncomingBindingExpression,
+    ) -> Self::IncomingBindingExpressionWriteUtf16Str;
+
+    type IncomingBindingExpressionWriteCopy;
+    fn incoming_binding_expression_write_copy(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: Self::IncomingBindingExpression,
+    ) -> Self::IncomingBindingExpressionWriteCopy;
+
     type WebidlTypeRef: From<Self::WebidlTypeRefNamed>
         + From<Self::WebidlTypeRefIndexed>
-        + From<Self::WebidlScalarType>;
+        + From<Self::WebidlScalarType>
+        + From<Self::WebidlTypeRefAnnotated>;
 
     type WebidlTypeRefNamed;
     fn webidl_type_ref_named(&mut self, name: &str) -> Option<Self::WebidlTypeRefNamed>;
@@ -257,6 +365,21 @@ pub trait Actions {
     type WebidlTypeRefIndexed;
     fn webidl_type_ref_indexed(&mut self, idx: u32) -> Option<Self::WebidlTypeRefIndexed>;
 
+    type WebidlTypeRefAnnotated;
+    fn webidl_type_ref_annotated(
+        &mut self,
+        attributes: Vec<Self::WebidlExtendedAttribute>,
+        ty: Self::WebidlScalarType,
+    ) -> Option<Self::WebidlTypeRefAnnotated>;
+
+    type WebidlExtendedAttribute;
+    fn webidl_extended_attribute_clamp(&mut self) -> Self::WebidlExtendedAttribute;
+    fn webidl_extended_attribute_enforce_range(&mut self) -> Self::WebidlExtendedAttribute;
+    fn webidl_extended_attribute_allow_shared(&mut self) -> Self::WebidlExtendedAttribute;
+    fn webidl_extended_attribute_legacy_null_to_empty_string(
+        &mut self,
+    ) -> Self::WebidlExtendedAttribute;
+
     type WebidlScalarType;
     fn webidl_scalar_type_any(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_boolean(&mut self) -> Self::WebidlScalarType;
@@ -268,6 +391,7 @@ pub trait Actions {
     fn webidl_scalar_type_unsigned_short(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_long_long(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_unsigned_long_long(&mut self) -> Self::WebidlScalarType;
+    fn webidl_scalar_type_bigint(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_float(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_unrestricted_float(&mut self) -> Self::WebidlScalarType;
     fn webidl_scalar_type_double(&mut self) -> Self::WebidlScalarType;
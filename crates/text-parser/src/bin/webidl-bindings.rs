@@ -0,0 +1,82 @@
+//! Command line tools for the Web IDL bindings text format.
+//!
+//! ```text
+//! webidl-bindings fmt [--check] [FILE...]
+//! ```
+//!
+//! `fmt` formats the given files in place, or its standard input to its
+//! standard output if no files are given. With `--check`, nothing is written,
+//! and it exits with status 1 if any input is not already formatted.
+
+use anyhow::{bail, Context, Result};
+use std::io::{self, Read, Write};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: webidl-bindings fmt [--check] [FILE...]";
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            process::exit(2);
+        }
+    }
+}
+
+/// Run the command, returning whether every input was already formatted when
+/// checking.
+fn run() -> Result<bool> {
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("fmt") => {}
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return Ok(true);
+        }
+        _ => bail!("{}", USAGE),
+    }
+
+    let mut check = false;
+    let mut files = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(true);
+            }
+            _ if arg.starts_with('-') => bail!("unknown option `{}`\n{}", arg, USAGE),
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        let output = wasm_webidl_bindings_text_parser::format(&input)?;
+        if check {
+            return Ok(input == output);
+        }
+        io::stdout().write_all(output.as_bytes())?;
+        return Ok(true);
+    }
+
+    let mut formatted = true;
+    for file in &files {
+        let input = fs::read_to_string(file).with_context(|| format!("failed to read {}", file))?;
+        let output = wasm_webidl_bindings_text_parser::format(&input)
+            .with_context(|| format!("failed to format {}", file))?;
+        if input == output {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            formatted = false;
+        } else {
+            fs::write(file, output).with_context(|| format!("failed to write {}", file))?;
+        }
+    }
+    Ok(formatted)
+}
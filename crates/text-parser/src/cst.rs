@@ -0,0 +1,352 @@
+//! A lossless concrete syntax tree for the text format.
+//!
+//! The parser skips whitespace and comments, but the concrete syntax tree
+//! keeps them, attached to the tokens around them, so that printing the tree
+//! gives back its input byte for byte. It only knows about tokens and how
+//! parentheses and brackets nest, not the rest of the grammar.
+
+use crate::lexer::LexerBuilder;
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::fmt;
+use std::ops::Range;
+
+/// Whitespace or a comment between two tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trivia<'a> {
+    Whitespace(&'a str),
+    /// A `;; ...` comment, up to but not including the end of the line.
+    LineComment(&'a str),
+    /// A `; ... ;` comment.
+    BlockComment(&'a str),
+}
+
+impl<'a> Trivia<'a> {
+    /// The source text of this trivia.
+    pub fn text(&self) -> &'a str {
+        match *self {
+            Trivia::Whitespace(s) | Trivia::LineComment(s) | Trivia::BlockComment(s) => s,
+        }
+    }
+
+    /// Is this trivia a comment?
+    pub fn is_comment(&self) -> bool {
+        match self {
+            Trivia::Whitespace(_) => false,
+            Trivia::LineComment(_) | Trivia::BlockComment(_) => true,
+        }
+    }
+}
+
+/// A token, with the trivia around it.
+///
+/// The trivia after a token that is on the same line as it is the token's
+/// trailing trivia. Everything else between two tokens is the leading trivia
+/// of the second token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub leading: Vec<Trivia<'a>>,
+    pub text: &'a str,
+    pub trailing: Vec<Trivia<'a>>,
+    /// Where the token's text is in the input.
+    pub span: Range<usize>,
+}
+
+impl Token<'_> {
+    /// Does this token have comments before or after it?
+    pub fn has_comments(&self) -> bool {
+        self.leading
+            .iter()
+            .chain(&self.trailing)
+            .any(Trivia::is_comment)
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for t in &self.leading {
+            f.write_str(t.text())?;
+        }
+        f.write_str(self.text)?;
+        for t in &self.trailing {
+            f.write_str(t.text())?;
+        }
+        Ok(())
+    }
+}
+
+/// A node in the concrete syntax tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node<'a> {
+    /// Any token other than a parenthesis or bracket.
+    Atom(Token<'a>),
+    /// A parenthesized or bracketed list of nodes.
+    Group {
+        open: Token<'a>,
+        children: Vec<Node<'a>>,
+        close: Token<'a>,
+    },
+}
+
+impl<'a> Node<'a> {
+    /// The first token of this node.
+    pub fn first_token(&self) -> &Token<'a> {
+        match self {
+            Node::Atom(t) | Node::Group { open: t, .. } => t,
+        }
+    }
+
+    /// Does this node have comments anywhere in it?
+    pub fn has_comments(&self) -> bool {
+        match self {
+            Node::Atom(t) => t.has_comments(),
+            Node::Group {
+                open,
+                children,
+                close,
+            } => {
+                open.has_comments()
+                    || close.has_comments()
+                    || children.iter().any(|c| c.has_comments())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Atom(t) => t.fmt(f),
+            Node::Group {
+                open,
+                children,
+                close,
+            } => {
+                open.fmt(f)?;
+                for c in children {
+                    c.fmt(f)?;
+                }
+                close.fmt(f)
+            }
+        }
+    }
+}
+
+/// The concrete syntax tree of a whole text format input.
+///
+/// Its `Display` implementation prints the input it was parsed from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cst<'a> {
+    pub nodes: Vec<Node<'a>>,
+    /// The trivia after the last token's trailing trivia.
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for n in &self.nodes {
+            n.fmt(f)?;
+        }
+        for t in &self.trailing {
+            f.write_str(t.text())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the given straw proposal text format input into a lossless concrete
+/// syntax tree.
+///
+/// Fails if the input does not lex, or if its parentheses and brackets do not
+/// match up.
+pub fn parse_cst(input: &str) -> Result<Cst<'_>> {
+    let trivia_regex = Regex::new(r#"^(?:(\p{White_Space}+)|(;[^;]+;)|(;;[^\n]+))"#).unwrap();
+    let split = |s, start| split_trivia(&trivia_regex, s, start);
+
+    let lexer_builder = LexerBuilder::new();
+    let mut tokens: Vec<Token> = vec![];
+    let mut end = 0;
+    for result in lexer_builder.lexer(input) {
+        let (start, _, token_end) = result.map_err(|e| anyhow::anyhow!("{}", e))?;
+        let mut trivia = split(&input[end..start], end)?;
+        if let Some(prev) = tokens.last_mut() {
+            let rest = trivia.split_off(same_line(&trivia));
+            prev.trailing = trivia;
+            trivia = rest;
+        }
+        tokens.push(Token {
+            leading: trivia,
+            text: &input[start..token_end],
+            trailing: vec![],
+            span: start..token_end,
+        });
+        end = token_end;
+    }
+    let mut trailing = split(&input[end..], end)?;
+    if let Some(last) = tokens.last_mut() {
+        let rest = trailing.split_off(same_line(&trailing));
+        last.trailing = trailing;
+        trailing = rest;
+    }
+
+    let mut stack: Vec<(Token, Vec<Node>)> = vec![];
+    let mut nodes = vec![];
+    for token in tokens {
+        match token.text {
+            "(" | "[" => stack.push((token, vec![])),
+            ")" | "]" => {
+                let (open, children) = match stack.pop() {
+                    Some(group) => group,
+                    None => bail!("unmatched `{}` at {}", token.text, token.span.start),
+                };
+                match (open.text, token.text) {
+                    ("(", ")") | ("[", "]") => {}
+                    _ => bail!(
+                        "`{}` at {} does not match `{}` at {}",
+                        token.text,
+                        token.span.start,
+                        open.text,
+                        open.span.start
+                    ),
+                }
+                let group = Node::Group {
+                    open,
+                    children,
+                    close: token,
+                };
+                match stack.last_mut() {
+                    Some((_, siblings)) => siblings.push(group),
+                    None => nodes.push(group),
+                }
+            }
+            _ => match stack.last_mut() {
+                Some((_, siblings)) => siblings.push(Node::Atom(token)),
+                None => nodes.push(Node::Atom(token)),
+            },
+        }
+    }
+    if let Some((open, _)) = stack.pop() {
+        bail!("unclosed `{}` at {}", open.text, open.span.start);
+    }
+
+    Ok(Cst { nodes, trailing })
+}
+
+/// Split the text between two tokens, which starts at `start` in the input,
+/// into its pieces of trivia.
+fn split_trivia<'a>(regex: &Regex, mut s: &'a str, start: usize) -> Result<Vec<Trivia<'a>>> {
+    let len = s.len();
+    let mut trivia = vec![];
+    while !s.is_empty() {
+        let caps = match regex.captures(s) {
+            Some(caps) => caps,
+            None => bail!("unexpected input at {}", start + len - s.len()),
+        };
+        let t = if let Some(m) = caps.get(1) {
+            Trivia::Whitespace(m.as_str())
+        } else if let Some(m) = caps.get(2) {
+            Trivia::BlockComment(m.as_str())
+        } else {
+            Trivia::LineComment(caps.get(3).unwrap().as_str())
+        };
+        s = &s[t.text().len()..];
+        trivia.push(t);
+    }
+    Ok(trivia)
+}
+
+/// The number of leading pieces of trivia that are on the same line as the
+/// token before them.
+fn same_line(trivia: &[Trivia]) -> usize {
+    trivia
+        .iter()
+        .position(|t| match t {
+            Trivia::Whitespace(s) => s.contains('\n'),
+            _ => false,
+        })
+        .unwrap_or(trivia.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#";; The signature of `encodeInto`.
+type $Result
+  (; a dictionary ; dict
+    (field "read" unsigned long long) ;; the bytes read
+    (field "written" unsigned long long))
+
+func-binding $b import 0 $Result
+  (param (as [Clamp, EnforceRange] long 0))
+  (result)
+
+bind 0 $b ;; done
+;; the end
+"#;
+
+    #[test]
+    fn lossless() {
+        let cst = parse_cst(INPUT).unwrap();
+        assert_eq!(cst.to_string(), INPUT);
+        assert_eq!(parse_cst("").unwrap(), Cst::default());
+        assert_eq!(parse_cst(" ;; x").unwrap().to_string(), " ;; x");
+    }
+
+    #[test]
+    fn trivia_is_attached_to_tokens() {
+        let cst = parse_cst(INPUT).unwrap();
+        assert_eq!(cst.nodes.len(), 13);
+        let ty = cst.nodes[0].first_token();
+        assert_eq!(ty.text, "type");
+        assert_eq!(
+            ty.leading,
+            vec![
+                Trivia::LineComment(";; The signature of `encodeInto`."),
+                Trivia::Whitespace("\n")
+            ]
+        );
+
+        let dict = match &cst.nodes[2] {
+            Node::Group { open, children, .. } => {
+                assert_eq!(
+                    open.trailing,
+                    vec![
+                        Trivia::BlockComment("; a dictionary ;"),
+                        Trivia::Whitespace(" ")
+                    ]
+                );
+                children
+            }
+            n => panic!("not a group: {:?}", n),
+        };
+        match &dict[1] {
+            Node::Group { close, .. } => {
+                assert_eq!(close.trailing[1], Trivia::LineComment(";; the bytes read"));
+            }
+            n => panic!("not a group: {:?}", n),
+        }
+        assert!(!dict[2].has_comments());
+
+        let last = cst.nodes[12].first_token();
+        assert_eq!(last.text, "$b");
+        assert_eq!(last.trailing[1], Trivia::LineComment(";; done"));
+        assert_eq!(
+            cst.trailing,
+            vec![
+                Trivia::Whitespace("\n"),
+                Trivia::LineComment(";; the end"),
+                Trivia::Whitespace("\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced() {
+        let err = |input| parse_cst(input).unwrap_err().to_string();
+        assert_eq!(err("type (func"), "unclosed `(` at 5");
+        assert_eq!(err("bind 0 1)"), "unmatched `)` at 8");
+        assert_eq!(err("(as [Clamp) long"), "`)` at 10 does not match `[` at 4");
+        assert_eq!(err("type %"), "Invalid token at 5: `%…`");
+    }
+}
@@ -0,0 +1,453 @@
+//! Formatting the text format in a canonical style, keeping its comments.
+//!
+//! The canonical style is:
+//!
+//! * Type definitions come first, then function bindings, then binds, each in
+//!   the order they were given. Declarations are separated by a blank line.
+//!
+//! * A declaration that fits in `MAX_WIDTH` columns is on one line. Otherwise
+//!   its groups are each on their own line, indented by `INDENT` spaces, and
+//!   the same goes for the groups inside them.
+//!
+//! * Extended attributes are in the order they are defined in Web IDL, and
+//!   are separated by `, `.
+//!
+//! Comments are kept next to the tokens they were attached to. Comments
+//! before a token are put on their own lines, and a group with any comments
+//! in it is never put on one line.
+
+use crate::cst::{parse_cst, Node, Token, Trivia};
+use anyhow::{bail, Result};
+
+/// The number of columns that lines are kept within, where possible.
+pub const MAX_WIDTH: usize = 80;
+
+/// The number of spaces each nested line is indented by.
+pub const INDENT: usize = 2;
+
+/// The keywords that start a declaration, in canonical order.
+const DECLARATIONS: &[&str] = &["type", "func-binding", "bind"];
+
+/// The extended attributes, in canonical order.
+const EXTENDED_ATTRIBUTES: &[&str] = &[
+    "Clamp",
+    "EnforceRange",
+    "AllowShared",
+    "LegacyNullToEmptyString",
+];
+
+/// Format the given straw proposal text format input in the canonical style.
+///
+/// The input is checked to lex and to have matching parentheses and brackets,
+/// and to be a sequence of `type`, `func-binding` and `bind` declarations, but
+/// is not otherwise checked against the grammar.
+pub fn format(input: &str) -> Result<String> {
+    let mut cst = parse_cst(input)?;
+    sort_extended_attributes(&mut cst.nodes);
+
+    let mut nodes = cst.nodes.into_iter().peekable();
+    let mut declarations = vec![];
+    while let Some(first) = nodes.next() {
+        let kind = match &first {
+            Node::Atom(t) => DECLARATIONS.iter().position(|k| *k == t.text),
+            Node::Group { .. } => None,
+        };
+        let kind = match kind {
+            Some(kind) => kind,
+            None => bail!(
+                "expected `type`, `func-binding` or `bind` at {}, found `{}`",
+                first.first_token().span.start,
+                first.first_token().text
+            ),
+        };
+        let mut declaration = vec![first];
+        while let Some(n) = nodes.peek() {
+            match n {
+                Node::Atom(t) if DECLARATIONS.contains(&t.text) => break,
+                _ => declaration.push(nodes.next().unwrap()),
+            }
+        }
+        declarations.push((kind, declaration));
+    }
+    // NB: the sort is stable, so declarations of the same kind, whose indices
+    // are the order they are declared in, stay in order.
+    declarations.sort_by_key(|(kind, _)| *kind);
+
+    let mut printer = Printer::default();
+    let header = header(&mut declarations);
+    if !header.is_empty() {
+        printer.comments(&header, 0);
+        printer.blank_line();
+    }
+    for (i, (_, declaration)) in declarations.iter().enumerate() {
+        if i > 0 {
+            printer.blank_line();
+        }
+        printer.declaration(declaration);
+    }
+    if cst.trailing.iter().any(Trivia::is_comment) {
+        // Keep a blank line before the comments at the end, if there was one.
+        match cst.trailing.first() {
+            Some(t) if is_blank_line(t) => printer.blank_line(),
+            _ => printer.newline(0),
+        }
+        printer.comments(&cst.trailing, 0);
+    }
+    Ok(printer.finish())
+}
+
+/// Take the comments before the first declaration that are separated from it
+/// by a blank line. They describe the whole input, so stay at the top even
+/// when the first declaration is moved.
+fn header<'a>(declarations: &mut [(usize, Vec<Node<'a>>)]) -> Vec<Trivia<'a>> {
+    let token = match declarations
+        .iter_mut()
+        .min_by_key(|(_, d)| d[0].first_token().span.start)
+    {
+        Some((_, declaration)) => match &mut declaration[0] {
+            Node::Atom(t) => t,
+            Node::Group { .. } => unreachable!(),
+        },
+        None => return vec![],
+    };
+    let split = token
+        .leading
+        .iter()
+        .enumerate()
+        .skip_while(|(_, t)| !t.is_comment())
+        .filter(|(_, t)| is_blank_line(t))
+        .map(|(i, _)| i)
+        .last();
+    match split {
+        Some(i) => {
+            let rest = token.leading.split_off(i);
+            std::mem::replace(&mut token.leading, rest)
+        }
+        None => vec![],
+    }
+}
+
+fn is_blank_line(t: &Trivia) -> bool {
+    match t {
+        Trivia::Whitespace(s) => s.matches('\n').count() > 1,
+        _ => false,
+    }
+}
+
+/// Sort the extended attributes in every bracketed group without comments.
+fn sort_extended_attributes(nodes: &mut [Node]) {
+    for n in nodes {
+        if let Node::Group { open, children, .. } = n {
+            sort_extended_attributes(children);
+            if open.text != "[" || n.has_comments() {
+                continue;
+            }
+            if let Node::Group { children, .. } = n {
+                let rank = |n: &Node| {
+                    let text = n.first_token().text;
+                    EXTENDED_ATTRIBUTES
+                        .iter()
+                        .position(|a| *a == text)
+                        .unwrap_or(EXTENDED_ATTRIBUTES.len())
+                };
+                let is_list = children
+                    .iter()
+                    .enumerate()
+                    .all(|(i, c)| (c.first_token().text == ",") == (i % 2 == 1));
+                if !is_list {
+                    continue;
+                }
+                let mut attributes: Vec<_> = children.iter().step_by(2).cloned().collect();
+                attributes.sort_by_key(rank);
+                for (child, attribute) in children.iter_mut().step_by(2).zip(attributes) {
+                    *child = attribute;
+                }
+            }
+        }
+    }
+}
+
+/// Should there be a space between these two tokens on the same line?
+fn spaced(before: &str, after: &str) -> bool {
+    !(before == "("
+        || before == "["
+        || before.ends_with('=')
+        || after == ")"
+        || after == "]"
+        || after == ",")
+}
+
+/// The width of the given node on one line.
+fn flat_width(node: &Node) -> usize {
+    match node {
+        Node::Atom(t) => t.text.len(),
+        Node::Group { children, .. } => {
+            let mut width = 2;
+            let mut before = "(";
+            for c in children {
+                let after = c.first_token().text;
+                width += flat_width(c) + spaced(before, after) as usize;
+                before = last_text(c);
+            }
+            width
+        }
+    }
+}
+
+fn last_text<'a>(node: &Node<'a>) -> &'a str {
+    match node {
+        Node::Atom(t) | Node::Group { close: t, .. } => t.text,
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    /// The indentation to write before the next text on the current line, if
+    /// nothing has been written on it yet.
+    indent: Option<usize>,
+    /// Whether the last thing written was a line comment, which the next
+    /// token cannot follow on the same line.
+    after_line_comment: bool,
+    /// Whether the last thing written was a comment, which the next token
+    /// is spaced apart from.
+    after_comment: bool,
+}
+
+impl Printer {
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.newline(0);
+        }
+        self.out
+    }
+
+    /// The length of the current line.
+    fn line_len(&self) -> usize {
+        self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// The column the next text on the current line will be written at.
+    fn column(&self) -> usize {
+        self.indent.unwrap_or_else(|| self.line_len())
+    }
+
+    fn write(&mut self, s: &str) {
+        if let Some(indent) = self.indent.take() {
+            self.out.push_str(&" ".repeat(indent));
+        }
+        self.out.push_str(s);
+    }
+
+    /// End the current line, unless nothing has been written on it, and
+    /// indent the next one.
+    fn newline(&mut self, indent: usize) {
+        if self.indent.is_none() && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.indent = Some(indent);
+        self.after_line_comment = false;
+        self.after_comment = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.newline(0);
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Write the comments among the given trivia on their own lines, keeping
+    /// single blank lines between them.
+    fn comments(&mut self, trivia: &[Trivia], indent: usize) {
+        let mut after_comment = false;
+        for t in trivia {
+            if t.is_comment() {
+                self.newline(indent);
+                self.write(t.text().trim_end());
+                self.newline(indent);
+                after_comment = true;
+            } else if after_comment && is_blank_line(t) {
+                self.out.push('\n');
+                after_comment = false;
+            }
+        }
+    }
+
+    fn token(&mut self, token: &Token, indent: usize, space: bool) {
+        if token.leading.iter().any(Trivia::is_comment) {
+            self.comments(&token.leading, indent);
+        }
+        if self.after_line_comment {
+            self.newline(indent);
+        }
+        if (space || self.after_comment) && self.indent.is_none() {
+            self.write(" ");
+        }
+        self.write(token.text);
+        self.after_comment = false;
+        for t in token.trailing.iter().filter(|t| t.is_comment()) {
+            self.write(" ");
+            self.write(t.text().trim_end());
+            self.after_comment = true;
+            self.after_line_comment = matches!(t, Trivia::LineComment(_));
+        }
+    }
+
+    fn declaration(&mut self, nodes: &[Node]) {
+        let flat = !nodes.iter().any(|n| n.has_comments())
+            && nodes.iter().map(|n| flat_width(n) + 1).sum::<usize>() <= MAX_WIDTH + 1;
+        let mut before = "";
+        for n in nodes {
+            let mut space = !before.is_empty() && spaced(before, n.first_token().text);
+            let indent = match n {
+                Node::Atom(_) => 0,
+                Node::Group { .. } => INDENT,
+            };
+            if !flat && indent > 0 {
+                self.newline(indent);
+                space = false;
+            }
+            self.node(n, indent, space);
+            before = last_text(n);
+        }
+    }
+
+    /// Write the given node, which starts on the current line and whose
+    /// further lines are indented by `indent`.
+    fn node(&mut self, node: &Node, indent: usize, space: bool) {
+        let (open, children, close) = match node {
+            Node::Atom(t) => return self.token(t, indent, space),
+            Node::Group {
+                open,
+                children,
+                close,
+            } => (open, children, close),
+        };
+        let flat =
+            !node.has_comments() && self.column() + space as usize + flat_width(node) <= MAX_WIDTH;
+        let inner = indent + INDENT;
+
+        self.token(open, indent, space);
+        let mut before = open.text;
+        // The atoms and bracketed groups before the first parenthesized group
+        // stay on the first line.
+        let mut head = true;
+        for c in children {
+            let text = c.first_token().text;
+            let mut space = spaced(before, text);
+            if text == "(" {
+                head = false;
+            }
+            if !flat && !head && text != "," {
+                self.newline(inner);
+                space = false;
+            }
+            self.node(c, inner, space);
+            before = last_text(c);
+        }
+        self.token(close, indent, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        let input = r#"
+bind $encodeInto $encodeIntoBinding
+type $TextEncoderEncodeIntoResult (dict (field "read" unsigned long long) (field "written" unsigned long long))
+func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL (param (as any 0) (as type=any idx=1) (view Uint8Array 2 3)) (result (as i64 (field 0 (get 0))) (as i64 (field 1 (get idx=0))))
+type $EncodeIntoFuncWebIDL (func (method any) (param USVString Uint8Array) (result $TextEncoderEncodeIntoResult))
+  type $Clamped(func(param [ EnforceRange,Clamp ]long))
+"#;
+        let expected = r#"type $TextEncoderEncodeIntoResult
+  (dict (field "read" unsigned long long) (field "written" unsigned long long))
+
+type $EncodeIntoFuncWebIDL
+  (func
+    (method any)
+    (param USVString Uint8Array)
+    (result $TextEncoderEncodeIntoResult))
+
+type $Clamped (func (param [Clamp, EnforceRange] long))
+
+func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL
+  (param (as any 0) (as type=any idx=1) (view Uint8Array 2 3))
+  (result (as i64 (field 0 (get 0))) (as i64 (field 1 (get idx=0))))
+
+bind $encodeInto $encodeIntoBinding
+"#;
+        assert_eq!(format(input).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+        assert_eq!(format("").unwrap(), "");
+    }
+
+    #[test]
+    fn comments() {
+        let input = r#";; A header comment.
+
+;; The signature of `encodeInto`.
+type $TextEncoderEncodeIntoResult
+  (; a dictionary ; dict
+     (field "read" unsigned long long) ;; the bytes read
+     (field "written" unsigned long long))
+bind 0 $b   ;; bind it
+
+;; the binding
+func-binding $b import 0 1 (param [ ; clamp ; Clamp ] long)
+
+  ;; the end
+"#;
+        let expected = r#";; A header comment.
+
+;; The signature of `encodeInto`.
+type $TextEncoderEncodeIntoResult
+  ( ; a dictionary ; dict
+    (field "read" unsigned long long) ;; the bytes read
+    (field "written" unsigned long long))
+
+;; the binding
+func-binding $b import 0 1
+  (param [ ; clamp ; Clamp] long)
+
+bind 0 $b ;; bind it
+
+;; the end
+"#;
+        let formatted = format(input).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), expected);
+        assert_eq!(format(";; just a comment").unwrap(), ";; just a comment\n");
+    }
+
+    #[test]
+    fn long_groups_break() {
+        let input = format!(
+            "type (dict (field \"{}\" (union long DOMString (dict (field \"x\" any)))))",
+            "a".repeat(50)
+        );
+        let expected = format!(
+            "type\n  (dict\n    (field \"{}\"\n      (union long DOMString (dict (field \"x\" any)))))\n",
+            "a".repeat(50)
+        );
+        assert_eq!(format(&input).unwrap(), expected);
+        assert_eq!(format(&expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |input| format(input).unwrap_err().to_string();
+        assert_eq!(
+            err("(type)"),
+            "expected `type`, `func-binding` or `bind` at 0, found `(`"
+        );
+        assert_eq!(
+            err(";; x\n$foo bind"),
+            "expected `type`, `func-binding` or `bind` at 5, found `$foo`"
+        );
+        assert_eq!(err("bind (0"), "unclosed `(` at 5");
+    }
+}
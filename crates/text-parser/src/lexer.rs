@@ -5,39 +5,52 @@ use std::{cmp, fmt};
 pub enum Token<'input> {
     LeftParenthesis,
     RightParenthesis,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    AllowShared,
     ArrayBuffer,
     ByteString,
+    Clamp,
     DOMString,
     DataView,
+    EnforceRange,
     Float32Array,
     Float64Array,
     Int16Array,
     Int32Array,
     Int8Array,
+    LegacyNullToEmptyString,
     USVString,
     Uint16Array,
     Uint32Array,
     Uint8Array,
     Uint8ClampedArray,
     AllocCopy,
+    AllocLatin1Str,
     AllocUtf8CStr,
     AllocUtf8Str,
+    AllocUtf16Str,
     Any,
     Anyref,
     As,
+    BigInt,
     Bind,
     BindExport,
     BindImport,
     Boolean,
     Byte,
+    Bytes,
     Constructor,
     Copy,
     DefaultNewTarget,
     Dict,
     Double,
+    Elements,
     Enum,
     EnumToI32,
     Export,
+    False,
     F32,
     F64,
     Field,
@@ -50,17 +63,23 @@ pub enum Token<'input> {
     I64,
     Index,
     Import,
+    Latin1Str,
     LongLong,
     Long,
     Method,
+    NewTarget,
+    Null,
     Object,
     Octet,
+    Optional,
     Param,
     Result,
     Short,
     Symbol,
     Type,
+    True,
     TypeRef,
+    Undefined,
     Union,
     UnrestrictedDouble,
     UnrestrictedFloat,
@@ -69,9 +88,15 @@ pub enum Token<'input> {
     UnsignedShort,
     Utf8CStr,
     Utf8Str,
+    Utf16Str,
     V128,
+    Variadic,
     View,
+    WriteCopy,
+    WriteUtf16Str,
+    WriteUtf8Str,
     Unsigned(&'input str),
+    Signed(&'input str),
     Identifier(&'input str),
     QuotedString(&'input str),
 }
@@ -93,39 +118,52 @@ impl LexerBuilder {
         let lexemes: &[&str] = &[
             "^\\(",
             "^\\)",
+            "^\\[",
+            "^\\]",
+            "^,",
+            "^AllowShared",
             "^ArrayBuffer",
             "^ByteString",
+            "^Clamp",
             "^DOMString",
             "^DataView",
+            "^EnforceRange",
             "^Float32Array",
             "^Float64Array",
             "^Int16Array",
             "^Int32Array",
             "^Int8Array",
+            "^LegacyNullToEmptyString",
             "^USVString",
             "^Uint16Array",
             "^Uint32Array",
             "^Uint8Array",
             "^Uint8ClampedArray",
             "^alloc\\-copy",
+            "^alloc\\-latin1\\-str",
             "^alloc\\-utf8\\-cstr",
             "^alloc\\-utf8\\-str",
+            "^alloc\\-utf16\\-str",
             "^any",
             "^anyref",
             "^as",
+            "^bigint",
             "^bind",
             "^bind\\-export",
             "^bind\\-import",
             "^boolean",
             "^byte",
+            "^bytes",
             "^constructor",
             "^copy",
             "^default\\-new\\-target",
             "^dict",
             "^double",
+            "^elements",
             "^enum",
             "^enum\\-to\\-i32",
             "^export",
+            "^false",
             "^f32",
             "^f64",
             "^field",
@@ -138,17 +176,23 @@ impl LexerBuilder {
             "^i64",
             "^idx=",
             "^import",
+            "^latin1\\-str",
             "^long long",
             "^long",
             "^method",
+            "^new\\-target",
+            "^null",
             "^object",
             "^octet",
+            "^optional",
             "^param",
             "^result",
             "^short",
             "^symbol",
             "^type=",
+            "^true",
             "^type",
+            "^undefined",
             "^union",
             "^unrestricted double",
             "^unrestricted float",
@@ -157,9 +201,15 @@ impl LexerBuilder {
             "^unsigned short",
             "^utf8\\-cstr",
             "^utf8\\-str",
+            "^utf16\\-str",
             "^v128",
+            "^variadic",
             "^view",
+            "^write\\-copy",
+            "^write\\-utf16\\-str",
+            "^write\\-utf8\\-str",
             r"^([0-9]+)",
+            r"^(\-[0-9]+)",
             r"^([a-zA-Z$][a-zA-Z0-9$_]*)",
             r#"^"(([^\\"]|\\.)*)""#,
         ];
@@ -174,6 +224,7 @@ impl LexerBuilder {
         }
     }
 
+    #[allow(clippy::redundant_field_names)]
     pub fn lexer<'input, 'builder>(&'builder self, input: &'input str) -> Lexer<'input, 'builder> {
         Lexer {
             input: input,
@@ -250,39 +301,52 @@ impl<'input, 'builder> Iterator for Lexer<'input, 'builder> {
                     match token_by_regex.as_ref() {
                         "^\\(" => Token::LeftParenthesis,
                         "^\\)" => Token::RightParenthesis,
+                        "^\\[" => Token::LeftBracket,
+                        "^\\]" => Token::RightBracket,
+                        "^," => Token::Comma,
+                        "^AllowShared" => Token::AllowShared,
                         "^ArrayBuffer" => Token::ArrayBuffer,
                         "^ByteString" => Token::ByteString,
+                        "^Clamp" => Token::Clamp,
                         "^DOMString" => Token::DOMString,
                         "^DataView" => Token::DataView,
+                        "^EnforceRange" => Token::EnforceRange,
                         "^Float32Array" => Token::Float32Array,
                         "^Float64Array" => Token::Float64Array,
                         "^Int16Array" => Token::Int16Array,
                         "^Int32Array" => Token::Int32Array,
                         "^Int8Array" => Token::Int8Array,
+                        "^LegacyNullToEmptyString" => Token::LegacyNullToEmptyString,
                         "^USVString" => Token::USVString,
                         "^Uint16Array" => Token::Uint16Array,
                         "^Uint32Array" => Token::Uint32Array,
                         "^Uint8Array" => Token::Uint8Array,
                         "^Uint8ClampedArray" => Token::Uint8ClampedArray,
                         "^alloc\\-copy" => Token::AllocCopy,
+                        "^alloc\\-latin1\\-str" => Token::AllocLatin1Str,
                         "^alloc\\-utf8\\-cstr" => Token::AllocUtf8CStr,
                         "^alloc\\-utf8\\-str" => Token::AllocUtf8Str,
+                        "^alloc\\-utf16\\-str" => Token::AllocUtf16Str,
                         "^any" => Token::Any,
                         "^anyref" => Token::Anyref,
                         "^as" => Token::As,
+                        "^bigint" => Token::BigInt,
                         "^bind" => Token::Bind,
                         "^bind\\-export" => Token::BindExport,
                         "^bind\\-import" => Token::BindImport,
                         "^boolean" => Token::Boolean,
                         "^byte" => Token::Byte,
+                        "^bytes" => Token::Bytes,
                         "^constructor" => Token::Constructor,
                         "^copy" => Token::Copy,
                         "^default\\-new\\-target" => Token::DefaultNewTarget,
                         "^dict" => Token::Dict,
                         "^double" => Token::Double,
+                        "^elements" => Token::Elements,
                         "^enum" => Token::Enum,
                         "^enum\\-to\\-i32" => Token::EnumToI32,
                         "^export" => Token::Export,
+                        "^false" => Token::False,
                         "^f32" => Token::F32,
                         "^f64" => Token::F64,
                         "^field" => Token::Field,
@@ -295,17 +359,23 @@ impl<'input, 'builder> Iterator for Lexer<'input, 'builder> {
                         "^i64" => Token::I64,
                         "^idx=" => Token::Index,
                         "^import" => Token::Import,
+                        "^latin1\\-str" => Token::Latin1Str,
                         "^long long" => Token::LongLong,
                         "^long" => Token::Long,
                         "^method" => Token::Method,
+                        "^new\\-target" => Token::NewTarget,
+                        "^null" => Token::Null,
                         "^object" => Token::Object,
                         "^octet" => Token::Octet,
+                        "^optional" => Token::Optional,
                         "^param" => Token::Param,
                         "^result" => Token::Result,
                         "^short" => Token::Short,
                         "^symbol" => Token::Symbol,
                         "^type=" => Token::TypeRef,
+                        "^true" => Token::True,
                         "^type" => Token::Type,
+                        "^undefined" => Token::Undefined,
                         "^union" => Token::Union,
                         "^unrestricted double" => Token::UnrestrictedDouble,
                         "^unrestricted float" => Token::UnrestrictedFloat,
@@ -314,9 +384,15 @@ impl<'input, 'builder> Iterator for Lexer<'input, 'builder> {
                         "^unsigned short" => Token::UnsignedShort,
                         "^utf8\\-cstr" => Token::Utf8CStr,
                         "^utf8\\-str" => Token::Utf8Str,
+                        "^utf16\\-str" => Token::Utf16Str,
                         "^v128" => Token::V128,
+                        "^variadic" => Token::Variadic,
                         "^view" => Token::View,
+                        "^write\\-copy" => Token::WriteCopy,
+                        "^write\\-utf16\\-str" => Token::WriteUtf16Str,
+                        "^write\\-utf8\\-str" => Token::WriteUtf8Str,
                         r"^([0-9]+)" => Token::Unsigned(result),
+                        r"^(\-[0-9]+)" => Token::Signed(result),
                         r"^([a-zA-Z$][a-zA-Z0-9$_]*)" => Token::Identifier(result),
                         r#"^"(([^\\"]|\\.)*)""# => Token::QuotedString(result),
                         _ => unreachable!(),
@@ -1,9 +1,13 @@
 //! Working with the text format.
 
 mod actions;
+pub mod cst;
 mod error;
+pub mod fmt;
 mod lexer;
 mod parser;
 
 pub use actions::Actions;
+pub use cst::parse_cst;
+pub use fmt::format;
 pub use parser::parse_with_actions;
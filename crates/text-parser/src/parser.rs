@@ -3,7 +3,16 @@
 use crate::actions::Actions;
 use crate::lexer;
 
-include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
+pub(crate) use self::grammar::*;
+
+// NB: the generated parser is not written to pass lints.
+#[allow(unused_parens, clippy::all)]
+mod grammar {
+    use crate::actions::Actions;
+    use crate::lexer;
+
+    include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
+}
 
 /// Parse the given straw proposal text format input with custom parse actions.
 ///
@@ -133,13 +142,52 @@ mod tests {
         ) -> Self::WebidlFunctionKindConstructor {
             t!("WebidlFunctionKindConstructor")
         }
+        fn webidl_function_kind_constructor_new_target(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+        ) -> Self::WebidlFunctionKindConstructor {
+            t!("WebidlFunctionKindConstructor" ty)
+        }
 
         type WebidlFunctionParams = ParseTree;
         fn webidl_function_params(
             &mut self,
-            tys: Vec<Self::WebidlTypeRef>,
+            required: Vec<Self::WebidlTypeRef>,
+            optional: Vec<Self::WebidlFunctionParamOptional>,
+            variadic: Option<Self::WebidlFunctionParamVariadic>,
         ) -> Self::WebidlFunctionParams {
-            t!("WebidlFunctionParams" tys)
+            t!("WebidlFunctionParams" required optional variadic)
+        }
+
+        type WebidlFunctionParamOptional = ParseTree;
+        fn webidl_function_param_optional(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+            default: Option<Self::WebidlDefaultValue>,
+        ) -> Self::WebidlFunctionParamOptional {
+            t!("WebidlFunctionParamOptional" ty default)
+        }
+
+        type WebidlFunctionParamVariadic = ParseTree;
+        fn webidl_function_param_variadic(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+        ) -> Self::WebidlFunctionParamVariadic {
+            t!("WebidlFunctionParamVariadic" ty)
+        }
+
+        type WebidlDefaultValue = ParseTree;
+        fn webidl_default_value_null(&mut self) -> Self::WebidlDefaultValue {
+            t!("WebidlDefaultValueNull")
+        }
+        fn webidl_default_value_boolean(&mut self, value: bool) -> Self::WebidlDefaultValue {
+            t!("WebidlDefaultValueBoolean" value.to_string())
+        }
+        fn webidl_default_value_integer(&mut self, value: i64) -> Self::WebidlDefaultValue {
+            t!("WebidlDefaultValueInteger" value.to_string())
+        }
+        fn webidl_default_value_string(&mut self, value: &str) -> Self::WebidlDefaultValue {
+            t!("WebidlDefaultValueString" value)
         }
 
         type WebidlFunctionResult = ParseTree;
@@ -291,8 +339,9 @@ mod tests {
             ty: Self::WebidlTypeRef,
             offset: u32,
             length: u32,
-        ) -> Self::OutgoingBindingExpressionView {
-            t!("OutgoingBindingExpressionView" ty offset length)
+            unit: Option<Self::BufferLengthUnit>,
+        ) -> Option<Self::OutgoingBindingExpressionView> {
+            Some(t!("OutgoingBindingExpressionView" ty offset length unit))
         }
 
         type OutgoingBindingExpressionCopy = ParseTree;
@@ -301,8 +350,17 @@ mod tests {
             ty: Self::WebidlTypeRef,
             offset: u32,
             length: u32,
-        ) -> Self::OutgoingBindingExpressionCopy {
-            t!("OutgoingBindingExpressionCopy" ty offset length)
+            unit: Option<Self::BufferLengthUnit>,
+        ) -> Option<Self::OutgoingBindingExpressionCopy> {
+            Some(t!("OutgoingBindingExpressionCopy" ty offset length unit))
+        }
+
+        type BufferLengthUnit = ParseTree;
+        fn buffer_length_unit_elements(&mut self) -> Self::BufferLengthUnit {
+            t!("BufferLengthUnit" "elements")
+        }
+        fn buffer_length_unit_bytes(&mut self) -> Self::BufferLengthUnit {
+            t!("BufferLengthUnit" "bytes")
         }
 
         type OutgoingBindingExpressionDict = ParseTree;
@@ -324,6 +382,44 @@ mod tests {
             t!("OutgoingBindingExpressionBindExport" ty binding idx)
         }
 
+        type OutgoingBindingExpressionUndefined = ParseTree;
+        fn outgoing_binding_expression_undefined(
+            &mut self,
+        ) -> Self::OutgoingBindingExpressionUndefined {
+            t!("OutgoingBindingExpressionUndefined")
+        }
+
+        type OutgoingBindingExpressionVariadic = ParseTree;
+        fn outgoing_binding_expression_variadic(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+            elem_ty: Self::WasmValType,
+            offset: u32,
+            length: u32,
+        ) -> Self::OutgoingBindingExpressionVariadic {
+            t!("OutgoingBindingExpressionVariadic" ty elem_ty offset length)
+        }
+
+        type OutgoingBindingExpressionUtf16Str = ParseTree;
+        fn outgoing_binding_expression_utf16_str(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+            offset: u32,
+            length: u32,
+        ) -> Self::OutgoingBindingExpressionUtf16Str {
+            t!("OutgoingBindingExpressionUtf16Str" ty offset length)
+        }
+
+        type OutgoingBindingExpressionLatin1Str = ParseTree;
+        fn outgoing_binding_expression_latin1_str(
+            &mut self,
+            ty: Self::WebidlTypeRef,
+            offset: u32,
+            length: u32,
+        ) -> Self::OutgoingBindingExpressionLatin1Str {
+            t!("OutgoingBindingExpressionLatin1Str" ty offset length)
+        }
+
         type IncomingBindingExpression = ParseTree;
 
         type IncomingBindingExpressionGet = ParseTree;
@@ -389,6 +485,54 @@ mod tests {
             t!("IncomingBindingExpressionBindImport" ty binding expr)
         }
 
+        type IncomingBindingExpressionAllocUtf16Str = ParseTree;
+        fn incoming_binding_expression_alloc_utf16_str(
+            &mut self,
+            alloc_func_name: &str,
+            expr: Self::IncomingBindingExpression,
+        ) -> Self::IncomingBindingExpressionAllocUtf16Str {
+            t!("IncomingBindingExpressionAllocUtf16Str" alloc_func_name expr)
+        }
+
+        type IncomingBindingExpressionAllocLatin1Str = ParseTree;
+        fn incoming_binding_expression_alloc_latin1_str(
+            &mut self,
+            alloc_func_name: &str,
+            expr: Self::IncomingBindingExpression,
+        ) -> Self::IncomingBindingExpressionAllocLatin1Str {
+            t!("IncomingBindingExpressionAllocLatin1Str" alloc_func_name expr)
+        }
+
+        type IncomingBindingExpressionWriteUtf8Str = ParseTree;
+        fn incoming_binding_expression_write_utf8_str(
+            &mut self,
+            offset: u32,
+            capacity: u32,
+            expr: Self::IncomingBindingExpression,
+        ) -> Self::IncomingBindingExpressionWriteUtf8Str {
+            t!("IncomingBindingExpressionWriteUtf8Str" offset capacity expr)
+        }
+
+        type IncomingBindingExpressionWriteUtf16Str = ParseTree;
+        fn incoming_binding_expression_write_utf16_str(
+            &mut self,
+            offset: u32,
+            capacity: u32,
+            expr: Self::IncomingBindingExpression,
+        ) -> Self::IncomingBindingExpressionWriteUtf16Str {
+            t!("IncomingBindingExpressionWriteUtf16Str" offset capacity expr)
+        }
+
+        type IncomingBindingExpressionWriteCopy = ParseTree;
+        fn incoming_binding_expression_write_copy(
+            &mut self,
+            offset: u32,
+            capacity: u32,
+            expr: Self::IncomingBindingExpression,
+        ) -> Self::IncomingBindingExpressionWriteCopy {
+            t!("IncomingBindingExpressionWriteCopy" offset capacity expr)
+        }
+
         type WebidlTypeRef = ParseTree;
 
         type WebidlTypeRefNamed = ParseTree;
@@ -401,6 +545,31 @@ mod tests {
             Some(t!("WebidlTypeRefIndexed" idx))
         }
 
+        type WebidlTypeRefAnnotated = ParseTree;
+        fn webidl_type_ref_annotated(
+            &mut self,
+            attributes: Vec<Self::WebidlExtendedAttribute>,
+            ty: Self::WebidlScalarType,
+        ) -> Option<Self::WebidlTypeRefAnnotated> {
+            Some(t!("WebidlTypeRefAnnotated" attributes ty))
+        }
+
+        type WebidlExtendedAttribute = ParseTree;
+        fn webidl_extended_attribute_clamp(&mut self) -> Self::WebidlExtendedAttribute {
+            t!("WebidlExtendedAttribute" "Clamp")
+        }
+        fn webidl_extended_attribute_enforce_range(&mut self) -> Self::WebidlExtendedAttribute {
+            t!("WebidlExtendedAttribute" "EnforceRange")
+        }
+        fn webidl_extended_attribute_allow_shared(&mut self) -> Self::WebidlExtendedAttribute {
+            t!("WebidlExtendedAttribute" "AllowShared")
+        }
+        fn webidl_extended_attribute_legacy_null_to_empty_string(
+            &mut self,
+        ) -> Self::WebidlExtendedAttribute {
+            t!("WebidlExtendedAttribute" "LegacyNullToEmptyString")
+        }
+
         type WebidlScalarType = ParseTree;
         fn webidl_scalar_type_any(&mut self) -> Self::WebidlScalarType {
             t!("WebidlScalarType" "any")
@@ -432,6 +601,9 @@ mod tests {
         fn webidl_scalar_type_unsigned_long_long(&mut self) -> Self::WebidlScalarType {
             t!("WebidlScalarType" "unsigned long long")
         }
+        fn webidl_scalar_type_bigint(&mut self) -> Self::WebidlScalarType {
+            t!("WebidlScalarType" "bigint")
+        }
         fn webidl_scalar_type_float(&mut self) -> Self::WebidlScalarType {
             t!("WebidlScalarType" "float")
         }
@@ -633,7 +805,9 @@ mod tests {
                        t!("Some" t!("WebidlFunctionKindMethod" t!("WebidlScalarType" "any")))
                        t!("Some" t!("WebidlFunctionParams"
                                     t!(t!("WebidlScalarType" "USVString")
-                                       t!("WebidlScalarType" "Uint8Array"))))
+                                       t!("WebidlScalarType" "Uint8Array"))
+                                    t!()
+                                    t!("None")))
                        t!("Some" t!("WebidlFunctionResult"
                                     t!("WebidlTypeRefNamed" "$TextEncoderEncodeIntoResult")))))))
            t!("WebidlFunctionBindingsSubsection"
@@ -651,7 +825,8 @@ mod tests {
                           t!("OutgoingBindingExpressionView"
                              t!("WebidlScalarType" "Uint8Array")
                              2
-                             3))))
+                             3
+                             t!("None")))))
                     t!("Some" t!("IncomingBindingMap"
                        t!(t!("IncomingBindingExpressionAs"
                              t!("WasmValType" "i64")
@@ -679,7 +854,9 @@ mod tests {
                            t!("WebidlScalarType" "any")))
               t!("Some" t!("WebidlFunctionParams"
                            t!(t!("WebidlTypeRefNamed" "$Contact")
-                              t!("WebidlScalarType" "DOMString"))))
+                              t!("WebidlScalarType" "DOMString"))
+                           t!()
+                           t!("None")))
               t!("Some" t!("WebidlFunctionResult" t!("WebidlScalarType" "boolean")))))
     );
     ok!(
@@ -693,7 +870,9 @@ mod tests {
                            t!("WebidlScalarType" "any")))
               t!("Some" t!("WebidlFunctionParams"
                            t!(t!("WebidlTypeRefNamed" "$Contact")
-                              t!("WebidlScalarType" "DOMString"))))
+                              t!("WebidlScalarType" "DOMString"))
+                           t!()
+                           t!("None")))
               t!("None")))
     );
     ok!(
@@ -704,7 +883,10 @@ mod tests {
            t!("Some" "$AddContactFuncWebIDL")
            t!("WebidlFunction"
               t!("None")
-              t!("Some" t!("WebidlFunctionParams" t!(t!("WebidlScalarType" "DOMString"))))
+              t!("Some" t!("WebidlFunctionParams"
+                           t!(t!("WebidlScalarType" "DOMString"))
+                           t!()
+                           t!("None")))
               t!("None")))
     );
     ok!(
@@ -715,7 +897,7 @@ mod tests {
            t!("Some" "$AddContactFuncWebIDL")
            t!("WebidlFunction"
               t!("None")
-              t!("Some" t!("WebidlFunctionParams" t!()))
+              t!("Some" t!("WebidlFunctionParams" t!() t!() t!("None")))
               t!("None")))
     );
     ok!(
@@ -751,6 +933,78 @@ mod tests {
               t!("None")
               t!("Some" t!("WebidlFunctionResult" t!("WebidlScalarType" "any")))))
     );
+    ok!(
+        webidl_type_func_ok_8,
+        WebidlTypeParser,
+        r#"type $Log (func (param DOMString (optional long 42) (optional DOMString "x") (variadic any)))"#,
+        t!("WebidlType"
+           t!("Some" "$Log")
+           t!("WebidlFunction"
+              t!("None")
+              t!("Some" t!("WebidlFunctionParams"
+                           t!(t!("WebidlScalarType" "DOMString"))
+                           t!(t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "long")
+                                 t!("Some" t!("WebidlDefaultValueInteger" "42")))
+                              t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "DOMString")
+                                 t!("Some" t!("WebidlDefaultValueString" "x"))))
+                           t!("Some" t!("WebidlFunctionParamVariadic"
+                                        t!("WebidlScalarType" "any")))))
+              t!("None")))
+    );
+    ok!(
+        webidl_type_func_ok_9,
+        WebidlTypeParser,
+        "type (func (param (optional any) (optional boolean true) (optional object null) (optional long -1)))",
+        t!("WebidlType"
+           t!("None")
+           t!("WebidlFunction"
+              t!("None")
+              t!("Some" t!("WebidlFunctionParams"
+                           t!()
+                           t!(t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "any")
+                                 t!("None"))
+                              t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "boolean")
+                                 t!("Some" t!("WebidlDefaultValueBoolean" "true")))
+                              t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "object")
+                                 t!("Some" t!("WebidlDefaultValueNull")))
+                              t!("WebidlFunctionParamOptional"
+                                 t!("WebidlScalarType" "long")
+                                 t!("Some" t!("WebidlDefaultValueInteger" "-1"))))
+                           t!("None")))
+              t!("None")))
+    );
+    ok!(
+        webidl_type_func_ok_10,
+        WebidlTypeParser,
+        "type (func (param (variadic long)))",
+        t!("WebidlType"
+           t!("None")
+           t!("WebidlFunction"
+              t!("None")
+              t!("Some" t!("WebidlFunctionParams"
+                           t!()
+                           t!()
+                           t!("Some" t!("WebidlFunctionParamVariadic"
+                                        t!("WebidlScalarType" "long")))))
+              t!("None")))
+    );
+    ok!(
+        webidl_type_func_ok_11,
+        WebidlTypeParser,
+        "type MySubclassCtor (func (constructor (new-target $MySubclass)) (result any))",
+        t!("WebidlType"
+           t!("Some" "MySubclassCtor")
+           t!("WebidlFunction"
+              t!("Some" t!("WebidlFunctionKindConstructor"
+                           t!("WebidlTypeRefNamed" "$MySubclass")))
+              t!("None")
+              t!("Some" t!("WebidlFunctionResult" t!("WebidlScalarType" "any")))))
+    );
     err!(
         webidl_type_func_err_1,
         WebidlTypeParser,
@@ -766,6 +1020,36 @@ mod tests {
         WebidlTypeParser,
         "type blahBlahBlah (func (method any) (method any))"
     );
+    err!(
+        webidl_type_func_err_4,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (param (optional any) DOMString))"
+    );
+    err!(
+        webidl_type_func_err_5,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (param (variadic any) (optional any)))"
+    );
+    err!(
+        webidl_type_func_err_6,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (param (variadic any) (variadic any)))"
+    );
+    err!(
+        webidl_type_func_err_7,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (param (optional long 99999999999999999999)))"
+    );
+    err!(
+        webidl_type_func_err_8,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (constructor))"
+    );
+    err!(
+        webidl_type_func_err_9,
+        WebidlTypeParser,
+        "type blahBlahBlah (func (constructor (new-target)))"
+    );
 
     ok!(
         webidl_type_dict_ok_1,
@@ -1049,7 +1333,38 @@ mod tests {
         "type=42",
         t!("WebidlTypeRefIndexed" 42)
     );
+    ok!(
+        webidl_type_ref_ok_5,
+        WebidlTypeRefParser,
+        "bigint",
+        t!("WebidlScalarType" "bigint")
+    );
+    ok!(
+        webidl_type_ref_ok_6,
+        WebidlTypeRefParser,
+        "[Clamp] octet",
+        t!("WebidlTypeRefAnnotated"
+           t!(t!("WebidlExtendedAttribute" "Clamp"))
+           t!("WebidlScalarType" "octet"))
+    );
+    ok!(
+        webidl_type_ref_ok_7,
+        WebidlTypeRefParser,
+        "type=[AllowShared, EnforceRange, LegacyNullToEmptyString] DOMString",
+        t!("WebidlTypeRefAnnotated"
+           t!(t!("WebidlExtendedAttribute" "AllowShared")
+              t!("WebidlExtendedAttribute" "EnforceRange")
+              t!("WebidlExtendedAttribute" "LegacyNullToEmptyString"))
+           t!("WebidlScalarType" "DOMString"))
+    );
     err!(webidl_type_ref_err, WebidlTypeRefParser, "1abc");
+    err!(webidl_type_ref_err_2, WebidlTypeRefParser, "[] octet");
+    err!(
+        webidl_type_ref_err_3,
+        WebidlTypeRefParser,
+        "[Clamp] $Contact"
+    );
+    err!(webidl_type_ref_err_4, WebidlTypeRefParser, "[Clamp,] octet");
 
     ok!(
         wasm_type_ref_ok_1,
@@ -1215,6 +1530,29 @@ mod tests {
            t!("WebidlScalarType" "Uint8Array")
            123
            456
+           t!("None")
+        )
+    );
+    ok!(
+        outgoing_binding_expression_view_ok_2,
+        OutgoingBindingExpressionParser,
+        "(view Float64Array 8 16 bytes)",
+        t!("OutgoingBindingExpressionView"
+           t!("WebidlScalarType" "Float64Array")
+           8
+           16
+           t!("Some" t!("BufferLengthUnit" "bytes"))
+        )
+    );
+    ok!(
+        outgoing_binding_expression_view_ok_3,
+        OutgoingBindingExpressionParser,
+        "(view Int32Array 8 2 elements)",
+        t!("OutgoingBindingExpressionView"
+           t!("WebidlScalarType" "Int32Array")
+           8
+           2
+           t!("Some" t!("BufferLengthUnit" "elements"))
         )
     );
     err!(
@@ -1236,8 +1574,14 @@ mod tests {
            t!("WebidlScalarType" "Uint8Array")
            123
            456
+           t!("None")
         )
     );
+    err!(
+        outgoing_binding_expression_copy_err_3,
+        OutgoingBindingExpressionParser,
+        "(copy Uint8Array 123 456 words)"
+    );
     err!(
         outgoing_binding_expression_copy_err_1,
         OutgoingBindingExpressionParser,
@@ -1291,6 +1635,76 @@ mod tests {
            2
         )
     );
+    ok!(
+        outgoing_binding_expression_undefined_ok_1,
+        OutgoingBindingExpressionParser,
+        "(undefined)",
+        t!("OutgoingBindingExpressionUndefined")
+    );
+    err!(
+        outgoing_binding_expression_undefined_err_1,
+        OutgoingBindingExpressionParser,
+        "(undefined 0)"
+    );
+
+    ok!(
+        outgoing_binding_expression_variadic_ok_1,
+        OutgoingBindingExpressionParser,
+        "(variadic long i32 1 2)",
+        t!("OutgoingBindingExpressionVariadic"
+           t!("WebidlScalarType" "long")
+           t!("WasmValType" "i32")
+           1
+           2)
+    );
+    err!(
+        outgoing_binding_expression_variadic_err_1,
+        OutgoingBindingExpressionParser,
+        "(variadic long 1 2)"
+    );
+    err!(
+        outgoing_binding_expression_variadic_err_2,
+        OutgoingBindingExpressionParser,
+        "(variadic long i32 1)"
+    );
+
+    ok!(
+        outgoing_binding_expression_utf16_str_ok,
+        OutgoingBindingExpressionParser,
+        "(utf16-str USVString 123 456)",
+        t!("OutgoingBindingExpressionUtf16Str"
+           t!("WebidlScalarType" "USVString")
+           123
+           456
+        )
+    );
+    err!(
+        outgoing_binding_expression_utf16_str_err_1,
+        OutgoingBindingExpressionParser,
+        "(utf16-str DOMString 123)"
+    );
+    err!(
+        outgoing_binding_expression_utf16_str_err_2,
+        OutgoingBindingExpressionParser,
+        "(utf16-str 123 456)"
+    );
+
+    ok!(
+        outgoing_binding_expression_latin1_str_ok,
+        OutgoingBindingExpressionParser,
+        "(latin1-str ByteString 123 456)",
+        t!("OutgoingBindingExpressionLatin1Str"
+           t!("WebidlScalarType" "ByteString")
+           123
+           456
+        )
+    );
+    err!(
+        outgoing_binding_expression_latin1_str_err_1,
+        OutgoingBindingExpressionParser,
+        "(latin1-str ByteString 123)"
+    );
+
     err!(
         outgoing_binding_expression_bind_export_err_1,
         OutgoingBindingExpressionParser,
@@ -1450,7 +1864,104 @@ mod tests {
         "(bind-import hello (get 1))"
     );
 
+    ok!(
+        incoming_binding_expression_alloc_utf16_str_ok_1,
+        IncomingBindingExpressionParser,
+        "(alloc-utf16-str malloc (get 0))",
+        t!("IncomingBindingExpressionAllocUtf16Str"
+           "malloc"
+           t!("IncomingBindingExpressionGet" 0)
+        )
+    );
+    err!(
+        incoming_binding_expression_alloc_utf16_str_err_1,
+        IncomingBindingExpressionParser,
+        "(alloc-utf16-str (get 0))"
+    );
+
+    ok!(
+        incoming_binding_expression_alloc_latin1_str_ok_1,
+        IncomingBindingExpressionParser,
+        "(alloc-latin1-str malloc (get 0))",
+        t!("IncomingBindingExpressionAllocLatin1Str"
+           "malloc"
+           t!("IncomingBindingExpressionGet" 0)
+        )
+    );
+    err!(
+        incoming_binding_expression_alloc_latin1_str_err_1,
+        IncomingBindingExpressionParser,
+        "(alloc-latin1-str malloc)"
+    );
+
+    ok!(
+        incoming_binding_expression_write_utf8_str_ok_1,
+        IncomingBindingExpressionParser,
+        "(write-utf8-str 2 3 (get 0))",
+        t!("IncomingBindingExpressionWriteUtf8Str"
+           2
+           3
+           t!("IncomingBindingExpressionGet" 0)
+        )
+    );
+    err!(
+        incoming_binding_expression_write_utf8_str_err_1,
+        IncomingBindingExpressionParser,
+        "(write-utf8-str 2 (get 0))"
+    );
+    err!(
+        incoming_binding_expression_write_utf8_str_err_2,
+        IncomingBindingExpressionParser,
+        "(write-utf8-str 2 3)"
+    );
+
+    ok!(
+        incoming_binding_expression_write_utf16_str_ok_1,
+        IncomingBindingExpressionParser,
+        "(write-utf16-str 2 3 (get 0))",
+        t!("IncomingBindingExpressionWriteUtf16Str"
+           2
+           3
+           t!("IncomingBindingExpressionGet" 0)
+        )
+    );
+
+    ok!(
+        incoming_binding_expression_write_copy_ok_1,
+        IncomingBindingExpressionParser,
+        "(write-copy 2 3 (get 0))",
+        t!("IncomingBindingExpressionWriteCopy"
+           2
+           3
+           t!("IncomingBindingExpressionGet" 0)
+        )
+    );
+    err!(
+        incoming_binding_expression_write_copy_err_1,
+        IncomingBindingExpressionParser,
+        "(write-copy malloc (get 0))"
+    );
+
     ok!(webidl_index_ok_1, WebidlIndexParser, "42", 42);
     ok!(webidl_index_ok_2, WebidlIndexParser, "idx=42", 42);
     err!(webidl_index_err_1, WebidlIndexParser, "idx=");
+    err!(webidl_index_err_2, WebidlIndexParser, "4294967296");
+
+    #[test]
+    fn formatting_preserves_meaning() {
+        let input = r#"
+            bind $encodeInto $encodeIntoBinding ;; out of order
+            type $Result (; a ; dict (field "read" [ Clamp,EnforceRange ] long) (field "written" unsigned long long))
+            func-binding $encodeIntoBinding import 0 $Result (param (as any 0) (as type=any idx=1) (view Uint8Array 2 3))
+              (result (as i64 (field 0 (get 0))) (as i64 (field 1 (get idx=0))))
+        "#;
+        let formatted = crate::format(input).unwrap();
+        assert_ne!(formatted, input);
+
+        let (bind, ty) = (input.find("bind").unwrap(), input.find("type").unwrap());
+        let in_order = format!("{}\n{}", &input[ty..], &input[bind..ty]);
+        let expected = parse_with_actions(&mut BuildParseTree, &in_order).unwrap();
+        let actual = parse_with_actions(&mut BuildParseTree, &formatted).unwrap();
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,20 @@
+//! Decode arbitrary Web IDL bindings sections.
+//!
+//! Decoding may fail, but must not panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasm_webidl_bindings_fuzz::MODULE;
+
+fuzz_target!(|data: &[u8]| {
+    let data = data.to_vec();
+    let mut config = walrus::ModuleConfig::new();
+    config.on_parse(move |_, ids| {
+        let _ = wasm_webidl_bindings::binary::decode(ids, &data);
+        Ok(())
+    });
+    config
+        .parse(&MODULE)
+        .expect("should parse the fixed module");
+});
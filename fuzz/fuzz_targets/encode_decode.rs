@@ -0,0 +1,20 @@
+//! Check that encoding is the inverse of decoding: re-encoding a decoded Web
+//! IDL bindings section and decoding it again gives the same encoding.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasm_webidl_bindings_fuzz::{reencode, MODULE};
+
+fuzz_target!(|data: &[u8]| {
+    let (wasm, encoded) = match reencode(&MODULE, data) {
+        Some(x) => x,
+        None => return,
+    };
+    let (_, reencoded) =
+        reencode(&wasm, &encoded).expect("should decode a section that we encoded ourselves");
+    assert!(
+        encoded == reencoded,
+        "re-encoding a decoded section should not change it"
+    );
+});
@@ -0,0 +1,42 @@
+//! Parse arbitrary text format input against the module from the README
+//! example, and encode the result.
+//!
+//! Parsing may fail, but must not panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use walrus::ValType::{Anyref, I32, I64};
+
+lazy_static::lazy_static! {
+    /// A module with the README example's `$EncodeIntoFuncWasm` type and
+    /// `$encodeInto` function.
+    static ref WASM: Vec<u8> = {
+        let mut module = walrus::Module::default();
+        let ty = module.types.add(&[Anyref, Anyref, I32, I32], &[I64, I64]);
+        let (func, _) = module.add_import_func("TextEncoder", "encodeInto", ty);
+        module.funcs.get_mut(func).name = Some("$encodeInto".into());
+        module.emit_wasm()
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let input = match std::str::from_utf8(data) {
+        Ok(input) => input.to_string(),
+        Err(_) => return,
+    };
+
+    let mut config = walrus::ModuleConfig::new();
+    config.on_parse(move |module, ids| {
+        // Type names aren't in the name section, so name it again here.
+        let ty = ids.get_type(0)?;
+        module.types.get_mut(ty).name = Some("$EncodeIntoFuncWasm".into());
+
+        if let Ok(wb) = wasm_webidl_bindings::text::parse(module, ids, &input) {
+            module.customs.add(wb);
+        }
+        Ok(())
+    });
+    let mut module = config.parse(&WASM).expect("should parse the README module");
+    module.emit_wasm();
+});
@@ -0,0 +1,89 @@
+//! Read every item of arbitrary Web IDL bindings sections lazily.
+//!
+//! Reading may fail, but must not panic.
+
+#![no_main]
+
+use anyhow::Result;
+use libfuzzer_sys::fuzz_target;
+use wasm_webidl_bindings::binary::reader::*;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = read(data);
+});
+
+fn read(data: &[u8]) -> Result<()> {
+    let section = SectionReader::new(data)?;
+    for ty in section.types() {
+        match ty? {
+            Type::Function(f) => {
+                for param in f.params {
+                    param?;
+                }
+            }
+            Type::Dictionary(fields) => {
+                for field in fields {
+                    field?;
+                }
+            }
+            Type::Enumeration(values) => {
+                for value in values {
+                    value?;
+                }
+            }
+            Type::Union(members) => {
+                for member in members {
+                    member?;
+                }
+            }
+        }
+    }
+    for binding in section.bindings() {
+        match binding? {
+            Binding::Import(i) => {
+                outgoing(i.params)?;
+                incoming(i.result)?;
+            }
+            Binding::Export(e) => {
+                incoming(e.params)?;
+                outgoing(e.result)?;
+            }
+        }
+    }
+    for bind in section.binds()? {
+        bind?;
+    }
+    Ok(())
+}
+
+fn outgoing(exprs: VecReader<Outgoing>) -> Result<()> {
+    for expr in exprs {
+        if let Outgoing::Dict { fields, .. } = expr? {
+            outgoing(fields)?;
+        }
+    }
+    Ok(())
+}
+
+fn incoming(exprs: VecReader<Incoming>) -> Result<()> {
+    for expr in exprs {
+        let mut expr = expr?;
+        loop {
+            expr = match expr {
+                Incoming::Get { .. } => break,
+                Incoming::As { expr, .. }
+                | Incoming::AllocUtf8Str { expr, .. }
+                | Incoming::AllocCopy { expr, .. }
+                | Incoming::EnumToI32 { expr, .. }
+                | Incoming::Field { expr, .. }
+                | Incoming::BindImport { expr, .. }
+                | Incoming::AllocUtf16Str { expr, .. }
+                | Incoming::AllocLatin1Str { expr, .. }
+                | Incoming::WriteUtf8Str { expr, .. }
+                | Incoming::WriteUtf16Str { expr, .. }
+                | Incoming::WriteCopy { expr, .. } => expr.get()?,
+            };
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Shared setup for the fuzz targets.
+//!
+//! The targets fuzz the contents of the Web IDL bindings section, rather than
+//! whole Wasm modules, so that they exercise this crate instead of the Wasm
+//! parser. Sections are decoded against a fixed module with enough types and
+//! functions for the indices in the seed corpus.
+//!
+//! The `decode`, `encode_decode` and `read` targets all take a bindings
+//! section, so they share the seed corpus in `corpus/sections`, which has to
+//! be passed explicitly:
+//!
+//! ```text
+//! cargo fuzz run decode fuzz/corpus/sections
+//! ```
+
+use walrus::ValType::{Anyref, F32, F64, I32, I64};
+
+lazy_static::lazy_static! {
+    /// A Wasm module with a variety of function types, and functions of each
+    /// of them.
+    pub static ref MODULE: Vec<u8> = {
+        let mut module = walrus::Module::default();
+        let val_types = [I32, I64, F32, F64, Anyref];
+        for i in 0..32 {
+            let params: Vec<_> = (0..i % 6).map(|j| val_types[(i + j) % 5]).collect();
+            let results: Vec<_> = (0..i / 6 % 3).map(|j| val_types[(i * j) % 5]).collect();
+            let ty = module.types.add(&params, &results);
+            module.add_import_func("env", &format!("f{}", i), ty);
+        }
+        module.emit_wasm()
+    };
+}
+
+/// Decode `section` as the Web IDL bindings section of the module `wasm`, and
+/// then encode it again.
+///
+/// Returns the re-emitted module and the section's new encoding, which are
+/// suitable for passing to this function again, or `None` if the section
+/// does not decode.
+pub fn reencode(wasm: &[u8], section: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let section = section.to_vec();
+    let mut config = walrus::ModuleConfig::new();
+    config.generate_producers_section(false);
+    config.on_parse(move |module, ids| {
+        module.customs.remove_raw("webidl-bindings");
+        let wb = wasm_webidl_bindings::binary::decode(ids, &section)?;
+        module.customs.add(wb);
+        Ok(())
+    });
+    let mut module = config.parse(wasm).ok()?;
+    let wasm = module.emit_wasm();
+
+    let mut module =
+        walrus::Module::from_buffer(&wasm).expect("should parse a module that we emitted");
+    let section = module
+        .customs
+        .remove_raw("webidl-bindings")
+        .expect("should have emitted the bindings section")
+        .data;
+    Some((wasm, section))
+}
@@ -1,21 +1,31 @@
 #[cfg(feature = "text")]
 use crate::text;
+#[cfg(feature = "walrus")]
+use crate::wasm::Walrus;
+use crate::wasm::WasmRefs;
 use id_arena::{Arena, Id};
+#[cfg(feature = "walrus")]
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops;
 
 #[derive(Clone, Debug, Default)]
-pub struct WebidlBindings {
+pub struct WebidlBindings<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     pub types: WebidlTypes,
-    pub bindings: FunctionBindings,
-    pub binds: Binds,
+    pub bindings: FunctionBindings<R>,
+    pub binds: Binds<R>,
 }
 
+#[cfg(feature = "walrus")]
 impl walrus::CustomSection for WebidlBindings {
     fn name(&self) -> &str {
         "webidl-bindings"
     }
 
+    #[allow(unknown_lints, mismatched_lifetime_syntaxes)]
     fn data(&self, ids_to_indices: &walrus::IdsToIndices) -> Cow<[u8]> {
         let mut data = vec![];
         crate::binary::encode(self, ids_to_indices, &mut data)
@@ -45,9 +55,30 @@ id_newtypes! {
     WebidlDictionaryId(WebidlCompoundType),
     WebidlEnumerationId(WebidlCompoundType),
     WebidlUnionId(WebidlCompoundType),
+}
+
+macro_rules! binding_id_newtypes {
+    ( $( $name:ident, )* ) => {
+        $(
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+            pub struct $name<
+                #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+                #[cfg(not(feature = "walrus"))] R: WasmRefs,
+            >(pub(crate) Id<FunctionBinding<R>>);
+
+            impl<R: WasmRefs> From<$name<R>> for Id<FunctionBinding<R>> {
+                #[inline]
+                fn from(id: $name<R>) -> Id<FunctionBinding<R>> {
+                    id.0
+                }
+            }
+        )*
+    }
+}
 
-    ImportBindingId(FunctionBinding),
-    ExportBindingId(FunctionBinding),
+binding_id_newtypes! {
+    ImportBindingId,
+    ExportBindingId,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -161,34 +192,41 @@ impl WebidlTypes {
 }
 
 #[derive(Clone, Debug, Default)]
-pub struct FunctionBindings {
-    pub(crate) names: HashMap<String, Id<FunctionBinding>>,
-    indices: Vec<Id<FunctionBinding>>,
-    pub(crate) arena: Arena<FunctionBinding>,
+pub struct FunctionBindings<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub(crate) names: HashMap<String, Id<FunctionBinding<R>>>,
+    indices: Vec<Id<FunctionBinding<R>>>,
+    pub(crate) arena: Arena<FunctionBinding<R>>,
 }
 
-pub trait FunctionBindingId: Into<FunctionBinding> {
-    type Id: Into<Id<FunctionBinding>>;
+pub trait FunctionBindingId<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+>: Into<FunctionBinding<R>>
+{
+    type Id: Into<Id<FunctionBinding<R>>>;
 
     #[doc(hidden)]
-    fn wrap(id: Id<FunctionBinding>) -> Self::Id;
+    fn wrap(id: Id<FunctionBinding<R>>) -> Self::Id;
     #[doc(hidden)]
-    fn get(b: &FunctionBinding) -> Option<&Self>;
+    fn get(b: &FunctionBinding<R>) -> Option<&Self>;
     #[doc(hidden)]
-    fn get_mut(b: &mut FunctionBinding) -> Option<&mut Self>;
+    fn get_mut(b: &mut FunctionBinding<R>) -> Option<&mut Self>;
 }
 
 macro_rules! impl_function_binding_id {
     ( $( $id:ident => $variant:ident($ty:ident); )* ) => {
         $(
-            impl FunctionBindingId for $ty {
-                type Id = $id;
+            impl<R: WasmRefs> FunctionBindingId<R> for $ty<R> {
+                type Id = $id<R>;
 
-                fn wrap(id: Id<FunctionBinding>) -> Self::Id {
+                fn wrap(id: Id<FunctionBinding<R>>) -> Self::Id {
                     $id(id)
                 }
 
-                fn get(ty: &FunctionBinding) -> Option<&Self> {
+                fn get(ty: &FunctionBinding<R>) -> Option<&Self> {
                     if let FunctionBinding::$variant(x) = ty {
                         Some(x)
                     } else {
@@ -196,7 +234,7 @@ macro_rules! impl_function_binding_id {
                     }
                 }
 
-                fn get_mut(ty: &mut FunctionBinding) -> Option<&mut Self> {
+                fn get_mut(ty: &mut FunctionBinding<R>) -> Option<&mut Self> {
                     if let FunctionBinding::$variant(x) = ty {
                         Some(x)
                     } else {
@@ -208,18 +246,18 @@ macro_rules! impl_function_binding_id {
     }
 }
 
-impl FunctionBindingId for FunctionBinding {
-    type Id = Id<FunctionBinding>;
+impl<R: WasmRefs> FunctionBindingId<R> for FunctionBinding<R> {
+    type Id = Id<FunctionBinding<R>>;
 
-    fn wrap(id: Id<FunctionBinding>) -> Self::Id {
+    fn wrap(id: Id<FunctionBinding<R>>) -> Self::Id {
         id
     }
 
-    fn get(ty: &FunctionBinding) -> Option<&Self> {
+    fn get(ty: &FunctionBinding<R>) -> Option<&Self> {
         Some(ty)
     }
 
-    fn get_mut(ty: &mut FunctionBinding) -> Option<&mut Self> {
+    fn get_mut(ty: &mut FunctionBinding<R>) -> Option<&mut Self> {
         Some(ty)
     }
 }
@@ -229,32 +267,32 @@ impl_function_binding_id! {
     ExportBindingId => Export(ExportBinding);
 }
 
-impl FunctionBindings {
-    pub fn by_name(&self, name: &str) -> Option<Id<FunctionBinding>> {
+impl<R: WasmRefs> FunctionBindings<R> {
+    pub fn by_name(&self, name: &str) -> Option<Id<FunctionBinding<R>>> {
         self.names.get(name).cloned()
     }
 
-    pub fn by_index(&self, index: u32) -> Option<Id<FunctionBinding>> {
+    pub fn by_index(&self, index: u32) -> Option<Id<FunctionBinding<R>>> {
         self.indices.get(index as usize).cloned()
     }
 
     pub fn get<T>(&self, id: T::Id) -> Option<&T>
     where
-        T: FunctionBindingId,
+        T: FunctionBindingId<R>,
     {
         self.arena.get(id.into()).and_then(T::get)
     }
 
     pub fn get_mut<T>(&mut self, id: T::Id) -> Option<&mut T>
     where
-        T: FunctionBindingId,
+        T: FunctionBindingId<R>,
     {
         self.arena.get_mut(id.into()).and_then(T::get_mut)
     }
 
     pub fn insert<T>(&mut self, binding: T) -> T::Id
     where
-        T: FunctionBindingId,
+        T: FunctionBindingId<R>,
     {
         let id = self.arena.alloc(binding.into());
         self.indices.push(id);
@@ -263,35 +301,43 @@ impl FunctionBindings {
 }
 
 #[derive(Clone, Debug, Default)]
-pub struct Binds {
-    pub(crate) arena: id_arena::Arena<Bind>,
+pub struct Binds<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub(crate) arena: id_arena::Arena<Bind<R>>,
 }
 
-impl Binds {
-    pub fn get(&self, id: Id<Bind>) -> Option<&Bind> {
+impl<R: WasmRefs> Binds<R> {
+    #[allow(clippy::useless_conversion)]
+    pub fn get(&self, id: Id<Bind<R>>) -> Option<&Bind<R>> {
         self.arena.get(id.into())
     }
 
-    pub fn get_mut(&mut self, id: Id<Bind>) -> Option<&mut Bind> {
+    #[allow(clippy::useless_conversion)]
+    pub fn get_mut(&mut self, id: Id<Bind<R>>) -> Option<&mut Bind<R>> {
         self.arena.get_mut(id.into())
     }
 
-    pub fn insert(&mut self, bind: Bind) -> Id<Bind> {
+    pub fn insert(&mut self, bind: Bind<R>) -> Id<Bind<R>> {
         self.arena.alloc(bind)
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Id<Bind>, &'a Bind)> + 'a {
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Id<Bind<R>>, &'a Bind<R>)> + 'a {
         self.arena.iter()
     }
 }
 
+#[cfg(feature = "walrus")]
 #[derive(Debug)]
+#[cfg_attr(not(feature = "text"), allow(dead_code))]
 pub struct BuildAstActions<'a> {
     section: &'a mut WebidlBindings,
     module: &'a walrus::Module,
     ids: &'a walrus::IndicesToIds,
 }
 
+#[cfg(feature = "walrus")]
 impl<'a> BuildAstActions<'a> {
     pub fn new(
         section: &'a mut WebidlBindings,
@@ -327,7 +373,7 @@ impl<'a> text::Actions for BuildAstActions<'a> {
     fn webidl_function(
         &mut self,
         kind: Option<WebidlFunctionKind>,
-        params: Option<Vec<WebidlTypeRef>>,
+        params: Option<Vec<WebidlFunctionParam>>,
         result: Option<WebidlTypeRef>,
     ) -> WebidlFunctionId {
         let kind = kind.unwrap_or(WebidlFunctionKind::Static);
@@ -346,14 +392,68 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         WebidlFunctionKindMethod { ty }
     }
 
-    type WebidlFunctionKindConstructor = WebidlFunctionKind;
-    fn webidl_function_kind_constructor_default_new_target(&mut self) -> WebidlFunctionKind {
-        WebidlFunctionKind::Constructor
+    type WebidlFunctionKindConstructor = WebidlFunctionKindConstructor;
+    fn webidl_function_kind_constructor_default_new_target(
+        &mut self,
+    ) -> WebidlFunctionKindConstructor {
+        WebidlFunctionKindConstructor { new_target: None }
+    }
+    fn webidl_function_kind_constructor_new_target(
+        &mut self,
+        ty: WebidlTypeRef,
+    ) -> WebidlFunctionKindConstructor {
+        WebidlFunctionKindConstructor {
+            new_target: Some(ty),
+        }
+    }
+
+    type WebidlFunctionParams = Vec<WebidlFunctionParam>;
+    fn webidl_function_params(
+        &mut self,
+        required: Vec<WebidlTypeRef>,
+        optional: Vec<WebidlFunctionParam>,
+        variadic: Option<WebidlFunctionParam>,
+    ) -> Vec<WebidlFunctionParam> {
+        required
+            .into_iter()
+            .map(Into::into)
+            .chain(optional)
+            .chain(variadic)
+            .collect()
+    }
+
+    type WebidlFunctionParamOptional = WebidlFunctionParam;
+    fn webidl_function_param_optional(
+        &mut self,
+        ty: WebidlTypeRef,
+        default: Option<WebidlDefaultValue>,
+    ) -> WebidlFunctionParam {
+        WebidlFunctionParam {
+            ty,
+            kind: WebidlFunctionParamOptional { default }.into(),
+        }
+    }
+
+    type WebidlFunctionParamVariadic = WebidlFunctionParam;
+    fn webidl_function_param_variadic(&mut self, ty: WebidlTypeRef) -> WebidlFunctionParam {
+        WebidlFunctionParam {
+            ty,
+            kind: WebidlFunctionParamKind::Variadic,
+        }
     }
 
-    type WebidlFunctionParams = Vec<WebidlTypeRef>;
-    fn webidl_function_params(&mut self, tys: Vec<WebidlTypeRef>) -> Vec<WebidlTypeRef> {
-        tys
+    type WebidlDefaultValue = WebidlDefaultValue;
+    fn webidl_default_value_null(&mut self) -> WebidlDefaultValue {
+        WebidlDefaultValue::Null
+    }
+    fn webidl_default_value_boolean(&mut self, value: bool) -> WebidlDefaultValue {
+        WebidlDefaultValue::Boolean(value)
+    }
+    fn webidl_default_value_integer(&mut self, value: i64) -> WebidlDefaultValue {
+        WebidlDefaultValue::Integer(value)
+    }
+    fn webidl_default_value_string(&mut self, value: &str) -> WebidlDefaultValue {
+        WebidlDefaultValue::String(value.to_string())
     }
 
     type WebidlFunctionResult = WebidlTypeRef;
@@ -497,6 +597,26 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         OutgoingBindingExpressionUtf8CStr { ty, offset }
     }
 
+    type OutgoingBindingExpressionUtf16Str = OutgoingBindingExpressionUtf16Str;
+    fn outgoing_binding_expression_utf16_str(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpressionUtf16Str {
+        OutgoingBindingExpressionUtf16Str { ty, offset, length }
+    }
+
+    type OutgoingBindingExpressionLatin1Str = OutgoingBindingExpressionLatin1Str;
+    fn outgoing_binding_expression_latin1_str(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpressionLatin1Str {
+        OutgoingBindingExpressionLatin1Str { ty, offset, length }
+    }
+
     type OutgoingBindingExpressionI32ToEnum = OutgoingBindingExpressionI32ToEnum;
     fn outgoing_binding_expression_i32_to_enum(
         &mut self,
@@ -512,8 +632,16 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         ty: WebidlTypeRef,
         offset: u32,
         length: u32,
-    ) -> OutgoingBindingExpressionView {
-        OutgoingBindingExpressionView { ty, offset, length }
+        unit: Option<BufferLengthUnit>,
+    ) -> Option<OutgoingBindingExpressionView> {
+        ty.buffer_element_size()?;
+        let unit = unit.unwrap_or_default();
+        Some(OutgoingBindingExpressionView {
+            ty,
+            offset,
+            length,
+            unit,
+        })
     }
 
     type OutgoingBindingExpressionCopy = OutgoingBindingExpressionCopy;
@@ -522,8 +650,24 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         ty: WebidlTypeRef,
         offset: u32,
         length: u32,
-    ) -> OutgoingBindingExpressionCopy {
-        OutgoingBindingExpressionCopy { ty, offset, length }
+        unit: Option<BufferLengthUnit>,
+    ) -> Option<OutgoingBindingExpressionCopy> {
+        ty.buffer_element_size()?;
+        let unit = unit.unwrap_or_default();
+        Some(OutgoingBindingExpressionCopy {
+            ty,
+            offset,
+            length,
+            unit,
+        })
+    }
+
+    type BufferLengthUnit = BufferLengthUnit;
+    fn buffer_length_unit_elements(&mut self) -> BufferLengthUnit {
+        BufferLengthUnit::Elements
+    }
+    fn buffer_length_unit_bytes(&mut self) -> BufferLengthUnit {
+        BufferLengthUnit::Bytes
     }
 
     type OutgoingBindingExpressionDict = OutgoingBindingExpressionDict;
@@ -545,6 +689,27 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         OutgoingBindingExpressionBindExport { ty, binding, idx }
     }
 
+    type OutgoingBindingExpressionUndefined = OutgoingBindingExpressionUndefined;
+    fn outgoing_binding_expression_undefined(&mut self) -> OutgoingBindingExpressionUndefined {
+        OutgoingBindingExpressionUndefined
+    }
+
+    type OutgoingBindingExpressionVariadic = OutgoingBindingExpressionVariadic;
+    fn outgoing_binding_expression_variadic(
+        &mut self,
+        ty: WebidlTypeRef,
+        elem_ty: walrus::ValType,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpressionVariadic {
+        OutgoingBindingExpressionVariadic {
+            ty,
+            elem_ty,
+            offset,
+            length,
+        }
+    }
+
     type IncomingBindingExpression = IncomingBindingExpression;
 
     type IncomingBindingExpressionGet = IncomingBindingExpressionGet;
@@ -576,6 +741,79 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         }
     }
 
+    type IncomingBindingExpressionAllocUtf16Str = IncomingBindingExpressionAllocUtf16Str;
+    fn incoming_binding_expression_alloc_utf16_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionAllocUtf16Str {
+        let alloc_func_name = alloc_func_name.into();
+        let expr = Box::new(expr);
+        IncomingBindingExpressionAllocUtf16Str {
+            alloc_func_name,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionAllocLatin1Str = IncomingBindingExpressionAllocLatin1Str;
+    fn incoming_binding_expression_alloc_latin1_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionAllocLatin1Str {
+        let alloc_func_name = alloc_func_name.into();
+        let expr = Box::new(expr);
+        IncomingBindingExpressionAllocLatin1Str {
+            alloc_func_name,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionWriteUtf8Str = IncomingBindingExpressionWriteUtf8Str;
+    fn incoming_binding_expression_write_utf8_str(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionWriteUtf8Str {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionWriteUtf8Str {
+            offset,
+            capacity,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionWriteUtf16Str = IncomingBindingExpressionWriteUtf16Str;
+    fn incoming_binding_expression_write_utf16_str(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionWriteUtf16Str {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionWriteUtf16Str {
+            offset,
+            capacity,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionWriteCopy = IncomingBindingExpressionWriteCopy;
+    fn incoming_binding_expression_write_copy(
+        &mut self,
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionWriteCopy {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionWriteCopy {
+            offset,
+            capacity,
+            expr,
+        }
+    }
+
     type IncomingBindingExpressionAllocCopy = IncomingBindingExpressionAllocCopy;
     fn incoming_binding_expression_alloc_copy(
         &mut self,
@@ -633,6 +871,35 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         self.section.types.by_index(idx).map(Into::into)
     }
 
+    type WebidlTypeRefAnnotated = WebidlTypeRef;
+    fn webidl_type_ref_annotated(
+        &mut self,
+        attributes: Vec<WebidlExtendedAttribute>,
+        ty: WebidlScalarType,
+    ) -> Option<WebidlTypeRef> {
+        let mut set = WebidlExtendedAttributes::default();
+        for a in attributes {
+            if !set.insert(a) {
+                return None;
+            }
+        }
+        WebidlAnnotatedTypeRef::new(ty, set).map(Into::into)
+    }
+
+    type WebidlExtendedAttribute = WebidlExtendedAttribute;
+    fn webidl_extended_attribute_clamp(&mut self) -> WebidlExtendedAttribute {
+        WebidlExtendedAttribute::Clamp
+    }
+    fn webidl_extended_attribute_enforce_range(&mut self) -> WebidlExtendedAttribute {
+        WebidlExtendedAttribute::EnforceRange
+    }
+    fn webidl_extended_attribute_allow_shared(&mut self) -> WebidlExtendedAttribute {
+        WebidlExtendedAttribute::AllowShared
+    }
+    fn webidl_extended_attribute_legacy_null_to_empty_string(&mut self) -> WebidlExtendedAttribute {
+        WebidlExtendedAttribute::LegacyNullToEmptyString
+    }
+
     type WebidlScalarType = WebidlScalarType;
     fn webidl_scalar_type_any(&mut self) -> WebidlScalarType {
         WebidlScalarType::Any
@@ -664,6 +931,9 @@ impl<'a> text::Actions for BuildAstActions<'a> {
     fn webidl_scalar_type_unsigned_long_long(&mut self) -> WebidlScalarType {
         WebidlScalarType::UnsignedLongLong
     }
+    fn webidl_scalar_type_bigint(&mut self) -> WebidlScalarType {
+        WebidlScalarType::BigInt
+    }
     fn webidl_scalar_type_float(&mut self) -> WebidlScalarType {
         WebidlScalarType::Float
     }
@@ -788,7 +1058,7 @@ pub struct WebidlType {
     pub ty: WebidlCompoundType,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlCompoundType {
     Function(WebidlFunction),
     Dictionary(WebidlDictionary),
@@ -820,18 +1090,82 @@ impl From<WebidlUnion> for WebidlCompoundType {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlFunction {
     pub kind: WebidlFunctionKind,
-    pub params: Vec<WebidlTypeRef>,
+    pub params: Vec<WebidlFunctionParam>,
     pub result: Option<WebidlTypeRef>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parameter of a Web IDL function.
+///
+/// Optional parameters may only be followed by other optional parameters or
+/// a single, trailing variadic parameter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WebidlFunctionParam {
+    pub ty: WebidlTypeRef,
+    pub kind: WebidlFunctionParamKind,
+}
+
+impl From<WebidlTypeRef> for WebidlFunctionParam {
+    fn from(ty: WebidlTypeRef) -> Self {
+        WebidlFunctionParam {
+            ty,
+            kind: WebidlFunctionParamKind::Required,
+        }
+    }
+}
+
+impl From<WebidlScalarType> for WebidlFunctionParam {
+    fn from(s: WebidlScalarType) -> Self {
+        WebidlTypeRef::from(s).into()
+    }
+}
+
+impl From<Id<WebidlCompoundType>> for WebidlFunctionParam {
+    fn from(id: Id<WebidlCompoundType>) -> Self {
+        WebidlTypeRef::from(id).into()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WebidlFunctionParamKind {
+    /// A parameter that callers must always supply.
+    Required,
+    /// An `optional` parameter, with an optional default value.
+    Optional(WebidlFunctionParamOptional),
+    /// A `...` parameter that accepts zero or more trailing arguments.
+    Variadic,
+}
+
+impl From<WebidlFunctionParamOptional> for WebidlFunctionParamKind {
+    fn from(a: WebidlFunctionParamOptional) -> Self {
+        WebidlFunctionParamKind::Optional(a)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WebidlFunctionParamOptional {
+    pub default: Option<WebidlDefaultValue>,
+}
+
+/// The default value of an optional parameter.
+///
+/// This is the subset of Web IDL's `DefaultValue` production that doesn't
+/// involve floating point constants.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WebidlDefaultValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlFunctionKind {
     Static,
     Method(WebidlFunctionKindMethod),
-    Constructor,
+    Constructor(WebidlFunctionKindConstructor),
 }
 
 impl From<WebidlFunctionKindMethod> for WebidlFunctionKind {
@@ -840,38 +1174,58 @@ impl From<WebidlFunctionKindMethod> for WebidlFunctionKind {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl From<WebidlFunctionKindConstructor> for WebidlFunctionKind {
+    fn from(a: WebidlFunctionKindConstructor) -> Self {
+        WebidlFunctionKind::Constructor(a)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlFunctionKindMethod {
     pub ty: WebidlTypeRef,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A constructor function.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WebidlFunctionKindConstructor {
+    /// The type of `new.target` when constructing.
+    ///
+    /// `None` means the default new target, i.e. the constructor's own
+    /// interface. `Some` is used to describe construction of a subclass.
+    pub new_target: Option<WebidlTypeRef>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlDictionary {
     pub fields: Vec<WebidlDictionaryField>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlDictionaryField {
     pub name: String,
     pub ty: WebidlTypeRef,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlEnumeration {
     pub values: Vec<String>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WebidlUnion {
     pub members: Vec<WebidlTypeRef>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum FunctionBinding {
-    Import(ImportBinding),
-    Export(ExportBinding),
+pub enum FunctionBinding<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    Import(ImportBinding<R>),
+    Export(ExportBinding<R>),
 }
 
+#[cfg(feature = "walrus")]
 impl FunctionBinding {
     /// In the context of a JS embedder that does *not* implement the Web IDL
     /// bindings proposal, are this binding's ingoing and outgoing conversions
@@ -944,6 +1298,13 @@ impl FunctionBinding {
     /// | `byte`, `short`, `long`                                                                                                             | `i32`           |
     /// | `byte`, `octet`, `short`, `unsigned short`, `float`, `unrestricted float`                                                           | `f32`           |
     /// | `byte`, `octet`, `short`, `unsigned short`, `long`, `unsigned long`, `float`, `unrestricted float`, `double`, `unrestricted double` | `f64`           |
+    ///
+    /// ## Multiple Results
+    ///
+    /// A Web IDL function has at most one result, but the WebAssembly
+    /// JavaScript interface converts multiple Wasm results to and from a JS
+    /// array. Therefore, a binding for a Wasm function type with more than one
+    /// result is never expressible without Web IDL bindings.
     pub fn is_expressible_in_js_without_webidl_bindings(
         &self,
         module: &walrus::Module,
@@ -958,29 +1319,93 @@ impl FunctionBinding {
             }
         }
     }
+
+    /// Check that this binding's parameter and result binding maps agree with
+    /// the Wasm function type and Web IDL function type that it binds.
+    ///
+    /// The Wasm function type may have any number of results. An import
+    /// binding's result map must produce exactly that many Wasm values, in
+    /// order, from the Web IDL function's single result. An export binding's
+    /// result map must combine the Wasm results into a single Web IDL value,
+    /// or none if the Web IDL function has no result.
+    pub fn validate(&self, module: &walrus::Module, wb: &WebidlBindings) -> anyhow::Result<()> {
+        match self {
+            FunctionBinding::Import(i) => i.validate(module, wb),
+            FunctionBinding::Export(e) => e.validate(module, wb),
+        }
+    }
+}
+
+/// Get the Web IDL function type that a binding refers to.
+#[cfg(feature = "walrus")]
+fn binding_webidl_function(
+    wb: &WebidlBindings,
+    webidl_ty: WebidlTypeRef,
+) -> anyhow::Result<&WebidlFunction> {
+    match webidl_ty.id().and_then(|id| wb.types.get(id)) {
+        Some(WebidlCompoundType::Function(f)) => Ok(f),
+        _ => anyhow::bail!("{:?} is not a Web IDL function type", webidl_ty),
+    }
+}
+
+/// The types of the Web IDL values that a binding's params map deals with:
+/// the receiver of a method, and then one per parameter.
+#[cfg(feature = "walrus")]
+fn webidl_param_tys(f: &WebidlFunction) -> Vec<WebidlTypeRef> {
+    let receiver = match &f.kind {
+        WebidlFunctionKind::Method(m) => Some(m.ty),
+        _ => None,
+    };
+    receiver
+        .into_iter()
+        .chain(f.params.iter().map(|p| p.ty))
+        .collect()
 }
 
-impl From<ImportBinding> for FunctionBinding {
-    fn from(a: ImportBinding) -> Self {
+impl<R: WasmRefs> From<ImportBinding<R>> for FunctionBinding<R> {
+    fn from(a: ImportBinding<R>) -> Self {
         FunctionBinding::Import(a)
     }
 }
 
-impl From<ExportBinding> for FunctionBinding {
-    fn from(a: ExportBinding) -> Self {
+impl<R: WasmRefs> From<ExportBinding<R>> for FunctionBinding<R> {
+    fn from(a: ExportBinding<R>) -> Self {
         FunctionBinding::Export(a)
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ImportBinding {
-    pub wasm_ty: walrus::TypeId,
+pub struct ImportBinding<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub wasm_ty: R::FuncTypeRef,
     pub webidl_ty: WebidlTypeRef,
-    pub params: OutgoingBindingMap,
-    pub result: IncomingBindingMap,
+    pub params: OutgoingBindingMap<R>,
+    pub result: IncomingBindingMap<R>,
 }
 
+#[cfg(feature = "walrus")]
 impl ImportBinding {
+    /// Check that this import binding agrees with the types it binds.
+    ///
+    /// See `FunctionBinding::validate` for details.
+    pub fn validate(&self, module: &walrus::Module, wb: &WebidlBindings) -> anyhow::Result<()> {
+        let wasm_ty = module.types.get(self.wasm_ty);
+        let webidl_ty = binding_webidl_function(wb, self.webidl_ty)?;
+        self.params
+            .validate(wasm_ty.params(), webidl_param_tys(webidl_ty).len())
+            .map_err(|e| e.context("invalid import binding params"))?;
+        self.result
+            .validate(
+                &webidl_ty.result.into_iter().collect::<Vec<_>>(),
+                &wb.types,
+                wasm_ty.results(),
+                Some(wasm_ty.params()),
+            )
+            .map_err(|e| e.context("invalid import binding result"))
+    }
+
     /// Is this import binding expressible in JS without Web IDL bindings, and
     /// without a polyfill for them?
     ///
@@ -996,8 +1421,9 @@ impl ImportBinding {
             Some(WebidlCompoundType::Function(f)) if f.kind == WebidlFunctionKind::Static => f,
             _ => return false,
         };
+        let webidl_params: Vec<_> = webidl_ty.params.iter().map(|p| p.ty).collect();
         self.params
-            .is_expressible_in_js_without_webidl_bindings(wasm_ty.params(), &webidl_ty.params)
+            .is_expressible_in_js_without_webidl_bindings(wasm_ty.params(), &webidl_params)
             && self.result.is_expressible_in_js_without_webidl_bindings(
                 &webidl_ty.result.into_iter().collect::<Vec<_>>(),
                 wasm_ty.results(),
@@ -1006,14 +1432,37 @@ impl ImportBinding {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ExportBinding {
-    pub wasm_ty: walrus::TypeId,
+pub struct ExportBinding<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub wasm_ty: R::FuncTypeRef,
     pub webidl_ty: WebidlTypeRef,
-    pub params: IncomingBindingMap,
-    pub result: OutgoingBindingMap,
+    pub params: IncomingBindingMap<R>,
+    pub result: OutgoingBindingMap<R>,
 }
 
+#[cfg(feature = "walrus")]
 impl ExportBinding {
+    /// Check that this export binding agrees with the types it binds.
+    ///
+    /// See `FunctionBinding::validate` for details.
+    pub fn validate(&self, module: &walrus::Module, wb: &WebidlBindings) -> anyhow::Result<()> {
+        let wasm_ty = module.types.get(self.wasm_ty);
+        let webidl_ty = binding_webidl_function(wb, self.webidl_ty)?;
+        self.params
+            .validate(
+                &webidl_param_tys(webidl_ty),
+                &wb.types,
+                wasm_ty.params(),
+                None,
+            )
+            .map_err(|e| e.context("invalid export binding params"))?;
+        self.result
+            .validate(wasm_ty.results(), webidl_ty.result.iter().count())
+            .map_err(|e| e.context("invalid export binding result"))
+    }
+
     /// Is this export binding expressible in JS without Web IDL bindings, and
     /// without a polyfill for them?
     ///
@@ -1029,8 +1478,18 @@ impl ExportBinding {
             Some(WebidlCompoundType::Function(f)) if f.kind == WebidlFunctionKind::Static => f,
             _ => return false,
         };
+        // JS callers may omit optional and variadic arguments, which would
+        // then arrive in Wasm as `undefined` rather than their Web IDL default.
+        if webidl_ty
+            .params
+            .iter()
+            .any(|p| p.kind != WebidlFunctionParamKind::Required)
+        {
+            return false;
+        }
+        let webidl_params: Vec<_> = webidl_ty.params.iter().map(|p| p.ty).collect();
         self.params
-            .is_expressible_in_js_without_webidl_bindings(&webidl_ty.params, wasm_ty.params())
+            .is_expressible_in_js_without_webidl_bindings(&webidl_params, wasm_ty.params())
             && self.result.is_expressible_in_js_without_webidl_bindings(
                 wasm_ty.results(),
                 &webidl_ty.result.into_iter().collect::<Vec<_>>(),
@@ -1039,17 +1498,45 @@ impl ExportBinding {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Bind {
-    pub func: walrus::FunctionId,
-    pub binding: Id<FunctionBinding>,
+pub struct Bind<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub func: R::FuncRef,
+    pub binding: Id<FunctionBinding<R>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct OutgoingBindingMap {
-    pub bindings: Vec<OutgoingBindingExpression>,
+pub struct OutgoingBindingMap<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub bindings: Vec<OutgoingBindingExpression<R>>,
 }
 
+#[cfg(feature = "walrus")]
 impl OutgoingBindingMap {
+    /// Check that this map uses only the given Wasm values, with the right
+    /// types, and produces the given number of Web IDL values.
+    pub fn validate(
+        &self,
+        from_wasm_tys: &[walrus::ValType],
+        to_webidl_count: usize,
+    ) -> anyhow::Result<()> {
+        if self.bindings.len() != to_webidl_count {
+            anyhow::bail!(
+                "expected {} outgoing binding expressions, found {}",
+                to_webidl_count,
+                self.bindings.len()
+            );
+        }
+        for (i, expr) in self.bindings.iter().enumerate() {
+            expr.validate(from_wasm_tys)
+                .map_err(|e| e.context(format!("invalid outgoing binding expression {}", i)))?;
+        }
+        Ok(())
+    }
+
     /// Is this outgoing binding map expressible in JS without Web IDL bindings,
     /// and without a polyfill for them?
     ///
@@ -1081,11 +1568,75 @@ impl OutgoingBindingMap {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingMap {
-    pub bindings: Vec<IncomingBindingExpression>,
+pub struct IncomingBindingMap<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub bindings: Vec<IncomingBindingExpression<R>>,
 }
 
+#[cfg(feature = "walrus")]
 impl IncomingBindingMap {
+    /// Check that this map uses only the Web IDL values whose types are
+    /// given, and produces Wasm values of exactly the given types.
+    ///
+    /// Each `as` expression's Web IDL value must be compatible with the Wasm
+    /// value it produces, as `WebidlTypeRef::is_compatible_with` says, when
+    /// its type can be found from `from_webidl_tys` and the dictionaries in
+    /// `types`.
+    ///
+    /// Expressions that write into a buffer provided by the Wasm caller are
+    /// only allowed when `caller_wasm_tys` gives the types of the caller's
+    /// parameters, and the offset and capacity they use must be `i32`s.
+    pub fn validate(
+        &self,
+        from_webidl_tys: &[WebidlTypeRef],
+        types: &WebidlTypes,
+        to_wasm_tys: &[walrus::ValType],
+        caller_wasm_tys: Option<&[walrus::ValType]>,
+    ) -> anyhow::Result<()> {
+        let from_webidl_count = from_webidl_tys.len();
+        let mut produced = 0;
+        for (i, expr) in self.bindings.iter().enumerate() {
+            produced += expr
+                .validate(to_wasm_tys, produced, caller_wasm_tys)
+                .map_err(|e| e.context(format!("invalid incoming binding expression {}", i)))?;
+
+            if let IncomingBindingExpression::As(a) = expr {
+                match a.expr.webidl_ty(from_webidl_tys, types) {
+                    Some(ty) if !ty.is_compatible_with(a.ty) => anyhow::bail!(
+                        "incoming binding expression {} cannot convert {:?} to Wasm {}",
+                        i,
+                        ty,
+                        a.ty
+                    ),
+                    _ => {}
+                }
+            }
+
+            if let Some(idx) = expr
+                .webidl_indices()
+                .into_iter()
+                .find(|idx| *idx as usize >= from_webidl_count)
+            {
+                anyhow::bail!(
+                    "incoming binding expression {} gets Web IDL value {}, but there are only {}",
+                    i,
+                    idx,
+                    from_webidl_count
+                );
+            }
+        }
+        if produced != to_wasm_tys.len() {
+            anyhow::bail!(
+                "incoming binding expressions produce {} Wasm values, but {} are expected",
+                produced,
+                to_wasm_tys.len()
+            );
+        }
+        Ok(())
+    }
+
     /// Is this incoming binding map expressible in JS without Web IDL bindings,
     /// and without a polyfill for them?
     ///
@@ -1117,71 +1668,202 @@ impl IncomingBindingMap {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum OutgoingBindingExpression {
+pub enum OutgoingBindingExpression<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     As(OutgoingBindingExpressionAs),
     Utf8Str(OutgoingBindingExpressionUtf8Str),
     Utf8CStr(OutgoingBindingExpressionUtf8CStr),
     I32ToEnum(OutgoingBindingExpressionI32ToEnum),
     View(OutgoingBindingExpressionView),
     Copy(OutgoingBindingExpressionCopy),
-    Dict(OutgoingBindingExpressionDict),
-    BindExport(OutgoingBindingExpressionBindExport),
+    Dict(OutgoingBindingExpressionDict<R>),
+    BindExport(OutgoingBindingExpressionBindExport<R>),
+    Undefined(OutgoingBindingExpressionUndefined),
+    Variadic(OutgoingBindingExpressionVariadic<R>),
+    Utf16Str(OutgoingBindingExpressionUtf16Str),
+    Latin1Str(OutgoingBindingExpressionLatin1Str),
 }
 
-impl From<OutgoingBindingExpressionAs> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionAs> for OutgoingBindingExpression<R> {
     fn from(a: OutgoingBindingExpressionAs) -> Self {
         OutgoingBindingExpression::As(a)
     }
 }
 
-impl From<OutgoingBindingExpressionUtf8Str> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionUtf8Str> for OutgoingBindingExpression<R> {
     fn from(s: OutgoingBindingExpressionUtf8Str) -> Self {
         OutgoingBindingExpression::Utf8Str(s)
     }
 }
 
-impl From<OutgoingBindingExpressionUtf8CStr> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionUtf8CStr> for OutgoingBindingExpression<R> {
     fn from(s: OutgoingBindingExpressionUtf8CStr) -> Self {
         OutgoingBindingExpression::Utf8CStr(s)
     }
 }
 
-impl From<OutgoingBindingExpressionI32ToEnum> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionI32ToEnum> for OutgoingBindingExpression<R> {
     fn from(s: OutgoingBindingExpressionI32ToEnum) -> Self {
         OutgoingBindingExpression::I32ToEnum(s)
     }
 }
 
-impl From<OutgoingBindingExpressionView> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionView> for OutgoingBindingExpression<R> {
     fn from(s: OutgoingBindingExpressionView) -> Self {
         OutgoingBindingExpression::View(s)
     }
 }
 
-impl From<OutgoingBindingExpressionCopy> for OutgoingBindingExpression {
+impl<R: WasmRefs> From<OutgoingBindingExpressionCopy> for OutgoingBindingExpression<R> {
     fn from(s: OutgoingBindingExpressionCopy) -> Self {
         OutgoingBindingExpression::Copy(s)
     }
 }
 
-impl From<OutgoingBindingExpressionDict> for OutgoingBindingExpression {
-    fn from(s: OutgoingBindingExpressionDict) -> Self {
+impl<R: WasmRefs> From<OutgoingBindingExpressionDict<R>> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionDict<R>) -> Self {
         OutgoingBindingExpression::Dict(s)
     }
 }
 
-impl From<OutgoingBindingExpressionBindExport> for OutgoingBindingExpression {
-    fn from(s: OutgoingBindingExpressionBindExport) -> Self {
+impl<R: WasmRefs> From<OutgoingBindingExpressionBindExport<R>> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionBindExport<R>) -> Self {
         OutgoingBindingExpression::BindExport(s)
     }
 }
 
+impl<R: WasmRefs> From<OutgoingBindingExpressionUndefined> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionUndefined) -> Self {
+        OutgoingBindingExpression::Undefined(s)
+    }
+}
+
+impl<R: WasmRefs> From<OutgoingBindingExpressionVariadic<R>> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionVariadic<R>) -> Self {
+        OutgoingBindingExpression::Variadic(s)
+    }
+}
+
+impl<R: WasmRefs> From<OutgoingBindingExpressionUtf16Str> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionUtf16Str) -> Self {
+        OutgoingBindingExpression::Utf16Str(s)
+    }
+}
+
+impl<R: WasmRefs> From<OutgoingBindingExpressionLatin1Str> for OutgoingBindingExpression<R> {
+    fn from(s: OutgoingBindingExpressionLatin1Str) -> Self {
+        OutgoingBindingExpression::Latin1Str(s)
+    }
+}
+
+#[cfg(feature = "walrus")]
 impl OutgoingBindingExpression {
+    /// Get the indices of the Wasm values that this expression uses.
+    pub fn wasm_indices(&self) -> Vec<u32> {
+        match self {
+            OutgoingBindingExpression::As(e) => vec![e.idx],
+            OutgoingBindingExpression::Utf8Str(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Utf8CStr(e) => vec![e.offset],
+            OutgoingBindingExpression::I32ToEnum(e) => vec![e.idx],
+            OutgoingBindingExpression::View(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Copy(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Dict(e) => {
+                e.fields.iter().flat_map(|f| f.wasm_indices()).collect()
+            }
+            OutgoingBindingExpression::BindExport(e) => vec![e.idx],
+            OutgoingBindingExpression::Undefined(_) => vec![],
+            OutgoingBindingExpression::Variadic(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Utf16Str(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Latin1Str(e) => vec![e.offset, e.length],
+        }
+    }
+
+    /// Check that this expression uses only the given Wasm values, and that
+    /// they have the right types.
+    ///
+    /// Offsets, lengths, and enum values must be `i32`s, and a bound export's
+    /// function must be an `i32` table index or an `anyref`. `as` values and
+    /// the elements of a variadic sequence must be compatible with their Web
+    /// IDL type, as `WebidlTypeRef::is_compatible_with` says.
+    pub fn validate(&self, from_wasm_tys: &[walrus::ValType]) -> anyhow::Result<()> {
+        let get = |idx: u32| match from_wasm_tys.get(idx as usize) {
+            Some(ty) => Ok(*ty),
+            None => anyhow::bail!(
+                "uses Wasm value {}, but there are only {}",
+                idx,
+                from_wasm_tys.len()
+            ),
+        };
+        let i32_indices = match self {
+            OutgoingBindingExpression::Utf8Str(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Utf8CStr(e) => vec![e.offset],
+            OutgoingBindingExpression::I32ToEnum(e) => vec![e.idx],
+            OutgoingBindingExpression::View(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Copy(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Variadic(e) => {
+                let in_memory = matches!(
+                    e.elem_ty,
+                    walrus::ValType::I32
+                        | walrus::ValType::I64
+                        | walrus::ValType::F32
+                        | walrus::ValType::F64
+                );
+                if !in_memory || !e.ty.is_compatible_with(e.elem_ty) {
+                    anyhow::bail!(
+                        "cannot convert variadic elements of type {} to {:?}",
+                        e.elem_ty,
+                        e.ty
+                    );
+                }
+                vec![e.offset, e.length]
+            }
+            OutgoingBindingExpression::Utf16Str(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Latin1Str(e) => vec![e.offset, e.length],
+            OutgoingBindingExpression::Dict(e) => {
+                for f in &e.fields {
+                    f.validate(from_wasm_tys)?;
+                }
+                return Ok(());
+            }
+            OutgoingBindingExpression::As(e) => {
+                let ty = get(e.idx)?;
+                if !e.ty.is_compatible_with(ty) {
+                    anyhow::bail!(
+                        "cannot convert Wasm value {} of type {} to {:?}",
+                        e.idx,
+                        ty,
+                        e.ty
+                    );
+                }
+                vec![]
+            }
+            OutgoingBindingExpression::BindExport(e) => match get(e.idx)? {
+                walrus::ValType::I32 | walrus::ValType::Anyref => vec![],
+                ty => anyhow::bail!(
+                    "expected Wasm value {} to be an i32 table index or an anyref, found {}",
+                    e.idx,
+                    ty
+                ),
+            },
+            OutgoingBindingExpression::Undefined(_) => vec![],
+        };
+        for idx in i32_indices {
+            let ty = get(idx)?;
+            if ty != walrus::ValType::I32 {
+                anyhow::bail!("expected Wasm value {} to be i32, found {}", idx, ty);
+            }
+        }
+        Ok(())
+    }
+
     /// Is this outgoing binding expression expressible in JS without Web IDL
     /// bindings, and without a polyfill for them?
     ///
     /// See `FunctionBinding::is_expressible_in_js_without_webidl_bindings` for
     /// details.
+    #[allow(clippy::match_like_matches_macro)]
     pub fn is_expressible_in_js_without_webidl_bindings(
         &self,
         from_wasm_ty: walrus::ValType,
@@ -1193,8 +1875,11 @@ impl OutgoingBindingExpression {
                 return false;
             }
 
+            // Extended attributes only affect conversions from JS, so they
+            // don't matter for outgoing values.
             let to_webidl_ty = match to_webidl_ty {
                 WebidlTypeRef::Scalar(s) => s,
+                WebidlTypeRef::Annotated(a) => a.ty,
                 _ => return false,
             };
 
@@ -1210,6 +1895,9 @@ impl OutgoingBindingExpression {
                 | (walrus::ValType::I32, WebidlScalarType::UnrestrictedFloat)
                 | (walrus::ValType::I32, WebidlScalarType::Double)
                 | (walrus::ValType::I32, WebidlScalarType::UnrestrictedDouble)
+                | (walrus::ValType::I64, WebidlScalarType::LongLong)
+                | (walrus::ValType::I64, WebidlScalarType::UnsignedLongLong)
+                | (walrus::ValType::I64, WebidlScalarType::BigInt)
                 | (walrus::ValType::F32, WebidlScalarType::Float)
                 | (walrus::ValType::F32, WebidlScalarType::UnrestrictedFloat)
                 | (walrus::ValType::F32, WebidlScalarType::Double)
@@ -1249,92 +1937,406 @@ pub struct OutgoingBindingExpressionI32ToEnum {
     pub idx: u32,
 }
 
+/// Create a view of linear memory as a buffer source type.
+///
+/// The view starts at the address given by the `offset` parameter, and its
+/// length is the value of the `length` parameter, counted in `unit`s. The `ty`
+/// must be `ArrayBuffer`, `DataView`, or a typed array type.
+///
+/// A typed array's byte offset must be a multiple of its element size, so a
+/// typed array cannot view linear memory at a misaligned address. In that case
+/// the bytes must be copied into a fresh buffer instead, as if this were an
+/// `OutgoingBindingExpressionCopy`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OutgoingBindingExpressionView {
     pub ty: WebidlTypeRef,
     pub offset: u32,
     pub length: u32,
+    pub unit: BufferLengthUnit,
+}
+
+impl OutgoingBindingExpressionView {
+    /// Get the range of linear memory addresses viewed, given the values of
+    /// the `offset` and `length` parameters.
+    pub fn byte_range(&self, offset: u32, length: u32) -> anyhow::Result<ops::Range<u32>> {
+        buffer_byte_range(self.ty, self.unit, offset, length)
+    }
+
+    /// Can a view be created directly over linear memory at the given
+    /// address, or must the bytes be copied because they are misaligned?
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub fn is_aligned(&self, offset: u32) -> bool {
+        match self.ty.buffer_element_size() {
+            Some(size) => offset % size == 0,
+            None => false,
+        }
+    }
 }
 
+/// Copy a region of linear memory into a fresh buffer source value.
+///
+/// The region starts at the address given by the `offset` parameter, and its
+/// length is the value of the `length` parameter, counted in `unit`s. The `ty`
+/// must be `ArrayBuffer`, `DataView`, or a typed array type. Because the bytes
+/// are copied, the region does not need to be aligned.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OutgoingBindingExpressionCopy {
     pub ty: WebidlTypeRef,
     pub offset: u32,
     pub length: u32,
+    pub unit: BufferLengthUnit,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct OutgoingBindingExpressionDict {
-    pub ty: WebidlTypeRef,
-    pub fields: Vec<OutgoingBindingExpression>,
+impl OutgoingBindingExpressionCopy {
+    /// Get the range of linear memory addresses copied, given the values of
+    /// the `offset` and `length` parameters.
+    pub fn byte_range(&self, offset: u32, length: u32) -> anyhow::Result<ops::Range<u32>> {
+        buffer_byte_range(self.ty, self.unit, offset, length)
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct OutgoingBindingExpressionBindExport {
-    pub ty: WebidlTypeRef,
-    pub binding: Id<FunctionBinding>,
-    pub idx: u32,
+/// What the `length` parameter of a view or copy counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BufferLengthUnit {
+    /// The length is the number of elements of the typed array, like the
+    /// `length` argument of JS typed array constructors.
+    Elements,
+    /// The length is the number of bytes, and must be a multiple of the
+    /// element size.
+    Bytes,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum IncomingBindingExpression {
-    Get(IncomingBindingExpressionGet),
-    As(IncomingBindingExpressionAs),
-    AllocUtf8Str(IncomingBindingExpressionAllocUtf8Str),
-    AllocCopy(IncomingBindingExpressionAllocCopy),
-    EnumToI32(IncomingBindingExpressionEnumToI32),
-    Field(IncomingBindingExpressionField),
-    BindImport(IncomingBindingExpressionBindImport),
+#[allow(clippy::derivable_impls)]
+impl Default for BufferLengthUnit {
+    fn default() -> BufferLengthUnit {
+        BufferLengthUnit::Elements
+    }
 }
 
-impl From<IncomingBindingExpressionGet> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionGet) -> Self {
-        IncomingBindingExpression::Get(a)
+#[allow(unknown_lints, clippy::manual_is_multiple_of)]
+fn buffer_byte_range(
+    ty: WebidlTypeRef,
+    unit: BufferLengthUnit,
+    offset: u32,
+    length: u32,
+) -> anyhow::Result<ops::Range<u32>> {
+    let size = match ty.buffer_element_size() {
+        Some(size) => size,
+        None => anyhow::bail!("{:?} is not a buffer source type", ty),
+    };
+    let byte_length = match unit {
+        BufferLengthUnit::Elements => match length.checked_mul(size) {
+            Some(n) => n,
+            None => anyhow::bail!("{} elements of {} bytes overflow a u32", length, size),
+        },
+        BufferLengthUnit::Bytes => {
+            if length % size != 0 {
+                anyhow::bail!(
+                    "byte length {} is not a multiple of the element size {}",
+                    length,
+                    size
+                );
+            }
+            length
+        }
+    };
+    match offset.checked_add(byte_length) {
+        Some(end) => Ok(offset..end),
+        None => anyhow::bail!("{} + {} overflows a u32", offset, byte_length),
     }
 }
 
-impl From<IncomingBindingExpressionAs> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionAs) -> Self {
-        IncomingBindingExpression::As(a)
-    }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionDict<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub ty: WebidlTypeRef,
+    pub fields: Vec<OutgoingBindingExpression<R>>,
 }
 
-impl From<IncomingBindingExpressionAllocUtf8Str> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionAllocUtf8Str) -> Self {
-        IncomingBindingExpression::AllocUtf8Str(a)
-    }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionBindExport<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub ty: WebidlTypeRef,
+    pub binding: Id<FunctionBinding<R>>,
+    pub idx: u32,
 }
 
-impl From<IncomingBindingExpressionAllocCopy> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionAllocCopy) -> Self {
+/// Pass `undefined` for an omitted optional parameter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionUndefined;
+
+/// Spread a sequence of values out of linear memory as the arguments of a
+/// variadic parameter.
+///
+/// The sequence starts at the address given by the `offset` parameter and
+/// contains as many elements as the value of the `length` parameter. Each
+/// element is a naturally aligned `elem_ty` value that is converted to `ty`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionVariadic<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub ty: WebidlTypeRef,
+    pub elem_ty: R::ValType,
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// Decode a UTF-16 string out of linear memory.
+///
+/// The string starts at the (two byte aligned) address given by the `offset`
+/// parameter and is as many 16-bit code units long as the value of the
+/// `length` parameter. Lone surrogates are preserved in a `DOMString` but
+/// replaced with U+FFFD in a `USVString`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionUtf16Str {
+    pub ty: WebidlTypeRef,
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// Decode a Latin-1 string out of linear memory.
+///
+/// The string starts at the address given by the `offset` parameter and is as
+/// many bytes long as the value of the `length` parameter. Each byte is the
+/// code point of one character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionLatin1Str {
+    pub ty: WebidlTypeRef,
+    pub offset: u32,
+    pub length: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncomingBindingExpression<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    Get(IncomingBindingExpressionGet),
+    As(IncomingBindingExpressionAs<R>),
+    AllocUtf8Str(IncomingBindingExpressionAllocUtf8Str<R>),
+    AllocCopy(IncomingBindingExpressionAllocCopy<R>),
+    EnumToI32(IncomingBindingExpressionEnumToI32<R>),
+    Field(IncomingBindingExpressionField<R>),
+    BindImport(IncomingBindingExpressionBindImport<R>),
+    AllocUtf16Str(IncomingBindingExpressionAllocUtf16Str<R>),
+    AllocLatin1Str(IncomingBindingExpressionAllocLatin1Str<R>),
+    WriteUtf8Str(IncomingBindingExpressionWriteUtf8Str<R>),
+    WriteUtf16Str(IncomingBindingExpressionWriteUtf16Str<R>),
+    WriteCopy(IncomingBindingExpressionWriteCopy<R>),
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionGet> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionGet) -> Self {
+        IncomingBindingExpression::Get(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionAs<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionAs<R>) -> Self {
+        IncomingBindingExpression::As(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionAllocUtf8Str<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionAllocUtf8Str<R>) -> Self {
+        IncomingBindingExpression::AllocUtf8Str(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionAllocCopy<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionAllocCopy<R>) -> Self {
         IncomingBindingExpression::AllocCopy(a)
     }
 }
 
-impl From<IncomingBindingExpressionEnumToI32> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionEnumToI32) -> Self {
+impl<R: WasmRefs> From<IncomingBindingExpressionEnumToI32<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionEnumToI32<R>) -> Self {
         IncomingBindingExpression::EnumToI32(a)
     }
 }
 
-impl From<IncomingBindingExpressionField> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionField) -> Self {
+impl<R: WasmRefs> From<IncomingBindingExpressionField<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionField<R>) -> Self {
         IncomingBindingExpression::Field(a)
     }
 }
 
-impl From<IncomingBindingExpressionBindImport> for IncomingBindingExpression {
-    fn from(a: IncomingBindingExpressionBindImport) -> Self {
+impl<R: WasmRefs> From<IncomingBindingExpressionBindImport<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionBindImport<R>) -> Self {
         IncomingBindingExpression::BindImport(a)
     }
 }
 
+impl<R: WasmRefs> From<IncomingBindingExpressionAllocUtf16Str<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionAllocUtf16Str<R>) -> Self {
+        IncomingBindingExpression::AllocUtf16Str(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionAllocLatin1Str<R>>
+    for IncomingBindingExpression<R>
+{
+    fn from(a: IncomingBindingExpressionAllocLatin1Str<R>) -> IncomingBindingExpression<R> {
+        IncomingBindingExpression::AllocLatin1Str(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionWriteUtf8Str<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionWriteUtf8Str<R>) -> Self {
+        IncomingBindingExpression::WriteUtf8Str(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionWriteUtf16Str<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionWriteUtf16Str<R>) -> Self {
+        IncomingBindingExpression::WriteUtf16Str(a)
+    }
+}
+
+impl<R: WasmRefs> From<IncomingBindingExpressionWriteCopy<R>> for IncomingBindingExpression<R> {
+    fn from(a: IncomingBindingExpressionWriteCopy<R>) -> Self {
+        IncomingBindingExpression::WriteCopy(a)
+    }
+}
+
+#[cfg(feature = "walrus")]
 impl IncomingBindingExpression {
+    /// Get the indices of the Web IDL values that this expression gets.
+    /// The Web IDL type of the value that this expression gets, if it is a
+    /// `get` of one of `from_webidl_tys`, or a `field` of a dictionary in
+    /// `types` that can be found the same way.
+    fn webidl_ty(
+        &self,
+        from_webidl_tys: &[WebidlTypeRef],
+        types: &WebidlTypes,
+    ) -> Option<WebidlTypeRef> {
+        match self {
+            IncomingBindingExpression::Get(g) => from_webidl_tys.get(g.idx as usize).cloned(),
+            IncomingBindingExpression::Field(f) => {
+                let dict = f.expr.webidl_ty(from_webidl_tys, types)?.id()?;
+                match types.get::<WebidlCompoundType>(dict) {
+                    Some(WebidlCompoundType::Dictionary(d)) => {
+                        d.fields.get(f.idx as usize).map(|f| f.ty)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn webidl_indices(&self) -> Vec<u32> {
+        match self {
+            IncomingBindingExpression::Get(e) => vec![e.idx],
+            IncomingBindingExpression::As(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::AllocUtf8Str(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::AllocCopy(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::EnumToI32(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::Field(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::BindImport(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::AllocUtf16Str(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::AllocLatin1Str(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::WriteUtf8Str(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::WriteUtf16Str(e) => e.expr.webidl_indices(),
+            IncomingBindingExpression::WriteCopy(e) => e.expr.webidl_indices(),
+        }
+    }
+
+    /// Get the indices of the Wasm caller's parameters that this expression
+    /// uses, which is only the case for the `write-*` expressions.
+    pub fn caller_wasm_indices(&self) -> Vec<u32> {
+        match self {
+            IncomingBindingExpression::WriteUtf8Str(e) => vec![e.offset, e.capacity],
+            IncomingBindingExpression::WriteUtf16Str(e) => vec![e.offset, e.capacity],
+            IncomingBindingExpression::WriteCopy(e) => vec![e.offset, e.capacity],
+            _ => vec![],
+        }
+    }
+
+    /// Check that this expression produces Wasm values of the types starting
+    /// at position `produced` in `to_wasm_tys`, and return how many values it
+    /// produces.
+    ///
+    /// The `alloc-*` expressions produce two `i32`s, `enum-to-i32` and the
+    /// `write-*` expressions produce one `i32`, and `bind-import` produces an
+    /// `anyref`. The `write-*` expressions are only allowed when
+    /// `caller_wasm_tys` gives the types of the Wasm caller's parameters, and
+    /// their offset and capacity must be `i32`s.
+    pub fn validate(
+        &self,
+        to_wasm_tys: &[walrus::ValType],
+        produced: usize,
+        caller_wasm_tys: Option<&[walrus::ValType]>,
+    ) -> anyhow::Result<usize> {
+        use walrus::ValType::{Anyref, I32};
+
+        let produces = match self {
+            IncomingBindingExpression::As(a) => vec![a.ty],
+            IncomingBindingExpression::AllocUtf8Str(_)
+            | IncomingBindingExpression::AllocCopy(_)
+            | IncomingBindingExpression::AllocUtf16Str(_)
+            | IncomingBindingExpression::AllocLatin1Str(_) => vec![I32, I32],
+            IncomingBindingExpression::EnumToI32(_)
+            | IncomingBindingExpression::WriteUtf8Str(_)
+            | IncomingBindingExpression::WriteUtf16Str(_)
+            | IncomingBindingExpression::WriteCopy(_) => vec![I32],
+            IncomingBindingExpression::BindImport(_) => vec![Anyref],
+            IncomingBindingExpression::Get(_) | IncomingBindingExpression::Field(_) => {
+                anyhow::bail!("produces a Web IDL value, not Wasm values")
+            }
+        };
+        for (i, ty) in produces.iter().enumerate() {
+            match to_wasm_tys.get(produced + i) {
+                Some(expected) if expected == ty => {}
+                Some(expected) => anyhow::bail!(
+                    "produces {}, but Wasm value {} is {}",
+                    ty,
+                    produced + i,
+                    expected
+                ),
+                None => anyhow::bail!(
+                    "produces more than the {} expected Wasm values",
+                    to_wasm_tys.len()
+                ),
+            }
+        }
+
+        let caller_indices = self.caller_wasm_indices();
+        match caller_wasm_tys {
+            None if !caller_indices.is_empty() => {
+                anyhow::bail!("writes into a caller's buffer, but there is no Wasm caller")
+            }
+            Some(tys) => {
+                for idx in caller_indices {
+                    match tys.get(idx as usize) {
+                        Some(I32) => {}
+                        Some(ty) => {
+                            anyhow::bail!("expected Wasm param {} to be i32, found {}", idx, ty)
+                        }
+                        None => anyhow::bail!(
+                            "uses Wasm param {}, but there are only {}",
+                            idx,
+                            tys.len()
+                        ),
+                    }
+                }
+            }
+            None => {}
+        }
+        Ok(produces.len())
+    }
+
     /// Is this incoming binding expression expressible in JS without Web IDL
     /// bindings, and without a polyfill for them?
     ///
     /// See `FunctionBinding::is_expressible_in_js_without_webidl_bindings` for
     /// details.
+    #[allow(clippy::match_like_matches_macro)]
     pub fn is_expressible_in_js_without_webidl_bindings(
         &self,
         from_webidl_ty: WebidlTypeRef,
@@ -1351,8 +2353,15 @@ impl IncomingBindingExpression {
                     return false;
                 }
 
+                // The JS API always wraps integers, so it can't clamp or
+                // enforce their range.
                 let from_webidl_ty = match from_webidl_ty {
                     WebidlTypeRef::Scalar(s) => s,
+                    WebidlTypeRef::Annotated(a)
+                        if a.attributes.integer_conversion() == WebidlIntegerConversion::Wrap =>
+                    {
+                        a.ty
+                    }
                     _ => return false,
                 };
 
@@ -1365,6 +2374,9 @@ impl IncomingBindingExpression {
                     | (WebidlScalarType::UnsignedShort, walrus::ValType::I32)
                     | (WebidlScalarType::Long, walrus::ValType::I32)
                     | (WebidlScalarType::UnsignedLong, walrus::ValType::I32)
+                    | (WebidlScalarType::LongLong, walrus::ValType::I64)
+                    | (WebidlScalarType::UnsignedLongLong, walrus::ValType::I64)
+                    | (WebidlScalarType::BigInt, walrus::ValType::I64)
                     | (WebidlScalarType::Byte, walrus::ValType::F32)
                     | (WebidlScalarType::Octet, walrus::ValType::F32)
                     | (WebidlScalarType::Short, walrus::ValType::F32)
@@ -1398,46 +2410,204 @@ pub struct IncomingBindingExpressionGet {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionAs {
-    pub ty: walrus::ValType,
-    pub expr: Box<IncomingBindingExpression>,
+pub struct IncomingBindingExpressionAs<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub ty: R::ValType,
+    pub expr: Box<IncomingBindingExpression<R>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionAllocUtf8Str {
+pub struct IncomingBindingExpressionAllocUtf8Str<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     pub alloc_func_name: String,
-    pub expr: Box<IncomingBindingExpression>,
+    pub expr: Box<IncomingBindingExpression<R>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionAllocCopy {
+pub struct IncomingBindingExpressionAllocCopy<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     pub alloc_func_name: String,
-    pub expr: Box<IncomingBindingExpression>,
+    pub expr: Box<IncomingBindingExpression<R>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionEnumToI32 {
+pub struct IncomingBindingExpressionEnumToI32<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     pub ty: WebidlTypeRef,
-    pub expr: Box<IncomingBindingExpression>,
+    pub expr: Box<IncomingBindingExpression<R>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionField {
+pub struct IncomingBindingExpressionField<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
     pub idx: u32,
-    pub expr: Box<IncomingBindingExpression>,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionBindImport<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub ty: R::FuncTypeRef,
+    pub binding: Id<FunctionBinding<R>>,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+/// Encode a string as UTF-16 into a fresh allocation in linear memory,
+/// producing the allocation's offset and its length in 16-bit code units.
+///
+/// Unlike UTF-8, UTF-16 can represent lone surrogates, so a `DOMString` is
+/// copied without loss.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionAllocUtf16Str<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub alloc_func_name: String,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+/// Encode a string as Latin-1 into a fresh allocation in linear memory,
+/// producing the allocation's offset and its length in bytes.
+///
+/// It is an error if the string contains a code unit greater than 255.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionAllocLatin1Str<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub alloc_func_name: String,
+    pub expr: Box<IncomingBindingExpression<R>>,
 }
 
+/// Encode a string as UTF-8 into a buffer provided by the Wasm caller,
+/// producing the number of bytes written.
+///
+/// The buffer starts at the address given by the Wasm parameter at index
+/// `offset`, and is as many bytes long as the value of the Wasm parameter at
+/// index `capacity`. Since these are parameters of the bound Wasm function,
+/// this expression may only appear in an import binding's result.
+///
+/// If the string does not fit, it is truncated to the longest prefix of whole
+/// characters that does, like `TextEncoder.prototype.encodeInto`. Lone
+/// surrogates are encoded as U+FFFD.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct IncomingBindingExpressionBindImport {
-    pub ty: walrus::TypeId,
-    pub binding: Id<FunctionBinding>,
-    pub expr: Box<IncomingBindingExpression>,
+pub struct IncomingBindingExpressionWriteUtf8Str<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub offset: u32,
+    pub capacity: u32,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+#[cfg(feature = "walrus")]
+impl IncomingBindingExpressionWriteUtf8Str {
+    /// Write the string given as UTF-16 code units into `buffer` with this
+    /// expression's truncation semantics, and return the number of bytes
+    /// written.
+    pub fn write(units: &[u16], buffer: &mut [u8]) -> u32 {
+        let mut written = 0;
+        for c in std::char::decode_utf16(units.iter().cloned()) {
+            let c = c.unwrap_or(std::char::REPLACEMENT_CHARACTER);
+            if buffer.len() - written < c.len_utf8() {
+                break;
+            }
+            written += c.encode_utf8(&mut buffer[written..]).len();
+        }
+        written as u32
+    }
+}
+
+/// Encode a string as UTF-16 into a buffer provided by the Wasm caller,
+/// producing the number of 16-bit code units written.
+///
+/// The buffer starts at the (two byte aligned) address given by the Wasm
+/// parameter at index `offset`, and has room for as many code units as the
+/// value of the Wasm parameter at index `capacity`. Like
+/// `IncomingBindingExpressionWriteUtf8Str`, this expression may only appear in
+/// an import binding's result.
+///
+/// If the string does not fit, it is truncated, but a surrogate pair is never
+/// split. Lone surrogates are written as-is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionWriteUtf16Str<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub offset: u32,
+    pub capacity: u32,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+#[cfg(feature = "walrus")]
+impl IncomingBindingExpressionWriteUtf16Str {
+    /// Write the string given as UTF-16 code units into `buffer` as little
+    /// endian code units with this expression's truncation semantics, and
+    /// return the number of code units written.
+    pub fn write(units: &[u16], buffer: &mut [u8]) -> u32 {
+        let capacity = buffer.len() / 2;
+        let mut n = units.len().min(capacity);
+        if n < units.len() && n > 0 && (0xD800..0xDC00).contains(&units[n - 1]) {
+            // Don't split a surrogate pair (or leave a trailing lead
+            // surrogate that would have been one).
+            n -= 1;
+        }
+        for (i, u) in units[..n].iter().enumerate() {
+            buffer[2 * i..2 * i + 2].copy_from_slice(&u.to_le_bytes());
+        }
+        n as u32
+    }
+}
+
+/// Copy the contents of a buffer source value into a buffer provided by the
+/// Wasm caller, producing the number of bytes written.
+///
+/// The buffer starts at the address given by the Wasm parameter at index
+/// `offset`, and is as many bytes long as the value of the Wasm parameter at
+/// index `capacity`. Like `IncomingBindingExpressionWriteUtf8Str`, this
+/// expression may only appear in an import binding's result.
+///
+/// If the value does not fit, it is truncated to as many whole elements as do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionWriteCopy<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    pub offset: u32,
+    pub capacity: u32,
+    pub expr: Box<IncomingBindingExpression<R>>,
+}
+
+#[cfg(feature = "walrus")]
+impl IncomingBindingExpressionWriteCopy {
+    /// Copy the bytes of a buffer source value whose elements are
+    /// `element_size` bytes long into `buffer` with this expression's
+    /// truncation semantics, and return the number of bytes written.
+    pub fn write(bytes: &[u8], element_size: u32, buffer: &mut [u8]) -> u32 {
+        let element_size = element_size.max(1) as usize;
+        let n = bytes.len().min(buffer.len() / element_size * element_size);
+        buffer[..n].copy_from_slice(&bytes[..n]);
+        n as u32
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlTypeRef {
     Id(Id<WebidlCompoundType>),
     Scalar(WebidlScalarType),
+    Annotated(WebidlAnnotatedTypeRef),
 }
 
 impl WebidlTypeRef {
@@ -1452,12 +2622,87 @@ impl WebidlTypeRef {
 
     /// Get this `WebidlTypeRef` as a `WebidlScalarType`, or `None` if it is
     /// actually a compound type.
+    ///
+    /// For annotated type references, this is the underlying scalar type
+    /// without its extended attributes.
     pub fn scalar(&self) -> Option<WebidlScalarType> {
         match self {
             WebidlTypeRef::Scalar(s) => Some(*s),
+            WebidlTypeRef::Annotated(a) => Some(a.ty),
             _ => None,
         }
     }
+
+    /// Get the extended attributes annotating this `WebidlTypeRef`, which are
+    /// empty unless it is an annotated type reference.
+    pub fn extended_attributes(&self) -> WebidlExtendedAttributes {
+        match self {
+            WebidlTypeRef::Annotated(a) => a.attributes,
+            _ => WebidlExtendedAttributes::default(),
+        }
+    }
+
+    /// If this references a buffer source type, get the size in bytes of its
+    /// elements. See `WebidlScalarType::buffer_element_size`.
+    pub fn buffer_element_size(&self) -> Option<u32> {
+        self.scalar().and_then(|s| s.buffer_element_size())
+    }
+
+    /// Can an `as` expression convert between a value of this Web IDL type and
+    /// a Wasm value of type `wasm_ty`?
+    ///
+    /// `any` goes with every Wasm value but a `v128`, and `bigint` with an
+    /// `i64`. `boolean`, `long long` and `unsigned long long` go with `i32`,
+    /// `i64`, `f32` and `f64`, and the other integer and floating point types
+    /// with `i32`, `f32` and `f64`. Every other type, including compound
+    /// types, is an `anyref`.
+    pub fn is_compatible_with(&self, wasm_ty: impl Into<crate::wasm::ValType>) -> bool {
+        use crate::wasm::ValType::*;
+        use WebidlScalarType::*;
+
+        match (self.scalar(), wasm_ty.into()) {
+            (_, V128) => false,
+            (Some(Any), _) => true,
+            (Some(BigInt), ty) => ty == I64,
+            (Some(Boolean), ty) | (Some(LongLong), ty) | (Some(UnsignedLongLong), ty) => {
+                ty != Anyref
+            }
+            (Some(Byte), ty)
+            | (Some(Octet), ty)
+            | (Some(Short), ty)
+            | (Some(UnsignedShort), ty)
+            | (Some(Long), ty)
+            | (Some(UnsignedLong), ty)
+            | (Some(Float), ty)
+            | (Some(UnrestrictedFloat), ty)
+            | (Some(Double), ty)
+            | (Some(UnrestrictedDouble), ty) => ty != Anyref && ty != I64,
+            (_, ty) => ty == Anyref,
+        }
+    }
+
+    /// Convert an incoming JS Number into the bits of a Wasm `i64` for this
+    /// `long long` or `unsigned long long` type, wrapping it, enforcing its
+    /// range or clamping it as the type's extended attributes say.
+    ///
+    /// See `WebidlScalarType::number_to_i64`.
+    pub fn number_to_i64(&self, x: f64) -> anyhow::Result<i64> {
+        match self.scalar() {
+            Some(s) => s.number_to_i64(x, self.extended_attributes().integer_conversion()),
+            None => anyhow::bail!("cannot convert a Number to an i64 via a compound type"),
+        }
+    }
+
+    /// Convert an incoming JS BigInt into a Wasm `i64` for this `bigint`
+    /// type, as its extended attributes say.
+    ///
+    /// See `WebidlScalarType::bigint_to_i64`.
+    pub fn bigint_to_i64(&self, x: i128) -> anyhow::Result<i64> {
+        match self.scalar() {
+            Some(s) => s.bigint_to_i64(x, self.extended_attributes().integer_conversion()),
+            None => anyhow::bail!("cannot convert a BigInt to an i64 via a compound type"),
+        }
+    }
 }
 
 impl From<WebidlScalarType> for WebidlTypeRef {
@@ -1472,7 +2717,175 @@ impl From<Id<WebidlCompoundType>> for WebidlTypeRef {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+impl From<WebidlAnnotatedTypeRef> for WebidlTypeRef {
+    fn from(a: WebidlAnnotatedTypeRef) -> Self {
+        WebidlTypeRef::Annotated(a)
+    }
+}
+
+/// A scalar type annotated with extended attributes, for example `[Clamp]
+/// octet`.
+///
+/// The extended attributes always apply to the type, so this can only be
+/// created with `WebidlAnnotatedTypeRef::new`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WebidlAnnotatedTypeRef {
+    ty: WebidlScalarType,
+    attributes: WebidlExtendedAttributes,
+}
+
+impl WebidlAnnotatedTypeRef {
+    /// Create a new annotated type reference, or return `None` if the given
+    /// extended attributes cannot annotate `ty`.
+    pub fn new(ty: WebidlScalarType, attributes: WebidlExtendedAttributes) -> Option<Self> {
+        if attributes.is_valid_for(ty) {
+            Some(WebidlAnnotatedTypeRef { ty, attributes })
+        } else {
+            None
+        }
+    }
+
+    /// The annotated scalar type.
+    pub fn ty(&self) -> WebidlScalarType {
+        self.ty
+    }
+
+    /// The extended attributes that annotate the scalar type.
+    pub fn attributes(&self) -> WebidlExtendedAttributes {
+        self.attributes
+    }
+}
+
+/// A Web IDL extended attribute that changes how values of the type it
+/// annotates are converted from JS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WebidlExtendedAttribute {
+    /// `[Clamp]`: out-of-range integers are clamped to the type's range.
+    Clamp,
+    /// `[EnforceRange]`: out-of-range integers throw a `TypeError`.
+    EnforceRange,
+    /// `[AllowShared]`: the buffer view may be backed by a
+    /// `SharedArrayBuffer`.
+    AllowShared,
+    /// `[LegacyNullToEmptyString]`: `null` converts to the empty string
+    /// rather than `"null"`.
+    LegacyNullToEmptyString,
+}
+
+impl WebidlExtendedAttribute {
+    const ALL: [WebidlExtendedAttribute; 4] = [
+        WebidlExtendedAttribute::Clamp,
+        WebidlExtendedAttribute::EnforceRange,
+        WebidlExtendedAttribute::AllowShared,
+        WebidlExtendedAttribute::LegacyNullToEmptyString,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    /// Can this extended attribute annotate the given scalar type?
+    pub fn applies_to(self, ty: WebidlScalarType) -> bool {
+        use WebidlScalarType::*;
+        match self {
+            WebidlExtendedAttribute::Clamp | WebidlExtendedAttribute::EnforceRange => matches!(
+                ty,
+                Byte | Octet
+                    | Short
+                    | UnsignedShort
+                    | Long
+                    | UnsignedLong
+                    | LongLong
+                    | UnsignedLongLong
+            ),
+            WebidlExtendedAttribute::AllowShared => matches!(
+                ty,
+                DataView
+                    | Int8Array
+                    | Int16Array
+                    | Int32Array
+                    | Uint8Array
+                    | Uint16Array
+                    | Uint32Array
+                    | Uint8ClampedArray
+                    | Float32Array
+                    | Float64Array
+            ),
+            WebidlExtendedAttribute::LegacyNullToEmptyString => ty == DomString,
+        }
+    }
+}
+
+/// A set of extended attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WebidlExtendedAttributes {
+    bits: u8,
+}
+
+impl WebidlExtendedAttributes {
+    /// Add an extended attribute to this set. Returns `false` if it was
+    /// already present.
+    pub fn insert(&mut self, attr: WebidlExtendedAttribute) -> bool {
+        let present = self.contains(attr);
+        self.bits |= attr.bit();
+        !present
+    }
+
+    /// Is the given extended attribute in this set?
+    pub fn contains(&self, attr: WebidlExtendedAttribute) -> bool {
+        self.bits & attr.bit() != 0
+    }
+
+    /// Is this set empty?
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Iterate over the extended attributes in this set.
+    pub fn iter(&self) -> impl Iterator<Item = WebidlExtendedAttribute> {
+        let attrs = *self;
+        WebidlExtendedAttribute::ALL
+            .iter()
+            .cloned()
+            .filter(move |a| attrs.contains(*a))
+    }
+
+    /// Can these extended attributes annotate the given scalar type?
+    ///
+    /// Every attribute must apply to `ty`, and `[Clamp]` and `[EnforceRange]`
+    /// are mutually exclusive.
+    pub fn is_valid_for(&self, ty: WebidlScalarType) -> bool {
+        self.iter().all(|a| a.applies_to(ty))
+            && !(self.contains(WebidlExtendedAttribute::Clamp)
+                && self.contains(WebidlExtendedAttribute::EnforceRange))
+    }
+
+    /// How incoming integers are converted under these extended attributes.
+    pub fn integer_conversion(&self) -> WebidlIntegerConversion {
+        if self.contains(WebidlExtendedAttribute::Clamp) {
+            WebidlIntegerConversion::Clamp
+        } else if self.contains(WebidlExtendedAttribute::EnforceRange) {
+            WebidlIntegerConversion::EnforceRange
+        } else {
+            WebidlIntegerConversion::Wrap
+        }
+    }
+}
+
+impl std::iter::FromIterator<WebidlExtendedAttribute> for WebidlExtendedAttributes {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = WebidlExtendedAttribute>,
+    {
+        let mut attrs = WebidlExtendedAttributes::default();
+        for a in iter {
+            attrs.insert(a);
+        }
+        attrs
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlScalarType {
     Any,
     Boolean,
@@ -1484,6 +2897,7 @@ pub enum WebidlScalarType {
     UnsignedShort,
     LongLong,
     UnsignedLongLong,
+    BigInt,
     Float,
     UnrestrictedFloat,
     Double,
@@ -1506,7 +2920,161 @@ pub enum WebidlScalarType {
     Float64Array,
 }
 
-#[cfg(test)]
+impl WebidlScalarType {
+    /// If this is `ArrayBuffer`, `DataView`, or a typed array type, get the
+    /// size in bytes of its elements, or `None` for other types.
+    pub fn buffer_element_size(self) -> Option<u32> {
+        use WebidlScalarType::*;
+        match self {
+            ArrayBuffer | DataView | Int8Array | Uint8Array | Uint8ClampedArray => Some(1),
+            Int16Array | Uint16Array => Some(2),
+            Int32Array | Uint32Array | Float32Array => Some(4),
+            Float64Array => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Is this one of the 64-bit integer types that a Wasm `i64` can be
+    /// converted to and from?
+    pub fn is_64_bit_integer(self) -> bool {
+        matches!(
+            self,
+            WebidlScalarType::LongLong
+                | WebidlScalarType::UnsignedLongLong
+                | WebidlScalarType::BigInt
+        )
+    }
+
+    /// Get the integer value of this 64-bit integer type that an outgoing Wasm
+    /// `i64` converts to.
+    ///
+    /// `long long` and `bigint` interpret the `i64` as signed, and `unsigned
+    /// long long` interprets it as unsigned. Note that `long long` and
+    /// `unsigned long long` values become JS Numbers, which cannot represent
+    /// every integer whose magnitude is greater than 2<sup>53</sup>.
+    ///
+    /// Returns `None` if this is not a 64-bit integer type.
+    pub fn integer_from_i64(self, x: i64) -> Option<i128> {
+        match self {
+            WebidlScalarType::LongLong | WebidlScalarType::BigInt => Some(x as i128),
+            WebidlScalarType::UnsignedLongLong => Some(x as u64 as i128),
+            _ => None,
+        }
+    }
+
+    /// Convert an incoming JS Number into the bits of a Wasm `i64`, following
+    /// Web IDL's `ConvertToInt` for `long long` and `unsigned long long`.
+    ///
+    /// Returns an error if this is not `long long` or `unsigned long long`, or
+    /// if the conversion throws a `TypeError`.
+    pub fn number_to_i64(self, x: f64, conversion: WebidlIntegerConversion) -> anyhow::Result<i64> {
+        const TWO_53: f64 = 9_007_199_254_740_992.0;
+        const TWO_63: f64 = 9_223_372_036_854_775_808.0;
+        const TWO_64: f64 = 18_446_744_073_709_551_616.0;
+
+        // Only integers that Numbers can represent exactly are in range.
+        let (lower, upper) = match self {
+            WebidlScalarType::LongLong => (-TWO_53 + 1.0, TWO_53 - 1.0),
+            WebidlScalarType::UnsignedLongLong => (0.0, TWO_53 - 1.0),
+            _ => anyhow::bail!("cannot convert a Number to an i64 via {:?}", self),
+        };
+
+        match conversion {
+            WebidlIntegerConversion::Wrap => {
+                if !x.is_finite() {
+                    return Ok(0);
+                }
+                let x = x.trunc() % TWO_64;
+                let x = if x >= TWO_63 {
+                    x - TWO_64
+                } else if x < -TWO_63 {
+                    x + TWO_64
+                } else {
+                    x
+                };
+                Ok(x as i64)
+            }
+            WebidlIntegerConversion::EnforceRange => {
+                if !x.is_finite() {
+                    anyhow::bail!("TypeError: {} is not a finite number", x);
+                }
+                let x = x.trunc();
+                if x < lower || x > upper {
+                    anyhow::bail!("TypeError: {} is out of range for {:?}", x, self);
+                }
+                Ok(x as i64)
+            }
+            WebidlIntegerConversion::Clamp => {
+                if x.is_nan() {
+                    return Ok(0);
+                }
+                let x = x.max(lower).min(upper);
+                let rounded = x.round();
+                let x = if (rounded - x).abs() == 0.5 {
+                    // Round ties to even.
+                    2.0 * (x / 2.0).round()
+                } else {
+                    rounded
+                };
+                Ok(x as i64)
+            }
+        }
+    }
+
+    /// Convert an incoming JS BigInt into a Wasm `i64`.
+    ///
+    /// Wrapping matches `BigInt.asIntN(64, x)`, which is what the JS API does
+    /// for `i64` parameters.
+    ///
+    /// Returns an error if this is not `bigint`, or if the conversion throws a
+    /// `TypeError`.
+    pub fn bigint_to_i64(
+        self,
+        x: i128,
+        conversion: WebidlIntegerConversion,
+    ) -> anyhow::Result<i64> {
+        if self != WebidlScalarType::BigInt {
+            anyhow::bail!("cannot convert a BigInt to an i64 via {:?}", self);
+        }
+
+        match conversion {
+            WebidlIntegerConversion::Wrap => Ok(x as i64),
+            WebidlIntegerConversion::EnforceRange => {
+                if x < i64::MIN as i128 || x > i64::MAX as i128 {
+                    anyhow::bail!("TypeError: {} is out of range for an i64", x);
+                }
+                Ok(x as i64)
+            }
+            WebidlIntegerConversion::Clamp => {
+                Ok(x.max(i64::MIN as i128).min(i64::MAX as i128) as i64)
+            }
+        }
+    }
+}
+
+/// How an incoming value that is out of range for an integer type is
+/// converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WebidlIntegerConversion {
+    /// The default: wrap around modulo 2<sup>N</sup>, and convert non-finite
+    /// values to zero.
+    Wrap,
+    /// `[EnforceRange]`: throw a `TypeError` for non-finite and out-of-range
+    /// values.
+    EnforceRange,
+    /// `[Clamp]`: saturate at the type's bounds, and round to the nearest
+    /// integer, with ties to even.
+    Clamp,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for WebidlIntegerConversion {
+    fn default() -> WebidlIntegerConversion {
+        WebidlIntegerConversion::Wrap
+    }
+}
+
+#[cfg(all(test, feature = "walrus"))]
 mod tests {
     use super::*;
 
@@ -1741,6 +3309,904 @@ mod tests {
         assert!(!binding.is_expressible_in_js_without_webidl_bindings(&m, &wb));
     }
 
+    #[test]
+    fn validate_multi_value_import_result() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(
+            &[walrus::ValType::I32, walrus::ValType::I32],
+            &[walrus::ValType::I64, walrus::ValType::I64],
+        );
+
+        let mut wb = WebidlBindings::default();
+        let dict = wb.types.insert(WebidlDictionary {
+            fields: vec![
+                WebidlDictionaryField {
+                    name: "read".into(),
+                    ty: WebidlScalarType::UnsignedLongLong.into(),
+                },
+                WebidlDictionaryField {
+                    name: "written".into(),
+                    ty: WebidlScalarType::UnsignedLongLong.into(),
+                },
+            ],
+        });
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlScalarType::Uint8Array.into()],
+            result: Some(dict.into()),
+        });
+
+        let field = |idx| -> IncomingBindingExpression {
+            IncomingBindingExpressionAs {
+                ty: walrus::ValType::I64,
+                expr: Box::new(
+                    IncomingBindingExpressionField {
+                        idx,
+                        expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                    }
+                    .into(),
+                ),
+            }
+            .into()
+        };
+        let mut binding = ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![OutgoingBindingExpressionView {
+                    ty: WebidlScalarType::Uint8Array.into(),
+                    offset: 0,
+                    length: 1,
+                    unit: BufferLengthUnit::Elements,
+                }
+                .into()],
+            },
+            result: IncomingBindingMap {
+                bindings: vec![field(0), field(1)],
+            },
+        };
+        binding.validate(&m, &wb).unwrap();
+        assert!(!binding.is_expressible_in_js_without_webidl_bindings(&m, &wb));
+
+        // Too few results.
+        binding.result.bindings.pop();
+        assert!(binding.validate(&m, &wb).is_err());
+
+        // Wrong result type.
+        binding.result.bindings.push(
+            IncomingBindingExpressionAs {
+                ty: walrus::ValType::I32,
+                expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+            }
+            .into(),
+        );
+        assert!(binding.validate(&m, &wb).is_err());
+
+        // Reading a Web IDL result that doesn't exist.
+        binding.result.bindings[1] = field(1);
+        if let IncomingBindingExpression::As(a) = &mut binding.result.bindings[1] {
+            *a.expr = IncomingBindingExpressionGet { idx: 1 }.into();
+        }
+        assert!(binding.validate(&m, &wb).is_err());
+    }
+
+    #[test]
+    fn validate_multi_value_export_result() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(
+            &[walrus::ValType::I32],
+            &[walrus::ValType::I32, walrus::ValType::I32],
+        );
+
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlScalarType::Long.into()],
+            result: Some(WebidlScalarType::DomString.into()),
+        });
+
+        let mut binding = ExportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: IncomingBindingMap {
+                bindings: vec![IncomingBindingExpressionAs {
+                    ty: walrus::ValType::I32,
+                    expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                }
+                .into()],
+            },
+            result: OutgoingBindingMap {
+                bindings: vec![OutgoingBindingExpressionUtf8Str {
+                    ty: WebidlScalarType::DomString.into(),
+                    offset: 0,
+                    length: 1,
+                }
+                .into()],
+            },
+        };
+        binding.validate(&m, &wb).unwrap();
+        assert!(!binding.is_expressible_in_js_without_webidl_bindings(&m, &wb));
+
+        // Using a Wasm result that doesn't exist.
+        binding.result.bindings[0] = OutgoingBindingExpressionUtf8Str {
+            ty: WebidlScalarType::DomString.into(),
+            offset: 1,
+            length: 2,
+        }
+        .into();
+        assert!(binding.validate(&m, &wb).is_err());
+
+        // More than one Web IDL result.
+        binding.result.bindings = vec![
+            OutgoingBindingExpressionAs {
+                ty: WebidlScalarType::Long.into(),
+                idx: 0,
+            }
+            .into(),
+            OutgoingBindingExpressionAs {
+                ty: WebidlScalarType::Long.into(),
+                idx: 1,
+            }
+            .into(),
+        ];
+        assert!(binding.validate(&m, &wb).is_err());
+    }
+
+    #[test]
+    fn validate_write_only_in_import_results() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(
+            &[walrus::ValType::I32, walrus::ValType::I32],
+            &[walrus::ValType::I32],
+        );
+
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: Some(WebidlScalarType::UsvString.into()),
+        });
+
+        let write: IncomingBindingExpression = IncomingBindingExpressionWriteUtf8Str {
+            offset: 0,
+            capacity: 1,
+            expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+        }
+        .into();
+        let import = ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap { bindings: vec![] },
+            result: IncomingBindingMap {
+                bindings: vec![write.clone()],
+            },
+        };
+        import.validate(&m, &wb).unwrap();
+
+        let export = ExportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: IncomingBindingMap {
+                bindings: vec![write],
+            },
+            result: OutgoingBindingMap { bindings: vec![] },
+        };
+        assert!(export.validate(&m, &wb).is_err());
+    }
+
+    #[test]
+    fn validate_outgoing_wasm_value_types() {
+        use walrus::ValType::{Anyref, F32, F64, I32, I64};
+        use WebidlScalarType::{Any, BigInt, ByteString, DomString, Long, Uint8Array};
+
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[], &[]);
+        let mut wb = WebidlBindings::default();
+        let binding = wb.bindings.insert(FunctionBinding::Import(ImportBinding {
+            wasm_ty,
+            webidl_ty: Any.into(),
+            params: OutgoingBindingMap { bindings: vec![] },
+            result: IncomingBindingMap { bindings: vec![] },
+        }));
+
+        // Every expression is checked on its own and as a dictionary field.
+        let validate = |expr: OutgoingBindingExpression| {
+            let dict = OutgoingBindingExpressionDict {
+                ty: Any.into(),
+                fields: vec![expr.clone()],
+            };
+            let from_wasm_tys = [I32, I64, F32];
+            let alone = OutgoingBindingMap {
+                bindings: vec![expr],
+            }
+            .validate(&from_wasm_tys, 1);
+            let nested = OutgoingBindingMap {
+                bindings: vec![dict.into()],
+            }
+            .validate(&from_wasm_tys, 1);
+            assert_eq!(alone.is_ok(), nested.is_ok());
+            alone
+        };
+
+        // Values of any type can be converted to `any`, but they must exist.
+        let as_ = |ty: WebidlScalarType, idx| OutgoingBindingExpressionAs { ty: ty.into(), idx };
+        for idx in 0..3 {
+            validate(as_(Any, idx).into()).unwrap();
+        }
+        assert!(validate(as_(Any, 3).into()).is_err());
+
+        // Other types need a compatible Wasm value: `[i32, i64, f32]`.
+        validate(as_(Long, 0).into()).unwrap();
+        assert!(validate(as_(Long, 1).into()).is_err());
+        validate(as_(Long, 2).into()).unwrap();
+        assert!(validate(as_(BigInt, 0).into()).is_err());
+        validate(as_(BigInt, 1).into()).unwrap();
+        assert!(validate(as_(BigInt, 2).into()).is_err());
+        assert!(validate(as_(DomString, 0).into()).is_err());
+
+        // Exports are bound from `i32` table indices or `anyref`s.
+        let bind_export = |idx| OutgoingBindingExpressionBindExport {
+            ty: Any.into(),
+            binding,
+            idx,
+        };
+        validate(bind_export(0).into()).unwrap();
+        assert!(validate(bind_export(1).into()).is_err());
+        assert!(validate(bind_export(2).into()).is_err());
+        assert!(validate(bind_export(3).into()).is_err());
+        validate(OutgoingBindingExpressionUndefined.into()).unwrap();
+
+        // Everything else treats its Wasm values as `i32` offsets, lengths, or
+        // enum values.
+        let utf8_str = |offset, length| OutgoingBindingExpressionUtf8Str {
+            ty: DomString.into(),
+            offset,
+            length,
+        };
+        let utf8_cstr = |offset| OutgoingBindingExpressionUtf8CStr {
+            ty: DomString.into(),
+            offset,
+        };
+        let i32_to_enum = |idx| OutgoingBindingExpressionI32ToEnum {
+            ty: DomString.into(),
+            idx,
+        };
+        let view = |offset, length| OutgoingBindingExpressionView {
+            ty: Uint8Array.into(),
+            offset,
+            length,
+            unit: BufferLengthUnit::Elements,
+        };
+        let copy = |offset, length| OutgoingBindingExpressionCopy {
+            ty: Uint8Array.into(),
+            offset,
+            length,
+            unit: BufferLengthUnit::Elements,
+        };
+        let variadic = |offset, length| OutgoingBindingExpressionVariadic {
+            ty: Long.into(),
+            elem_ty: I32,
+            offset,
+            length,
+        };
+        let utf16_str = |offset, length| OutgoingBindingExpressionUtf16Str {
+            ty: DomString.into(),
+            offset,
+            length,
+        };
+        let latin1_str = |offset, length| OutgoingBindingExpressionLatin1Str {
+            ty: ByteString.into(),
+            offset,
+            length,
+        };
+
+        validate(utf8_cstr(0).into()).unwrap();
+        validate(i32_to_enum(0).into()).unwrap();
+        for idx in 1..4 {
+            assert!(validate(utf8_cstr(idx).into()).is_err());
+            assert!(validate(i32_to_enum(idx).into()).is_err());
+        }
+
+        validate(utf8_str(0, 0).into()).unwrap();
+        validate(view(0, 0).into()).unwrap();
+        validate(copy(0, 0).into()).unwrap();
+        validate(variadic(0, 0).into()).unwrap();
+        validate(utf16_str(0, 0).into()).unwrap();
+        validate(latin1_str(0, 0).into()).unwrap();
+        for &(offset, length) in &[(1, 0), (0, 1), (2, 0), (0, 2), (3, 0), (0, 3)] {
+            assert!(validate(utf8_str(offset, length).into()).is_err());
+            assert!(validate(view(offset, length).into()).is_err());
+            assert!(validate(copy(offset, length).into()).is_err());
+            assert!(validate(variadic(offset, length).into()).is_err());
+            assert!(validate(utf16_str(offset, length).into()).is_err());
+            assert!(validate(latin1_str(offset, length).into()).is_err());
+        }
+
+        // Variadic elements are numbers in memory of a compatible type.
+        let variadic_of = |ty: WebidlScalarType, elem_ty| OutgoingBindingExpressionVariadic {
+            ty: ty.into(),
+            elem_ty,
+            offset: 0,
+            length: 0,
+        };
+        validate(variadic_of(Long, F64).into()).unwrap();
+        validate(variadic_of(BigInt, I64).into()).unwrap();
+        assert!(validate(variadic_of(Long, I64).into()).is_err());
+        assert!(validate(variadic_of(BigInt, I32).into()).is_err());
+        assert!(validate(variadic_of(Any, Anyref).into()).is_err());
+    }
+
+    #[test]
+    fn validate_incoming_wasm_value_types() {
+        use walrus::ValType::{Anyref, F64, I32, I64};
+
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[], &[]);
+        let mut wb = WebidlBindings::default();
+        let binding = wb.bindings.insert(FunctionBinding::Import(ImportBinding {
+            wasm_ty,
+            webidl_ty: WebidlScalarType::Any.into(),
+            params: OutgoingBindingMap { bindings: vec![] },
+            result: IncomingBindingMap { bindings: vec![] },
+        }));
+
+        let dict = wb.types.insert(WebidlDictionary {
+            fields: vec![WebidlDictionaryField {
+                name: "x".into(),
+                ty: WebidlScalarType::Long.into(),
+            }],
+        });
+        let validate_from = |from: WebidlTypeRef,
+                             expr: IncomingBindingExpression,
+                             to_wasm_tys: &[walrus::ValType]| {
+            IncomingBindingMap {
+                bindings: vec![expr],
+            }
+            .validate(&[from], &wb.types, to_wasm_tys, Some(&[I32, I64]))
+        };
+        let validate = |expr, to_wasm_tys: &[walrus::ValType]| {
+            validate_from(WebidlScalarType::Any.into(), expr, to_wasm_tys)
+        };
+        let get = || Box::new(IncomingBindingExpressionGet { idx: 0 }.into());
+
+        // `get` and `field` produce Web IDL values, not Wasm values.
+        assert!(validate(IncomingBindingExpressionGet { idx: 0 }.into(), &[Anyref]).is_err());
+        let field = IncomingBindingExpressionField {
+            idx: 0,
+            expr: get(),
+        };
+        assert!(validate(field.into(), &[Anyref]).is_err());
+
+        let as_ = |ty| IncomingBindingExpressionAs { ty, expr: get() };
+        validate(as_(F64).into(), &[F64]).unwrap();
+        assert!(validate(as_(F64).into(), &[I32]).is_err());
+        assert!(validate(as_(F64).into(), &[]).is_err());
+
+        // The Web IDL value must be compatible with the Wasm value, whether it
+        // is a param or a dictionary's field.
+        use WebidlScalarType::{BigInt, DomString, Long};
+        validate_from(Long.into(), as_(I32).into(), &[I32]).unwrap();
+        assert!(validate_from(Long.into(), as_(I64).into(), &[I64]).is_err());
+        assert!(validate_from(Long.into(), as_(Anyref).into(), &[Anyref]).is_err());
+        validate_from(BigInt.into(), as_(I64).into(), &[I64]).unwrap();
+        assert!(validate_from(BigInt.into(), as_(I32).into(), &[I32]).is_err());
+        validate_from(DomString.into(), as_(Anyref).into(), &[Anyref]).unwrap();
+        assert!(validate_from(DomString.into(), as_(F64).into(), &[F64]).is_err());
+        let field_as = |ty| IncomingBindingExpressionAs {
+            ty,
+            expr: Box::new(
+                IncomingBindingExpressionField {
+                    idx: 0,
+                    expr: get(),
+                }
+                .into(),
+            ),
+        };
+        validate_from(dict.into(), field_as(F64).into(), &[F64]).unwrap();
+        assert!(validate_from(dict.into(), field_as(I64).into(), &[I64]).is_err());
+
+        let enum_to_i32 = IncomingBindingExpressionEnumToI32 {
+            ty: WebidlScalarType::DomString.into(),
+            expr: get(),
+        };
+        validate(enum_to_i32.clone().into(), &[I32]).unwrap();
+        assert!(validate(enum_to_i32.clone().into(), &[I64]).is_err());
+        assert!(validate(enum_to_i32.into(), &[]).is_err());
+
+        let bind_import = IncomingBindingExpressionBindImport {
+            ty: wasm_ty,
+            binding,
+            expr: get(),
+        };
+        validate(bind_import.clone().into(), &[Anyref]).unwrap();
+        assert!(validate(bind_import.into(), &[I32]).is_err());
+
+        let alloc_func_name = "malloc".to_string();
+        let allocs: Vec<IncomingBindingExpression> = vec![
+            IncomingBindingExpressionAllocUtf8Str {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: get(),
+            }
+            .into(),
+            IncomingBindingExpressionAllocCopy {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: get(),
+            }
+            .into(),
+            IncomingBindingExpressionAllocUtf16Str {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: get(),
+            }
+            .into(),
+            IncomingBindingExpressionAllocLatin1Str {
+                alloc_func_name,
+                expr: get(),
+            }
+            .into(),
+        ];
+        for alloc in allocs {
+            validate(alloc.clone(), &[I32, I32]).unwrap();
+            assert!(validate(alloc.clone(), &[I32, I64]).is_err());
+            assert!(validate(alloc.clone(), &[I64, I32]).is_err());
+            assert!(validate(alloc, &[I32]).is_err());
+        }
+
+        // The `write-*` expressions produce the `i32` length written, and
+        // take an `i32` offset and capacity from the Wasm caller.
+        let writes: Vec<fn(u32, u32) -> IncomingBindingExpression> = vec![
+            |offset, capacity| {
+                let expr = Box::new(IncomingBindingExpressionGet { idx: 0 }.into());
+                IncomingBindingExpressionWriteUtf8Str {
+                    offset,
+                    capacity,
+                    expr,
+                }
+                .into()
+            },
+            |offset, capacity| {
+                let expr = Box::new(IncomingBindingExpressionGet { idx: 0 }.into());
+                IncomingBindingExpressionWriteUtf16Str {
+                    offset,
+                    capacity,
+                    expr,
+                }
+                .into()
+            },
+            |offset, capacity| {
+                let expr = Box::new(IncomingBindingExpressionGet { idx: 0 }.into());
+                IncomingBindingExpressionWriteCopy {
+                    offset,
+                    capacity,
+                    expr,
+                }
+                .into()
+            },
+        ];
+        for write in writes {
+            validate(write(0, 0), &[I32]).unwrap();
+            assert!(validate(write(0, 0), &[I64]).is_err());
+            assert!(validate(write(1, 0), &[I32]).is_err());
+            assert!(validate(write(0, 1), &[I32]).is_err());
+            assert!(validate(write(2, 0), &[I32]).is_err());
+            assert!(validate(write(0, 2), &[I32]).is_err());
+        }
+    }
+
+    #[test]
+    fn bigint_expressible_without_webidl_bindings() {
+        let outgoing = OutgoingBindingMap {
+            bindings: vec![OutgoingBindingExpressionAs {
+                ty: WebidlScalarType::BigInt.into(),
+                idx: 0,
+            }
+            .into()],
+        };
+        assert!(outgoing.is_expressible_in_js_without_webidl_bindings(
+            &[walrus::ValType::I64],
+            &[WebidlScalarType::BigInt.into()],
+        ));
+        assert!(!outgoing.is_expressible_in_js_without_webidl_bindings(
+            &[walrus::ValType::I32],
+            &[WebidlScalarType::BigInt.into()],
+        ));
+
+        let incoming = IncomingBindingMap {
+            bindings: vec![IncomingBindingExpressionAs {
+                ty: walrus::ValType::I64,
+                expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+            }
+            .into()],
+        };
+        assert!(incoming.is_expressible_in_js_without_webidl_bindings(
+            &[WebidlScalarType::BigInt.into()],
+            &[walrus::ValType::I64],
+        ));
+        assert!(!incoming.is_expressible_in_js_without_webidl_bindings(
+            &[WebidlScalarType::Long.into()],
+            &[walrus::ValType::I64],
+        ));
+    }
+
+    #[test]
+    fn long_long_expressible_without_webidl_bindings() {
+        use WebidlScalarType::{LongLong, UnsignedLongLong};
+
+        for &ty in &[LongLong, UnsignedLongLong] {
+            let outgoing = OutgoingBindingMap {
+                bindings: vec![OutgoingBindingExpressionAs {
+                    ty: ty.into(),
+                    idx: 0,
+                }
+                .into()],
+            };
+            assert!(outgoing.is_expressible_in_js_without_webidl_bindings(
+                &[walrus::ValType::I64],
+                &[ty.into()]
+            ));
+
+            let incoming = IncomingBindingMap {
+                bindings: vec![IncomingBindingExpressionAs {
+                    ty: walrus::ValType::I64,
+                    expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                }
+                .into()],
+            };
+            assert!(incoming.is_expressible_in_js_without_webidl_bindings(
+                &[ty.into()],
+                &[walrus::ValType::I64]
+            ));
+
+            // The JS API wraps incoming `i64`s, so it can't clamp them.
+            let clamped: WebidlTypeRef = WebidlAnnotatedTypeRef::new(
+                ty,
+                std::iter::once(WebidlExtendedAttribute::Clamp).collect(),
+            )
+            .unwrap()
+            .into();
+            assert!(!incoming
+                .is_expressible_in_js_without_webidl_bindings(&[clamped], &[walrus::ValType::I64]));
+        }
+    }
+
+    #[test]
+    fn extended_attributes_validity() {
+        use WebidlExtendedAttribute::*;
+
+        let attrs = |a: &[WebidlExtendedAttribute]| -> WebidlExtendedAttributes {
+            a.iter().cloned().collect()
+        };
+
+        assert!(attrs(&[Clamp]).is_valid_for(WebidlScalarType::Octet));
+        assert!(attrs(&[EnforceRange]).is_valid_for(WebidlScalarType::UnsignedLongLong));
+        assert!(!attrs(&[Clamp, EnforceRange]).is_valid_for(WebidlScalarType::Long));
+        assert!(!attrs(&[Clamp]).is_valid_for(WebidlScalarType::Double));
+        assert!(!attrs(&[Clamp]).is_valid_for(WebidlScalarType::BigInt));
+        assert!(!attrs(&[EnforceRange]).is_valid_for(WebidlScalarType::BigInt));
+        assert!(attrs(&[AllowShared]).is_valid_for(WebidlScalarType::Uint8Array));
+        assert!(!attrs(&[AllowShared]).is_valid_for(WebidlScalarType::ArrayBuffer));
+        assert!(attrs(&[LegacyNullToEmptyString]).is_valid_for(WebidlScalarType::DomString));
+        assert!(!attrs(&[LegacyNullToEmptyString]).is_valid_for(WebidlScalarType::UsvString));
+
+        assert_eq!(
+            attrs(&[Clamp]).integer_conversion(),
+            WebidlIntegerConversion::Clamp
+        );
+        assert_eq!(
+            attrs(&[EnforceRange]).integer_conversion(),
+            WebidlIntegerConversion::EnforceRange
+        );
+        assert_eq!(
+            attrs(&[]).integer_conversion(),
+            WebidlIntegerConversion::Wrap
+        );
+    }
+
+    #[test]
+    fn annotated_expressible_without_webidl_bindings() {
+        let clamped: WebidlTypeRef = WebidlAnnotatedTypeRef::new(
+            WebidlScalarType::Octet,
+            std::iter::once(WebidlExtendedAttribute::Clamp).collect(),
+        )
+        .unwrap()
+        .into();
+
+        // Outgoing values aren't affected by extended attributes.
+        let outgoing = OutgoingBindingMap {
+            bindings: vec![OutgoingBindingExpressionAs {
+                ty: clamped,
+                idx: 0,
+            }
+            .into()],
+        };
+        assert!(outgoing
+            .is_expressible_in_js_without_webidl_bindings(&[walrus::ValType::I32], &[clamped]));
+
+        // But the JS API can't clamp incoming values.
+        let incoming = IncomingBindingMap {
+            bindings: vec![IncomingBindingExpressionAs {
+                ty: walrus::ValType::I32,
+                expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+            }
+            .into()],
+        };
+        assert!(!incoming
+            .is_expressible_in_js_without_webidl_bindings(&[clamped], &[walrus::ValType::I32]));
+    }
+
+    #[test]
+    fn integer_from_i64() {
+        assert_eq!(WebidlScalarType::LongLong.integer_from_i64(-1), Some(-1));
+        assert_eq!(WebidlScalarType::BigInt.integer_from_i64(-1), Some(-1));
+        assert_eq!(
+            WebidlScalarType::UnsignedLongLong.integer_from_i64(-1),
+            Some(u64::MAX as i128)
+        );
+        assert_eq!(WebidlScalarType::Long.integer_from_i64(-1), None);
+    }
+
+    #[test]
+    fn number_to_i64() {
+        use WebidlIntegerConversion::*;
+        use WebidlScalarType::*;
+
+        assert_eq!(LongLong.number_to_i64(-1.5, Wrap).unwrap(), -1);
+        assert_eq!(LongLong.number_to_i64(f64::NAN, Wrap).unwrap(), 0);
+        assert_eq!(LongLong.number_to_i64(f64::INFINITY, Wrap).unwrap(), 0);
+        assert_eq!(
+            LongLong
+                .number_to_i64(18446744073709551616.0, Wrap)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            LongLong.number_to_i64(9223372036854775808.0, Wrap).unwrap(),
+            i64::MIN
+        );
+        assert_eq!(UnsignedLongLong.number_to_i64(-1.0, Wrap).unwrap(), -1);
+
+        assert_eq!(LongLong.number_to_i64(-42.9, EnforceRange).unwrap(), -42);
+        assert!(LongLong.number_to_i64(f64::NAN, EnforceRange).is_err());
+        assert!(LongLong
+            .number_to_i64(9007199254740992.0, EnforceRange)
+            .is_err());
+        assert!(UnsignedLongLong.number_to_i64(-1.0, EnforceRange).is_err());
+
+        assert_eq!(LongLong.number_to_i64(f64::NAN, Clamp).unwrap(), 0);
+        assert_eq!(LongLong.number_to_i64(2.5, Clamp).unwrap(), 2);
+        assert_eq!(LongLong.number_to_i64(3.5, Clamp).unwrap(), 4);
+        assert_eq!(LongLong.number_to_i64(-2.5, Clamp).unwrap(), -2);
+        assert_eq!(
+            LongLong.number_to_i64(f64::NEG_INFINITY, Clamp).unwrap(),
+            -9007199254740991
+        );
+        assert_eq!(UnsignedLongLong.number_to_i64(-7.0, Clamp).unwrap(), 0);
+
+        assert!(Long.number_to_i64(1.0, Wrap).is_err());
+        assert!(BigInt.number_to_i64(1.0, Wrap).is_err());
+    }
+
+    #[test]
+    fn type_ref_integer_conversions() {
+        use WebidlExtendedAttribute::{Clamp, EnforceRange};
+        use WebidlScalarType::*;
+
+        const TWO_53: f64 = 9_007_199_254_740_992.0;
+        const TWO_64: f64 = 18_446_744_073_709_551_616.0;
+        let annotated = |ty, attr| -> WebidlTypeRef {
+            WebidlAnnotatedTypeRef::new(ty, std::iter::once(attr).collect())
+                .unwrap()
+                .into()
+        };
+
+        // Without extended attributes, values wrap around modulo 2^64.
+        let long_long = WebidlTypeRef::from(LongLong);
+        let unsigned_long_long = WebidlTypeRef::from(UnsignedLongLong);
+        assert_eq!(long_long.number_to_i64(TWO_64).unwrap(), 0);
+        assert_eq!(long_long.number_to_i64(TWO_64 + 4096.0).unwrap(), 4096);
+        assert_eq!(long_long.number_to_i64(-TWO_64 - 4096.0).unwrap(), -4096);
+        assert_eq!(long_long.number_to_i64(i64::MIN as f64).unwrap(), i64::MIN);
+        assert_eq!(long_long.number_to_i64(i64::MAX as f64).unwrap(), i64::MIN);
+        assert_eq!(
+            unsigned_long_long.number_to_i64(u64::MAX as f64).unwrap(),
+            0
+        );
+        assert_eq!(
+            UnsignedLongLong.integer_from_i64(unsigned_long_long.number_to_i64(-1.0).unwrap()),
+            Some(u64::MAX as i128)
+        );
+
+        // `[EnforceRange]` throws outside of the Numbers that are exact
+        // integers.
+        let enforced = annotated(LongLong, EnforceRange);
+        assert_eq!(enforced.number_to_i64(TWO_53 - 1.0).unwrap(), (1 << 53) - 1);
+        assert_eq!(
+            enforced.number_to_i64(-TWO_53 + 1.0).unwrap(),
+            -(1 << 53) + 1
+        );
+        assert!(enforced.number_to_i64(TWO_53).is_err());
+        assert!(enforced.number_to_i64(i64::MIN as f64).is_err());
+        assert!(enforced.number_to_i64(i64::MAX as f64).is_err());
+        let enforced = annotated(UnsignedLongLong, EnforceRange);
+        assert!(enforced.number_to_i64(u64::MAX as f64).is_err());
+        assert!(enforced.number_to_i64(-1.0).is_err());
+
+        // `[Clamp]` saturates at the same bounds.
+        let clamped = annotated(LongLong, Clamp);
+        assert_eq!(
+            clamped.number_to_i64(i64::MAX as f64).unwrap(),
+            (1 << 53) - 1
+        );
+        assert_eq!(
+            clamped.number_to_i64(i64::MIN as f64).unwrap(),
+            -(1 << 53) + 1
+        );
+        let clamped = annotated(UnsignedLongLong, Clamp);
+        assert_eq!(
+            clamped.number_to_i64(u64::MAX as f64).unwrap(),
+            (1 << 53) - 1
+        );
+        assert_eq!(clamped.number_to_i64(TWO_64).unwrap(), (1 << 53) - 1);
+        assert_eq!(clamped.number_to_i64(-1.0).unwrap(), 0);
+
+        // `bigint` can't be annotated, so it always wraps like
+        // `BigInt.asIntN(64, x)`.
+        let bigint = WebidlTypeRef::from(BigInt);
+        assert_eq!(bigint.bigint_to_i64(i64::MIN as i128).unwrap(), i64::MIN);
+        assert_eq!(bigint.bigint_to_i64(i64::MAX as i128).unwrap(), i64::MAX);
+        assert_eq!(bigint.bigint_to_i64(u64::MAX as i128).unwrap(), -1);
+        assert_eq!(bigint.bigint_to_i64(1 << 64).unwrap(), 0);
+        assert_eq!(
+            bigint.bigint_to_i64(i64::MAX as i128 + 1).unwrap(),
+            i64::MIN
+        );
+        assert!(bigint.number_to_i64(0.0).is_err());
+        assert!(long_long.bigint_to_i64(0).is_err());
+    }
+
+    #[test]
+    fn bigint_to_i64() {
+        use WebidlIntegerConversion::*;
+        use WebidlScalarType::*;
+
+        let big = i64::MAX as i128 + 1;
+        assert_eq!(BigInt.bigint_to_i64(big, Wrap).unwrap(), i64::MIN);
+        assert!(BigInt.bigint_to_i64(big, EnforceRange).is_err());
+        assert_eq!(BigInt.bigint_to_i64(-5, EnforceRange).unwrap(), -5);
+        assert_eq!(BigInt.bigint_to_i64(big, Clamp).unwrap(), i64::MAX);
+        assert_eq!(BigInt.bigint_to_i64(-big - 1, Clamp).unwrap(), i64::MIN);
+        assert!(LongLong.bigint_to_i64(0, Wrap).is_err());
+    }
+
+    #[test]
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    fn buffer_byte_range() {
+        let view = OutgoingBindingExpressionView {
+            ty: WebidlScalarType::Float64Array.into(),
+            offset: 0,
+            length: 1,
+            unit: BufferLengthUnit::Elements,
+        };
+        assert_eq!(view.byte_range(16, 3).unwrap(), 16..40);
+        assert!(view.byte_range(16, u32::MAX).is_err());
+        assert!(view.is_aligned(16));
+        assert!(!view.is_aligned(12));
+
+        let copy = OutgoingBindingExpressionCopy {
+            ty: WebidlScalarType::Int32Array.into(),
+            offset: 0,
+            length: 1,
+            unit: BufferLengthUnit::Bytes,
+        };
+        assert_eq!(copy.byte_range(6, 8).unwrap(), 6..14);
+        assert!(copy.byte_range(6, 7).is_err());
+        assert!(copy.byte_range(u32::MAX, 4).is_err());
+
+        let not_a_buffer = OutgoingBindingExpressionCopy {
+            ty: WebidlScalarType::DomString.into(),
+            offset: 0,
+            length: 1,
+            unit: BufferLengthUnit::Bytes,
+        };
+        assert!(not_a_buffer.byte_range(0, 1).is_err());
+    }
+
+    #[test]
+    fn write_utf8_str() {
+        let units: Vec<u16> = "a\u{e9}\u{1F600}".encode_utf16().collect();
+
+        let mut buffer = [0; 16];
+        let n = IncomingBindingExpressionWriteUtf8Str::write(&units, &mut buffer);
+        assert_eq!(&buffer[..n as usize], "a\u{e9}\u{1F600}".as_bytes());
+
+        // Truncation never splits a character.
+        let mut buffer = [0; 6];
+        let n = IncomingBindingExpressionWriteUtf8Str::write(&units, &mut buffer);
+        assert_eq!(&buffer[..n as usize], "a\u{e9}".as_bytes());
+
+        // Lone surrogates are replaced.
+        let mut buffer = [0; 16];
+        let n = IncomingBindingExpressionWriteUtf8Str::write(&[0xDC00, 0x62], &mut buffer);
+        assert_eq!(&buffer[..n as usize], "\u{FFFD}b".as_bytes());
+    }
+
+    #[test]
+    fn write_utf16_str() {
+        let units = [0x61, 0xD83D, 0xDE00, 0xDC00];
+
+        let mut buffer = [0; 8];
+        assert_eq!(
+            IncomingBindingExpressionWriteUtf16Str::write(&units, &mut buffer),
+            4
+        );
+        assert_eq!(buffer, [0x61, 0, 0x3D, 0xD8, 0x00, 0xDE, 0x00, 0xDC]);
+
+        // Truncation never splits a surrogate pair.
+        let mut buffer = [0; 5];
+        assert_eq!(
+            IncomingBindingExpressionWriteUtf16Str::write(&units, &mut buffer),
+            1
+        );
+        assert_eq!(buffer, [0x61, 0, 0, 0, 0]);
+
+        let mut buffer = [0; 6];
+        assert_eq!(
+            IncomingBindingExpressionWriteUtf16Str::write(&units, &mut buffer),
+            3
+        );
+    }
+
+    #[test]
+    fn write_copy() {
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let mut buffer = [0; 16];
+        assert_eq!(
+            IncomingBindingExpressionWriteCopy::write(&bytes, 4, &mut buffer),
+            8
+        );
+        assert_eq!(&buffer[..8], &bytes);
+
+        // Truncation keeps whole elements.
+        let mut buffer = [0; 7];
+        assert_eq!(
+            IncomingBindingExpressionWriteCopy::write(&bytes, 4, &mut buffer),
+            4
+        );
+        assert_eq!(buffer, [1, 2, 3, 4, 0, 0, 0]);
+        assert_eq!(
+            IncomingBindingExpressionWriteCopy::write(&bytes, 1, &mut buffer),
+            7
+        );
+    }
+
+    #[test]
+    fn strings_not_expressible_without_webidl_bindings() {
+        let outgoing = OutgoingBindingMap {
+            bindings: vec![OutgoingBindingExpressionUtf16Str {
+                ty: WebidlScalarType::DomString.into(),
+                offset: 0,
+                length: 1,
+            }
+            .into()],
+        };
+        assert!(!outgoing.is_expressible_in_js_without_webidl_bindings(
+            &[walrus::ValType::I32, walrus::ValType::I32],
+            &[WebidlScalarType::DomString.into()]
+        ));
+
+        let incoming = IncomingBindingMap {
+            bindings: vec![IncomingBindingExpressionAllocLatin1Str {
+                alloc_func_name: "malloc".into(),
+                expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+            }
+            .into()],
+        };
+        assert!(!incoming.is_expressible_in_js_without_webidl_bindings(
+            &[WebidlScalarType::ByteString.into()],
+            &[walrus::ValType::I32, walrus::ValType::I32]
+        ));
+    }
+
     #[test]
     fn incoming_empty_trivially_expressible_without_webidl_bindings() {
         let map = IncomingBindingMap { bindings: vec![] };
@@ -1905,6 +4371,7 @@ mod tests {
         WebidlTypeRef::Scalar(WebidlScalarType::UnsignedShort),
         WebidlTypeRef::Scalar(WebidlScalarType::LongLong),
         WebidlTypeRef::Scalar(WebidlScalarType::UnsignedLongLong),
+        WebidlTypeRef::Scalar(WebidlScalarType::BigInt),
         WebidlTypeRef::Scalar(WebidlScalarType::Float),
         WebidlTypeRef::Scalar(WebidlScalarType::UnrestrictedFloat),
         WebidlTypeRef::Scalar(WebidlScalarType::Double),
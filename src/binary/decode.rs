@@ -1,11 +1,16 @@
+use super::FormatVersion;
 use crate::ast::*;
+#[cfg(feature = "walrus")]
+use crate::wasm::Walrus;
+use crate::wasm::{FromIndices, ValType, WasmRefs};
 use anyhow::{anyhow, bail, Result};
 use id_arena::Id;
+use std::fmt;
 use std::io::Read;
 
 /// A trait implemented by every Web IDL bindings thing that can be decoded from
 /// an input stream.
-pub(crate) trait Decode {
+pub(crate) trait Decode<R: WasmRefs> {
     /// The output of decoding `Self` from the input stream.
     ///
     /// Since the custom section's arenas and AST nodes typically live inside
@@ -13,23 +18,88 @@ pub(crate) trait Decode {
     type Output;
 
     /// Decode an instance of `Self` into the given `cx`.
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output>;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output>;
 }
 
-pub(crate) struct DecodeContext<'a> {
-    ids: &'a walrus::IndicesToIds,
-    pub(crate) webidl_bindings: WebidlBindings,
+/// Limits on the size and shape of a Web IDL bindings section being decoded.
+///
+/// Sections from untrusted sources can otherwise make the decoder allocate
+/// huge strings and vectors, or recurse deeply enough to overflow the stack.
+/// Decoding a section that exceeds any of these limits fails with an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// The maximum length of a string, in bytes.
+    pub max_string_len: u32,
+    /// The maximum number of elements in a vector, such as the parameters of
+    /// a function type or the binds of the section.
+    pub max_vec_len: u32,
+    /// The maximum depth of nested binding expressions and annotated type
+    /// references.
+    pub max_nesting_depth: u32,
+    /// The maximum number of Web IDL types in the section.
+    pub max_types: u32,
+    /// The maximum number of function bindings in the section.
+    pub max_bindings: u32,
 }
 
-impl<'a> DecodeContext<'a> {
-    pub(crate) fn new(ids: &'a walrus::IndicesToIds) -> Self {
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits {
+            max_string_len: 1 << 20,
+            max_vec_len: 100_000,
+            max_nesting_depth: 100,
+            max_types: 100_000,
+            max_bindings: 100_000,
+        }
+    }
+}
+
+pub(crate) struct DecodeContext<
+    'a,
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    ids: &'a dyn FromIndices<R>,
+    limits: DecodeLimits,
+    /// The format version of the section, from its version marker.
+    version: FormatVersion,
+    /// How deeply nested the thing currently being decoded is.
+    depth: u32,
+    pub(crate) webidl_bindings: WebidlBindings<R>,
+}
+
+impl<'a, R: WasmRefs> DecodeContext<'a, R> {
+    pub(crate) fn new(ids: &'a dyn FromIndices<R>, limits: DecodeLimits) -> Self {
         let webidl_bindings = WebidlBindings::default();
         DecodeContext {
             ids,
+            limits,
+            version: FormatVersion::CURRENT,
+            depth: 0,
             webidl_bindings,
         }
     }
 
+    /// Decode something nested one level deeper than the current thing.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.limits.max_nesting_depth {
+            bail!(
+                "nesting depth exceeds the limit of {}",
+                self.limits.max_nesting_depth
+            );
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Fail unless the section's format version is at least `version`, the
+    /// first that has `what`.
+    fn require(&self, version: FormatVersion, what: impl fmt::Display) -> Result<()> {
+        require_version(self.version, version, what)
+    }
+
     fn webidl_type_id(&self, index: u32) -> Result<Id<WebidlCompoundType>> {
         self.webidl_bindings
             .types
@@ -37,35 +107,56 @@ impl<'a> DecodeContext<'a> {
             .ok_or_else(|| anyhow!("no Web IDL type for index {}", index))
     }
 
-    fn binding_id(&self, index: u32) -> Result<Id<FunctionBinding>> {
+    fn binding_id(&self, index: u32) -> Result<Id<FunctionBinding<R>>> {
         self.webidl_bindings
             .bindings
             .by_index(index)
             .ok_or_else(|| anyhow!("no function binding for index {}", index))
     }
 
-    fn wasm_func_id(&self, index: u32) -> Result<walrus::FunctionId> {
-        self.ids.get_func(index)
+    fn wasm_func_id(&self, index: u32) -> Result<R::FuncRef> {
+        self.ids.func(index)
+    }
+
+    fn wasm_func_type_id(&self, index: u32) -> Result<R::FuncTypeRef> {
+        self.ids.func_type(index)
     }
+}
 
-    fn wasm_func_type_id(&self, index: u32) -> Result<walrus::TypeId> {
-        self.ids.get_type(index)
+/// Fail unless `actual`, the format version of a section, is at least
+/// `required`, the first that has `what`.
+pub(super) fn require_version(
+    actual: FormatVersion,
+    required: FormatVersion,
+    what: impl fmt::Display,
+) -> Result<()> {
+    if actual < required {
+        bail!("{} is not supported in format version {}", what, actual);
     }
+    Ok(())
 }
 
-trait ReadExt: Read {
+pub(super) trait ReadExt: Read {
     fn read_byte(&mut self) -> Result<u8>;
     fn expect_byte(&mut self, expected: u8) -> Result<()>;
     fn uleb(&mut self) -> Result<u32>;
     fn ileb(&mut self) -> Result<i32>;
-    fn vec<T, E>(&mut self, cx: &mut DecodeContext, e: &mut E) -> Result<()>
+    fn ileb64(&mut self) -> Result<i64>;
+    fn vec<T, E, R>(&mut self, cx: &mut DecodeContext<R>, e: &mut E) -> Result<()>
+    where
+        T: Decode<R>,
+        E: Extend<<T as Decode<R>>::Output>,
+        R: WasmRefs;
+    fn option<T, R>(
+        &mut self,
+        cx: &mut DecodeContext<R>,
+    ) -> Result<Option<<T as Decode<R>>::Output>>
     where
-        T: Decode,
-        E: Extend<<T as Decode>::Output>;
-    fn option<T>(&mut self, cx: &mut DecodeContext) -> Result<Option<<T as Decode>::Output>>
+        T: Decode<R>,
+        R: WasmRefs;
+    fn string<R>(&mut self, cx: &DecodeContext<R>) -> Result<String>
     where
-        T: Decode;
-    fn string(&mut self) -> Result<String>;
+        R: WasmRefs;
 }
 
 impl ReadExt for &'_ [u8] {
@@ -84,6 +175,7 @@ impl ReadExt for &'_ [u8] {
         }
     }
 
+    #[allow(clippy::legacy_numeric_constants)]
     fn uleb(&mut self) -> Result<u32> {
         let n = leb128::read::unsigned(self)?;
         if n <= (std::u32::MAX as u64) {
@@ -93,6 +185,7 @@ impl ReadExt for &'_ [u8] {
         }
     }
 
+    #[allow(clippy::legacy_numeric_constants)]
     fn ileb(&mut self) -> Result<i32> {
         let n = leb128::read::signed(self)?;
         if (std::i32::MIN as i64) <= n && n <= (std::i32::MAX as i64) {
@@ -102,10 +195,15 @@ impl ReadExt for &'_ [u8] {
         }
     }
 
-    fn vec<T, E>(&mut self, cx: &mut DecodeContext, e: &mut E) -> Result<()>
+    fn ileb64(&mut self) -> Result<i64> {
+        Ok(leb128::read::signed(self)?)
+    }
+
+    fn vec<T, E, R>(&mut self, cx: &mut DecodeContext<R>, e: &mut E) -> Result<()>
     where
-        T: Decode,
-        E: Extend<<T as Decode>::Output>,
+        T: Decode<R>,
+        E: Extend<<T as Decode<R>>::Output>,
+        R: WasmRefs,
     {
         // TODO: instead of repeatedly extending with `std::iter::once`, create
         // an iterable of all the parsed values, implement the size hint for it
@@ -113,15 +211,26 @@ impl ReadExt for &'_ [u8] {
         // thing being extended to reserve space for everything up front, which
         // should be more efficient.
         let n = self.uleb()?;
+        if n > cx.limits.max_vec_len {
+            bail!(
+                "vector length {} exceeds the limit of {}",
+                n,
+                cx.limits.max_vec_len
+            );
+        }
         for _ in 0..n {
             e.extend(std::iter::once(T::decode(cx, self)?));
         }
         Ok(())
     }
 
-    fn option<T>(&mut self, cx: &mut DecodeContext) -> Result<Option<<T as Decode>::Output>>
+    fn option<T, R>(
+        &mut self,
+        cx: &mut DecodeContext<R>,
+    ) -> Result<Option<<T as Decode<R>>::Output>>
     where
-        T: Decode,
+        T: Decode<R>,
+        R: WasmRefs,
     {
         match self.read_byte()? {
             0 => Ok(None),
@@ -133,9 +242,28 @@ impl ReadExt for &'_ [u8] {
         }
     }
 
-    fn string(&mut self) -> Result<String> {
+    fn string<R>(&mut self, cx: &DecodeContext<R>) -> Result<String>
+    where
+        R: WasmRefs,
+    {
         let n = self.uleb()?;
-        let mut v = vec![0; n as usize];
+        if n > cx.limits.max_string_len {
+            bail!(
+                "string length {} exceeds the limit of {}",
+                n,
+                cx.limits.max_string_len
+            );
+        }
+        let n = n as usize;
+        // Don't allocate more than could possibly be read.
+        if n > self.len() {
+            bail!(
+                "string length {} is longer than the remaining {} bytes",
+                n,
+                self.len()
+            );
+        }
+        let mut v = vec![0; n];
         self.read_exact(&mut v)?;
         let s = String::from_utf8(v)?;
         Ok(s)
@@ -151,78 +279,111 @@ impl<T> Extend<T> for Ignore {
     }
 }
 
-impl Decode for String {
+impl<R: WasmRefs> Decode<R> for String {
     type Output = Self;
 
-    fn decode(_cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        r.string()
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        r.string(cx)
     }
 }
 
-impl<D> Decode for Box<D>
+impl<D, R> Decode<R> for Box<D>
 where
-    D: Decode,
+    D: Decode<R>,
+    R: WasmRefs,
 {
     type Output = Box<D::Output>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let d = D::decode(cx, r)?;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let d = cx.nested(|cx| D::decode(cx, r))?;
         Ok(Box::new(d))
     }
 }
 
-impl Decode for WebidlBindings {
+impl<R: WasmRefs> Decode<R> for WebidlBindings<R> {
     type Output = ();
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<()> {
-        // A temporary version marker while we wait for the official spec to
-        // stabilize
-        let version = String::decode(cx, r)?;
-        if version != crate::version() {
-            bail!(
-                "version mismatch in the bindings section: wasm file has `{}` \
-                 and this library supports `{}`",
-                version,
-                crate::version()
-            );
-        }
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<()> {
+        // The format version marker.
+        cx.version = super::format_version(r)?;
+        String::decode(cx, r)?;
 
         // Web IDL Type Subsection.
         WebidlTypes::decode(cx, r)?;
 
         // Web IDL Function Binding Subsection.
-        r.expect_byte(1)?;
+        subsection(cx, r, 1, |cx, r| {
+            // Function bindings.
+            r.vec::<FunctionBinding<R>, _, _>(cx, &mut Ignore)?;
 
-        // Function bindings.
-        r.vec::<FunctionBinding, _>(cx, &mut Ignore)?;
+            // Bind statements.
+            r.vec::<Bind<R>, _, _>(cx, &mut Ignore)
+        })
+    }
+}
 
-        // Bind statements.
-        r.vec::<Bind, _>(cx, &mut Ignore)
+/// Decode the subsection with the given id, whose contents are decoded by
+/// `contents`.
+///
+/// Since version 2, the contents are prefixed with their size, and must be
+/// exactly that size.
+fn subsection<R: WasmRefs>(
+    cx: &mut DecodeContext<R>,
+    r: &mut &[u8],
+    id: u8,
+    contents: impl FnOnce(&mut DecodeContext<R>, &mut &[u8]) -> Result<()>,
+) -> Result<()> {
+    r.expect_byte(id)?;
+    if cx.version < FormatVersion::V2 {
+        return contents(cx, r);
+    }
+    let size = r.uleb()? as usize;
+    if size > r.len() {
+        bail!(
+            "subsection size {} exceeds the remaining {} bytes",
+            size,
+            r.len()
+        );
+    }
+    let (mut sub, rest) = r.split_at(size);
+    *r = rest;
+    contents(cx, &mut sub)?;
+    if !sub.is_empty() {
+        bail!(
+            "subsection {} has {} bytes left over after its contents",
+            id,
+            sub.len()
+        );
     }
+    Ok(())
 }
 
-impl Decode for WebidlTypes {
+impl<R: WasmRefs> Decode<R> for WebidlTypes {
     type Output = ();
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<()> {
-        r.expect_byte(0)?;
-        r.vec::<WebidlType, _>(cx, &mut Ignore)
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<()> {
+        subsection(cx, r, 0, |cx, r| r.vec::<WebidlType, _, _>(cx, &mut Ignore))
     }
 }
 
-impl Decode for WebidlType {
+impl<R: WasmRefs> Decode<R> for WebidlType {
     type Output = Id<WebidlCompoundType>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let id = WebidlCompoundType::decode(cx, r)?;
-        Ok(id.into())
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        if cx.webidl_bindings.types.arena.len() >= cx.limits.max_types as usize {
+            bail!(
+                "number of Web IDL types exceeds the limit of {}",
+                cx.limits.max_types
+            );
+        }
+        WebidlCompoundType::decode(cx, r)
     }
 }
 
-impl Decode for WebidlCompoundType {
+impl<R: WasmRefs> Decode<R> for WebidlCompoundType {
     type Output = Id<WebidlCompoundType>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         match r.read_byte()? {
             0 => WebidlFunction::decode(cx, r).map(Into::into),
             1 => WebidlDictionary::decode(cx, r).map(Into::into),
@@ -233,16 +394,25 @@ impl Decode for WebidlCompoundType {
     }
 }
 
-impl Decode for WebidlFunction {
+impl<R: WasmRefs> Decode<R> for WebidlFunction {
     type Output = WebidlFunctionId;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let kind = WebidlFunctionKind::decode(cx, r)?;
 
         let mut params = vec![];
-        r.vec::<WebidlTypeRef, _>(cx, &mut params)?;
+        if cx.version < FormatVersion::V2 {
+            // Before version 2, every parameter was required and encoded as
+            // just its type.
+            let mut tys = vec![];
+            r.vec::<WebidlTypeRef, _, _>(cx, &mut tys)?;
+            params.extend(tys.into_iter().map(WebidlFunctionParam::from));
+        } else {
+            r.vec::<WebidlFunctionParam, _, _>(cx, &mut params)?;
+        }
+        validate_param_order(&params)?;
 
-        let result = r.option::<WebidlTypeRef>(cx)?;
+        let result = r.option::<WebidlTypeRef, _>(cx)?;
 
         Ok(cx.webidl_bindings.types.insert(WebidlFunction {
             kind,
@@ -252,17 +422,84 @@ impl Decode for WebidlFunction {
     }
 }
 
-impl Decode for WebidlFunctionKind {
+/// Required parameters come first, then optional parameters, and then at most
+/// one variadic parameter, which must be last.
+pub(crate) fn validate_param_order(params: &[WebidlFunctionParam]) -> Result<()> {
+    let mut seen_optional = false;
+    for (i, param) in params.iter().enumerate() {
+        match param.kind {
+            WebidlFunctionParamKind::Required if seen_optional => {
+                bail!("required parameter {} follows an optional parameter", i)
+            }
+            WebidlFunctionParamKind::Required => {}
+            WebidlFunctionParamKind::Optional(_) => seen_optional = true,
+            WebidlFunctionParamKind::Variadic if i + 1 != params.len() => {
+                bail!("variadic parameter {} is not the last parameter", i)
+            }
+            WebidlFunctionParamKind::Variadic => {}
+        }
+    }
+    Ok(())
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlFunctionParam {
+    type Output = Self;
+
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let kind = match r.read_byte()? {
+            0 => WebidlFunctionParamKind::Required,
+            1 => {
+                let ty = WebidlTypeRef::decode(cx, r)?;
+                let default = r.option::<WebidlDefaultValue, _>(cx)?;
+                let kind = WebidlFunctionParamOptional { default }.into();
+                return Ok(WebidlFunctionParam { ty, kind });
+            }
+            2 => WebidlFunctionParamKind::Variadic,
+            n => bail!(
+                "expected 0x0, 0x1, or 0x2, found bad Web IDL function parameter discriminant: 0x{:02X}",
+                n
+            ),
+        };
+        let ty = WebidlTypeRef::decode(cx, r)?;
+        Ok(WebidlFunctionParam { ty, kind })
+    }
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlDefaultValue {
+    type Output = Self;
+
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        match r.read_byte()? {
+            0 => Ok(WebidlDefaultValue::Null),
+            1 => match r.read_byte()? {
+                0 => Ok(WebidlDefaultValue::Boolean(false)),
+                1 => Ok(WebidlDefaultValue::Boolean(true)),
+                n => bail!("expected 0x0 or 0x1, found bad boolean: 0x{:02X}", n),
+            },
+            2 => Ok(WebidlDefaultValue::Integer(r.ileb64()?)),
+            3 => Ok(WebidlDefaultValue::String(r.string(cx)?)),
+            n => bail!("unknown Web IDL default value discriminant: 0x{:02X}", n),
+        }
+    }
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlFunctionKind {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         match r.read_byte()? {
             0 => Ok(WebidlFunctionKind::Static),
             1 => {
                 let ty = WebidlTypeRef::decode(cx, r)?;
                 Ok(WebidlFunctionKind::Method(WebidlFunctionKindMethod { ty }))
             }
-            2 => Ok(WebidlFunctionKind::Constructor),
+            2 if cx.version < FormatVersion::V2 => {
+                Ok(WebidlFunctionKindConstructor { new_target: None }.into())
+            }
+            2 => {
+                let new_target = r.option::<WebidlTypeRef, _>(cx)?;
+                Ok(WebidlFunctionKindConstructor { new_target }.into())
+            }
             n => bail!(
                 "expected 0x0, 0x1, or 0x2, found bad Web IDL function kind discriminant: 0x{:02X}",
                 n
@@ -271,48 +508,32 @@ impl Decode for WebidlFunctionKind {
     }
 }
 
-impl Decode for WebidlTypeRef {
+impl<R: WasmRefs> Decode<R> for WebidlTypeRef {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        use WebidlScalarType::*;
-
-        fn scalar(s: WebidlScalarType) -> Result<WebidlTypeRef> {
-            Ok(s.into())
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let n = r.ileb()?;
+        if n == -31 || n == -64 {
+            cx.require(
+                FormatVersion::V2,
+                format_args!("Web IDL type reference {}", n),
+            )?;
         }
 
-        match r.ileb()? {
-            // Scalar Web IDL types.
-            -1 => scalar(Any),
-            -2 => scalar(Boolean),
-            -3 => scalar(Byte),
-            -4 => scalar(Octet),
-            -5 => scalar(Long),
-            -6 => scalar(UnsignedLong),
-            -7 => scalar(Short),
-            -8 => scalar(UnsignedShort),
-            -9 => scalar(LongLong),
-            -10 => scalar(UnsignedLongLong),
-            -11 => scalar(Float),
-            -12 => scalar(UnrestrictedFloat),
-            -13 => scalar(Double),
-            -14 => scalar(UnrestrictedDouble),
-            -15 => scalar(DomString),
-            -16 => scalar(ByteString),
-            -17 => scalar(UsvString),
-            -18 => scalar(Object),
-            -19 => scalar(Symbol),
-            -20 => scalar(ArrayBuffer),
-            -21 => scalar(DataView),
-            -22 => scalar(Int8Array),
-            -23 => scalar(Int16Array),
-            -24 => scalar(Int32Array),
-            -25 => scalar(Uint8Array),
-            -26 => scalar(Uint16Array),
-            -27 => scalar(Uint32Array),
-            -28 => scalar(Uint8ClampedArray),
-            -29 => scalar(Float32Array),
-            -30 => scalar(Float64Array),
+        match n {
+            // A scalar Web IDL type annotated with extended attributes.
+            -64 => {
+                let ty = match cx.nested(|cx| WebidlTypeRef::decode(cx, r))? {
+                    WebidlTypeRef::Scalar(s) => s,
+                    ty => bail!("only scalar Web IDL types can be annotated, found {:?}", ty),
+                };
+                let attributes = WebidlExtendedAttributes::decode(cx, r)?;
+                WebidlAnnotatedTypeRef::new(ty, attributes)
+                    .map(Into::into)
+                    .ok_or_else(|| {
+                        anyhow!("invalid extended attributes {:?} for {:?}", attributes, ty)
+                    })
+            }
 
             // Indices of compound Web IDL types.
             n if n >= 0 => {
@@ -320,38 +541,115 @@ impl Decode for WebidlTypeRef {
                 Ok(WebidlTypeRef::Id(id))
             }
 
-            // Bad Web IDL scalar type references.
-            n => bail!("reference to an unknown Web IDL scalar type: {}", n),
+            // Scalar Web IDL types.
+            n => scalar_type(n)
+                .map(Into::into)
+                .ok_or_else(|| anyhow!("reference to an unknown Web IDL scalar type: {}", n)),
         }
     }
 }
 
-impl Decode for WebidlDictionary {
+/// The scalar Web IDL type that is referenced by `n`, if any.
+pub(super) fn scalar_type(n: i32) -> Option<WebidlScalarType> {
+    use WebidlScalarType::*;
+
+    Some(match n {
+        -1 => Any,
+        -2 => Boolean,
+        -3 => Byte,
+        -4 => Octet,
+        -5 => Long,
+        -6 => UnsignedLong,
+        -7 => Short,
+        -8 => UnsignedShort,
+        -9 => LongLong,
+        -10 => UnsignedLongLong,
+        -11 => Float,
+        -12 => UnrestrictedFloat,
+        -13 => Double,
+        -14 => UnrestrictedDouble,
+        -15 => DomString,
+        -16 => ByteString,
+        -17 => UsvString,
+        -18 => Object,
+        -19 => Symbol,
+        -20 => ArrayBuffer,
+        -21 => DataView,
+        -22 => Int8Array,
+        -23 => Int16Array,
+        -24 => Int32Array,
+        -25 => Uint8Array,
+        -26 => Uint16Array,
+        -27 => Uint32Array,
+        -28 => Uint8ClampedArray,
+        -29 => Float32Array,
+        -30 => Float64Array,
+        -31 => BigInt,
+        _ => return None,
+    })
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlExtendedAttributes {
+    type Output = Self;
+
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let mut attrs = vec![];
+        r.vec::<WebidlExtendedAttribute, _, _>(cx, &mut attrs)?;
+
+        let mut set = WebidlExtendedAttributes::default();
+        for a in attrs {
+            if !set.insert(a) {
+                bail!("duplicate extended attribute: {:?}", a);
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlExtendedAttribute {
+    type Output = Self;
+
+    fn decode(_cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        extended_attribute(r.read_byte()?)
+    }
+}
+
+pub(super) fn extended_attribute(discriminant: u8) -> Result<WebidlExtendedAttribute> {
+    match discriminant {
+        0 => Ok(WebidlExtendedAttribute::Clamp),
+        1 => Ok(WebidlExtendedAttribute::EnforceRange),
+        2 => Ok(WebidlExtendedAttribute::AllowShared),
+        3 => Ok(WebidlExtendedAttribute::LegacyNullToEmptyString),
+        n => bail!("unknown extended attribute discriminant: 0x{:02X}", n),
+    }
+}
+
+impl<R: WasmRefs> Decode<R> for WebidlDictionary {
     type Output = WebidlDictionaryId;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let mut fields = vec![];
-        r.vec::<WebidlDictionaryField, _>(cx, &mut fields)?;
+        r.vec::<WebidlDictionaryField, _, _>(cx, &mut fields)?;
         Ok(cx.webidl_bindings.types.insert(WebidlDictionary { fields }))
     }
 }
 
-impl Decode for WebidlDictionaryField {
+impl<R: WasmRefs> Decode<R> for WebidlDictionaryField {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let name = r.string()?;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let name = r.string(cx)?;
         let ty = WebidlTypeRef::decode(cx, r)?;
         Ok(WebidlDictionaryField { name, ty })
     }
 }
 
-impl Decode for WebidlEnumeration {
+impl<R: WasmRefs> Decode<R> for WebidlEnumeration {
     type Output = WebidlEnumerationId;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let mut values = vec![];
-        r.vec::<String, _>(cx, &mut values)?;
+        r.vec::<String, _, _>(cx, &mut values)?;
         Ok(cx
             .webidl_bindings
             .types
@@ -359,20 +657,26 @@ impl Decode for WebidlEnumeration {
     }
 }
 
-impl Decode for WebidlUnion {
+impl<R: WasmRefs> Decode<R> for WebidlUnion {
     type Output = WebidlUnionId;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let mut members = vec![];
-        r.vec::<WebidlTypeRef, _>(cx, &mut members)?;
+        r.vec::<WebidlTypeRef, _, _>(cx, &mut members)?;
         Ok(cx.webidl_bindings.types.insert(WebidlUnion { members }))
     }
 }
 
-impl Decode for FunctionBinding {
-    type Output = Id<FunctionBinding>;
+impl<R: WasmRefs> Decode<R> for FunctionBinding<R> {
+    type Output = Id<FunctionBinding<R>>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        if cx.webidl_bindings.bindings.arena.len() >= cx.limits.max_bindings as usize {
+            bail!(
+                "number of function bindings exceeds the limit of {}",
+                cx.limits.max_bindings
+            );
+        }
         match r.read_byte()? {
             0 => ImportBinding::decode(cx, r).map(Into::into),
             1 => ExportBinding::decode(cx, r).map(Into::into),
@@ -384,11 +688,11 @@ impl Decode for FunctionBinding {
     }
 }
 
-impl Decode for ImportBinding {
-    type Output = ImportBindingId;
+impl<R: WasmRefs> Decode<R> for ImportBinding<R> {
+    type Output = ImportBindingId<R>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let wasm_ty = walrus::TypeId::decode(cx, r)?;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let wasm_ty = cx.wasm_func_type_id(r.uleb()?)?;
         let webidl_ty = WebidlTypeRef::decode(cx, r)?;
         let params = OutgoingBindingMap::decode(cx, r)?;
         let result = IncomingBindingMap::decode(cx, r)?;
@@ -402,11 +706,11 @@ impl Decode for ImportBinding {
     }
 }
 
-impl Decode for ExportBinding {
-    type Output = ExportBindingId;
+impl<R: WasmRefs> Decode<R> for ExportBinding<R> {
+    type Output = ExportBindingId<R>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let wasm_ty = walrus::TypeId::decode(cx, r)?;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let wasm_ty = cx.wasm_func_type_id(r.uleb()?)?;
         let webidl_ty = WebidlTypeRef::decode(cx, r)?;
         let params = IncomingBindingMap::decode(cx, r)?;
         let result = OutgoingBindingMap::decode(cx, r)?;
@@ -420,50 +724,60 @@ impl Decode for ExportBinding {
     }
 }
 
-impl Decode for walrus::ValType {
+impl<R: WasmRefs> Decode<R> for ValType {
     type Output = Self;
 
-    fn decode(_cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        match r.read_byte()? {
-            0x7f => Ok(walrus::ValType::I32),
-            0x7e => Ok(walrus::ValType::I64),
-            0x7d => Ok(walrus::ValType::F32),
-            0x7c => Ok(walrus::ValType::F64),
-            0x7b => Ok(walrus::ValType::V128),
-            0x6f => Ok(walrus::ValType::Anyref),
-            n => bail!("invalid valtype encoding: 0x{:02X}", n),
-        }
+    fn decode(_cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        val_type(r.read_byte()?)
     }
 }
 
-impl Decode for walrus::TypeId {
-    type Output = Self;
-
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let index = r.uleb()?;
-        cx.wasm_func_type_id(index)
+pub(super) fn val_type(encoding: u8) -> Result<ValType> {
+    match encoding {
+        0x7f => Ok(ValType::I32),
+        0x7e => Ok(ValType::I64),
+        0x7d => Ok(ValType::F32),
+        0x7c => Ok(ValType::F64),
+        0x7b => Ok(ValType::V128),
+        0x6f => Ok(ValType::Anyref),
+        n => bail!("invalid valtype encoding: 0x{:02X}", n),
     }
 }
 
-impl Decode for OutgoingBindingMap {
+impl<R: WasmRefs> Decode<R> for OutgoingBindingMap<R> {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let mut bindings = vec![];
-        r.vec::<OutgoingBindingExpression, _>(cx, &mut bindings)?;
+        r.vec::<OutgoingBindingExpression<R>, _, _>(cx, &mut bindings)?;
         Ok(OutgoingBindingMap { bindings })
     }
 }
 
-impl Decode for OutgoingBindingExpression {
+impl<R: WasmRefs> Decode<R> for OutgoingBindingExpression<R> {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        fn e<T: Into<OutgoingBindingExpression>>(e: T) -> Result<OutgoingBindingExpression> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        fn e<T, R>(e: T) -> Result<OutgoingBindingExpression<R>>
+        where
+            T: Into<OutgoingBindingExpression<R>>,
+            R: WasmRefs,
+        {
             Ok(e.into())
         }
 
-        match r.read_byte()? {
+        let discriminant = r.read_byte()?;
+        if discriminant > 7 {
+            cx.require(
+                FormatVersion::V2,
+                format_args!(
+                    "outgoing binding expression discriminant 0x{:02X}",
+                    discriminant
+                ),
+            )?;
+        }
+
+        match discriminant {
             0 => {
                 let ty = WebidlTypeRef::decode(cx, r)?;
                 let idx = r.uleb()?;
@@ -486,29 +800,66 @@ impl Decode for OutgoingBindingExpression {
                 e(OutgoingBindingExpressionI32ToEnum { ty, idx })
             }
             4 => {
-                let ty = WebidlTypeRef::decode(cx, r)?;
+                let ty = decode_buffer_type_ref(cx, r)?;
                 let offset = r.uleb()?;
                 let length = r.uleb()?;
-                e(OutgoingBindingExpressionView { ty, offset, length })
+                let unit = BufferLengthUnit::decode(cx, r)?;
+                e(OutgoingBindingExpressionView {
+                    ty,
+                    offset,
+                    length,
+                    unit,
+                })
             }
             5 => {
-                let ty = WebidlTypeRef::decode(cx, r)?;
+                let ty = decode_buffer_type_ref(cx, r)?;
                 let offset = r.uleb()?;
                 let length = r.uleb()?;
-                e(OutgoingBindingExpressionCopy { ty, offset, length })
+                let unit = BufferLengthUnit::decode(cx, r)?;
+                e(OutgoingBindingExpressionCopy {
+                    ty,
+                    offset,
+                    length,
+                    unit,
+                })
             }
             6 => {
                 let ty = WebidlTypeRef::decode(cx, r)?;
                 let mut fields = vec![];
-                r.vec::<OutgoingBindingExpression, _>(cx, &mut fields)?;
+                cx.nested(|cx| r.vec::<OutgoingBindingExpression<R>, _, _>(cx, &mut fields))?;
                 e(OutgoingBindingExpressionDict { ty, fields })
             }
             7 => {
                 let ty = WebidlTypeRef::decode(cx, r)?;
-                let binding = <Id<FunctionBinding>>::decode(cx, r)?;
+                let binding = <Id<FunctionBinding<R>>>::decode(cx, r)?;
                 let idx = r.uleb()?;
                 e(OutgoingBindingExpressionBindExport { ty, binding, idx })
             }
+            8 => e(OutgoingBindingExpressionUndefined),
+            9 => {
+                let ty = WebidlTypeRef::decode(cx, r)?;
+                let elem_ty = ValType::decode(cx, r)?.into();
+                let offset = r.uleb()?;
+                let length = r.uleb()?;
+                e(OutgoingBindingExpressionVariadic {
+                    ty,
+                    elem_ty,
+                    offset,
+                    length,
+                })
+            }
+            10 => {
+                let ty = WebidlTypeRef::decode(cx, r)?;
+                let offset = r.uleb()?;
+                let length = r.uleb()?;
+                e(OutgoingBindingExpressionUtf16Str { ty, offset, length })
+            }
+            11 => {
+                let ty = WebidlTypeRef::decode(cx, r)?;
+                let offset = r.uleb()?;
+                let length = r.uleb()?;
+                e(OutgoingBindingExpressionLatin1Str { ty, offset, length })
+            }
             n => bail!(
                 "unknown outgoing binding expression discriminant: 0x{:02X}",
                 n
@@ -517,46 +868,89 @@ impl Decode for OutgoingBindingExpression {
     }
 }
 
-impl Decode for Id<FunctionBinding> {
+fn decode_buffer_type_ref<R: WasmRefs>(
+    cx: &mut DecodeContext<R>,
+    r: &mut &[u8],
+) -> Result<WebidlTypeRef> {
+    let ty = WebidlTypeRef::decode(cx, r)?;
+    if ty.buffer_element_size().is_none() {
+        bail!("expected a buffer source type, found {:?}", ty);
+    }
+    Ok(ty)
+}
+
+impl<R: WasmRefs> Decode<R> for BufferLengthUnit {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        // Before version 2, every length was in elements, and the unit was
+        // not encoded.
+        if cx.version < FormatVersion::V2 {
+            return Ok(BufferLengthUnit::Elements);
+        }
+        match r.read_byte()? {
+            0 => Ok(BufferLengthUnit::Elements),
+            1 => Ok(BufferLengthUnit::Bytes),
+            n => bail!("unknown buffer length unit discriminant: 0x{:02X}", n),
+        }
+    }
+}
+
+impl<R: WasmRefs> Decode<R> for Id<FunctionBinding<R>> {
+    type Output = Self;
+
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let index = r.uleb()?;
         cx.binding_id(index)
     }
 }
 
-impl Decode for IncomingBindingMap {
+impl<R: WasmRefs> Decode<R> for IncomingBindingMap<R> {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
         let mut bindings = vec![];
-        r.vec::<IncomingBindingExpression, _>(cx, &mut bindings)?;
+        r.vec::<IncomingBindingExpression<R>, _, _>(cx, &mut bindings)?;
         Ok(IncomingBindingMap { bindings })
     }
 }
 
-impl Decode for IncomingBindingExpression {
+impl<R: WasmRefs> Decode<R> for IncomingBindingExpression<R> {
     type Output = Self;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        fn e<T: Into<IncomingBindingExpression>>(e: T) -> Result<IncomingBindingExpression> {
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        fn e<T, R>(e: T) -> Result<IncomingBindingExpression<R>>
+        where
+            T: Into<IncomingBindingExpression<R>>,
+            R: WasmRefs,
+        {
             Ok(e.into())
         }
 
-        match r.read_byte()? {
+        let discriminant = r.read_byte()?;
+        if discriminant > 6 {
+            cx.require(
+                FormatVersion::V2,
+                format_args!(
+                    "incoming binding expression discriminant 0x{:02X}",
+                    discriminant
+                ),
+            )?;
+        }
+
+        match discriminant {
             0 => {
                 let idx = r.uleb()?;
                 e(IncomingBindingExpressionGet { idx })
             }
             1 => {
-                let ty = walrus::ValType::decode(cx, r)?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let ty = ValType::decode(cx, r)?.into();
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionAs { ty, expr })
             }
             2 => {
                 let alloc_func_name = String::decode(cx, r)?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionAllocUtf8Str {
                     alloc_func_name,
                     expr,
@@ -564,7 +958,7 @@ impl Decode for IncomingBindingExpression {
             }
             3 => {
                 let alloc_func_name = String::decode(cx, r)?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionAllocCopy {
                     alloc_func_name,
                     expr,
@@ -572,20 +966,66 @@ impl Decode for IncomingBindingExpression {
             }
             4 => {
                 let ty = WebidlTypeRef::decode(cx, r)?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionEnumToI32 { ty, expr })
             }
             5 => {
                 let idx = r.uleb()?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionField { idx, expr })
             }
             6 => {
-                let ty = walrus::TypeId::decode(cx, r)?;
-                let binding = <Id<FunctionBinding>>::decode(cx, r)?;
-                let expr = <Box<IncomingBindingExpression>>::decode(cx, r)?;
+                let ty = cx.wasm_func_type_id(r.uleb()?)?;
+                let binding = <Id<FunctionBinding<R>>>::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
                 e(IncomingBindingExpressionBindImport { ty, binding, expr })
             }
+            7 => {
+                let alloc_func_name = String::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
+                e(IncomingBindingExpressionAllocUtf16Str {
+                    alloc_func_name,
+                    expr,
+                })
+            }
+            8 => {
+                let alloc_func_name = String::decode(cx, r)?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
+                e(IncomingBindingExpressionAllocLatin1Str {
+                    alloc_func_name,
+                    expr,
+                })
+            }
+            9 => {
+                let offset = r.uleb()?;
+                let capacity = r.uleb()?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
+                e(IncomingBindingExpressionWriteUtf8Str {
+                    offset,
+                    capacity,
+                    expr,
+                })
+            }
+            10 => {
+                let offset = r.uleb()?;
+                let capacity = r.uleb()?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
+                e(IncomingBindingExpressionWriteUtf16Str {
+                    offset,
+                    capacity,
+                    expr,
+                })
+            }
+            11 => {
+                let offset = r.uleb()?;
+                let capacity = r.uleb()?;
+                let expr = <Box<IncomingBindingExpression<R>>>::decode(cx, r)?;
+                e(IncomingBindingExpressionWriteCopy {
+                    offset,
+                    capacity,
+                    expr,
+                })
+            }
             n => bail!(
                 "unknown incoming binding expression discriminant: 0x{:02X}",
                 n
@@ -594,26 +1034,23 @@ impl Decode for IncomingBindingExpression {
     }
 }
 
-impl Decode for Bind {
-    type Output = Id<Bind>;
+impl<R: WasmRefs> Decode<R> for Bind<R> {
+    type Output = Id<Bind<R>>;
 
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let func = walrus::FunctionId::decode(cx, r)?;
-        let binding = <Id<FunctionBinding>>::decode(cx, r)?;
+    fn decode(cx: &mut DecodeContext<R>, r: &mut &[u8]) -> Result<Self::Output> {
+        let func = cx.wasm_func_id(r.uleb()?)?;
+        let binding = <Id<FunctionBinding<R>>>::decode(cx, r)?;
         Ok(cx.webidl_bindings.binds.insert(Bind { func, binding }))
     }
 }
 
-impl Decode for walrus::FunctionId {
-    type Output = Self;
-
-    fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
-        let index = r.uleb()?;
-        cx.wasm_func_id(index)
-    }
-}
-
-#[cfg(test)]
+#[cfg(all(test, feature = "walrus"))]
+#[allow(
+    clippy::clone_on_copy,
+    clippy::double_ended_iterator_last,
+    clippy::iter_nth_zero,
+    clippy::match_like_matches_macro
+)]
 mod tests {
     use super::*;
     use std::fmt::Debug;
@@ -654,7 +1091,7 @@ mod tests {
             f(module, ids);
             Ok(())
         });
-        let result = config.parse(&*WASM_BUF);
+        let result = config.parse(&WASM_BUF);
         assert!(result.is_ok());
     }
 
@@ -689,7 +1126,7 @@ mod tests {
         #![allow(unused_variables)]
 
         with_test_module(move |module, ids| {
-            let mut cx = DecodeContext::new(ids);
+            let mut cx = DecodeContext::new(ids, DecodeLimits::default());
 
             // Now insert a bunch of stuff into the custom section so that we
             // have things to test against.
@@ -765,8 +1202,8 @@ mod tests {
         expected: F,
         mut encoded: &[u8],
     ) where
-        F: FnOnce(&walrus::Module, &walrus::IndicesToIds, &WebidlBindings) -> T::Output,
-        T: Decode,
+        F: FnOnce(&walrus::Module, &dyn FromIndices<Walrus>, &WebidlBindings) -> T::Output,
+        T: Decode<Walrus>,
         T::Output: PartialEq + Debug,
     {
         let actual = T::decode(cx, &mut encoded).expect("should decode OK");
@@ -776,7 +1213,7 @@ mod tests {
 
     fn do_assert_decode_err<T>(cx: &mut DecodeContext, mut encoded: &[u8])
     where
-        T: Decode,
+        T: Decode<Walrus>,
     {
         let result = T::decode(cx, &mut encoded);
         assert!(result.is_err());
@@ -810,6 +1247,13 @@ mod tests {
         };
     }
 
+    /// `prefix` repeated `n` times, followed by `suffix`.
+    fn repeat_then(prefix: &[u8], n: usize, suffix: &[u8]) -> Vec<u8> {
+        let mut bytes = prefix.repeat(n);
+        bytes.extend_from_slice(suffix);
+        bytes
+    }
+
     macro_rules! assert_decode_err {
         (
             $ty:ty,
@@ -853,6 +1297,10 @@ mod tests {
             1, // length
                // no chars
         ]),
+        string_err_2([
+            0xff, 0xff, 0xff, 0xff, 0x0f, // length
+            b'f', b'o', b'o',
+        ]),
     );
 
     // WebidlBindings
@@ -861,13 +1309,14 @@ mod tests {
         webidl_bindings_ok_0(|m, i, b| {}, {
             let section = [
                 0, // types subsection
+                1, // size
                 0, // number of types
                 1, // bindings subsection
+                2, // size
                 0, // number of bindings
                 0, // number of bind statements
             ];
-            let mut bytes = vec![crate::version().len() as u8];
-            bytes.extend_from_slice(crate::version().as_bytes());
+            let mut bytes = vec![1, b'2'];
             bytes.extend_from_slice(&section);
             bytes
         }),
@@ -898,6 +1347,161 @@ mod tests {
         ]),
     );
 
+    #[test]
+    fn webidl_bindings_decode_limits() {
+        with_test_module(|_module, ids| {
+            let decode = |limits: DecodeLimits| {
+                let section = [
+                    &[
+                        0,  // types subsection
+                        39, // size
+                        2,  // number of types
+                    ][..],
+                    &[2, 1, 16], // enumeration with one 16 byte value
+                    b"abcdefghijklmnop",
+                    &[2, 1, 16], // enumeration with one 16 byte value
+                    b"qrstuvwxyzABCDEF",
+                    &[
+                        1, // bindings subsection
+                        7, // size
+                        1, // number of bindings
+                        0, // import binding
+                        0, // wasm type
+                        0, // webidl type
+                        0, // number of outgoing expressions
+                        0, // number of incoming expressions
+                        0, // number of bind statements
+                    ],
+                ]
+                .concat();
+                let mut bytes = vec![1, b'2'];
+                bytes.extend_from_slice(&section);
+                crate::binary::decode_with_limits(ids, &bytes, &limits).map_err(|e| e.to_string())
+            };
+
+            assert!(decode(DecodeLimits::default()).is_ok());
+            assert_eq!(
+                decode(DecodeLimits {
+                    max_string_len: 15,
+                    ..Default::default()
+                })
+                .unwrap_err(),
+                "string length 16 exceeds the limit of 15"
+            );
+            assert_eq!(
+                decode(DecodeLimits {
+                    max_vec_len: 1,
+                    ..Default::default()
+                })
+                .unwrap_err(),
+                "vector length 2 exceeds the limit of 1"
+            );
+            assert_eq!(
+                decode(DecodeLimits {
+                    max_types: 1,
+                    ..Default::default()
+                })
+                .unwrap_err(),
+                "number of Web IDL types exceeds the limit of 1"
+            );
+            assert_eq!(
+                decode(DecodeLimits {
+                    max_bindings: 0,
+                    ..Default::default()
+                })
+                .unwrap_err(),
+                "number of function bindings exceeds the limit of 0"
+            );
+        });
+    }
+
+    #[test]
+    fn webidl_bindings_format_version_1() {
+        with_test_module(|module, ids| {
+            let v1 = |section: &[u8]| [&[5][..], b"0.8.0", section].concat();
+
+            let bytes = v1(&[
+                0, // types subsection
+                1, // number of types
+                0, // function
+                2, // constructor, without a new target
+                2, // number of params, without kinds
+                0x7b, 0x71, // long, DOMString
+                0,    // no result
+                1,    // bindings subsection
+                1,    // number of bindings
+                0,    // import binding
+                0,    // wasm type
+                0,    // webidl type
+                1,    // number of outgoing expressions
+                4, 0x67, 0, 1, // view, without a length unit
+                0, // number of incoming expressions
+                0, // number of bind statements
+            ]);
+            assert_eq!(
+                crate::binary::format_version(&bytes).unwrap(),
+                FormatVersion::V1
+            );
+            let wb = crate::binary::decode(ids, &bytes).unwrap();
+
+            let (ty_id, ty) = wb.types.arena.iter().next().unwrap();
+            assert_eq!(
+                *ty,
+                WebidlCompoundType::Function(WebidlFunction {
+                    kind: WebidlFunctionKindConstructor { new_target: None }.into(),
+                    params: vec![
+                        WebidlScalarType::Long.into(),
+                        WebidlScalarType::DomString.into(),
+                    ],
+                    result: None,
+                })
+            );
+            let (_, binding) = wb.bindings.arena.iter().next().unwrap();
+            assert_eq!(
+                *binding,
+                FunctionBinding::Import(ImportBinding {
+                    wasm_ty: get_type_id(module),
+                    webidl_ty: ty_id.into(),
+                    params: OutgoingBindingMap {
+                        bindings: vec![OutgoingBindingExpressionView {
+                            ty: WebidlScalarType::Uint8Array.into(),
+                            offset: 0,
+                            length: 1,
+                            unit: BufferLengthUnit::Elements,
+                        }
+                        .into()],
+                    },
+                    result: IncomingBindingMap { bindings: vec![] },
+                })
+            );
+
+            // `bigint` was added in version 2.
+            let bytes = v1(&[
+                0,    // types subsection
+                1,    // number of types
+                0,    // function
+                0,    // static
+                1,    // number of params
+                0x61, // bigint
+                0,    // no result
+                1,    // bindings subsection
+                0,    // number of bindings
+                0,    // number of bind statements
+            ]);
+            assert_eq!(
+                crate::binary::decode(ids, &bytes).unwrap_err().to_string(),
+                "Web IDL type reference -31 is not supported in format version 1"
+            );
+
+            let bytes = [&[5][..], b"0.7.0", &[0, 0, 1, 0, 0]].concat();
+            assert_eq!(
+                crate::binary::decode(ids, &bytes).unwrap_err().to_string(),
+                "unsupported Web IDL bindings format version `0.7.0`; \
+                 this library reads `0.8.0`, `2`"
+            );
+        });
+    }
+
     // WebidlTypes
     assert_decode_ok!(
         WebidlTypes,
@@ -905,6 +1509,7 @@ mod tests {
             |m, i, b| {},
             [
                 0, // subsection number
+                1, // size
                 0, // number of types
             ]
         ),
@@ -915,13 +1520,27 @@ mod tests {
         webidl_types_err_0([]),
         webidl_types_err_1([
             0, // subsection number
+            1, // size
             1, // number of types
                // no types
         ]),
         webidl_types_err_2([
             0, // subsection number
+            0, // size
                // no number-of-types
         ]),
+        webidl_types_err_3([
+            0, // subsection number
+            2, // size
+            0, // number of types
+            0, // one byte left over
+        ]),
+        webidl_types_err_4([
+            0, // subsection number
+            2, // size
+            0, // number of types
+               // one byte short
+        ]),
     );
 
     // WebidlCompoundType
@@ -940,6 +1559,7 @@ mod tests {
                 0,    // function discriminant
                 0,    // static kind
                 1,    // number of params
+                0,    // required
                 0x7f, // any
                 0,    // no result
             ]
@@ -1012,6 +1632,7 @@ mod tests {
             [
                 0,    // static kind
                 1,    // number of params
+                0,    // required
                 0x7f, // any
                 1,    // has a result
                 0x7f, // any
@@ -1031,6 +1652,48 @@ mod tests {
             [
                 0,    // static kind
                 1,    // number of params
+                0,    // required
+                0x7f, // any
+                0,    // no result
+            ],
+        ),
+        webidl_function_ok_2(
+            |m, i, b| {
+                let (id, ty) = b.types.arena.iter().last().unwrap();
+                let ty = match ty {
+                    WebidlCompoundType::Function(f) => f,
+                    _ => panic!(),
+                };
+                assert_eq!(
+                    ty.params,
+                    [
+                        WebidlScalarType::Any.into(),
+                        WebidlFunctionParam {
+                            ty: WebidlScalarType::Long.into(),
+                            kind: WebidlFunctionParamOptional {
+                                default: Some(WebidlDefaultValue::Integer(-1)),
+                            }
+                            .into(),
+                        },
+                        WebidlFunctionParam {
+                            ty: WebidlScalarType::Any.into(),
+                            kind: WebidlFunctionParamKind::Variadic,
+                        },
+                    ]
+                );
+                <WebidlFunction as WebidlTypeId>::wrap(id)
+            },
+            [
+                0,    // static kind
+                3,    // number of params
+                0,    // required
+                0x7f, // any
+                1,    // optional
+                0x7b, // long
+                1,    // has a default
+                2,    // integer
+                0x7f, // -1
+                2,    // variadic
                 0x7f, // any
                 0,    // no result
             ],
@@ -1043,6 +1706,7 @@ mod tests {
         webidl_function_err_1([
             0,    // static kind
             1,    // number of params
+            0,    // required
             0x7f, // any
             1,    // has a result
                   // no result
@@ -1050,6 +1714,7 @@ mod tests {
         webidl_function_err_2([
             0, // static kind
             1, // number of params
+            0, // required
             0x7f, // any
                // no has-a-result
         ]),
@@ -1062,6 +1727,41 @@ mod tests {
             0, // static kind
                // no number-of-params
         ]),
+        webidl_function_err_5([
+            0,    // static kind
+            2,    // number of params
+            1,    // optional
+            0x7f, // any
+            0,    // no default
+            0,    // required
+            0x7f, // any
+            0,    // no result
+        ]),
+        webidl_function_err_6([
+            0,    // static kind
+            2,    // number of params
+            2,    // variadic
+            0x7f, // any
+            0,    // required
+            0x7f, // any
+            0,    // no result
+        ]),
+        webidl_function_err_7([
+            0,    // static kind
+            1,    // number of params
+            3,    // bad param discriminant
+            0x7f, // any
+            0,    // no result
+        ]),
+        webidl_function_err_8([
+            0,    // static kind
+            1,    // number of params
+            1,    // optional
+            0x7f, // any
+            1,    // has a default
+            4,    // bad default value discriminant
+            0,    // no result
+        ]),
     );
 
     // WebidlFunctionKind
@@ -1075,7 +1775,17 @@ mod tests {
             .into(),
             [1, 0x7f]
         ),
-        webidl_function_kind_ok_2(|m, i, b| WebidlFunctionKind::Constructor, [2]),
+        webidl_function_kind_ok_2(
+            |m, i, b| WebidlFunctionKindConstructor { new_target: None }.into(),
+            [2, 0]
+        ),
+        webidl_function_kind_ok_3(
+            |m, i, b| WebidlFunctionKindConstructor {
+                new_target: Some(get_my_dict_id(b)),
+            }
+            .into(),
+            [2, 1, 0]
+        ),
     );
     assert_decode_err!(
         WebidlFunctionKind,
@@ -1083,6 +1793,10 @@ mod tests {
         webidl_function_kind_err_0([]),
         // Bad discriminant.
         webidl_function_kind_err_1([3]),
+        // Constructor without its new target option.
+        webidl_function_kind_err_2([2]),
+        // Constructor with a bad new target option discriminant.
+        webidl_function_kind_err_3([2, 2]),
     );
 
     // WebidlTypeRef
@@ -1126,6 +1840,35 @@ mod tests {
         webidl_type_ref_ok_30(|m, i, b| WebidlScalarType::Uint8ClampedArray.into(), [0x64]),
         webidl_type_ref_ok_31(|m, i, b| WebidlScalarType::Float32Array.into(), [0x63]),
         webidl_type_ref_ok_32(|m, i, b| WebidlScalarType::Float64Array.into(), [0x62]),
+        webidl_type_ref_ok_33(|m, i, b| WebidlScalarType::BigInt.into(), [0x61]),
+        webidl_type_ref_ok_34(
+            |m, i, b| WebidlAnnotatedTypeRef::new(
+                WebidlScalarType::Octet,
+                std::iter::once(WebidlExtendedAttribute::Clamp).collect(),
+            )
+            .unwrap()
+            .into(),
+            [
+                0x40, // annotated
+                0x7c, // octet
+                1,    // number of attributes
+                0,    // [Clamp]
+            ]
+        ),
+        webidl_type_ref_ok_35(
+            |m, i, b| WebidlAnnotatedTypeRef::new(
+                WebidlScalarType::Uint8Array,
+                std::iter::once(WebidlExtendedAttribute::AllowShared).collect(),
+            )
+            .unwrap()
+            .into(),
+            [
+                0x40, // annotated
+                0x67, // Uint8Array
+                1,    // number of attributes
+                2,    // [AllowShared]
+            ]
+        ),
     );
     assert_decode_err!(
         WebidlTypeRef,
@@ -1134,7 +1877,21 @@ mod tests {
         // Bad positive ileb index.
         webidl_type_ref_err_1([0x3f]),
         // Bad negative ileb scalar.
-        webidl_type_ref_err_2([0x40]),
+        webidl_type_ref_err_2([0x41]),
+        // Annotated compound type.
+        webidl_type_ref_err_3([0x40, 0, 1, 0]),
+        // Attribute that doesn't apply to the type.
+        webidl_type_ref_err_4([0x40, 0x7c, 1, 2]),
+        // Duplicate attribute.
+        webidl_type_ref_err_5([0x40, 0x7c, 2, 0, 0]),
+        // Both [Clamp] and [EnforceRange].
+        webidl_type_ref_err_6([0x40, 0x7c, 2, 0, 1]),
+        // Bad attribute discriminant.
+        webidl_type_ref_err_7([0x40, 0x7c, 1, 4]),
+        // Missing attributes.
+        webidl_type_ref_err_8([0x40, 0x7c]),
+        // Annotations nested too deeply.
+        webidl_type_ref_err_9(repeat_then(&[0x40], 100_000, &[0x7c])),
     );
 
     // WebidlDictionary
@@ -1309,7 +2066,7 @@ mod tests {
                 let (id, binding) = b.bindings.arena.iter().last().unwrap();
                 assert!(match binding {
                     FunctionBinding::Import(_) => true,
-                    FunctionBinding::Export(_) => false,
+                    _ => false,
                 });
                 id
             },
@@ -1332,8 +2089,8 @@ mod tests {
             |m, i, b| {
                 let (id, binding) = b.bindings.arena.iter().last().unwrap();
                 assert!(match binding {
-                    FunctionBinding::Import(_) => false,
                     FunctionBinding::Export(_) => true,
+                    _ => false,
                 });
                 id
             },
@@ -1539,16 +2296,16 @@ mod tests {
 
     // walrus::ValType
     assert_decode_ok!(
-        walrus::ValType,
-        valtype_ok_0(|m, i, b| walrus::ValType::I32, [0x7f]),
-        valtype_ok_1(|m, i, b| walrus::ValType::I64, [0x7e]),
-        valtype_ok_2(|m, i, b| walrus::ValType::F32, [0x7d]),
-        valtype_ok_3(|m, i, b| walrus::ValType::F64, [0x7c]),
-        valtype_ok_4(|m, i, b| walrus::ValType::V128, [0x7b]),
-        valtype_ok_5(|m, i, b| walrus::ValType::Anyref, [0x6f]),
+        ValType,
+        valtype_ok_0(|m, i, b| ValType::I32, [0x7f]),
+        valtype_ok_1(|m, i, b| ValType::I64, [0x7e]),
+        valtype_ok_2(|m, i, b| ValType::F32, [0x7d]),
+        valtype_ok_3(|m, i, b| ValType::F64, [0x7c]),
+        valtype_ok_4(|m, i, b| ValType::V128, [0x7b]),
+        valtype_ok_5(|m, i, b| ValType::Anyref, [0x6f]),
     );
     assert_decode_err!(
-        walrus::ValType,
+        ValType,
         // Empty input stream.
         valtype_err_0([]),
         // Unknown negative ileb.
@@ -1557,9 +2314,19 @@ mod tests {
         valtype_err_2([4]),
     );
 
-    // walrus::TypeId
+    /// A reference to a Wasm function type, which is decoded as a `walrus::TypeId`.
+    struct WasmFuncTypeRef;
+
+    impl Decode<Walrus> for WasmFuncTypeRef {
+        type Output = walrus::TypeId;
+
+        fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+            cx.wasm_func_type_id(r.uleb()?)
+        }
+    }
+
     assert_decode_ok!(
-        walrus::TypeId,
+        WasmFuncTypeRef,
         type_id_ok_0(
             |m, i, b| get_type_id(m),
             [
@@ -1568,7 +2335,7 @@ mod tests {
         ),
     );
     assert_decode_err!(
-        walrus::TypeId,
+        WasmFuncTypeRef,
         // Empty input stream.
         type_id_err_0([]),
         // Index that isn't associated with a type.
@@ -1667,12 +2434,14 @@ mod tests {
                 ty: WebidlScalarType::Uint8Array.into(),
                 offset: 4,
                 length: 5,
+                unit: BufferLengthUnit::Elements,
             }),
             [
                 4,    // discriminant
                 0x67, // Uint8Array
                 4,    // offset
                 5,    // length
+                0,    // elements
             ],
         ),
         outgoing_binding_expression_ok_5(
@@ -1680,12 +2449,29 @@ mod tests {
                 ty: WebidlScalarType::Uint8Array.into(),
                 offset: 8,
                 length: 9,
+                unit: BufferLengthUnit::Elements,
             }),
             [
                 5,    // discriminant
                 0x67, // Uint8Array
                 8,    // offset
                 9,    // length
+                0,    // elements
+            ],
+        ),
+        outgoing_binding_expression_ok_5_bytes(
+            |m, i, b| obe(OutgoingBindingExpressionCopy {
+                ty: WebidlScalarType::Float64Array.into(),
+                offset: 8,
+                length: 16,
+                unit: BufferLengthUnit::Bytes,
+            }),
+            [
+                5,    // discriminant
+                0x62, // Float64Array
+                8,    // offset
+                16,   // length
+                1,    // bytes
             ],
         ),
         outgoing_binding_expression_ok_6(
@@ -1718,9 +2504,66 @@ mod tests {
                 7, // idx
             ],
         ),
+        outgoing_binding_expression_ok_8(
+            |m, i, b| obe(OutgoingBindingExpressionUndefined),
+            [
+                8, // discriminant
+            ],
+        ),
+        outgoing_binding_expression_ok_9(
+            |m, i, b| obe(OutgoingBindingExpressionVariadic {
+                ty: WebidlScalarType::Long.into(),
+                elem_ty: walrus::ValType::I32,
+                offset: 1,
+                length: 2,
+            }),
+            [
+                9,    // discriminant
+                0x7b, // long
+                0x7f, // i32
+                1,    // offset
+                2,    // length
+            ],
+        ),
+        outgoing_binding_expression_ok_10(
+            |m, i, b| obe(OutgoingBindingExpressionUtf16Str {
+                ty: WebidlScalarType::UsvString.into(),
+                offset: 0,
+                length: 1,
+            }),
+            [
+                10,   // discriminant
+                0x6f, // USVString
+                0,    // offset
+                1,    // length
+            ],
+        ),
+        outgoing_binding_expression_ok_11(
+            |m, i, b| obe(OutgoingBindingExpressionLatin1Str {
+                ty: WebidlScalarType::ByteString.into(),
+                offset: 0,
+                length: 1,
+            }),
+            [
+                11,   // discriminant
+                0x70, // ByteString
+                0,    // offset
+                1,    // length
+            ],
+        ),
     );
     assert_decode_err!(
         OutgoingBindingExpression,
+        // Dictionaries nested too deeply.
+        outgoing_binding_expression_err_deep(repeat_then(
+            &[
+                6,    // discriminant
+                0x7f, // any
+                1,    // number of fields
+            ],
+            100_000,
+            &[8], // undefined
+        )),
         // With the discriminant, but missing various parts of the expression.
         outgoing_binding_expression_err_0([
             0, // discriminant
@@ -1850,6 +2693,53 @@ mod tests {
         ]),
         // Empty input stream.
         outgoing_binding_expression_err_22([]),
+        outgoing_binding_expression_err_23([
+            9,    // discriminant
+            0x7b, // long
+            0x7f, // i32
+            1,    // offset
+                  // no length
+        ]),
+        outgoing_binding_expression_err_24([
+            9, // discriminant
+            0x7b, // long
+               // no element type
+               // no offset
+               // no length
+        ]),
+        outgoing_binding_expression_err_25([
+            10,   // discriminant
+            0x71, // DOMString
+            0,    // offset
+                  // no length
+        ]),
+        outgoing_binding_expression_err_26([
+            11, // discriminant
+                // no ty
+                // no offset
+                // no length
+        ]),
+        outgoing_binding_expression_err_27([
+            4,    // discriminant
+            0x71, // DOMString
+            0,    // offset
+            1,    // length
+            0,    // elements
+        ]),
+        outgoing_binding_expression_err_28([
+            5,    // discriminant
+            0x67, // Uint8Array
+            0,    // offset
+            1,    // length
+            2,    // unknown unit
+        ]),
+        outgoing_binding_expression_err_29([
+            5,    // discriminant
+            0x67, // Uint8Array
+            0,    // offset
+            1,    // length
+                  // no unit
+        ]),
     );
 
     // Id<FunctionBinding>
@@ -1998,6 +2888,34 @@ mod tests {
                 0, // idx
             ],
         ),
+        incoming_bind_expression_ok_7(
+            |m, i, b| WriteUtf8Str(IncomingBindingExpressionWriteUtf8Str {
+                offset: 2,
+                capacity: 3,
+                expr: Box::new(Get(IncomingBindingExpressionGet { idx: 0 })),
+            }),
+            [
+                9, // discriminant
+                2, // offset
+                3, // capacity
+                0, // discriminant
+                0, // idx
+            ],
+        ),
+        incoming_bind_expression_ok_8(
+            |m, i, b| WriteCopy(IncomingBindingExpressionWriteCopy {
+                offset: 2,
+                capacity: 3,
+                expr: Box::new(Get(IncomingBindingExpressionGet { idx: 0 })),
+            }),
+            [
+                11, // discriminant
+                2,  // offset
+                3,  // capacity
+                0,  // discriminant
+                0,  // idx
+            ],
+        ),
     );
     assert_decode_err!(
         IncomingBindingExpression,
@@ -2018,8 +2936,35 @@ mod tests {
                // no my_type_id
                // no import binding
         ]),
+        incoming_bind_expression_err_9([7]),
+        incoming_bind_expression_err_10([
+            8, // discriminant
+            6, // length
+            b'm', b'a', b'l', b'l', b'o', b'c', // "malloc"
+                  // no expression
+        ]),
+        incoming_bind_expression_err_11([
+            9, // discriminant
+            2, // offset
+            3, // capacity
+               // no expression
+        ]),
+        incoming_bind_expression_err_12([
+            11, // discriminant
+            2,  // offset
+                // no capacity
+        ]),
         // Empty input stream.
         incoming_bind_expression_err_8([]),
+        // Fields nested too deeply.
+        incoming_bind_expression_err_13(repeat_then(
+            &[
+                5, // discriminant
+                0, // idx
+            ],
+            100_000,
+            &[0, 0], // (get 0)
+        )),
     );
 
     // Bind
@@ -2041,13 +2986,24 @@ mod tests {
         bind_err_1([0]),
     );
 
-    // walrus::FunctionId
+    /// A reference to a Wasm function, which is decoded as a
+    /// `walrus::FunctionId`.
+    struct WasmFuncRef;
+
+    impl Decode<Walrus> for WasmFuncRef {
+        type Output = walrus::FunctionId;
+
+        fn decode(cx: &mut DecodeContext, r: &mut &[u8]) -> Result<Self::Output> {
+            cx.wasm_func_id(r.uleb()?)
+        }
+    }
+
     assert_decode_ok!(
-        walrus::FunctionId,
+        WasmFuncRef,
         function_id_ok_0(|m, i, b| { m.funcs.iter().nth(0).unwrap().id() }, [0]),
     );
     assert_decode_err!(
-        walrus::FunctionId,
+        WasmFuncRef,
         // Empty input stream.
         function_id_err_0([]),
         // Function index that doesn't exist in the Wasm module.
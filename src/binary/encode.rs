@@ -1,25 +1,36 @@
+use super::FormatVersion;
 use crate::ast::*;
-use id_arena::Id;
+#[cfg(feature = "walrus")]
+use crate::wasm::Walrus;
+use crate::wasm::{RawIndices, ToIndices, ValType, WasmRefs};
+use id_arena::{ArenaBehavior, DefaultArenaBehavior, Id};
 use std::collections::HashMap;
 use std::io;
-
-pub(crate) struct EncodeContext<'a> {
-    indices: &'a walrus::IdsToIndices,
+use std::marker::PhantomData;
+
+pub(crate) struct EncodeContext<
+    'a,
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    indices: &'a dyn ToIndices<R>,
+    version: FormatVersion,
     webidl_type_id_to_idx: HashMap<Id<WebidlCompoundType>, u32>,
-    binding_id_to_idx: HashMap<Id<FunctionBinding>, u32>,
+    binding_id_to_idx: HashMap<Id<FunctionBinding<R>>, u32>,
 }
 
 // Factor this out into a trait to make testing easier.
-pub(crate) trait Indices {
+pub(crate) trait Indices<R: WasmRefs> {
     fn assign_webidl_type_index(&mut self, id: Id<WebidlCompoundType>);
     fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32;
-    fn assign_binding_index(&mut self, id: Id<FunctionBinding>);
-    fn binding_index(&self, id: Id<FunctionBinding>) -> u32;
-    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32;
-    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32;
+    fn assign_binding_index(&mut self, id: Id<FunctionBinding<R>>);
+    fn binding_index(&self, id: Id<FunctionBinding<R>>) -> u32;
+    fn wasm_func_index(&self, id: R::FuncRef) -> u32;
+    fn wasm_func_type_index(&self, id: R::FuncTypeRef) -> u32;
+    fn version(&self) -> FormatVersion;
 }
 
-impl Indices for EncodeContext<'_> {
+impl<R: WasmRefs> Indices<R> for EncodeContext<'_, R> {
     fn assign_webidl_type_index(&mut self, id: Id<WebidlCompoundType>) {
         let idx = self.webidl_type_id_to_idx.len() as u32;
         let old_idx = self.webidl_type_id_to_idx.insert(id, idx);
@@ -30,49 +41,56 @@ impl Indices for EncodeContext<'_> {
         self.webidl_type_id_to_idx[&id]
     }
 
-    fn assign_binding_index(&mut self, id: Id<FunctionBinding>) {
+    fn assign_binding_index(&mut self, id: Id<FunctionBinding<R>>) {
         let idx = self.binding_id_to_idx.len() as u32;
         let old_idx = self.binding_id_to_idx.insert(id, idx);
         assert!(old_idx.is_none());
     }
 
-    fn binding_index(&self, id: Id<FunctionBinding>) -> u32 {
+    fn binding_index(&self, id: Id<FunctionBinding<R>>) -> u32 {
         self.binding_id_to_idx[&id]
     }
 
-    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32 {
-        self.indices.get_func_index(id)
+    fn wasm_func_index(&self, id: R::FuncRef) -> u32 {
+        self.indices.func_index(id)
+    }
+
+    fn wasm_func_type_index(&self, id: R::FuncTypeRef) -> u32 {
+        self.indices.func_type_index(id)
     }
 
-    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32 {
-        self.indices.get_type_index(id)
+    fn version(&self) -> FormatVersion {
+        self.version
     }
 }
 
-impl EncodeContext<'_> {
-    pub fn new(indices: &walrus::IdsToIndices) -> EncodeContext {
+impl<'a, R: WasmRefs> EncodeContext<'a, R> {
+    pub fn new(indices: &'a dyn ToIndices<R>, version: FormatVersion) -> Self {
         EncodeContext {
             indices,
+            version,
             webidl_type_id_to_idx: Default::default(),
             binding_id_to_idx: Default::default(),
         }
     }
 }
 
-pub(crate) trait Encode {
+pub(crate) trait Encode<R: WasmRefs> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write;
 }
 
-impl<'a, T> Encode for &'a T
+#[allow(clippy::needless_lifetimes)]
+impl<'a, T, R> Encode<R> for &'a T
 where
-    T: Encode,
+    T: Encode<R>,
+    R: WasmRefs,
 {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         (**self).encode(cx, w)
@@ -95,12 +113,18 @@ trait WriteExt: io::Write {
         Ok(())
     }
 
-    fn vec<Cx, I, E>(&mut self, cx: &mut Cx, items: I) -> io::Result<()>
+    fn ileb64(&mut self, val: i64) -> io::Result<()> {
+        leb128::write::signed(self, val)?;
+        Ok(())
+    }
+
+    fn vec<R, Cx, I, E>(&mut self, cx: &mut Cx, items: I) -> io::Result<()>
     where
-        Cx: Indices,
+        R: WasmRefs,
+        Cx: Indices<R>,
         I: IntoIterator<Item = E>,
         I::IntoIter: ExactSizeIterator,
-        E: Encode,
+        E: Encode<R>,
     {
         let items = items.into_iter();
         self.uleb(items.len() as u32)?;
@@ -113,10 +137,329 @@ trait WriteExt: io::Write {
 
 impl<W> WriteExt for W where W: ?Sized + io::Write {}
 
-impl Encode for String {
+/// Fail unless the format version being encoded is at least `version`, the
+/// first that can represent `what`.
+fn require<R, Cx>(cx: &Cx, version: FormatVersion, what: &str) -> io::Result<()>
+where
+    R: WasmRefs,
+    Cx: Indices<R>,
+{
+    if cx.version() >= version {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{} cannot be encoded in format version {}, only in version {} and later",
+            what,
+            cx.version(),
+            version
+        ),
+    ))
+}
+
+/// Check that `section` can be encoded in format version `version`.
+#[cfg(feature = "walrus")]
+pub(crate) fn check_version<R>(
+    section: &WebidlBindings<R>,
+    version: FormatVersion,
+) -> io::Result<()>
+where
+    R: WasmRefs,
+{
+    /// Indices that are never written anywhere.
+    struct Discard(FormatVersion);
+
+    impl<R: WasmRefs> Indices<R> for Discard {
+        fn assign_webidl_type_index(&mut self, _: Id<WebidlCompoundType>) {}
+
+        fn webidl_type_index(&self, _: Id<WebidlCompoundType>) -> u32 {
+            0
+        }
+
+        fn assign_binding_index(&mut self, _: Id<FunctionBinding<R>>) {}
+
+        fn binding_index(&self, _: Id<FunctionBinding<R>>) -> u32 {
+            0
+        }
+
+        fn wasm_func_index(&self, _: R::FuncRef) -> u32 {
+            0
+        }
+
+        fn wasm_func_type_index(&self, _: R::FuncTypeRef) -> u32 {
+            0
+        }
+
+        fn version(&self) -> FormatVersion {
+            self.0
+        }
+    }
+
+    section.encode(&mut Discard(version), &mut io::sink())
+}
+
+/// Resolves the references in a Web IDL bindings section to the indices they
+/// are encoded as.
+///
+/// This is how a `StreamingEncoder` learns the indices that the caller has
+/// assigned to Web IDL types, function bindings, and Wasm functions and types.
+/// To stream without any arena, use `RawIndices`, and refer to types and
+/// bindings by the indices that `StreamingEncoder` returns for them.
+pub trait IndexResolver<
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+>
+{
+    /// The index of the given Web IDL type in the type subsection.
+    fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32;
+    /// The index of the given function binding in the function binding
+    /// subsection.
+    fn binding_index(&self, id: Id<FunctionBinding<R>>) -> u32;
+    /// The index of the given function in the Wasm module.
+    fn wasm_func_index(&self, id: R::FuncRef) -> u32;
+    /// The index of the given function type in the Wasm module.
+    fn wasm_func_type_index(&self, id: R::FuncTypeRef) -> u32;
+}
+
+/// The arena id of the ids made by `RawIndices::webidl_type` and
+/// `RawIndices::binding`, which no real arena is given.
+const RAW_ARENA_ID: u32 = u32::MAX;
+
+impl RawIndices {
+    /// A reference to the Web IDL type with the given index in the type
+    /// subsection, for streaming a section with `RawIndices` as the
+    /// `IndexResolver`.
+    ///
+    /// The id does not refer to anything in any `WebidlBindings`.
+    pub fn webidl_type(index: u32) -> Id<WebidlCompoundType> {
+        DefaultArenaBehavior::new_id(RAW_ARENA_ID, index as usize)
+    }
+
+    /// A reference to the function binding with the given index in the
+    /// function binding subsection, for streaming a section with `RawIndices`
+    /// as the `IndexResolver`.
+    ///
+    /// The id does not refer to anything in any `WebidlBindings`.
+    pub fn binding(index: u32) -> Id<FunctionBinding<RawIndices>> {
+        DefaultArenaBehavior::new_id(RAW_ARENA_ID, index as usize)
+    }
+}
+
+/// Resolves references made with `RawIndices::webidl_type` and
+/// `RawIndices::binding` back to their indices, so a section can be streamed
+/// without any `WebidlBindings` or Wasm module.
+impl IndexResolver<RawIndices> for RawIndices {
+    fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32 {
+        debug_assert_eq!(DefaultArenaBehavior::arena_id(id), RAW_ARENA_ID);
+        id.index() as u32
+    }
+
+    fn binding_index(&self, id: Id<FunctionBinding<RawIndices>>) -> u32 {
+        debug_assert_eq!(DefaultArenaBehavior::arena_id(id), RAW_ARENA_ID);
+        id.index() as u32
+    }
+
+    fn wasm_func_index(&self, index: u32) -> u32 {
+        index
+    }
+
+    fn wasm_func_type_index(&self, index: u32) -> u32 {
+        index
+    }
+}
+
+/// `Indices` that come from an `IndexResolver` rather than being assigned
+/// while encoding.
+struct Resolved<'a, I> {
+    resolver: &'a I,
+    version: FormatVersion,
+}
+
+impl<I, R> Indices<R> for Resolved<'_, I>
+where
+    I: IndexResolver<R>,
+    R: WasmRefs,
+{
+    fn assign_webidl_type_index(&mut self, _: Id<WebidlCompoundType>) {}
+
+    fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32 {
+        self.resolver.webidl_type_index(id)
+    }
+
+    fn assign_binding_index(&mut self, _: Id<FunctionBinding<R>>) {}
+
+    fn binding_index(&self, id: Id<FunctionBinding<R>>) -> u32 {
+        self.resolver.binding_index(id)
+    }
+
+    fn wasm_func_index(&self, id: R::FuncRef) -> u32 {
+        self.resolver.wasm_func_index(id)
+    }
+
+    fn wasm_func_type_index(&self, id: R::FuncTypeRef) -> u32 {
+        self.resolver.wasm_func_type_index(id)
+    }
+
+    fn version(&self) -> FormatVersion {
+        self.version
+    }
+}
+
+/// Which items a `StreamingEncoder` is currently accepting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    Types,
+    Bindings,
+    Binds,
+}
+
+/// An encoder that writes a Web IDL bindings section one item at a time,
+/// without a `WebidlBindings` holding all of them.
+///
+/// Types must be added first, then function bindings, then binds, and each
+/// is given the next index of its kind. References to other items are
+/// encoded as the indices that the `IndexResolver` gives for them, so the
+/// caller must assign indices in the same order that it adds the items.
+///
+/// Each subsection is buffered until it is complete, and then written with
+/// its size. `finish` must be called to write the last one.
+#[derive(Debug)]
+pub struct StreamingEncoder<
+    W,
+    I,
+    #[cfg(feature = "walrus")] R: WasmRefs = Walrus,
+    #[cfg(not(feature = "walrus"))] R: WasmRefs,
+> {
+    w: W,
+    resolver: I,
+    version: FormatVersion,
+    stage: Stage,
+    /// The number of items in `buf`.
+    count: u32,
+    buf: Vec<u8>,
+    /// The number of function bindings and their encoding, once binds are
+    /// being added.
+    bindings: Option<(u32, Vec<u8>)>,
+    refs: PhantomData<R>,
+}
+
+impl<W, I, R> StreamingEncoder<W, I, R>
+where
+    W: io::Write,
+    I: IndexResolver<R>,
+    R: WasmRefs,
+{
+    /// Create an encoder that writes a section in the current format version
+    /// to `w`.
+    pub fn new(w: W, resolver: I) -> io::Result<Self> {
+        StreamingEncoder::with_version(w, resolver, FormatVersion::CURRENT)
+    }
+
+    /// Create an encoder that writes a section in the given format version to
+    /// `w`.
+    pub fn with_version(mut w: W, resolver: I, version: FormatVersion) -> io::Result<Self> {
+        write_version_marker(&mut w, version)?;
+        Ok(StreamingEncoder {
+            w,
+            resolver,
+            version,
+            stage: Stage::Types,
+            count: 0,
+            buf: vec![],
+            bindings: None,
+            refs: PhantomData,
+        })
+    }
+
+    /// Add the next Web IDL type, and return its index.
+    pub fn add_type(&mut self, ty: &WebidlCompoundType) -> io::Result<u32> {
+        self.add(Stage::Types, ty)
+    }
+
+    /// Add the next function binding, and return its index.
+    ///
+    /// This finishes the type subsection, so no more types can be added.
+    pub fn add_binding(&mut self, binding: &FunctionBinding<R>) -> io::Result<u32> {
+        self.add(Stage::Bindings, binding)
+    }
+
+    /// Add the next bind.
+    ///
+    /// No more types or function bindings can be added after this.
+    pub fn add_bind(&mut self, bind: &Bind<R>) -> io::Result<()> {
+        self.add(Stage::Binds, bind).map(drop)
+    }
+
+    /// Write the rest of the section, and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.advance(Stage::Binds)?;
+        let (bindings_count, bindings) = self.bindings.take().unwrap();
+        let bindings_count = leb(bindings_count);
+        let binds_count = leb(self.count);
+        write_subsection(
+            &mut self.w,
+            self.version,
+            1,
+            &[&bindings_count, &bindings, &binds_count, &self.buf],
+        )?;
+        Ok(self.w)
+    }
+
+    fn add<E>(&mut self, stage: Stage, item: &E) -> io::Result<u32>
+    where
+        E: Encode<R>,
+    {
+        self.advance(stage)?;
+        let cx = &mut Resolved {
+            resolver: &self.resolver,
+            version: self.version,
+        };
+        // Don't leave a partly encoded item behind if encoding fails.
+        let len = self.buf.len();
+        if let Err(e) = item.encode(cx, &mut self.buf) {
+            self.buf.truncate(len);
+            return Err(e);
+        }
+        let index = self.count;
+        self.count += 1;
+        Ok(index)
+    }
+
+    /// Finish the subsections and vectors before `stage`.
+    fn advance(&mut self, stage: Stage) -> io::Result<()> {
+        if stage < self.stage {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "types must be added before function bindings, and function bindings before binds",
+            ));
+        }
+        if self.stage == Stage::Types && stage > Stage::Types {
+            let count = leb(self.count);
+            write_subsection(&mut self.w, self.version, 0, &[&count, &self.buf])?;
+            self.buf.clear();
+            self.count = 0;
+        }
+        if self.stage <= Stage::Bindings && stage == Stage::Binds {
+            let count = std::mem::replace(&mut self.count, 0);
+            self.bindings = Some((count, std::mem::take(&mut self.buf)));
+        }
+        self.stage = stage;
+        Ok(())
+    }
+}
+
+/// The unsigned LEB128 encoding of `n`.
+fn leb(n: u32) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.uleb(n).expect("writing to a vec can't fail");
+    buf
+}
+
+impl<R: WasmRefs> Encode<R> for String {
     fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.uleb(self.len() as u32)?;
@@ -124,64 +467,109 @@ impl Encode for String {
     }
 }
 
-impl Encode for WebidlBindings {
+impl<R: WasmRefs> Encode<R> for WebidlBindings<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
-        // A temporary version marker while we wait for the official spec to
-        // stabilize
-        crate::version().to_string().encode(cx, w)?;
+        write_version_marker(w, cx.version())?;
 
         // Web IDL Type Subsection.
         self.types.encode(cx, w)?;
 
         // Web IDL Function Binding Subsection.
-        w.byte(1)?;
+        subsection(cx, w, 1, |cx, w| {
+            // Bindings.
+            //
+            // First assign them all indices.
+            for (id, _) in self.bindings.arena.iter() {
+                cx.assign_binding_index(id);
+            }
+            // Then actually encode them.
+            w.vec(cx, self.bindings.arena.iter().map(|(_, binding)| binding))?;
 
-        // Bindings.
-        //
-        // First assign them all indices.
-        for (id, _) in self.bindings.arena.iter() {
-            cx.assign_binding_index(id);
-        }
-        // Then actually encode them.
-        w.vec(cx, self.bindings.arena.iter().map(|(_, binding)| binding))?;
+            // Binds.
+            w.vec(cx, self.binds.arena.iter().map(|(_id, b)| b))
+        })
+    }
+}
+
+/// Write the subsection with the given id, whose contents are written by
+/// `contents`.
+///
+/// Since version 2, the contents are prefixed with their size, so that readers
+/// can skip over the subsection.
+fn subsection<R, Cx, W>(
+    cx: &mut Cx,
+    w: &mut W,
+    id: u8,
+    contents: impl FnOnce(&mut Cx, &mut Vec<u8>) -> io::Result<()>,
+) -> io::Result<()>
+where
+    R: WasmRefs,
+    Cx: Indices<R>,
+    W: ?Sized + io::Write,
+{
+    let mut buf = vec![];
+    contents(cx, &mut buf)?;
+    write_subsection(w, cx.version(), id, &[&buf])
+}
 
-        // Binds.
-        w.vec(cx, self.binds.arena.iter().map(|(_id, b)| b))
+fn write_version_marker<W>(w: &mut W, version: FormatVersion) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    let marker = version.marker();
+    w.uleb(marker.len() as u32)?;
+    w.write_all(marker.as_bytes())
+}
+
+/// Write the subsection with the given id, whose contents are the
+/// concatenation of `parts`.
+fn write_subsection<W>(w: &mut W, version: FormatVersion, id: u8, parts: &[&[u8]]) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    w.byte(id)?;
+    if version >= FormatVersion::V2 {
+        w.uleb(parts.iter().map(|p| p.len()).sum::<usize>() as u32)?;
     }
+    for part in parts {
+        w.write_all(part)?;
+    }
+    Ok(())
 }
 
-impl Encode for WebidlTypes {
+impl<R: WasmRefs> Encode<R> for WebidlTypes {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
-        w.byte(0)?;
-        for (id, _) in self.arena.iter() {
-            cx.assign_webidl_type_index(id);
-        }
-        w.vec(cx, self.arena.iter().map(|(_, ty)| ty))
+        subsection(cx, w, 0, |cx, w| {
+            for (id, _) in self.arena.iter() {
+                cx.assign_webidl_type_index(id);
+            }
+            w.vec(cx, self.arena.iter().map(|(_, ty)| ty))
+        })
     }
 }
 
-impl Encode for WebidlType {
+impl<R: WasmRefs> Encode<R> for WebidlType {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         self.ty.encode(cx, w)
     }
 }
 
-impl Encode for WebidlCompoundType {
+impl<R: WasmRefs> Encode<R> for WebidlCompoundType {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -205,14 +593,26 @@ impl Encode for WebidlCompoundType {
     }
 }
 
-impl Encode for WebidlFunction {
+impl<R: WasmRefs> Encode<R> for WebidlFunction {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         self.kind.encode(cx, w)?;
-        w.vec(cx, &self.params)?;
+        if cx.version() < FormatVersion::V2 {
+            // Before version 2, every parameter was required and encoded as
+            // just its type.
+            w.uleb(self.params.len() as u32)?;
+            for param in &self.params {
+                if param.kind != WebidlFunctionParamKind::Required {
+                    require(cx, FormatVersion::V2, "an optional or variadic parameter")?;
+                }
+                param.ty.encode(cx, w)?;
+            }
+        } else {
+            w.vec(cx, &self.params)?;
+        }
         if let Some(result) = self.result.as_ref() {
             w.byte(1)?;
             result.encode(cx, w)
@@ -222,10 +622,63 @@ impl Encode for WebidlFunction {
     }
 }
 
-impl Encode for WebidlFunctionKind {
+impl<R: WasmRefs> Encode<R> for WebidlFunctionParam {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
+        W: ?Sized + io::Write,
+    {
+        match &self.kind {
+            WebidlFunctionParamKind::Required => {
+                w.byte(0)?;
+                self.ty.encode(cx, w)
+            }
+            WebidlFunctionParamKind::Optional(o) => {
+                w.byte(1)?;
+                self.ty.encode(cx, w)?;
+                if let Some(default) = o.default.as_ref() {
+                    w.byte(1)?;
+                    default.encode(cx, w)
+                } else {
+                    w.byte(0)
+                }
+            }
+            WebidlFunctionParamKind::Variadic => {
+                w.byte(2)?;
+                self.ty.encode(cx, w)
+            }
+        }
+    }
+}
+
+impl<R: WasmRefs> Encode<R> for WebidlDefaultValue {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Indices<R>,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            WebidlDefaultValue::Null => w.byte(0),
+            WebidlDefaultValue::Boolean(b) => {
+                w.byte(1)?;
+                w.byte(*b as u8)
+            }
+            WebidlDefaultValue::Integer(i) => {
+                w.byte(2)?;
+                w.ileb64(*i)
+            }
+            WebidlDefaultValue::String(s) => {
+                w.byte(3)?;
+                s.encode(cx, w)
+            }
+        }
+    }
+}
+
+impl<R: WasmRefs> Encode<R> for WebidlFunctionKind {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -234,28 +687,73 @@ impl Encode for WebidlFunctionKind {
                 w.byte(1)?;
                 m.ty.encode(cx, w)
             }
-            WebidlFunctionKind::Constructor => w.byte(2),
+            WebidlFunctionKind::Constructor(c) => {
+                w.byte(2)?;
+                if cx.version() < FormatVersion::V2 {
+                    if c.new_target.is_some() {
+                        require(cx, FormatVersion::V2, "a constructor's new target")?;
+                    }
+                    return Ok(());
+                }
+                if let Some(new_target) = c.new_target.as_ref() {
+                    w.byte(1)?;
+                    new_target.encode(cx, w)
+                } else {
+                    w.byte(0)
+                }
+            }
         }
     }
 }
 
-impl Encode for WebidlTypeRef {
+impl<R: WasmRefs> Encode<R> for WebidlTypeRef {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
             WebidlTypeRef::Id(id) => w.ileb(cx.webidl_type_index(*id) as i32),
             WebidlTypeRef::Scalar(s) => s.encode(cx, w),
+            WebidlTypeRef::Annotated(a) => {
+                require(cx, FormatVersion::V2, "an annotated type")?;
+                w.ileb(-64)?;
+                a.ty().encode(cx, w)?;
+                a.attributes().encode(cx, w)
+            }
         }
     }
 }
 
-impl Encode for WebidlScalarType {
+impl<R: WasmRefs> Encode<R> for WebidlExtendedAttributes {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Indices<R>,
+        W: ?Sized + io::Write,
+    {
+        w.vec(cx, self.iter().collect::<Vec<_>>())
+    }
+}
+
+impl<R: WasmRefs> Encode<R> for WebidlExtendedAttribute {
     fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            WebidlExtendedAttribute::Clamp => w.byte(0),
+            WebidlExtendedAttribute::EnforceRange => w.byte(1),
+            WebidlExtendedAttribute::AllowShared => w.byte(2),
+            WebidlExtendedAttribute::LegacyNullToEmptyString => w.byte(3),
+        }
+    }
+}
+
+impl<R: WasmRefs> Encode<R> for WebidlScalarType {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -289,24 +787,28 @@ impl Encode for WebidlScalarType {
             WebidlScalarType::Uint8ClampedArray => w.ileb(-28),
             WebidlScalarType::Float32Array => w.ileb(-29),
             WebidlScalarType::Float64Array => w.ileb(-30),
+            WebidlScalarType::BigInt => {
+                require(cx, FormatVersion::V2, "`bigint`")?;
+                w.ileb(-31)
+            }
         }
     }
 }
 
-impl Encode for WebidlDictionary {
+impl<R: WasmRefs> Encode<R> for WebidlDictionary {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.fields)
     }
 }
 
-impl Encode for WebidlDictionaryField {
+impl<R: WasmRefs> Encode<R> for WebidlDictionaryField {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         self.name.encode(cx, w)?;
@@ -314,30 +816,30 @@ impl Encode for WebidlDictionaryField {
     }
 }
 
-impl Encode for WebidlEnumeration {
+impl<R: WasmRefs> Encode<R> for WebidlEnumeration {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.values)
     }
 }
 
-impl Encode for WebidlUnion {
+impl<R: WasmRefs> Encode<R> for WebidlUnion {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.members)
     }
 }
 
-impl Encode for FunctionBinding {
+impl<R: WasmRefs> Encode<R> for FunctionBinding<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -353,73 +855,63 @@ impl Encode for FunctionBinding {
     }
 }
 
-impl Encode for ImportBinding {
+impl<R: WasmRefs> Encode<R> for ImportBinding<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
-        self.wasm_ty.encode(cx, w)?;
+        w.uleb(cx.wasm_func_type_index(self.wasm_ty))?;
         self.webidl_ty.encode(cx, w)?;
         self.params.encode(cx, w)?;
         self.result.encode(cx, w)
     }
 }
 
-impl Encode for ExportBinding {
+impl<R: WasmRefs> Encode<R> for ExportBinding<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
-        self.wasm_ty.encode(cx, w)?;
+        w.uleb(cx.wasm_func_type_index(self.wasm_ty))?;
         self.webidl_ty.encode(cx, w)?;
         self.params.encode(cx, w)?;
         self.result.encode(cx, w)
     }
 }
 
-impl Encode for walrus::ValType {
+impl<R: WasmRefs> Encode<R> for ValType {
     fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
-            walrus::ValType::I32 => w.byte(0x7f),
-            walrus::ValType::I64 => w.byte(0x7e),
-            walrus::ValType::F32 => w.byte(0x7d),
-            walrus::ValType::F64 => w.byte(0x7c),
-            walrus::ValType::V128 => w.byte(0x7b),
-            walrus::ValType::Anyref => w.byte(0x6f),
+            ValType::I32 => w.byte(0x7f),
+            ValType::I64 => w.byte(0x7e),
+            ValType::F32 => w.byte(0x7d),
+            ValType::F64 => w.byte(0x7c),
+            ValType::V128 => w.byte(0x7b),
+            ValType::Anyref => w.byte(0x6f),
         }
     }
 }
 
-impl Encode for walrus::TypeId {
-    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
-    where
-        Cx: Indices,
-        W: ?Sized + io::Write,
-    {
-        w.uleb(cx.wasm_func_type_index(*self))
-    }
-}
-
-impl Encode for OutgoingBindingMap {
+impl<R: WasmRefs> Encode<R> for OutgoingBindingMap<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.bindings)
     }
 }
 
-impl Encode for OutgoingBindingExpression {
+impl<R: WasmRefs> Encode<R> for OutgoingBindingExpression<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -448,13 +940,15 @@ impl Encode for OutgoingBindingExpression {
                 w.byte(4)?;
                 e.ty.encode(cx, w)?;
                 w.uleb(e.offset)?;
-                w.uleb(e.length)
+                w.uleb(e.length)?;
+                e.unit.encode(cx, w)
             }
             OutgoingBindingExpression::Copy(e) => {
                 w.byte(5)?;
                 e.ty.encode(cx, w)?;
                 w.uleb(e.offset)?;
-                w.uleb(e.length)
+                w.uleb(e.length)?;
+                e.unit.encode(cx, w)
             }
             OutgoingBindingExpression::Dict(e) => {
                 w.byte(6)?;
@@ -467,34 +961,82 @@ impl Encode for OutgoingBindingExpression {
                 e.binding.encode(cx, w)?;
                 w.uleb(e.idx)
             }
+            OutgoingBindingExpression::Undefined(_) => {
+                require(cx, FormatVersion::V2, "an `undefined` binding expression")?;
+                w.byte(8)
+            }
+            OutgoingBindingExpression::Variadic(e) => {
+                require(cx, FormatVersion::V2, "a `variadic` binding expression")?;
+                w.byte(9)?;
+                e.ty.encode(cx, w)?;
+                let elem_ty: ValType = e.elem_ty.into();
+                elem_ty.encode(cx, w)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.length)
+            }
+            OutgoingBindingExpression::Utf16Str(e) => {
+                require(cx, FormatVersion::V2, "a `utf16-str` binding expression")?;
+                w.byte(10)?;
+                e.ty.encode(cx, w)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.length)
+            }
+            OutgoingBindingExpression::Latin1Str(e) => {
+                require(cx, FormatVersion::V2, "a `latin1-str` binding expression")?;
+                w.byte(11)?;
+                e.ty.encode(cx, w)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.length)
+            }
+        }
+    }
+}
+
+impl<R: WasmRefs> Encode<R> for BufferLengthUnit {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Indices<R>,
+        W: ?Sized + io::Write,
+    {
+        // Before version 2, every length was in elements, and the unit was
+        // not encoded.
+        if cx.version() < FormatVersion::V2 {
+            if *self != BufferLengthUnit::Elements {
+                require(cx, FormatVersion::V2, "a length in bytes")?;
+            }
+            return Ok(());
         }
+        w.byte(match self {
+            BufferLengthUnit::Elements => 0,
+            BufferLengthUnit::Bytes => 1,
+        })
     }
 }
 
-impl Encode for Id<FunctionBinding> {
+impl<R: WasmRefs> Encode<R> for Id<FunctionBinding<R>> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.uleb(cx.binding_index(*self))
     }
 }
 
-impl Encode for IncomingBindingMap {
+impl<R: WasmRefs> Encode<R> for IncomingBindingMap<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.bindings)
     }
 }
 
-impl Encode for IncomingBindingExpression {
+impl<R: WasmRefs> Encode<R> for IncomingBindingExpression<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
         match self {
@@ -504,7 +1046,8 @@ impl Encode for IncomingBindingExpression {
             }
             IncomingBindingExpression::As(e) => {
                 w.byte(1)?;
-                e.ty.encode(cx, w)?;
+                let ty: ValType = e.ty.into();
+                ty.encode(cx, w)?;
                 e.expr.encode(cx, w)
             }
             IncomingBindingExpression::AllocUtf8Str(e) => {
@@ -529,42 +1072,81 @@ impl Encode for IncomingBindingExpression {
             }
             IncomingBindingExpression::BindImport(e) => {
                 w.byte(6)?;
-                e.ty.encode(cx, w)?;
+                w.uleb(cx.wasm_func_type_index(e.ty))?;
                 e.binding.encode(cx, w)?;
                 e.expr.encode(cx, w)
             }
+            IncomingBindingExpression::AllocUtf16Str(e) => {
+                require(
+                    cx,
+                    FormatVersion::V2,
+                    "an `alloc-utf16-str` binding expression",
+                )?;
+                w.byte(7)?;
+                e.alloc_func_name.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            IncomingBindingExpression::AllocLatin1Str(e) => {
+                require(
+                    cx,
+                    FormatVersion::V2,
+                    "an `alloc-latin1-str` binding expression",
+                )?;
+                w.byte(8)?;
+                e.alloc_func_name.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            IncomingBindingExpression::WriteUtf8Str(e) => {
+                require(
+                    cx,
+                    FormatVersion::V2,
+                    "a `write-utf8-str` binding expression",
+                )?;
+                w.byte(9)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.capacity)?;
+                e.expr.encode(cx, w)
+            }
+            IncomingBindingExpression::WriteUtf16Str(e) => {
+                require(
+                    cx,
+                    FormatVersion::V2,
+                    "a `write-utf16-str` binding expression",
+                )?;
+                w.byte(10)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.capacity)?;
+                e.expr.encode(cx, w)
+            }
+            IncomingBindingExpression::WriteCopy(e) => {
+                require(cx, FormatVersion::V2, "a `write-copy` binding expression")?;
+                w.byte(11)?;
+                w.uleb(e.offset)?;
+                w.uleb(e.capacity)?;
+                e.expr.encode(cx, w)
+            }
         }
     }
 }
 
-impl Encode for Bind {
+impl<R: WasmRefs> Encode<R> for Bind<R> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Indices<R>,
         W: ?Sized + io::Write,
     {
-        self.func.encode(cx, w)?;
+        w.uleb(cx.wasm_func_index(self.func))?;
         self.binding.encode(cx, w)
     }
 }
 
-impl Encode for walrus::FunctionId {
-    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
-    where
-        Cx: Indices,
-        W: ?Sized + io::Write,
-    {
-        w.uleb(cx.wasm_func_index(*self))
-    }
-}
-
-#[cfg(test)]
+#[cfg(all(test, feature = "walrus"))]
 mod tests {
     use super::*;
 
-    struct TestIndices;
+    struct TestIndices(FormatVersion);
 
-    impl Indices for TestIndices {
+    impl Indices<Walrus> for TestIndices {
         fn assign_webidl_type_index(&mut self, _: Id<WebidlCompoundType>) {}
 
         fn webidl_type_index(&self, _: Id<WebidlCompoundType>) -> u32 {
@@ -584,26 +1166,56 @@ mod tests {
         fn wasm_func_type_index(&self, _: walrus::TypeId) -> u32 {
             44
         }
+
+        fn version(&self) -> FormatVersion {
+            self.0
+        }
     }
 
-    // fn get_func_index(&self, id: walrus::FunctionId) -> u32;
-    // fn get_func_type_index(&self, id: walrus::TypeId) -> u32;
     fn do_assert_encoding<E>(ast: E, expected: &[u8])
     where
-        E: Encode,
+        E: Encode<Walrus>,
     {
         let mut actual = vec![];
-        ast.encode(&mut TestIndices, &mut actual)
+        ast.encode(&mut TestIndices(FormatVersion::CURRENT), &mut actual)
             .expect("writing to a vec can't fail");
         assert_eq!(expected, &actual[..]);
     }
 
+    impl IndexResolver for TestIndices {
+        fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32 {
+            Indices::webidl_type_index(self, id)
+        }
+
+        fn binding_index(&self, id: Id<FunctionBinding>) -> u32 {
+            Indices::binding_index(self, id)
+        }
+
+        fn wasm_func_index(&self, id: walrus::FunctionId) -> u32 {
+            Indices::wasm_func_index(self, id)
+        }
+
+        fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32 {
+            Indices::wasm_func_type_index(self, id)
+        }
+    }
+
+    fn encode_v1<E>(ast: E) -> io::Result<Vec<u8>>
+    where
+        E: Encode<Walrus>,
+    {
+        let mut actual = vec![];
+        ast.encode(&mut TestIndices(FormatVersion::V1), &mut actual)?;
+        Ok(actual)
+    }
+
     fn get_webidl_type_ref(b: &mut WebidlBindings) -> WebidlTypeRef {
         let id: WebidlUnionId = b.types.insert(WebidlUnion { members: vec![] });
         let id: Id<WebidlCompoundType> = id.into();
         id.into()
     }
 
+    #[allow(clippy::never_loop, clippy::useless_conversion)]
     fn get_wasm_func_ref(m: &mut walrus::Module) -> walrus::FunctionId {
         for f in m.funcs.iter() {
             return f.id().into();
@@ -628,7 +1240,7 @@ mod tests {
             result: IncomingBindingMap { bindings: vec![] },
         });
         let id: Id<FunctionBinding> = id.into();
-        id.into()
+        id
     }
 
     macro_rules! assert_encoding {
@@ -644,7 +1256,7 @@ mod tests {
                 #[test]
                 #[allow(unused_variables)]
                 fn $name() {
-                    let $bindings = &mut WebidlBindings::default();
+                    let $bindings = &mut <WebidlBindings>::default();
                     let $module = &mut walrus::Module::default();
                     let ast = $ast;
                     do_assert_encoding(ast, &$expected);
@@ -696,7 +1308,8 @@ mod tests {
                             OutgoingBindingExpression::View(OutgoingBindingExpressionView {
                                 ty: WebidlScalarType::Uint8Array.into(),
                                 offset: 2,
-                                length: 3
+                                length: 3,
+                                unit: BufferLengthUnit::Elements,
                             }),
                         ],
                     },
@@ -741,6 +1354,8 @@ mod tests {
                 let section = vec![
                     // types subsection
                     0,
+                    // size
+                    28,
                     // number of types
                     2,
                     // dictionary type
@@ -758,11 +1373,13 @@ mod tests {
                     // method
                     1, 127,
                     // params
-                    2, 111, 103,
+                    2, 0, 111, 0, 103,
                     // result
                     1, 11,
                     // bindings subsection
                     1,
+                    // size
+                    32,
                     // number of bindings
                     1,
                     // import
@@ -776,7 +1393,7 @@ mod tests {
                     // as
                     0, 127, 1,
                     // view
-                    4, 103, 2, 3,
+                    4, 103, 2, 3, 0,
                     // results
                     2,
                     // as
@@ -796,9 +1413,7 @@ mod tests {
                     // bind
                     33, 22,
                 ];
-                let mut bytes = vec![];
-                crate::version().to_string().encode(&mut TestIndices, &mut bytes)
-                    .expect("writing to a vec can't fail");
+                let mut bytes = vec![1, b'2'];
                 bytes.extend_from_slice(&section);
                 bytes
             },
@@ -917,13 +1532,35 @@ mod tests {
         );
         webidl_function_constructor(
             |b, m| WebidlFunction {
-                kind: WebidlFunctionKind::Constructor,
+                kind: WebidlFunctionKindConstructor { new_target: None }.into(),
                 params: vec![],
                 result: None
             },
             [
                 // Constructor kind
                 2,
+                // Has new target?
+                0,
+                // Number of params
+                0,
+                // Has result?
+                0,
+            ]
+        );
+        webidl_function_constructor_new_target(
+            |b, m| WebidlFunction {
+                kind: WebidlFunctionKindConstructor {
+                    new_target: Some(get_webidl_type_ref(b)),
+                }.into(),
+                params: vec![],
+                result: None
+            },
+            [
+                // Constructor kind
+                2,
+                // Has new target?
+                1,
+                11,
                 // Number of params
                 0,
                 // Has result?
@@ -933,7 +1570,7 @@ mod tests {
         webidl_function_params(
             |b, m| WebidlFunction {
                 kind: WebidlFunctionKind::Static,
-                params: vec![get_webidl_type_ref(b), get_webidl_type_ref(b)],
+                params: vec![get_webidl_type_ref(b).into(), get_webidl_type_ref(b).into()],
                 result: None
             },
             [
@@ -941,7 +1578,57 @@ mod tests {
                 0,
                 // Number of params
                 2,
+                // Required
+                0,
+                11,
+                // Required
+                0,
+                11,
+                // Has result?
+                0,
+            ]
+        );
+        webidl_function_optional_and_variadic_params(
+            |b, m| WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params: vec![
+                    WebidlFunctionParam {
+                        ty: get_webidl_type_ref(b),
+                        kind: WebidlFunctionParamOptional { default: None }.into(),
+                    },
+                    WebidlFunctionParam {
+                        ty: get_webidl_type_ref(b),
+                        kind: WebidlFunctionParamOptional {
+                            default: Some(WebidlDefaultValue::Integer(-2)),
+                        }.into(),
+                    },
+                    WebidlFunctionParam {
+                        ty: get_webidl_type_ref(b),
+                        kind: WebidlFunctionParamKind::Variadic,
+                    },
+                ],
+                result: None
+            },
+            [
+                // Static kind
+                0,
+                // Number of params
+                3,
+                // Optional
+                1,
                 11,
+                // Has default?
+                0,
+                // Optional
+                1,
+                11,
+                // Has default?
+                1,
+                // Integer
+                2,
+                0x7e,
+                // Variadic
+                2,
                 11,
                 // Has result?
                 0,
@@ -964,6 +1651,62 @@ mod tests {
             ]
         );
 
+        webidl_default_value_null(
+            |b, m| WebidlDefaultValue::Null,
+            [0],
+        );
+        webidl_default_value_boolean(
+            |b, m| WebidlDefaultValue::Boolean(true),
+            [1, 1],
+        );
+        webidl_default_value_integer(
+            |b, m| WebidlDefaultValue::Integer(128),
+            [2, 0x80, 0x01],
+        );
+        webidl_default_value_string(
+            |b, m| WebidlDefaultValue::String("a".into()),
+            [3, 1, 97],
+        );
+
+        webidl_type_ref_annotated(
+            |b, m| WebidlTypeRef::from(
+                WebidlAnnotatedTypeRef::new(
+                    WebidlScalarType::DomString,
+                    std::iter::once(WebidlExtendedAttribute::LegacyNullToEmptyString).collect(),
+                )
+                .unwrap()
+            ),
+            [
+                // Annotated
+                0x40,
+                // DOMString
+                0x71,
+                // Number of attributes
+                1,
+                // [LegacyNullToEmptyString]
+                3,
+            ],
+        );
+        webidl_type_ref_annotated_enforce_range(
+            |b, m| WebidlTypeRef::from(
+                WebidlAnnotatedTypeRef::new(
+                    WebidlScalarType::Long,
+                    std::iter::once(WebidlExtendedAttribute::EnforceRange).collect(),
+                )
+                .unwrap()
+            ),
+            [
+                // Annotated
+                0x40,
+                // long
+                0x7b,
+                // Number of attributes
+                1,
+                // [EnforceRange]
+                1,
+            ],
+        );
+
         webidl_dictionary(
             |b, m| WebidlDictionary {
                 fields: vec![
@@ -1143,6 +1886,7 @@ mod tests {
                 ty: get_webidl_type_ref(b),
                 offset: 1,
                 length: 2,
+                unit: BufferLengthUnit::Elements,
             }),
             [
                 // view
@@ -1152,6 +1896,8 @@ mod tests {
                 1,
                 // length
                 2,
+                // elements
+                0,
             ],
         );
         outgoing_binding_expression_copy(
@@ -1159,6 +1905,7 @@ mod tests {
                 ty: get_webidl_type_ref(b),
                 offset: 1,
                 length: 2,
+                unit: BufferLengthUnit::Bytes,
             }),
             [
                 // copy
@@ -1168,6 +1915,8 @@ mod tests {
                 1,
                 // length
                 2,
+                // bytes
+                1,
             ],
         );
         outgoing_binding_expression_dict(
@@ -1206,6 +1955,57 @@ mod tests {
                 3,
             ],
         );
+        outgoing_binding_expression_undefined(
+            |b, m| OutgoingBindingExpression::Undefined(OutgoingBindingExpressionUndefined),
+            [
+                // undefined
+                8,
+            ],
+        );
+        outgoing_binding_expression_variadic(
+            |b, m| OutgoingBindingExpression::Variadic(OutgoingBindingExpressionVariadic {
+                ty: get_webidl_type_ref(b),
+                elem_ty: walrus::ValType::I32,
+                offset: 1,
+                length: 2,
+            }),
+            [
+                // variadic
+                9,
+                11,
+                0x7f,
+                1,
+                2,
+            ],
+        );
+        outgoing_binding_expression_utf16_str(
+            |b, m| OutgoingBindingExpression::Utf16Str(OutgoingBindingExpressionUtf16Str {
+                ty: get_webidl_type_ref(b),
+                offset: 3,
+                length: 4,
+            }),
+            [
+                // utf16-str
+                10,
+                11,
+                3,
+                4,
+            ],
+        );
+        outgoing_binding_expression_latin1_str(
+            |b, m| OutgoingBindingExpression::Latin1Str(OutgoingBindingExpressionLatin1Str {
+                ty: get_webidl_type_ref(b),
+                offset: 3,
+                length: 4,
+            }),
+            [
+                // latin1-str
+                11,
+                11,
+                3,
+                4,
+            ],
+        );
 
         incoming_binding_map(
             |b, m| IncomingBindingMap {
@@ -1333,6 +2133,94 @@ mod tests {
                 3
             ],
         );
+        incoming_binding_expression_alloc_utf16_str(
+            |b, m| IncomingBindingExpression::AllocUtf16Str(IncomingBindingExpressionAllocUtf16Str {
+                alloc_func_name: "malloc".into(),
+                expr: Box::new(IncomingBindingExpression::Get(IncomingBindingExpressionGet {
+                    idx: 1,
+                })),
+            }),
+            [
+                // alloc-utf16-str
+                7,
+                // "malloc"
+                6, 109, 97, 108, 108, 111, 99,
+                // get
+                0,
+                1
+            ],
+        );
+        incoming_binding_expression_alloc_latin1_str(
+            |b, m| IncomingBindingExpression::AllocLatin1Str(IncomingBindingExpressionAllocLatin1Str {
+                alloc_func_name: "malloc".into(),
+                expr: Box::new(IncomingBindingExpression::Get(IncomingBindingExpressionGet {
+                    idx: 1,
+                })),
+            }),
+            [
+                // alloc-latin1-str
+                8,
+                // "malloc"
+                6, 109, 97, 108, 108, 111, 99,
+                // get
+                0,
+                1
+            ],
+        );
+        incoming_binding_expression_write_utf8_str(
+            |b, m| IncomingBindingExpression::WriteUtf8Str(IncomingBindingExpressionWriteUtf8Str {
+                offset: 2,
+                capacity: 3,
+                expr: Box::new(IncomingBindingExpression::Get(IncomingBindingExpressionGet {
+                    idx: 1,
+                })),
+            }),
+            [
+                // write-utf8-str
+                9,
+                2,
+                3,
+                // get
+                0,
+                1
+            ],
+        );
+        incoming_binding_expression_write_utf16_str(
+            |b, m| IncomingBindingExpression::WriteUtf16Str(IncomingBindingExpressionWriteUtf16Str {
+                offset: 2,
+                capacity: 3,
+                expr: Box::new(IncomingBindingExpression::Get(IncomingBindingExpressionGet {
+                    idx: 1,
+                })),
+            }),
+            [
+                // write-utf16-str
+                10,
+                2,
+                3,
+                // get
+                0,
+                1
+            ],
+        );
+        incoming_binding_expression_write_copy(
+            |b, m| IncomingBindingExpression::WriteCopy(IncomingBindingExpressionWriteCopy {
+                offset: 2,
+                capacity: 3,
+                expr: Box::new(IncomingBindingExpression::Get(IncomingBindingExpressionGet {
+                    idx: 1,
+                })),
+            }),
+            [
+                // write-copy
+                11,
+                2,
+                3,
+                // get
+                0,
+                1
+            ],
+        );
 
         bind(
             |b, m| Bind {
@@ -1344,4 +2232,253 @@ mod tests {
 
         webidl_type_ref(|b, m| get_webidl_type_ref(b), [11]);
     }
+
+    #[test]
+    fn encode_format_version_1() {
+        let mut b = WebidlBindings::default();
+
+        // Constructs that version 1 has are encoded without the parts that
+        // were added later.
+        let ty = get_webidl_type_ref(&mut b);
+        let f = WebidlFunction {
+            kind: WebidlFunctionKindConstructor { new_target: None }.into(),
+            params: vec![ty.into(), WebidlScalarType::Long.into()],
+            result: None,
+        };
+        assert_eq!(
+            encode_v1(&f).unwrap(),
+            [
+                // Constructor kind
+                2, // Number of params
+                2, 11, 0x7b, // Has result?
+                0,
+            ]
+        );
+        let view = OutgoingBindingExpression::View(OutgoingBindingExpressionView {
+            ty: WebidlScalarType::Uint8Array.into(),
+            offset: 1,
+            length: 2,
+            unit: BufferLengthUnit::Elements,
+        });
+        assert_eq!(encode_v1(&view).unwrap(), [4, 0x67, 1, 2]);
+
+        // Constructs that version 1 doesn't have are errors.
+        let errors = vec![
+            encode_v1(WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params: vec![WebidlFunctionParam {
+                    ty,
+                    kind: WebidlFunctionParamKind::Variadic,
+                }],
+                result: None,
+            }),
+            encode_v1(WebidlFunctionKind::Constructor(
+                WebidlFunctionKindConstructor {
+                    new_target: Some(ty),
+                },
+            )),
+            encode_v1(WebidlTypeRef::from(WebidlScalarType::BigInt)),
+            encode_v1(OutgoingBindingExpression::View(
+                OutgoingBindingExpressionView {
+                    ty: WebidlScalarType::Uint8Array.into(),
+                    offset: 1,
+                    length: 2,
+                    unit: BufferLengthUnit::Bytes,
+                },
+            )),
+            encode_v1(OutgoingBindingExpression::Undefined(
+                OutgoingBindingExpressionUndefined,
+            )),
+        ];
+        let messages: Vec<_> = errors
+            .into_iter()
+            .map(|e| e.map_err(|e| e.to_string()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                Err(
+                    "an optional or variadic parameter cannot be encoded in format version 1, \
+                     only in version 2 and later"
+                        .to_string()
+                ),
+                Err(
+                    "a constructor's new target cannot be encoded in format version 1, \
+                     only in version 2 and later"
+                        .to_string()
+                ),
+                Err("`bigint` cannot be encoded in format version 1, \
+                     only in version 2 and later"
+                    .to_string()),
+                Err("a length in bytes cannot be encoded in format version 1, \
+                     only in version 2 and later"
+                    .to_string()),
+                Err(
+                    "an `undefined` binding expression cannot be encoded in format version 1, \
+                     only in version 2 and later"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    /// A section with two types, a function binding and two binds.
+    fn streamed_section() -> WebidlBindings {
+        let mut b = WebidlBindings::default();
+        let mut m = walrus::Module::default();
+        b.types.insert(WebidlEnumeration {
+            values: vec!["a".into(), "b".into()],
+        });
+        let binding = get_binding_ref(&mut b, &mut m);
+        for _ in 0..2 {
+            b.binds.insert(Bind {
+                func: get_wasm_func_ref(&mut m),
+                binding,
+            });
+        }
+        b
+    }
+
+    fn stream(b: &WebidlBindings, version: FormatVersion) -> io::Result<Vec<u8>> {
+        let mut encoder = StreamingEncoder::with_version(vec![], TestIndices(version), version)?;
+        for (i, (_, ty)) in b.types.arena.iter().enumerate() {
+            assert_eq!(encoder.add_type(ty)?, i as u32);
+        }
+        for (i, (_, binding)) in b.bindings.arena.iter().enumerate() {
+            assert_eq!(encoder.add_binding(binding)?, i as u32);
+        }
+        for (_, bind) in b.binds.iter() {
+            encoder.add_bind(bind)?;
+        }
+        encoder.finish()
+    }
+
+    #[test]
+    fn streaming_encoder_matches_encode() {
+        let b = streamed_section();
+        for &version in &FormatVersion::ALL {
+            let mut expected = vec![];
+            b.encode(&mut TestIndices(version), &mut expected).unwrap();
+            assert_eq!(stream(&b, version).unwrap(), expected);
+        }
+
+        let empty = WebidlBindings::default();
+        assert_eq!(
+            stream(&empty, FormatVersion::CURRENT).unwrap(),
+            [
+                1, b'2', // version marker
+                0, 1, 0, // empty type subsection
+                1, 2, 0, 0, // empty function binding subsection
+            ]
+        );
+    }
+
+    #[test]
+    fn streaming_encoder_with_raw_indices() {
+        let mut encoder = StreamingEncoder::new(vec![], RawIndices).unwrap();
+        let dict = encoder
+            .add_type(&WebidlCompoundType::Dictionary(WebidlDictionary {
+                fields: vec![WebidlDictionaryField {
+                    name: "x".into(),
+                    ty: WebidlScalarType::Long.into(),
+                }],
+            }))
+            .unwrap();
+        let func = encoder
+            .add_type(&WebidlCompoundType::Function(WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params: vec![RawIndices::webidl_type(dict).into()],
+                result: None,
+            }))
+            .unwrap();
+        let binding = encoder
+            .add_binding(&FunctionBinding::Import(ImportBinding {
+                wasm_ty: 3,
+                webidl_ty: RawIndices::webidl_type(func).into(),
+                params: OutgoingBindingMap {
+                    bindings: vec![OutgoingBindingExpressionDict {
+                        ty: RawIndices::webidl_type(dict).into(),
+                        fields: vec![OutgoingBindingExpressionAs {
+                            ty: WebidlScalarType::Long.into(),
+                            idx: 0,
+                        }
+                        .into()],
+                    }
+                    .into()],
+                },
+                result: IncomingBindingMap { bindings: vec![] },
+            }))
+            .unwrap();
+        encoder
+            .add_bind(&Bind {
+                func: 7,
+                binding: RawIndices::binding(binding),
+            })
+            .unwrap();
+        let data = encoder.finish().unwrap();
+
+        let decoded: WebidlBindings<RawIndices> = super::super::decode(&RawIndices, &data).unwrap();
+        let dict = decoded.types.by_index(dict).unwrap();
+        let func = decoded.types.by_index(func).unwrap();
+        match decoded.types.arena.get(func) {
+            Some(WebidlCompoundType::Function(f)) => assert_eq!(f.params, [dict.into()]),
+            ty => panic!("expected a function, found {:?}", ty),
+        }
+        let binding = decoded.bindings.by_index(binding).unwrap();
+        match decoded.bindings.arena.get(binding) {
+            Some(FunctionBinding::Import(b)) => {
+                assert_eq!(b.wasm_ty, 3);
+                assert_eq!(b.webidl_ty, func.into());
+                assert_eq!(
+                    b.params.bindings,
+                    [OutgoingBindingExpressionDict {
+                        ty: dict.into(),
+                        fields: vec![OutgoingBindingExpressionAs {
+                            ty: WebidlScalarType::Long.into(),
+                            idx: 0,
+                        }
+                        .into()],
+                    }
+                    .into()]
+                );
+            }
+            b => panic!("expected an import binding, found {:?}", b),
+        }
+        let binds = decoded.binds.iter().map(|(_, b)| b).collect::<Vec<_>>();
+        assert_eq!(binds, [&Bind { func: 7, binding }]);
+    }
+
+    #[test]
+    fn streaming_encoder_errors() {
+        let b = streamed_section();
+        let (_, ty) = b.types.arena.iter().next().unwrap();
+        let (_, binding) = b.bindings.arena.iter().next().unwrap();
+
+        let mut encoder =
+            StreamingEncoder::new(vec![], TestIndices(FormatVersion::CURRENT)).unwrap();
+        encoder.add_binding(binding).unwrap();
+        assert_eq!(
+            encoder.add_type(ty).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        // A type that can't be encoded is left out, and doesn't use up an
+        // index.
+        let mut encoder = StreamingEncoder::with_version(
+            vec![],
+            TestIndices(FormatVersion::V1),
+            FormatVersion::V1,
+        )
+        .unwrap();
+        let bigint = WebidlCompoundType::Union(WebidlUnion {
+            members: vec![WebidlScalarType::BigInt.into()],
+        });
+        assert!(encoder.add_type(&bigint).is_err());
+        assert_eq!(encoder.add_type(ty).unwrap(), 0);
+        let mut expected = vec![5];
+        expected.extend_from_slice(b"0.8.0");
+        // Type subsection with the enumeration, and no bindings or binds.
+        expected.extend_from_slice(&[0, 1, 2, 2, 1, b'a', 1, b'b', 1, 0, 0]);
+        assert_eq!(encoder.finish().unwrap(), expected);
+    }
 }
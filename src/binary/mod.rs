@@ -1,30 +1,218 @@
 mod decode;
 mod encode;
+mod module;
+pub mod reader;
+
+#[cfg(feature = "serde")]
+pub(crate) use self::decode::validate_param_order;
+pub use self::decode::DecodeLimits;
+pub use self::encode::{IndexResolver, StreamingEncoder};
+pub use self::module::{decode_module, find_section, splice_section, strip_section};
+pub use self::reader::SectionReader;
 
 use self::decode::{Decode, DecodeContext};
 use self::encode::{Encode, EncodeContext};
 use crate::ast::WebidlBindings;
+use crate::wasm::{FromIndices, ToIndices, WasmRefs};
+use anyhow::bail;
+#[cfg(feature = "walrus")]
+use std::borrow::Cow;
+use std::fmt;
 use std::io;
 
+/// A version of the Web IDL bindings section's binary format.
+///
+/// Every section starts with a marker of the format version it is encoded
+/// in. The decoder reads every version listed here, upgrading older versions
+/// to the current AST, and the encoder can target any of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FormatVersion {
+    /// The format written by version 0.8.0 of this crate.
+    ///
+    /// It has no optional or variadic parameters, constructor new targets,
+    /// annotated types, `bigint`, buffer length units, or binding
+    /// expressions beyond `bind-export` and `bind-import`.
+    V1,
+    /// The current format.
+    V2,
+}
+
+impl FormatVersion {
+    /// The version that `encode` writes.
+    pub const CURRENT: FormatVersion = FormatVersion::V2;
+
+    /// Every version that `decode` can read, oldest first.
+    pub const ALL: [FormatVersion; 2] = [FormatVersion::V1, FormatVersion::V2];
+
+    /// The version marker at the start of a section in this version.
+    ///
+    /// Version 1 predates format versions, and is marked with the version of
+    /// the crate that wrote it.
+    fn marker(self) -> &'static str {
+        match self {
+            FormatVersion::V1 => "0.8.0",
+            FormatVersion::V2 => "2",
+        }
+    }
+
+    fn from_marker(marker: &str) -> Option<FormatVersion> {
+        FormatVersion::ALL
+            .iter()
+            .cloned()
+            .find(|v| v.marker() == marker)
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatVersion::V1 => f.write_str("1"),
+            FormatVersion::V2 => f.write_str("2"),
+        }
+    }
+}
+
 /// Encode the given Web IDL bindings section into the given write-able.
-pub fn encode<W>(
-    section: &WebidlBindings,
-    indices: &walrus::IdsToIndices,
+///
+/// The section's Wasm references are encoded as the indices that `indices`
+/// gives for them, such as a `walrus::IdsToIndices` for a section that uses
+/// `walrus` IDs.
+pub fn encode<R, I, W>(section: &WebidlBindings<R>, indices: &I, into: &mut W) -> io::Result<()>
+where
+    R: WasmRefs,
+    I: ToIndices<R>,
+    W: io::Write,
+{
+    encode_with_version(section, indices, FormatVersion::CURRENT, into)
+}
+
+/// Like `encode`, but encode the section in the given format version, for
+/// consumers that don't read the current one yet.
+///
+/// Fails with an `InvalidInput` error if the section uses something that
+/// `version` cannot represent.
+pub fn encode_with_version<R, I, W>(
+    section: &WebidlBindings<R>,
+    indices: &I,
+    version: FormatVersion,
     into: &mut W,
 ) -> io::Result<()>
 where
+    R: WasmRefs,
+    I: ToIndices<R>,
     W: io::Write,
 {
-    let cx = &mut EncodeContext::new(indices);
+    let cx = &mut EncodeContext::new(indices, version);
     section.encode(cx, into)
 }
 
+/// Read the format version of the given Web IDL bindings custom section data,
+/// without decoding the rest of it.
+pub fn format_version(from: &[u8]) -> anyhow::Result<FormatVersion> {
+    let mut from = from;
+    let len = leb128::read::unsigned(&mut from)?;
+    let marker = match from.get(..len as usize) {
+        Some(marker) => String::from_utf8_lossy(marker),
+        None => bail!("unexpected end of input in the format version marker"),
+    };
+    match FormatVersion::from_marker(&marker) {
+        Some(version) => Ok(version),
+        None => bail!(
+            "unsupported Web IDL bindings format version `{}`; this library reads {}",
+            marker,
+            supported_markers()
+        ),
+    }
+}
+
+fn supported_markers() -> String {
+    FormatVersion::ALL
+        .iter()
+        .map(|v| format!("`{}`", v.marker()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A Web IDL bindings section that is emitted in a particular format version.
+///
+/// Add this to a module's custom sections instead of the `WebidlBindings`
+/// itself to emit the section in an older format version.
+#[cfg(feature = "walrus")]
+#[derive(Debug)]
+pub struct VersionedSection {
+    section: WebidlBindings,
+    version: FormatVersion,
+}
+
+#[cfg(feature = "walrus")]
+impl VersionedSection {
+    /// Wrap `section` to be emitted in format version `version`.
+    ///
+    /// Fails if the section uses something that `version` cannot represent.
+    pub fn new(section: WebidlBindings, version: FormatVersion) -> anyhow::Result<Self> {
+        encode::check_version(&section, version)?;
+        Ok(VersionedSection { section, version })
+    }
+
+    /// The wrapped section.
+    pub fn section(&self) -> &WebidlBindings {
+        &self.section
+    }
+
+    /// The format version the section is emitted in.
+    pub fn version(&self) -> FormatVersion {
+        self.version
+    }
+
+    /// Unwrap the section.
+    pub fn into_inner(self) -> WebidlBindings {
+        self.section
+    }
+}
+
+#[cfg(feature = "walrus")]
+impl walrus::CustomSection for VersionedSection {
+    fn name(&self) -> &str {
+        "webidl-bindings"
+    }
+
+    fn data(&self, ids_to_indices: &walrus::IdsToIndices) -> Cow<'_, [u8]> {
+        let mut data = vec![];
+        encode_with_version(&self.section, ids_to_indices, self.version, &mut data)
+            .expect("the section was checked to be representable in its version");
+        data.into()
+    }
+}
+
 /// Decode the Web IDL bindings custom section data from the given input stream.
 ///
 /// This does *not* parse the custom section discriminant and "webidl-bindings"
 /// custom section name, just the inner data.
-pub fn decode(ids: &walrus::IndicesToIds, from: &[u8]) -> anyhow::Result<WebidlBindings> {
-    let mut cx = DecodeContext::new(ids);
+///
+/// Indices of Wasm functions and types are resolved with `ids`, such as the
+/// `walrus::IndicesToIds` given to an `on_parse` callback, or `RawIndices` to
+/// keep them as they are.
+///
+/// The section is decoded with the default `DecodeLimits`.
+pub fn decode<R, I>(ids: &I, from: &[u8]) -> anyhow::Result<WebidlBindings<R>>
+where
+    R: WasmRefs,
+    I: FromIndices<R>,
+{
+    decode_with_limits(ids, from, &DecodeLimits::default())
+}
+
+/// Like `decode`, but fail if the section exceeds the given `limits`.
+pub fn decode_with_limits<R, I>(
+    ids: &I,
+    from: &[u8],
+    limits: &DecodeLimits,
+) -> anyhow::Result<WebidlBindings<R>>
+where
+    R: WasmRefs,
+    I: FromIndices<R>,
+{
+    let mut cx = DecodeContext::new(ids, limits.clone());
     let mut from = from;
     WebidlBindings::decode(&mut cx, &mut from)?;
     Ok(cx.webidl_bindings)
@@ -32,6 +220,7 @@ pub fn decode(ids: &walrus::IndicesToIds, from: &[u8]) -> anyhow::Result<WebidlB
 
 /// Callback for `walrus::ModuleConfig::on_parse` to parse the webidl bindings
 /// custom section if one is found.
+#[cfg(feature = "walrus")]
 pub fn on_parse(module: &mut walrus::Module, ids: &walrus::IndicesToIds) -> anyhow::Result<()> {
     let section = match module.customs.remove_raw("webidl-bindings") {
         Some(s) => s,
@@ -41,3 +230,119 @@ pub fn on_parse(module: &mut walrus::Module, ids: &walrus::IndicesToIds) -> anyh
     module.customs.add(bindings);
     Ok(())
 }
+
+#[cfg(all(test, feature = "walrus"))]
+mod tests {
+    use super::*;
+    use crate::ast::*;
+    use crate::wasm::{RawIndices, ValType};
+
+    fn module_with_section(ty: WebidlScalarType) -> walrus::Module {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[walrus::ValType::I32], &[]);
+        let (func, _) = m.add_import_func("env", "f", wasm_ty);
+
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![ty.into()],
+            result: None,
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![crate::builder::outgoing::as_(ty, 0)],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+        m.customs.add(wb);
+        m
+    }
+
+    #[test]
+    fn emit_older_format_version() {
+        let mut m = module_with_section(WebidlScalarType::Long);
+        let wb = m.customs.delete_typed::<WebidlBindings>().unwrap();
+        m.customs
+            .add(VersionedSection::new(*wb, FormatVersion::V1).unwrap());
+        let wasm = m.emit_wasm();
+
+        let mut config = walrus::ModuleConfig::new();
+        config.on_parse(|module, ids| {
+            let section = module.customs.remove_raw("webidl-bindings").unwrap();
+            assert_eq!(format_version(&section.data)?, FormatVersion::V1);
+            let wb = decode(ids, &section.data)?;
+            assert_eq!(wb.binds.iter().count(), 1);
+            Ok(())
+        });
+        config.parse(&wasm).unwrap();
+
+        let mut m = module_with_section(WebidlScalarType::BigInt);
+        let wb = m.customs.delete_typed::<WebidlBindings>().unwrap();
+        assert_eq!(
+            VersionedSection::new(*wb, FormatVersion::V1)
+                .unwrap_err()
+                .to_string(),
+            "`bigint` cannot be encoded in format version 1, only in version 2 and later"
+        );
+    }
+
+    #[test]
+    fn raw_indices_round_trip() {
+        let mut wb = WebidlBindings::<RawIndices>::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: Some(WebidlScalarType::Long.into()),
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty: 3,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap { bindings: vec![] },
+            result: IncomingBindingMap {
+                bindings: vec![IncomingBindingExpressionAs {
+                    ty: ValType::I64,
+                    expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                }
+                .into()],
+            },
+        });
+        wb.binds.insert(Bind {
+            func: 7,
+            binding: binding.into(),
+        });
+
+        let mut data = vec![];
+        encode(&wb, &RawIndices, &mut data).unwrap();
+        let decoded: WebidlBindings<RawIndices> = decode(&RawIndices, &data).unwrap();
+
+        let (_, bind) = decoded.binds.iter().next().unwrap();
+        assert_eq!(bind.func, 7);
+        let actual: &FunctionBinding<RawIndices> = decoded.bindings.get(bind.binding).unwrap();
+        let expected: &ImportBinding<RawIndices> = wb.bindings.get(binding).unwrap();
+        match actual {
+            FunctionBinding::Import(i) => {
+                assert_eq!(i.wasm_ty, 3);
+                assert_eq!(i.result, expected.result);
+            }
+            b => panic!("expected an import binding, found {:?}", b),
+        }
+
+        // A section emitted by walrus decodes to the indices it was encoded as.
+        let wasm = module_with_section(WebidlScalarType::Long).emit_wasm();
+        let mut m = walrus::Module::from_buffer(&wasm).unwrap();
+        let data = m.customs.remove_raw("webidl-bindings").unwrap().data;
+        let decoded: WebidlBindings<RawIndices> = decode(&RawIndices, &data).unwrap();
+        let (_, bind) = decoded.binds.iter().next().unwrap();
+        assert_eq!(bind.func, 0);
+        match decoded.bindings.get(bind.binding) {
+            Some(FunctionBinding::Import(i)) => assert_eq!(i.wasm_ty, 0),
+            b => panic!("expected an import binding, found {:?}", b),
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Finding, removing and replacing the Web IDL bindings custom section in an
+//! encoded Wasm module, without parsing the rest of the module.
+//!
+//! Only the module's header and the ids and sizes of its sections are read,
+//! and every other section is copied through byte for byte.
+
+use super::decode::ReadExt;
+use super::DecodeLimits;
+use crate::ast::WebidlBindings;
+use crate::wasm::RawIndices;
+use anyhow::{bail, Result};
+use std::ops::Range;
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+const SECTION_NAME: &[u8] = b"webidl-bindings";
+
+/// A section of an encoded Wasm module.
+struct Section<'a> {
+    id: u8,
+    /// Where the whole section, including its id and size, is in the module.
+    range: Range<usize>,
+    contents: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    /// The data of this section, if it is a Web IDL bindings custom section.
+    fn webidl_bindings_data(&self) -> Result<Option<&'a [u8]>> {
+        if self.id != 0 {
+            return Ok(None);
+        }
+        let mut r = self.contents;
+        let len = r.uleb()? as usize;
+        if len > r.len() {
+            bail!(
+                "custom section name length {} exceeds the remaining {} bytes",
+                len,
+                r.len()
+            );
+        }
+        let (name, data) = r.split_at(len);
+        Ok(if name == SECTION_NAME {
+            Some(data)
+        } else {
+            None
+        })
+    }
+}
+
+/// Split the encoded Wasm module `wasm` into its sections.
+fn sections(wasm: &[u8]) -> Result<Vec<Section<'_>>> {
+    if wasm.get(..MAGIC.len()) != Some(MAGIC) {
+        bail!("not a Wasm module: missing the `\\0asm` magic number");
+    }
+    match wasm.get(MAGIC.len()..MAGIC.len() + VERSION.len()) {
+        Some(VERSION) => {}
+        Some(v) => bail!(
+            "unsupported Wasm version 0x{:02X}{:02X}{:02X}{:02X}",
+            v[3],
+            v[2],
+            v[1],
+            v[0]
+        ),
+        None => bail!("unexpected end of input in the Wasm version"),
+    }
+
+    let mut r = &wasm[MAGIC.len() + VERSION.len()..];
+    let mut sections = vec![];
+    while !r.is_empty() {
+        let start = wasm.len() - r.len();
+        let id = r.read_byte()?;
+        let size = r.uleb()? as usize;
+        if size > r.len() {
+            bail!(
+                "section size {} exceeds the remaining {} bytes",
+                size,
+                r.len()
+            );
+        }
+        let (contents, rest) = r.split_at(size);
+        r = rest;
+        sections.push(Section {
+            id,
+            range: start..wasm.len() - r.len(),
+            contents,
+        });
+    }
+    Ok(sections)
+}
+
+/// Find the Web IDL bindings custom section in the encoded Wasm module `wasm`,
+/// and return its data.
+///
+/// The data does *not* include the custom section's discriminant and name,
+/// so it can be given to `decode` or `SectionReader::new`. Fails if the module
+/// has more than one Web IDL bindings section.
+pub fn find_section(wasm: &[u8]) -> Result<Option<&[u8]>> {
+    let mut found = None;
+    for section in sections(wasm)? {
+        if let Some(data) = section.webidl_bindings_data()? {
+            if found.is_some() {
+                bail!("found more than one Web IDL bindings custom section");
+            }
+            found = Some(data);
+        }
+    }
+    Ok(found)
+}
+
+/// Find and decode the Web IDL bindings custom section in the encoded Wasm
+/// module `wasm`.
+///
+/// Wasm functions and types are referred to by their indices in the module,
+/// and are not checked against it. The section is decoded with the default
+/// `DecodeLimits`.
+pub fn decode_module(wasm: &[u8]) -> Result<Option<WebidlBindings<RawIndices>>> {
+    match find_section(wasm)? {
+        Some(data) => {
+            let section = super::decode_with_limits(&RawIndices, data, &DecodeLimits::default())?;
+            Ok(Some(section))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Remove every Web IDL bindings custom section from the encoded Wasm module
+/// `wasm`.
+pub fn strip_section(wasm: &[u8]) -> Result<Vec<u8>> {
+    splice(wasm, None)
+}
+
+/// Put a Web IDL bindings custom section with the given `data` in the encoded
+/// Wasm module `wasm`.
+///
+/// The data is the section's encoding without its discriminant and name, as
+/// written by `encode`. It replaces the module's existing Web IDL bindings
+/// section, if it has one, and is appended to the module otherwise.
+pub fn splice_section(wasm: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    splice(wasm, Some(data))
+}
+
+fn splice(wasm: &[u8], data: Option<&[u8]>) -> Result<Vec<u8>> {
+    let sections = sections(wasm)?;
+    let mut out = Vec::with_capacity(wasm.len() + data.map_or(0, |d| d.len()));
+    out.extend_from_slice(&wasm[..MAGIC.len() + VERSION.len()]);
+    let mut data = data;
+    for section in &sections {
+        if section.webidl_bindings_data()?.is_none() {
+            out.extend_from_slice(&wasm[section.range.clone()]);
+        } else if let Some(data) = data.take() {
+            write_section(&mut out, data)?;
+        }
+    }
+    if let Some(data) = data {
+        write_section(&mut out, data)?;
+    }
+    Ok(out)
+}
+
+/// Write a Web IDL bindings custom section with the given `data`.
+fn write_section(out: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    let mut name = vec![];
+    leb128::write::unsigned(&mut name, SECTION_NAME.len() as u64)?;
+    name.extend_from_slice(SECTION_NAME);
+    out.push(0);
+    leb128::write::unsigned(out, (name.len() + data.len()) as u64)?;
+    out.extend_from_slice(&name);
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::*;
+    use crate::binary::{SectionReader, StreamingEncoder};
+
+    /// A module with a type, an imported function, an export, and a Web IDL
+    /// bindings section that binds the function.
+    #[cfg(feature = "walrus")]
+    fn module() -> walrus::Module {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[walrus::ValType::I32], &[]);
+        let (func, _) = m.add_import_func("env", "f", wasm_ty);
+        m.exports.add("f", func);
+
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlScalarType::Long.into()],
+            result: None,
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![crate::builder::outgoing::as_(WebidlScalarType::Long, 0)],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+        m.customs.add(wb);
+        m
+    }
+
+    #[cfg(feature = "walrus")]
+    fn walrus_section_data(wasm: &[u8]) -> Option<Vec<u8>> {
+        let mut m = walrus::Module::from_buffer(wasm).unwrap();
+        m.customs.remove_raw("webidl-bindings").map(|s| s.data)
+    }
+
+    #[test]
+    #[cfg(feature = "walrus")]
+    fn find_and_decode() {
+        let wasm = module().emit_wasm();
+        let data = find_section(&wasm).unwrap().unwrap();
+        assert_eq!(Some(data.to_vec()), walrus_section_data(&wasm));
+
+        let wb = decode_module(&wasm).unwrap().unwrap();
+        let (_, bind) = wb.binds.iter().next().unwrap();
+        assert_eq!(bind.func, 0);
+
+        let stripped = strip_section(&wasm).unwrap();
+        assert_eq!(find_section(&stripped).unwrap(), None);
+        assert!(decode_module(&stripped).unwrap().is_none());
+        assert_eq!(walrus_section_data(&stripped), None);
+    }
+
+    #[test]
+    #[cfg(feature = "walrus")]
+    fn splice() {
+        let wasm = module().emit_wasm();
+        let data = find_section(&wasm).unwrap().unwrap().to_vec();
+
+        // A section spliced into a module without one is appended, and the
+        // rest of the module is left alone.
+        let stripped = strip_section(&wasm).unwrap();
+        let spliced = splice_section(&stripped, &data).unwrap();
+        assert_eq!(&spliced[..stripped.len()], &stripped[..]);
+        assert_eq!(walrus_section_data(&spliced), Some(data.clone()));
+
+        // An existing section is replaced where it is. Since walrus emits
+        // custom sections last, that is also at the end.
+        assert_eq!(splice_section(&wasm, &data).unwrap(), spliced);
+        let replaced = splice_section(&wasm, &[1, b'2', 0, 1, 0, 1, 2, 0, 0]).unwrap();
+        assert_eq!(&replaced[..stripped.len()], &stripped[..]);
+        assert!(decode_module(&replaced)
+            .unwrap()
+            .unwrap()
+            .binds
+            .iter()
+            .next()
+            .is_none());
+
+        // Duplicate sections are all replaced by one.
+        let mut doubled = spliced.clone();
+        doubled.extend_from_slice(&spliced[stripped.len()..]);
+        assert!(find_section(&doubled).is_err());
+        assert_eq!(splice_section(&doubled, &data).unwrap(), spliced);
+    }
+
+    #[test]
+    fn raw_indices_without_a_module_library() {
+        let mut encoder = StreamingEncoder::new(vec![], RawIndices).unwrap();
+        let func = encoder
+            .add_type(&WebidlCompoundType::Function(WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params: vec![WebidlScalarType::Long.into()],
+                result: None,
+            }))
+            .unwrap();
+        let binding = encoder
+            .add_binding(&FunctionBinding::Import(ImportBinding {
+                wasm_ty: 0,
+                webidl_ty: RawIndices::webidl_type(func).into(),
+                params: OutgoingBindingMap {
+                    bindings: vec![OutgoingBindingExpressionAs {
+                        ty: WebidlScalarType::Long.into(),
+                        idx: 0,
+                    }
+                    .into()],
+                },
+                result: IncomingBindingMap { bindings: vec![] },
+            }))
+            .unwrap();
+        encoder
+            .add_bind(&Bind {
+                func: 0,
+                binding: RawIndices::binding(binding),
+            })
+            .unwrap();
+        let data = encoder.finish().unwrap();
+
+        let empty = b"\0asm\x01\0\0\0";
+        let wasm = splice_section(empty, &data).unwrap();
+        assert_eq!(find_section(&wasm).unwrap(), Some(&data[..]));
+
+        let wb = decode_module(&wasm).unwrap().unwrap();
+        let (_, bind) = wb.binds.iter().next().unwrap();
+        assert_eq!(bind.func, 0);
+        let mut encoded = vec![];
+        crate::binary::encode(&wb, &RawIndices, &mut encoded).unwrap();
+        assert_eq!(encoded, data);
+
+        let reader = SectionReader::new(&data).unwrap();
+        assert_eq!(reader.types().len(), 1);
+        let binds = reader.binds().unwrap().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!((binds[0].func, binds[0].binding), (0, 0));
+
+        assert_eq!(strip_section(&wasm).unwrap(), empty);
+    }
+
+    #[test]
+    fn malformed_modules() {
+        let err = |wasm: &[u8]| find_section(wasm).unwrap_err().to_string();
+        assert_eq!(
+            err(b"\0wasm"),
+            "not a Wasm module: missing the `\\0asm` magic number"
+        );
+        assert_eq!(
+            err(b"\0asm\x01\0"),
+            "unexpected end of input in the Wasm version"
+        );
+        assert_eq!(
+            err(b"\0asm\x02\0\0\0"),
+            "unsupported Wasm version 0x00000002"
+        );
+        assert_eq!(
+            err(b"\0asm\x01\0\0\0\x01\x05\0"),
+            "section size 5 exceeds the remaining 1 bytes"
+        );
+        assert_eq!(
+            err(b"\0asm\x01\0\0\0\0\x02\x09a"),
+            "custom section name length 9 exceeds the remaining 1 bytes"
+        );
+        assert_eq!(find_section(b"\0asm\x01\0\0\0").unwrap(), None);
+    }
+}
@@ -0,0 +1,1423 @@
+//! Reading an encoded Web IDL bindings section lazily, without decoding it
+//! into a `WebidlBindings`.
+//!
+//! `SectionReader` borrows the custom section's data and reads its types,
+//! function bindings and binds on demand. Strings are borrowed from the data
+//! and references to Wasm functions, Wasm types, Web IDL types and function
+//! bindings are left as raw indices, so nothing is allocated.
+//!
+//! Vectors nested inside an item are checked when the item is read, so that
+//! the items after it can be found, but their elements are only returned
+//! when they are iterated over.
+
+use super::decode::{self, ReadExt};
+use super::{DecodeLimits, FormatVersion};
+use crate::ast::{
+    BufferLengthUnit, WebidlAnnotatedTypeRef, WebidlExtendedAttributes, WebidlScalarType,
+};
+use crate::wasm::ValType;
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A lazy, borrowed view of the data of a Web IDL bindings custom section.
+///
+/// Since format version 2, the subsections are prefixed with their sizes, so
+/// creating a reader does not read any types or bindings. For older versions,
+/// the types are skipped over to find the function binding subsection.
+///
+/// The items inside a subsection are not size-prefixed, so finding one of
+/// them, or the binds after the function bindings, reads every item before
+/// it.
+#[derive(Clone, Debug)]
+pub struct SectionReader<'a> {
+    version: FormatVersion,
+    types: VecReader<'a, Type<'a>>,
+    bindings: VecReader<'a, Binding<'a>>,
+}
+
+impl<'a> SectionReader<'a> {
+    /// Create a reader for the given custom section data, which does *not*
+    /// include the custom section's discriminant and name.
+    pub fn new(data: &'a [u8]) -> Result<SectionReader<'a>> {
+        let version = super::format_version(data)?;
+        let mut reader = BinaryReader::new(data, version);
+        reader.string()?;
+
+        let types = reader.subsection(0)?.vec_lazy()?;
+        if version < FormatVersion::V2 {
+            // Without subsection sizes, the types have to be read to find the
+            // end of their subsection.
+            reader = types.clone().skip_to_end()?;
+        }
+
+        let bindings = reader.subsection(1)?.vec_lazy()?;
+        Ok(SectionReader {
+            version,
+            types,
+            bindings,
+        })
+    }
+
+    /// The format version that the section is encoded in.
+    pub fn version(&self) -> FormatVersion {
+        self.version
+    }
+
+    /// The section's Web IDL types, in index order.
+    pub fn types(&self) -> VecReader<'a, Type<'a>> {
+        self.types.clone()
+    }
+
+    /// The section's function bindings, in index order.
+    pub fn bindings(&self) -> VecReader<'a, Binding<'a>> {
+        self.bindings.clone()
+    }
+
+    /// The function binding with index `n`.
+    ///
+    /// Bindings are not individually size-prefixed, so the `n` bindings
+    /// before it, including their nested expressions, are read to find it.
+    /// This takes time linear in their encoded size; to visit many bindings,
+    /// iterate over `bindings` instead of calling this for each index.
+    pub fn binding(&self, n: u32) -> Result<Binding<'a>> {
+        if n >= self.bindings.len() {
+            bail!("no function binding for index {}", n);
+        }
+        let mut bindings = self.bindings();
+        for _ in 0..n {
+            bindings.next().unwrap()?;
+        }
+        bindings.next().unwrap()
+    }
+
+    /// The section's binds.
+    ///
+    /// The binds subsection follows the function bindings, which are all read
+    /// to find it, so this takes time linear in their encoded size.
+    pub fn binds(&self) -> Result<VecReader<'a, Bind>> {
+        self.bindings().skip_to_end()?.vec_lazy()
+    }
+}
+
+/// Something that can be read from the data of a Web IDL bindings section.
+pub trait FromReader<'a>: Sized {
+    /// Read `Self` from `reader`, leaving it just after `Self`.
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self>;
+}
+
+/// A position in the data of a Web IDL bindings section.
+#[derive(Clone)]
+pub struct BinaryReader<'a> {
+    data: &'a [u8],
+    version: FormatVersion,
+    /// How deeply nested the thing currently being read is.
+    depth: u32,
+    max_nesting_depth: u32,
+}
+
+impl fmt::Debug for BinaryReader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BinaryReader")
+            .field("remaining", &self.data.len())
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl<'a> BinaryReader<'a> {
+    fn new(data: &'a [u8], version: FormatVersion) -> BinaryReader<'a> {
+        BinaryReader {
+            data,
+            version,
+            depth: 0,
+            max_nesting_depth: DecodeLimits::default().max_nesting_depth,
+        }
+    }
+
+    fn require(&self, version: FormatVersion, what: impl fmt::Display) -> Result<()> {
+        decode::require_version(self.version, version, what)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        self.data.read_byte()
+    }
+
+    fn uleb(&mut self) -> Result<u32> {
+        self.data.uleb()
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        let len = self.uleb()? as usize;
+        if len > self.data.len() {
+            bail!(
+                "string length {} exceeds the remaining {} bytes",
+                len,
+                self.data.len()
+            );
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(std::str::from_utf8(bytes)?)
+    }
+
+    fn option<T: FromReader<'a>>(&mut self) -> Result<Option<T>> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => T::from_reader(self).map(Some),
+            n => bail!(
+                "expected 0x0 or 0x1, found bad option discriminant: 0x{:02X}",
+                n
+            ),
+        }
+    }
+
+    /// Read the id, and since version 2 the size, of a subsection, and return
+    /// a reader for its contents.
+    ///
+    /// Before version 2, the returned reader and `self` are both at the start
+    /// of the contents.
+    fn subsection(&mut self, id: u8) -> Result<BinaryReader<'a>> {
+        self.data.expect_byte(id)?;
+        if self.version < FormatVersion::V2 {
+            return Ok(self.clone());
+        }
+        let size = self.uleb()? as usize;
+        if size > self.data.len() {
+            bail!(
+                "subsection size {} exceeds the remaining {} bytes",
+                size,
+                self.data.len()
+            );
+        }
+        let (contents, rest) = self.data.split_at(size);
+        self.data = rest;
+        Ok(BinaryReader {
+            data: contents,
+            ..self.clone()
+        })
+    }
+
+    /// Read the length of a vector, and return a reader for its elements
+    /// without reading them.
+    fn vec_lazy<T: FromReader<'a>>(&mut self) -> Result<VecReader<'a, T>> {
+        let remaining = self.uleb()?;
+        Ok(VecReader {
+            reader: self.clone(),
+            remaining,
+            _item: PhantomData,
+        })
+    }
+
+    /// Read a vector nested in the current item, and return a reader for its
+    /// elements.
+    fn vec<T: FromReader<'a>>(&mut self) -> Result<VecReader<'a, T>> {
+        self.nested(|r| {
+            let vec = r.vec_lazy()?;
+            *r = vec.clone().skip_to_end()?;
+            Ok(vec)
+        })
+    }
+
+    /// Read something nested one level deeper than the current item.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_nesting_depth {
+            bail!(
+                "nesting depth exceeds the limit of {}",
+                self.max_nesting_depth
+            );
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+}
+
+/// A lazily read vector of `T`s, which are read as they are iterated over.
+///
+/// Iteration stops after the first error.
+pub struct VecReader<'a, T> {
+    reader: BinaryReader<'a>,
+    remaining: u32,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for VecReader<'_, T> {
+    fn clone(&self) -> Self {
+        VecReader {
+            reader: self.reader.clone(),
+            remaining: self.remaining,
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for VecReader<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VecReader")
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+impl<'a, T> VecReader<'a, T> {
+    /// The number of elements left to read.
+    pub fn len(&self) -> u32 {
+        self.remaining
+    }
+
+    /// Are there no elements left to read?
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl<'a, T: FromReader<'a>> VecReader<'a, T> {
+    /// Read the rest of the elements, and return a reader just after them.
+    fn skip_to_end(mut self) -> Result<BinaryReader<'a>> {
+        for elem in &mut self {
+            elem?;
+        }
+        Ok(self.reader)
+    }
+}
+
+impl<'a, T: FromReader<'a>> Iterator for VecReader<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let elem = T::from_reader(&mut self.reader);
+        if elem.is_err() {
+            self.remaining = 0;
+        }
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> FromReader<'a> for &'a str {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        reader.string()
+    }
+}
+
+/// A reference to a Web IDL type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeRef {
+    /// The index of a compound type in the Web IDL type subsection.
+    Index(u32),
+    Scalar(WebidlScalarType),
+    Annotated(WebidlAnnotatedTypeRef),
+}
+
+impl FromReader<'_> for TypeRef {
+    fn from_reader(reader: &mut BinaryReader) -> Result<Self> {
+        let n = reader.data.ileb()?;
+        if n == -31 || n == -64 {
+            reader.require(
+                FormatVersion::V2,
+                format_args!("Web IDL type reference {}", n),
+            )?;
+        }
+
+        match n {
+            -64 => {
+                let ty = match reader.nested(TypeRef::from_reader)? {
+                    TypeRef::Scalar(s) => s,
+                    ty => bail!("only scalar Web IDL types can be annotated, found {:?}", ty),
+                };
+                let mut attributes = WebidlExtendedAttributes::default();
+                for _ in 0..reader.uleb()? {
+                    let a = decode::extended_attribute(reader.byte()?)?;
+                    if !attributes.insert(a) {
+                        bail!("duplicate extended attribute: {:?}", a);
+                    }
+                }
+                WebidlAnnotatedTypeRef::new(ty, attributes)
+                    .map(TypeRef::Annotated)
+                    .ok_or_else(|| {
+                        anyhow!("invalid extended attributes {:?} for {:?}", attributes, ty)
+                    })
+            }
+            n if n >= 0 => Ok(TypeRef::Index(n as u32)),
+            n => decode::scalar_type(n)
+                .map(TypeRef::Scalar)
+                .ok_or_else(|| anyhow!("reference to an unknown Web IDL scalar type: {}", n)),
+        }
+    }
+}
+
+/// A compound Web IDL type.
+#[derive(Clone, Debug)]
+pub enum Type<'a> {
+    Function(Function<'a>),
+    Dictionary(VecReader<'a, DictionaryField<'a>>),
+    Enumeration(VecReader<'a, &'a str>),
+    Union(VecReader<'a, TypeRef>),
+}
+
+impl<'a> FromReader<'a> for Type<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        match reader.byte()? {
+            0 => Function::from_reader(reader).map(Type::Function),
+            1 => reader.vec().map(Type::Dictionary),
+            2 => reader.vec().map(Type::Enumeration),
+            3 => reader.vec().map(Type::Union),
+            n => bail!("unexpected Web IDL compound type discriminant: {}", n),
+        }
+    }
+}
+
+/// A Web IDL function type.
+#[derive(Clone, Debug)]
+pub struct Function<'a> {
+    pub kind: FunctionKind,
+    pub params: VecReader<'a, Param<'a>>,
+    pub result: Option<TypeRef>,
+}
+
+impl<'a> FromReader<'a> for Function<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        let kind = FunctionKind::from_reader(reader)?;
+        let params = reader.vec()?;
+        let result = reader.option()?;
+        Ok(Function {
+            kind,
+            params,
+            result,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunctionKind {
+    Static,
+    Method(TypeRef),
+    Constructor { new_target: Option<TypeRef> },
+}
+
+impl FromReader<'_> for FunctionKind {
+    fn from_reader(reader: &mut BinaryReader) -> Result<Self> {
+        match reader.byte()? {
+            0 => Ok(FunctionKind::Static),
+            1 => TypeRef::from_reader(reader).map(FunctionKind::Method),
+            2 if reader.version < FormatVersion::V2 => {
+                Ok(FunctionKind::Constructor { new_target: None })
+            }
+            2 => {
+                let new_target = reader.option()?;
+                Ok(FunctionKind::Constructor { new_target })
+            }
+            n => bail!(
+                "expected 0x0, 0x1, or 0x2, found bad Web IDL function kind discriminant: 0x{:02X}",
+                n
+            ),
+        }
+    }
+}
+
+/// A parameter of a Web IDL function type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Param<'a> {
+    pub ty: TypeRef,
+    pub kind: ParamKind<'a>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamKind<'a> {
+    Required,
+    Optional { default: Option<DefaultValue<'a>> },
+    Variadic,
+}
+
+impl<'a> FromReader<'a> for Param<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        // Before version 2, every parameter was required and encoded as just
+        // its type.
+        if reader.version < FormatVersion::V2 {
+            let ty = TypeRef::from_reader(reader)?;
+            return Ok(Param {
+                ty,
+                kind: ParamKind::Required,
+            });
+        }
+        let discriminant = reader.byte()?;
+        let ty = TypeRef::from_reader(reader)?;
+        let kind = match discriminant {
+            0 => ParamKind::Required,
+            1 => ParamKind::Optional {
+                default: reader.option()?,
+            },
+            2 => ParamKind::Variadic,
+            n => bail!(
+                "expected 0x0, 0x1, or 0x2, found bad Web IDL function parameter discriminant: 0x{:02X}",
+                n
+            ),
+        };
+        Ok(Param { ty, kind })
+    }
+}
+
+/// The default value of an optional parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultValue<'a> {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    String(&'a str),
+}
+
+impl<'a> FromReader<'a> for DefaultValue<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        match reader.byte()? {
+            0 => Ok(DefaultValue::Null),
+            1 => match reader.byte()? {
+                0 => Ok(DefaultValue::Boolean(false)),
+                1 => Ok(DefaultValue::Boolean(true)),
+                n => bail!("expected 0x0 or 0x1, found bad boolean: 0x{:02X}", n),
+            },
+            2 => Ok(DefaultValue::Integer(reader.data.ileb64()?)),
+            3 => Ok(DefaultValue::String(reader.string()?)),
+            n => bail!("unknown Web IDL default value discriminant: 0x{:02X}", n),
+        }
+    }
+}
+
+/// A field of a Web IDL dictionary type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DictionaryField<'a> {
+    pub name: &'a str,
+    pub ty: TypeRef,
+}
+
+impl<'a> FromReader<'a> for DictionaryField<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        let name = reader.string()?;
+        let ty = TypeRef::from_reader(reader)?;
+        Ok(DictionaryField { name, ty })
+    }
+}
+
+/// A function binding.
+#[derive(Clone, Debug)]
+pub enum Binding<'a> {
+    Import(ImportBinding<'a>),
+    Export(ExportBinding<'a>),
+}
+
+/// A binding of an imported function, whose parameters are converted from
+/// Wasm to Web IDL, and whose results back.
+#[derive(Clone, Debug)]
+pub struct ImportBinding<'a> {
+    /// The index of the Wasm function type.
+    pub wasm_ty: u32,
+    pub webidl_ty: TypeRef,
+    pub params: VecReader<'a, Outgoing<'a>>,
+    pub result: VecReader<'a, Incoming<'a>>,
+}
+
+/// A binding of an exported function, whose parameters are converted from
+/// Web IDL to Wasm, and whose results back.
+#[derive(Clone, Debug)]
+pub struct ExportBinding<'a> {
+    /// The index of the Wasm function type.
+    pub wasm_ty: u32,
+    pub webidl_ty: TypeRef,
+    pub params: VecReader<'a, Incoming<'a>>,
+    pub result: VecReader<'a, Outgoing<'a>>,
+}
+
+impl<'a> FromReader<'a> for Binding<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        match reader.byte()? {
+            0 => Ok(Binding::Import(ImportBinding {
+                wasm_ty: reader.uleb()?,
+                webidl_ty: TypeRef::from_reader(reader)?,
+                params: reader.vec()?,
+                result: reader.vec()?,
+            })),
+            1 => Ok(Binding::Export(ExportBinding {
+                wasm_ty: reader.uleb()?,
+                webidl_ty: TypeRef::from_reader(reader)?,
+                params: reader.vec()?,
+                result: reader.vec()?,
+            })),
+            n => bail!(
+                "expected 0x0 or 0x1, found unknown function binding discriminant 0x{:02X}",
+                n
+            ),
+        }
+    }
+}
+
+/// An outgoing binding expression, from Wasm values to a Web IDL value.
+///
+/// See the corresponding `OutgoingBindingExpression` variants.
+#[derive(Clone, Debug)]
+pub enum Outgoing<'a> {
+    As {
+        ty: TypeRef,
+        idx: u32,
+    },
+    Utf8Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Utf8CStr {
+        ty: TypeRef,
+        offset: u32,
+    },
+    I32ToEnum {
+        ty: TypeRef,
+        idx: u32,
+    },
+    View {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    },
+    Copy {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    },
+    Dict {
+        ty: TypeRef,
+        fields: VecReader<'a, Outgoing<'a>>,
+    },
+    BindExport {
+        ty: TypeRef,
+        /// The index of the function binding.
+        binding: u32,
+        idx: u32,
+    },
+    Undefined,
+    Variadic {
+        ty: TypeRef,
+        elem_ty: ValType,
+        offset: u32,
+        length: u32,
+    },
+    Utf16Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Latin1Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+}
+
+impl<'a> FromReader<'a> for Outgoing<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        let discriminant = reader.byte()?;
+        if discriminant > 7 {
+            reader.require(
+                FormatVersion::V2,
+                format_args!(
+                    "outgoing binding expression discriminant 0x{:02X}",
+                    discriminant
+                ),
+            )?;
+        }
+
+        Ok(match discriminant {
+            0 => Outgoing::As {
+                ty: TypeRef::from_reader(reader)?,
+                idx: reader.uleb()?,
+            },
+            1 => Outgoing::Utf8Str {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+            },
+            2 => Outgoing::Utf8CStr {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+            },
+            3 => Outgoing::I32ToEnum {
+                ty: TypeRef::from_reader(reader)?,
+                idx: reader.uleb()?,
+            },
+            4 => Outgoing::View {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+                unit: BufferLengthUnit::from_reader(reader)?,
+            },
+            5 => Outgoing::Copy {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+                unit: BufferLengthUnit::from_reader(reader)?,
+            },
+            6 => Outgoing::Dict {
+                ty: TypeRef::from_reader(reader)?,
+                fields: reader.vec()?,
+            },
+            7 => Outgoing::BindExport {
+                ty: TypeRef::from_reader(reader)?,
+                binding: reader.uleb()?,
+                idx: reader.uleb()?,
+            },
+            8 => Outgoing::Undefined,
+            9 => Outgoing::Variadic {
+                ty: TypeRef::from_reader(reader)?,
+                elem_ty: decode::val_type(reader.byte()?)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+            },
+            10 => Outgoing::Utf16Str {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+            },
+            11 => Outgoing::Latin1Str {
+                ty: TypeRef::from_reader(reader)?,
+                offset: reader.uleb()?,
+                length: reader.uleb()?,
+            },
+            n => bail!(
+                "unknown outgoing binding expression discriminant: 0x{:02X}",
+                n
+            ),
+        })
+    }
+}
+
+impl FromReader<'_> for BufferLengthUnit {
+    fn from_reader(reader: &mut BinaryReader) -> Result<Self> {
+        // Before version 2, every length was in elements, and the unit was
+        // not encoded.
+        if reader.version < FormatVersion::V2 {
+            return Ok(BufferLengthUnit::Elements);
+        }
+        match reader.byte()? {
+            0 => Ok(BufferLengthUnit::Elements),
+            1 => Ok(BufferLengthUnit::Bytes),
+            n => bail!("unknown buffer length unit discriminant: 0x{:02X}", n),
+        }
+    }
+}
+
+/// An incoming binding expression, from a Web IDL value to a Wasm value.
+///
+/// See the corresponding `IncomingBindingExpression` variants.
+#[derive(Clone, Debug)]
+pub enum Incoming<'a> {
+    Get {
+        idx: u32,
+    },
+    As {
+        ty: ValType,
+        expr: IncomingReader<'a>,
+    },
+    AllocUtf8Str {
+        alloc_func_name: &'a str,
+        expr: IncomingReader<'a>,
+    },
+    AllocCopy {
+        alloc_func_name: &'a str,
+        expr: IncomingReader<'a>,
+    },
+    EnumToI32 {
+        ty: TypeRef,
+        expr: IncomingReader<'a>,
+    },
+    Field {
+        idx: u32,
+        expr: IncomingReader<'a>,
+    },
+    BindImport {
+        /// The index of the Wasm function type.
+        ty: u32,
+        /// The index of the function binding.
+        binding: u32,
+        expr: IncomingReader<'a>,
+    },
+    AllocUtf16Str {
+        alloc_func_name: &'a str,
+        expr: IncomingReader<'a>,
+    },
+    AllocLatin1Str {
+        alloc_func_name: &'a str,
+        expr: IncomingReader<'a>,
+    },
+    WriteUtf8Str {
+        offset: u32,
+        capacity: u32,
+        expr: IncomingReader<'a>,
+    },
+    WriteUtf16Str {
+        offset: u32,
+        capacity: u32,
+        expr: IncomingReader<'a>,
+    },
+    WriteCopy {
+        offset: u32,
+        capacity: u32,
+        expr: IncomingReader<'a>,
+    },
+}
+
+impl<'a> FromReader<'a> for Incoming<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        let discriminant = reader.byte()?;
+        if discriminant > 6 {
+            reader.require(
+                FormatVersion::V2,
+                format_args!(
+                    "incoming binding expression discriminant 0x{:02X}",
+                    discriminant
+                ),
+            )?;
+        }
+
+        Ok(match discriminant {
+            0 => Incoming::Get {
+                idx: reader.uleb()?,
+            },
+            1 => Incoming::As {
+                ty: decode::val_type(reader.byte()?)?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            2 => Incoming::AllocUtf8Str {
+                alloc_func_name: reader.string()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            3 => Incoming::AllocCopy {
+                alloc_func_name: reader.string()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            4 => Incoming::EnumToI32 {
+                ty: TypeRef::from_reader(reader)?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            5 => Incoming::Field {
+                idx: reader.uleb()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            6 => Incoming::BindImport {
+                ty: reader.uleb()?,
+                binding: reader.uleb()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            7 => Incoming::AllocUtf16Str {
+                alloc_func_name: reader.string()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            8 => Incoming::AllocLatin1Str {
+                alloc_func_name: reader.string()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            9 => Incoming::WriteUtf8Str {
+                offset: reader.uleb()?,
+                capacity: reader.uleb()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            10 => Incoming::WriteUtf16Str {
+                offset: reader.uleb()?,
+                capacity: reader.uleb()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            11 => Incoming::WriteCopy {
+                offset: reader.uleb()?,
+                capacity: reader.uleb()?,
+                expr: IncomingReader::from_reader(reader)?,
+            },
+            n => bail!(
+                "unknown incoming binding expression discriminant: 0x{:02X}",
+                n
+            ),
+        })
+    }
+}
+
+/// An incoming binding expression nested inside another, which is read on
+/// demand.
+#[derive(Clone, Debug)]
+pub struct IncomingReader<'a> {
+    reader: BinaryReader<'a>,
+}
+
+impl<'a> IncomingReader<'a> {
+    /// Read the nested expression.
+    pub fn get(&self) -> Result<Incoming<'a>> {
+        let mut reader = self.reader.clone();
+        reader.nested(Incoming::from_reader)
+    }
+}
+
+impl<'a> FromReader<'a> for IncomingReader<'a> {
+    fn from_reader(reader: &mut BinaryReader<'a>) -> Result<Self> {
+        let start = reader.clone();
+        reader.nested(Incoming::from_reader)?;
+        Ok(IncomingReader { reader: start })
+    }
+}
+
+/// A bind of a Wasm function to a function binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bind {
+    /// The index of the Wasm function.
+    pub func: u32,
+    /// The index of the function binding.
+    pub binding: u32,
+}
+
+impl FromReader<'_> for Bind {
+    fn from_reader(reader: &mut BinaryReader) -> Result<Self> {
+        let func = reader.uleb()?;
+        let binding = reader.uleb()?;
+        Ok(Bind { func, binding })
+    }
+}
+
+#[cfg(all(test, feature = "walrus"))]
+mod tests {
+    use super::*;
+    use crate::ast::{self, WebidlBindings};
+    use crate::binary::VersionedSection;
+    use crate::builder::{incoming as inc, outgoing as out};
+    use walrus::ValType::I32;
+    use WebidlScalarType::DomString;
+
+    /// A module and a section with a dictionary type, a function type, an
+    /// import binding and an export binding, and two binds.
+    fn module_and_section() -> (walrus::Module, WebidlBindings) {
+        let mut m = walrus::Module::default();
+        let import_ty = m.types.add(&[I32, I32], &[]);
+        let export_ty = m.types.add(&[I32], &[]);
+        let (imported, _) = m.add_import_func("env", "log", import_ty);
+        let exported =
+            walrus::FunctionBuilder::new(&mut m.types, &[I32], &[]).finish(vec![], &mut m.funcs);
+        m.exports.add("run", exported);
+
+        let mut wb = WebidlBindings::default();
+        let dict = wb.types.insert(ast::WebidlDictionary {
+            fields: vec![ast::WebidlDictionaryField {
+                name: "message".into(),
+                ty: DomString.into(),
+            }],
+        });
+        let func = wb.types.insert(ast::WebidlFunction {
+            kind: ast::WebidlFunctionKind::Static,
+            params: vec![ast::WebidlTypeRef::from(dict).into()],
+            result: None,
+        });
+        let import = wb.bindings.insert(ast::ImportBinding {
+            wasm_ty: import_ty,
+            webidl_ty: func.into(),
+            params: ast::OutgoingBindingMap {
+                bindings: vec![ast::OutgoingBindingExpressionDict {
+                    ty: dict.into(),
+                    fields: vec![out::utf8_str(DomString, 0, 1)],
+                }
+                .into()],
+            },
+            result: ast::IncomingBindingMap { bindings: vec![] },
+        });
+        let export = wb.bindings.insert(ast::ExportBinding {
+            wasm_ty: export_ty,
+            webidl_ty: func.into(),
+            params: ast::IncomingBindingMap {
+                bindings: vec![inc::as_(I32, inc::field(0, inc::get(0)))],
+            },
+            result: ast::OutgoingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(ast::Bind {
+            func: imported,
+            binding: import.into(),
+        });
+        wb.binds.insert(ast::Bind {
+            func: exported,
+            binding: export.into(),
+        });
+        (m, wb)
+    }
+
+    /// Emit `section` in `m`, and return the custom section's data.
+    fn emit(mut m: walrus::Module, section: impl walrus::CustomSection) -> Vec<u8> {
+        m.customs.add(section);
+        let wasm = m.emit_wasm();
+        let mut m = walrus::Module::from_buffer(&wasm).unwrap();
+        m.customs.remove_raw("webidl-bindings").unwrap().data
+    }
+
+    #[test]
+    fn read_section() {
+        let (m, wb) = module_and_section();
+        let data = emit(m, wb);
+        let section = SectionReader::new(&data).unwrap();
+        assert_eq!(section.version(), FormatVersion::CURRENT);
+
+        let types = section.types().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(types.len(), 2);
+        match &types[0] {
+            Type::Dictionary(fields) => assert_eq!(
+                fields.clone().collect::<Result<Vec<_>>>().unwrap(),
+                [DictionaryField {
+                    name: "message",
+                    ty: TypeRef::Scalar(DomString),
+                }]
+            ),
+            ty => panic!("expected a dictionary, found {:?}", ty),
+        }
+        match &types[1] {
+            Type::Function(f) => {
+                assert_eq!(f.kind, FunctionKind::Static);
+                assert_eq!(
+                    f.params.clone().collect::<Result<Vec<_>>>().unwrap(),
+                    [Param {
+                        ty: TypeRef::Index(0),
+                        kind: ParamKind::Required,
+                    }]
+                );
+                assert_eq!(f.result, None);
+            }
+            ty => panic!("expected a function, found {:?}", ty),
+        }
+
+        // Seek to the export binding, and read its nested expressions.
+        let export = match section.binding(1).unwrap() {
+            Binding::Export(e) => e,
+            b => panic!("expected an export binding, found {:?}", b),
+        };
+        assert_eq!(export.webidl_ty, TypeRef::Index(1));
+        let params = export.params.collect::<Result<Vec<_>>>().unwrap();
+        let expr = match &params[..] {
+            [Incoming::As {
+                ty: ValType::I32,
+                expr,
+            }] => expr.get().unwrap(),
+            params => panic!("expected an `as` expression, found {:?}", params),
+        };
+        let expr = match expr {
+            Incoming::Field { idx: 0, expr } => expr.get().unwrap(),
+            expr => panic!("expected a `field` expression, found {:?}", expr),
+        };
+        assert!(matches!(expr, Incoming::Get { idx: 0 }));
+        assert!(section.binding(2).is_err());
+
+        let binds = section.binds().unwrap();
+        assert_eq!(binds.len(), 2);
+        assert_eq!(
+            binds.map(|b| b.unwrap().binding).collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn read_format_version_1() {
+        let (m, wb) = module_and_section();
+        let data = emit(m, VersionedSection::new(wb, FormatVersion::V1).unwrap());
+        let section = SectionReader::new(&data).unwrap();
+        assert_eq!(section.version(), FormatVersion::V1);
+        assert_eq!(section.types().len(), 2);
+        assert!(matches!(section.binding(0).unwrap(), Binding::Import(_)));
+        assert_eq!(section.binds().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn read_errors_lazily() {
+        let data = [
+            1, b'2', // format version marker
+            0, 1, // types subsection and its size
+            0, // number of types
+            1, 3, // bindings subsection and its size
+            1, // number of bindings
+            7, // bad function binding discriminant
+            0, // number of binds
+        ];
+        let section = SectionReader::new(&data).unwrap();
+        assert!(section.types().is_empty());
+        assert_eq!(
+            section.binding(0).unwrap_err().to_string(),
+            "expected 0x0 or 0x1, found unknown function binding discriminant 0x07"
+        );
+        assert!(section.binds().is_err());
+
+        let truncated = &data[..data.len() - 1];
+        assert_eq!(
+            SectionReader::new(truncated).unwrap_err().to_string(),
+            "subsection size 3 exceeds the remaining 2 bytes"
+        );
+    }
+
+    /// Differential tests of the reader against `decode`: everything read
+    /// from an arbitrary section, with its indices resolved through the
+    /// decoded section, should equal what `decode` produced.
+    #[cfg(feature = "quickchecking")]
+    mod differential {
+        use super::super::*;
+        use crate::ast;
+        use crate::binary::VersionedSection;
+        use crate::quickcheck::ModuleAndBindings;
+        use crate::wasm::RawIndices;
+
+        type Decoded = ast::WebidlBindings<RawIndices>;
+
+        fn type_ref(wb: &Decoded, ty: TypeRef) -> ast::WebidlTypeRef {
+            match ty {
+                TypeRef::Index(n) => ast::WebidlTypeRef::Id(wb.types.by_index(n).unwrap()),
+                TypeRef::Scalar(s) => ast::WebidlTypeRef::Scalar(s),
+                TypeRef::Annotated(a) => ast::WebidlTypeRef::Annotated(a),
+            }
+        }
+
+        fn binding_id(wb: &Decoded, n: u32) -> id_arena::Id<ast::FunctionBinding<RawIndices>> {
+            wb.bindings.by_index(n).unwrap()
+        }
+
+        fn all<'a, T: FromReader<'a>>(v: VecReader<'a, T>) -> Vec<T> {
+            v.collect::<Result<_>>().unwrap()
+        }
+
+        fn ty(wb: &Decoded, ty: Type) -> ast::WebidlCompoundType {
+            match ty {
+                Type::Function(f) => ast::WebidlFunction {
+                    kind: match f.kind {
+                        FunctionKind::Static => ast::WebidlFunctionKind::Static,
+                        FunctionKind::Method(ty) => ast::WebidlFunctionKindMethod {
+                            ty: type_ref(wb, ty),
+                        }
+                        .into(),
+                        FunctionKind::Constructor { new_target } => {
+                            ast::WebidlFunctionKindConstructor {
+                                new_target: new_target.map(|ty| type_ref(wb, ty)),
+                            }
+                            .into()
+                        }
+                    },
+                    params: all(f.params)
+                        .into_iter()
+                        .map(|p| ast::WebidlFunctionParam {
+                            ty: type_ref(wb, p.ty),
+                            kind: match p.kind {
+                                ParamKind::Required => ast::WebidlFunctionParamKind::Required,
+                                ParamKind::Optional { default } => {
+                                    ast::WebidlFunctionParamOptional {
+                                        default: default.map(default_value),
+                                    }
+                                    .into()
+                                }
+                                ParamKind::Variadic => ast::WebidlFunctionParamKind::Variadic,
+                            },
+                        })
+                        .collect(),
+                    result: f.result.map(|ty| type_ref(wb, ty)),
+                }
+                .into(),
+                Type::Dictionary(fields) => ast::WebidlDictionary {
+                    fields: all(fields)
+                        .into_iter()
+                        .map(|f| ast::WebidlDictionaryField {
+                            name: f.name.to_string(),
+                            ty: type_ref(wb, f.ty),
+                        })
+                        .collect(),
+                }
+                .into(),
+                Type::Enumeration(values) => ast::WebidlEnumeration {
+                    values: all(values).into_iter().map(String::from).collect(),
+                }
+                .into(),
+                Type::Union(members) => ast::WebidlUnion {
+                    members: all(members)
+                        .into_iter()
+                        .map(|ty| type_ref(wb, ty))
+                        .collect(),
+                }
+                .into(),
+            }
+        }
+
+        fn default_value(value: DefaultValue) -> ast::WebidlDefaultValue {
+            match value {
+                DefaultValue::Null => ast::WebidlDefaultValue::Null,
+                DefaultValue::Boolean(b) => ast::WebidlDefaultValue::Boolean(b),
+                DefaultValue::Integer(i) => ast::WebidlDefaultValue::Integer(i),
+                DefaultValue::String(s) => ast::WebidlDefaultValue::String(s.to_string()),
+            }
+        }
+
+        fn binding(wb: &Decoded, binding: Binding) -> ast::FunctionBinding<RawIndices> {
+            match binding {
+                Binding::Import(b) => ast::FunctionBinding::Import(ast::ImportBinding {
+                    wasm_ty: b.wasm_ty,
+                    webidl_ty: type_ref(wb, b.webidl_ty),
+                    params: ast::OutgoingBindingMap {
+                        bindings: outgoing_all(wb, b.params),
+                    },
+                    result: ast::IncomingBindingMap {
+                        bindings: incoming_all(wb, b.result),
+                    },
+                }),
+                Binding::Export(b) => ast::FunctionBinding::Export(ast::ExportBinding {
+                    wasm_ty: b.wasm_ty,
+                    webidl_ty: type_ref(wb, b.webidl_ty),
+                    params: ast::IncomingBindingMap {
+                        bindings: incoming_all(wb, b.params),
+                    },
+                    result: ast::OutgoingBindingMap {
+                        bindings: outgoing_all(wb, b.result),
+                    },
+                }),
+            }
+        }
+
+        fn outgoing_all(
+            wb: &Decoded,
+            exprs: VecReader<Outgoing>,
+        ) -> Vec<ast::OutgoingBindingExpression<RawIndices>> {
+            all(exprs).into_iter().map(|e| outgoing(wb, e)).collect()
+        }
+
+        fn outgoing(wb: &Decoded, expr: Outgoing) -> ast::OutgoingBindingExpression<RawIndices> {
+            use ast::OutgoingBindingExpression as E;
+            match expr {
+                Outgoing::As { ty, idx } => ast::OutgoingBindingExpressionAs {
+                    ty: type_ref(wb, ty),
+                    idx,
+                }
+                .into(),
+                Outgoing::Utf8Str { ty, offset, length } => ast::OutgoingBindingExpressionUtf8Str {
+                    ty: type_ref(wb, ty),
+                    offset,
+                    length,
+                }
+                .into(),
+                Outgoing::Utf8CStr { ty, offset } => ast::OutgoingBindingExpressionUtf8CStr {
+                    ty: type_ref(wb, ty),
+                    offset,
+                }
+                .into(),
+                Outgoing::I32ToEnum { ty, idx } => ast::OutgoingBindingExpressionI32ToEnum {
+                    ty: type_ref(wb, ty),
+                    idx,
+                }
+                .into(),
+                Outgoing::View {
+                    ty,
+                    offset,
+                    length,
+                    unit,
+                } => ast::OutgoingBindingExpressionView {
+                    ty: type_ref(wb, ty),
+                    offset,
+                    length,
+                    unit,
+                }
+                .into(),
+                Outgoing::Copy {
+                    ty,
+                    offset,
+                    length,
+                    unit,
+                } => ast::OutgoingBindingExpressionCopy {
+                    ty: type_ref(wb, ty),
+                    offset,
+                    length,
+                    unit,
+                }
+                .into(),
+                Outgoing::Dict { ty, fields } => ast::OutgoingBindingExpressionDict {
+                    ty: type_ref(wb, ty),
+                    fields: outgoing_all(wb, fields),
+                }
+                .into(),
+                Outgoing::BindExport { ty, binding, idx } => {
+                    ast::OutgoingBindingExpressionBindExport {
+                        ty: type_ref(wb, ty),
+                        binding: binding_id(wb, binding),
+                        idx,
+                    }
+                    .into()
+                }
+                Outgoing::Undefined => ast::OutgoingBindingExpressionUndefined.into(),
+                Outgoing::Variadic {
+                    ty,
+                    elem_ty,
+                    offset,
+                    length,
+                } => E::Variadic(ast::OutgoingBindingExpressionVariadic {
+                    ty: type_ref(wb, ty),
+                    elem_ty,
+                    offset,
+                    length,
+                }),
+                Outgoing::Utf16Str { ty, offset, length } => {
+                    E::Utf16Str(ast::OutgoingBindingExpressionUtf16Str {
+                        ty: type_ref(wb, ty),
+                        offset,
+                        length,
+                    })
+                }
+                Outgoing::Latin1Str { ty, offset, length } => {
+                    E::Latin1Str(ast::OutgoingBindingExpressionLatin1Str {
+                        ty: type_ref(wb, ty),
+                        offset,
+                        length,
+                    })
+                }
+            }
+        }
+
+        fn incoming_all(
+            wb: &Decoded,
+            exprs: VecReader<Incoming>,
+        ) -> Vec<ast::IncomingBindingExpression<RawIndices>> {
+            all(exprs).into_iter().map(|e| incoming(wb, e)).collect()
+        }
+
+        fn incoming(wb: &Decoded, expr: Incoming) -> ast::IncomingBindingExpression<RawIndices> {
+            use ast::IncomingBindingExpression as E;
+            let nested = |expr: IncomingReader| Box::new(incoming(wb, expr.get().unwrap()));
+            match expr {
+                Incoming::Get { idx } => E::Get(ast::IncomingBindingExpressionGet { idx }),
+                Incoming::As { ty, expr } => E::As(ast::IncomingBindingExpressionAs {
+                    ty,
+                    expr: nested(expr),
+                }),
+                Incoming::AllocUtf8Str {
+                    alloc_func_name,
+                    expr,
+                } => E::AllocUtf8Str(ast::IncomingBindingExpressionAllocUtf8Str {
+                    alloc_func_name: alloc_func_name.to_string(),
+                    expr: nested(expr),
+                }),
+                Incoming::AllocCopy {
+                    alloc_func_name,
+                    expr,
+                } => E::AllocCopy(ast::IncomingBindingExpressionAllocCopy {
+                    alloc_func_name: alloc_func_name.to_string(),
+                    expr: nested(expr),
+                }),
+                Incoming::EnumToI32 { ty, expr } => {
+                    E::EnumToI32(ast::IncomingBindingExpressionEnumToI32 {
+                        ty: type_ref(wb, ty),
+                        expr: nested(expr),
+                    })
+                }
+                Incoming::Field { idx, expr } => E::Field(ast::IncomingBindingExpressionField {
+                    idx,
+                    expr: nested(expr),
+                }),
+                Incoming::BindImport { ty, binding, expr } => {
+                    E::BindImport(ast::IncomingBindingExpressionBindImport {
+                        ty,
+                        binding: binding_id(wb, binding),
+                        expr: nested(expr),
+                    })
+                }
+                Incoming::AllocUtf16Str {
+                    alloc_func_name,
+                    expr,
+                } => E::AllocUtf16Str(ast::IncomingBindingExpressionAllocUtf16Str {
+                    alloc_func_name: alloc_func_name.to_string(),
+                    expr: nested(expr),
+                }),
+                Incoming::AllocLatin1Str {
+                    alloc_func_name,
+                    expr,
+                } => E::AllocLatin1Str(ast::IncomingBindingExpressionAllocLatin1Str {
+                    alloc_func_name: alloc_func_name.to_string(),
+                    expr: nested(expr),
+                }),
+                Incoming::WriteUtf8Str {
+                    offset,
+                    capacity,
+                    expr,
+                } => E::WriteUtf8Str(ast::IncomingBindingExpressionWriteUtf8Str {
+                    offset,
+                    capacity,
+                    expr: nested(expr),
+                }),
+                Incoming::WriteUtf16Str {
+                    offset,
+                    capacity,
+                    expr,
+                } => E::WriteUtf16Str(ast::IncomingBindingExpressionWriteUtf16Str {
+                    offset,
+                    capacity,
+                    expr: nested(expr),
+                }),
+                Incoming::WriteCopy {
+                    offset,
+                    capacity,
+                    expr,
+                } => E::WriteCopy(ast::IncomingBindingExpressionWriteCopy {
+                    offset,
+                    capacity,
+                    expr: nested(expr),
+                }),
+            }
+        }
+
+        /// Check that reading `data` agrees with decoding it.
+        fn check(data: &[u8]) {
+            let decoded: Decoded = crate::binary::decode(&RawIndices, data).unwrap();
+            let section = SectionReader::new(data).unwrap();
+
+            let types = all(section.types());
+            assert_eq!(types.len(), decoded.types.arena.len());
+            for (i, t) in types.into_iter().enumerate() {
+                let id = decoded.types.by_index(i as u32).unwrap();
+                assert_eq!(ty(&decoded, t), decoded.types.arena[id]);
+            }
+
+            let bindings = all(section.bindings());
+            assert_eq!(bindings.len(), decoded.bindings.arena.len());
+            for (i, b) in bindings.into_iter().enumerate() {
+                let id = binding_id(&decoded, i as u32);
+                assert_eq!(binding(&decoded, b), decoded.bindings.arena[id]);
+                let seeked = section.binding(i as u32).unwrap();
+                assert_eq!(binding(&decoded, seeked), decoded.bindings.arena[id]);
+            }
+            assert!(section
+                .binding(decoded.bindings.arena.len() as u32)
+                .is_err());
+
+            let binds = all(section.binds().unwrap());
+            let expected = decoded
+                .binds
+                .arena
+                .iter()
+                .map(|(_, b)| (b.func, b.binding))
+                .collect::<Vec<_>>();
+            let binds = binds
+                .into_iter()
+                .map(|b| (b.func, binding_id(&decoded, b.binding)))
+                .collect::<Vec<_>>();
+            assert_eq!(binds, expected);
+        }
+
+        quickcheck::quickcheck! {
+            fn reader_agrees_with_decode(mb: ModuleAndBindings) -> () {
+                let (module, section) = mb.build();
+                check(&super::emit(module, section));
+            }
+
+            fn reader_agrees_with_decode_version_1(mb: ModuleAndBindings) -> () {
+                let (module, section) = mb.build();
+                // Not every section can be encoded in version 1.
+                if let Ok(section) = VersionedSection::new(section, FormatVersion::V1) {
+                    check(&super::emit(module, section));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,671 @@
+//! Fluent builders for constructing function bindings from Rust.
+//!
+//! Code generators can use these builders to emit a Web IDL bindings section
+//! without going through the text format. The `outgoing` and `incoming`
+//! modules have constructors for every kind of binding expression, and
+//! `ImportBindingBuilder` and `ExportBindingBuilder` check each expression's
+//! arity and types against the bound Wasm function type as it is added, with
+//! the same checks as `FunctionBinding::validate`.
+//!
+//! ```
+//! use wasm_webidl_bindings::ast::{WebidlBindings, WebidlFunction, WebidlFunctionKind, WebidlScalarType};
+//! use wasm_webidl_bindings::builder::{incoming as inc, outgoing as out, ImportBindingBuilder};
+//! use walrus::ValType::I32;
+//! use WebidlScalarType::Long;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut module = walrus::Module::default();
+//! let wasm_ty = module.types.add(&[I32], &[I32]);
+//!
+//! let mut wb = WebidlBindings::default();
+//! let webidl_ty = wb.types.insert(WebidlFunction {
+//!     kind: WebidlFunctionKind::Static,
+//!     params: vec![Long.into()],
+//!     result: Some(Long.into()),
+//! });
+//!
+//! let binding = ImportBindingBuilder::new(&module, wasm_ty, webidl_ty)
+//!     .param(out::as_(Long, 0))
+//!     .result(inc::as_(I32, inc::get(0)))
+//!     .insert(&mut wb)?;
+//! # let _ = binding;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::ast::*;
+use anyhow::{bail, Result};
+
+/// Constructors for outgoing binding expressions.
+pub mod outgoing {
+    use crate::ast::*;
+    use id_arena::Id;
+
+    /// `(as <ty> <idx>)`
+    pub fn as_(ty: impl Into<WebidlTypeRef>, idx: u32) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionAs { ty: ty.into(), idx }.into()
+    }
+
+    /// `(utf8-str <ty> <offset> <length>)`
+    pub fn utf8_str(
+        ty: impl Into<WebidlTypeRef>,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionUtf8Str {
+            ty: ty.into(),
+            offset,
+            length,
+        }
+        .into()
+    }
+
+    /// `(utf8-cstr <ty> <offset>)`
+    pub fn utf8_cstr(ty: impl Into<WebidlTypeRef>, offset: u32) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionUtf8CStr {
+            ty: ty.into(),
+            offset,
+        }
+        .into()
+    }
+
+    /// `(i32-to-enum <ty> <idx>)`
+    pub fn i32_to_enum(ty: impl Into<WebidlTypeRef>, idx: u32) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionI32ToEnum { ty: ty.into(), idx }.into()
+    }
+
+    /// `(view <ty> <offset> <length> <unit>)`
+    pub fn view(
+        ty: impl Into<WebidlTypeRef>,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionView {
+            ty: ty.into(),
+            offset,
+            length,
+            unit,
+        }
+        .into()
+    }
+
+    /// `(copy <ty> <offset> <length> <unit>)`
+    pub fn copy(
+        ty: impl Into<WebidlTypeRef>,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionCopy {
+            ty: ty.into(),
+            offset,
+            length,
+            unit,
+        }
+        .into()
+    }
+
+    /// `(dict <ty> <field>*)`
+    pub fn dict(
+        ty: impl Into<WebidlTypeRef>,
+        fields: impl IntoIterator<Item = OutgoingBindingExpression>,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionDict {
+            ty: ty.into(),
+            fields: fields.into_iter().collect(),
+        }
+        .into()
+    }
+
+    /// `(bind-export <ty> <binding> <idx>)`
+    pub fn bind_export(
+        ty: impl Into<WebidlTypeRef>,
+        binding: impl Into<Id<FunctionBinding>>,
+        idx: u32,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionBindExport {
+            ty: ty.into(),
+            binding: binding.into(),
+            idx,
+        }
+        .into()
+    }
+
+    /// `(undefined)`
+    pub fn undefined() -> OutgoingBindingExpression {
+        OutgoingBindingExpressionUndefined.into()
+    }
+
+    /// `(variadic <ty> <elem_ty> <offset> <length>)`
+    pub fn variadic(
+        ty: impl Into<WebidlTypeRef>,
+        elem_ty: walrus::ValType,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionVariadic {
+            ty: ty.into(),
+            elem_ty,
+            offset,
+            length,
+        }
+        .into()
+    }
+
+    /// `(utf16-str <ty> <offset> <length>)`
+    pub fn utf16_str(
+        ty: impl Into<WebidlTypeRef>,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionUtf16Str {
+            ty: ty.into(),
+            offset,
+            length,
+        }
+        .into()
+    }
+
+    /// `(latin1-str <ty> <offset> <length>)`
+    pub fn latin1_str(
+        ty: impl Into<WebidlTypeRef>,
+        offset: u32,
+        length: u32,
+    ) -> OutgoingBindingExpression {
+        OutgoingBindingExpressionLatin1Str {
+            ty: ty.into(),
+            offset,
+            length,
+        }
+        .into()
+    }
+}
+
+/// Constructors for incoming binding expressions.
+pub mod incoming {
+    use crate::ast::*;
+    use id_arena::Id;
+
+    /// `(get <idx>)`
+    pub fn get(idx: u32) -> IncomingBindingExpression {
+        IncomingBindingExpressionGet { idx }.into()
+    }
+
+    /// `(as <ty> <expr>)`
+    pub fn as_(ty: walrus::ValType, expr: IncomingBindingExpression) -> IncomingBindingExpression {
+        IncomingBindingExpressionAs {
+            ty,
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(alloc-utf8-str <alloc_func_name> <expr>)`
+    pub fn alloc_utf8_str(
+        alloc_func_name: impl Into<String>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionAllocUtf8Str {
+            alloc_func_name: alloc_func_name.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(alloc-copy <alloc_func_name> <expr>)`
+    pub fn alloc_copy(
+        alloc_func_name: impl Into<String>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionAllocCopy {
+            alloc_func_name: alloc_func_name.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(enum-to-i32 <ty> <expr>)`
+    pub fn enum_to_i32(
+        ty: impl Into<WebidlTypeRef>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionEnumToI32 {
+            ty: ty.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(field <idx> <expr>)`
+    pub fn field(idx: u32, expr: IncomingBindingExpression) -> IncomingBindingExpression {
+        IncomingBindingExpressionField {
+            idx,
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(bind-import <ty> <binding> <expr>)`
+    pub fn bind_import(
+        ty: walrus::TypeId,
+        binding: impl Into<Id<FunctionBinding>>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionBindImport {
+            ty,
+            binding: binding.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(alloc-utf16-str <alloc_func_name> <expr>)`
+    pub fn alloc_utf16_str(
+        alloc_func_name: impl Into<String>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionAllocUtf16Str {
+            alloc_func_name: alloc_func_name.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(alloc-latin1-str <alloc_func_name> <expr>)`
+    pub fn alloc_latin1_str(
+        alloc_func_name: impl Into<String>,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionAllocLatin1Str {
+            alloc_func_name: alloc_func_name.into(),
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(write-utf8-str <offset> <capacity> <expr>)`
+    pub fn write_utf8_str(
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionWriteUtf8Str {
+            offset,
+            capacity,
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(write-utf16-str <offset> <capacity> <expr>)`
+    pub fn write_utf16_str(
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionWriteUtf16Str {
+            offset,
+            capacity,
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+
+    /// `(write-copy <offset> <capacity> <expr>)`
+    pub fn write_copy(
+        offset: u32,
+        capacity: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpression {
+        IncomingBindingExpressionWriteCopy {
+            offset,
+            capacity,
+            expr: Box::new(expr),
+        }
+        .into()
+    }
+}
+
+/// A builder for an `ImportBinding`.
+///
+/// The first error found while adding expressions is reported by `build` or
+/// `insert`.
+#[derive(Debug)]
+pub struct ImportBindingBuilder<'a> {
+    module: &'a walrus::Module,
+    binding: ImportBinding,
+    produced: usize,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> ImportBindingBuilder<'a> {
+    /// Start building a binding from the given Wasm function type, defined in
+    /// `module`, to the given Web IDL function type.
+    pub fn new(
+        module: &'a walrus::Module,
+        wasm_ty: walrus::TypeId,
+        webidl_ty: impl Into<WebidlTypeRef>,
+    ) -> Self {
+        ImportBindingBuilder {
+            module,
+            binding: ImportBinding {
+                wasm_ty,
+                webidl_ty: webidl_ty.into(),
+                params: OutgoingBindingMap { bindings: vec![] },
+                result: IncomingBindingMap { bindings: vec![] },
+            },
+            produced: 0,
+            error: None,
+        }
+    }
+
+    /// Add an outgoing binding expression for the next Web IDL parameter.
+    pub fn param(mut self, expr: OutgoingBindingExpression) -> Self {
+        if self.error.is_none() {
+            let params = self.module.types.get(self.binding.wasm_ty).params();
+            let i = self.binding.params.bindings.len();
+            self.error = expr
+                .validate(params)
+                .err()
+                .map(|e| e.context(format!("invalid import binding param {}", i)));
+        }
+        self.binding.params.bindings.push(expr);
+        self
+    }
+
+    /// Add an incoming binding expression for the next Wasm results.
+    pub fn result(mut self, expr: IncomingBindingExpression) -> Self {
+        if self.error.is_none() {
+            let ty = self.module.types.get(self.binding.wasm_ty);
+            let i = self.binding.result.bindings.len();
+            match expr.validate(ty.results(), self.produced, Some(ty.params())) {
+                Ok(arity) => self.produced += arity,
+                Err(e) => {
+                    self.error = Some(e.context(format!("invalid import binding result {}", i)))
+                }
+            }
+        }
+        self.binding.result.bindings.push(expr);
+        self
+    }
+
+    /// Finish building the binding.
+    ///
+    /// This checks the binding against the Wasm function type only. Use
+    /// `insert` to also check it against the Web IDL function type.
+    pub fn build(self) -> Result<ImportBinding> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let results = self.module.types.get(self.binding.wasm_ty).results();
+        if self.produced != results.len() {
+            bail!(
+                "import binding results produce {} Wasm values, but {} are expected",
+                self.produced,
+                results.len()
+            );
+        }
+        Ok(self.binding)
+    }
+
+    /// Finish building the binding, check it against its Web IDL function
+    /// type, and insert it into `wb`.
+    pub fn insert(self, wb: &mut WebidlBindings) -> Result<ImportBindingId> {
+        let module = self.module;
+        let binding = self.build()?;
+        binding.validate(module, wb)?;
+        Ok(wb.bindings.insert(binding))
+    }
+}
+
+/// A builder for an `ExportBinding`.
+///
+/// The first error found while adding expressions is reported by `build` or
+/// `insert`.
+#[derive(Debug)]
+pub struct ExportBindingBuilder<'a> {
+    module: &'a walrus::Module,
+    binding: ExportBinding,
+    produced: usize,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> ExportBindingBuilder<'a> {
+    /// Start building a binding from the given Wasm function type, defined in
+    /// `module`, to the given Web IDL function type.
+    pub fn new(
+        module: &'a walrus::Module,
+        wasm_ty: walrus::TypeId,
+        webidl_ty: impl Into<WebidlTypeRef>,
+    ) -> Self {
+        ExportBindingBuilder {
+            module,
+            binding: ExportBinding {
+                wasm_ty,
+                webidl_ty: webidl_ty.into(),
+                params: IncomingBindingMap { bindings: vec![] },
+                result: OutgoingBindingMap { bindings: vec![] },
+            },
+            produced: 0,
+            error: None,
+        }
+    }
+
+    /// Add an incoming binding expression for the next Wasm parameters.
+    pub fn param(mut self, expr: IncomingBindingExpression) -> Self {
+        if self.error.is_none() {
+            let params = self.module.types.get(self.binding.wasm_ty).params();
+            let i = self.binding.params.bindings.len();
+            match expr.validate(params, self.produced, None) {
+                Ok(arity) => self.produced += arity,
+                Err(e) => {
+                    self.error = Some(e.context(format!("invalid export binding param {}", i)))
+                }
+            }
+        }
+        self.binding.params.bindings.push(expr);
+        self
+    }
+
+    /// Add an outgoing binding expression for the Web IDL result.
+    pub fn result(mut self, expr: OutgoingBindingExpression) -> Self {
+        if self.error.is_none() {
+            let results = self.module.types.get(self.binding.wasm_ty).results();
+            let i = self.binding.result.bindings.len();
+            self.error = expr
+                .validate(results)
+                .err()
+                .map(|e| e.context(format!("invalid export binding result {}", i)));
+        }
+        self.binding.result.bindings.push(expr);
+        self
+    }
+
+    /// Finish building the binding.
+    ///
+    /// This checks the binding against the Wasm function type only. Use
+    /// `insert` to also check it against the Web IDL function type.
+    pub fn build(self) -> Result<ExportBinding> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let params = self.module.types.get(self.binding.wasm_ty).params();
+        if self.produced != params.len() {
+            bail!(
+                "export binding params produce {} Wasm values, but {} are expected",
+                self.produced,
+                params.len()
+            );
+        }
+        Ok(self.binding)
+    }
+
+    /// Finish building the binding, check it against its Web IDL function
+    /// type, and insert it into `wb`.
+    pub fn insert(self, wb: &mut WebidlBindings) -> Result<ExportBindingId> {
+        let module = self.module;
+        let binding = self.build()?;
+        binding.validate(module, wb)?;
+        Ok(wb.bindings.insert(binding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::incoming as inc;
+    use super::outgoing as out;
+    use super::*;
+    use walrus::ValType::{I32, I64};
+    use WebidlScalarType::{DomString, Long, LongLong};
+
+    fn function(
+        wb: &mut WebidlBindings,
+        params: Vec<WebidlTypeRef>,
+        result: Option<WebidlTypeRef>,
+    ) -> WebidlFunctionId {
+        wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: params.into_iter().map(Into::into).collect(),
+            result,
+        })
+    }
+
+    #[test]
+    fn import_binding() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32, I32], &[I32, I32]);
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = function(&mut wb, vec![DomString.into()], Some(DomString.into()));
+
+        let id = ImportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(out::utf8_str(DomString, 0, 1))
+            .result(inc::alloc_utf8_str("malloc", inc::get(0)))
+            .insert(&mut wb)
+            .unwrap();
+        assert_eq!(
+            wb.bindings.get(id),
+            Some(&ImportBinding {
+                wasm_ty,
+                webidl_ty: webidl_ty.into(),
+                params: OutgoingBindingMap {
+                    bindings: vec![OutgoingBindingExpressionUtf8Str {
+                        ty: DomString.into(),
+                        offset: 0,
+                        length: 1,
+                    }
+                    .into()],
+                },
+                result: IncomingBindingMap {
+                    bindings: vec![IncomingBindingExpressionAllocUtf8Str {
+                        alloc_func_name: "malloc".into(),
+                        expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                    }
+                    .into()],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn import_binding_errors() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32, I64], &[I32]);
+
+        // Out-of-bounds Wasm param.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .param(out::as_(Long, 2))
+            .result(inc::as_(I32, inc::get(0)))
+            .build()
+            .is_err());
+
+        // Offsets and lengths must be `i32`s.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .param(out::utf8_str(DomString, 0, 1))
+            .result(inc::as_(I32, inc::get(0)))
+            .build()
+            .is_err());
+
+        // Mismatched result type.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .result(inc::as_(I64, inc::get(0)))
+            .build()
+            .is_err());
+
+        // Too many results.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .result(inc::alloc_copy("malloc", inc::get(0)))
+            .build()
+            .is_err());
+
+        // Too few results.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .build()
+            .is_err());
+
+        // Results must produce Wasm values.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .result(inc::get(0))
+            .build()
+            .is_err());
+
+        // Writing into a buffer whose capacity is not an `i32`.
+        assert!(ImportBindingBuilder::new(&m, wasm_ty, Long)
+            .result(inc::write_utf8_str(0, 1, inc::get(0)))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn import_binding_checks_webidl_types_on_insert() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32], &[I32]);
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = function(&mut wb, vec![], Some(Long.into()));
+
+        // Well-typed for Wasm, but the Web IDL function has no params.
+        let builder = ImportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(out::as_(Long, 0))
+            .result(inc::as_(I32, inc::get(0)));
+        assert!(builder.insert(&mut wb).is_err());
+    }
+
+    #[test]
+    fn export_binding() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32, I32, I64], &[I32, I32]);
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = function(
+            &mut wb,
+            vec![DomString.into(), LongLong.into()],
+            Some(DomString.into()),
+        );
+
+        let binding = ExportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(inc::alloc_utf8_str("malloc", inc::get(0)))
+            .param(inc::as_(I64, inc::get(1)))
+            .result(out::utf8_str(DomString, 0, 1));
+        binding.insert(&mut wb).unwrap();
+
+        // Writing into a caller's buffer is only possible for import results.
+        assert!(ExportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(inc::write_utf8_str(0, 1, inc::get(0)))
+            .build()
+            .is_err());
+
+        // Too few params.
+        assert!(ExportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(inc::alloc_utf8_str("malloc", inc::get(0)))
+            .result(out::utf8_str(DomString, 0, 1))
+            .build()
+            .is_err());
+
+        // Out-of-bounds Wasm result.
+        assert!(ExportBindingBuilder::new(&m, wasm_ty, webidl_ty)
+            .param(inc::alloc_utf8_str("malloc", inc::get(0)))
+            .param(inc::as_(I64, inc::get(1)))
+            .result(out::utf8_str(DomString, 0, 2))
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Comparing Web IDL bindings sections structurally, for example to check that
+//! a section is unchanged by encoding and decoding it.
+
+use crate::ast::*;
+use crate::merge::IdRemap;
+use crate::visit::VisitorMut;
+use id_arena::Id;
+use std::collections::HashMap;
+
+impl WebidlBindings {
+    /// Is this section the same as `other`, apart from the arena ids that
+    /// their types and function bindings were given?
+    ///
+    /// Types, function bindings and binds are compared in order, so the `n`th
+    /// type of this section must be the same as the `n`th type of `other`,
+    /// and references to it must be references to that type. Wasm functions
+    /// and types referenced by this section are mapped to `other`'s module
+    /// through `id_remap`; the sections are not equal if any of them is
+    /// missing from it.
+    ///
+    /// Names of types and function bindings are ignored, since they are not
+    /// part of the binary format.
+    pub fn structurally_eq(&self, other: &WebidlBindings, id_remap: &IdRemap) -> bool {
+        if self.types.arena.len() != other.types.arena.len()
+            || self.bindings.arena.len() != other.bindings.arena.len()
+            || self.binds.arena.len() != other.binds.arena.len()
+        {
+            return false;
+        }
+
+        let mut remap = Remap {
+            id_remap,
+            types: self
+                .types
+                .arena
+                .iter()
+                .zip(other.types.arena.iter())
+                .map(|((a, _), (b, _))| (a, b))
+                .collect(),
+            bindings: self
+                .bindings
+                .arena
+                .iter()
+                .zip(other.bindings.arena.iter())
+                .map(|((a, _), (b, _))| (a, b))
+                .collect(),
+            missing: false,
+        };
+
+        for ((id, a), (_, b)) in self.types.arena.iter().zip(other.types.arena.iter()) {
+            let mut a = a.clone();
+            remap.visit_webidl_compound_type(id, &mut a);
+            if remap.missing || a != *b {
+                return false;
+            }
+        }
+        for ((id, a), (_, b)) in self.bindings.arena.iter().zip(other.bindings.arena.iter()) {
+            let mut a = a.clone();
+            remap.visit_function_binding(id, &mut a);
+            if remap.missing || a != *b {
+                return false;
+            }
+        }
+        for ((id, a), (_, b)) in self.binds.iter().zip(other.binds.iter()) {
+            let mut a = a.clone();
+            remap.visit_bind(id, &mut a);
+            if remap.missing || a != *b {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Rewrite references in one section into references to the corresponding
+/// items of the other.
+struct Remap<'a> {
+    id_remap: &'a IdRemap,
+    types: HashMap<Id<WebidlCompoundType>, Id<WebidlCompoundType>>,
+    bindings: HashMap<Id<FunctionBinding>, Id<FunctionBinding>>,
+    /// Whether a reference has been visited that has no corresponding item.
+    missing: bool,
+}
+
+impl Remap<'_> {
+    fn remap<T>(map: &HashMap<T, T>, id: &mut T, missing: &mut bool)
+    where
+        T: Copy + Eq + std::hash::Hash,
+    {
+        match map.get(id) {
+            Some(new_id) => *id = *new_id,
+            None => *missing = true,
+        }
+    }
+}
+
+impl VisitorMut for Remap<'_> {
+    fn visit_function_id(&mut self, id: &mut walrus::FunctionId) {
+        Self::remap(&self.id_remap.funcs, id, &mut self.missing);
+    }
+
+    fn visit_type_id(&mut self, id: &mut walrus::TypeId) {
+        Self::remap(&self.id_remap.types, id, &mut self.missing);
+    }
+
+    fn visit_webidl_compound_type_id(&mut self, id: &mut Id<WebidlCompoundType>) {
+        Self::remap(&self.types, id, &mut self.missing);
+    }
+
+    fn visit_function_binding_id(&mut self, id: &mut Id<FunctionBinding>) {
+        Self::remap(&self.bindings, id, &mut self.missing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::outgoing as out;
+    use walrus::ValType::I32;
+    use WebidlScalarType::{DomString, Long};
+
+    /// A section with a `(func (param T))` type, an import binding of it, and
+    /// a bind of `func` to that binding.
+    fn section(
+        wasm_ty: walrus::TypeId,
+        func: walrus::FunctionId,
+        param: WebidlScalarType,
+    ) -> WebidlBindings {
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![param.into()],
+            result: None,
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![out::as_(param, 0)],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+        wb
+    }
+
+    #[test]
+    fn structurally_eq_ignores_arena_ids_and_names() {
+        let mut m = walrus::Module::default();
+        let ty = m.types.add(&[I32], &[]);
+        let (func, _) = m.add_import_func("env", "f", ty);
+        let remap = IdRemap {
+            funcs: vec![(func, func)].into_iter().collect(),
+            types: vec![(ty, ty)].into_iter().collect(),
+        };
+
+        let a = section(ty, func, Long);
+        let mut b = section(ty, func, Long);
+        b.types
+            .names
+            .insert("$t".into(), b.types.arena.iter().next().unwrap().0);
+        assert!(a.structurally_eq(&b, &remap));
+        assert!(b.structurally_eq(&a, &remap));
+
+        let c = section(ty, func, DomString);
+        assert!(!a.structurally_eq(&c, &remap));
+        assert!(!a.structurally_eq(&WebidlBindings::default(), &remap));
+    }
+
+    #[test]
+    fn structurally_eq_remaps_wasm_ids() {
+        let mut m = walrus::Module::default();
+        let ty = m.types.add(&[I32], &[]);
+        let (f, _) = m.add_import_func("env", "f", ty);
+        let (g, _) = m.add_import_func("env", "g", ty);
+
+        let a = section(ty, f, Long);
+        let b = section(ty, g, Long);
+        let f_to_g = IdRemap {
+            funcs: vec![(f, g)].into_iter().collect(),
+            types: vec![(ty, ty)].into_iter().collect(),
+        };
+        assert!(a.structurally_eq(&b, &f_to_g));
+        assert!(!a.structurally_eq(&b, &IdRemap::default()));
+    }
+}
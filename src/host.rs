@@ -0,0 +1,1289 @@
+//! Generating Rust host-side trait definitions for the Wasm imports that have
+//! Web IDL import bindings.
+//!
+//! For every imported Wasm function that is bound to an `ImportBinding`, we
+//! generate a method on an `Imports` trait for the function's import module,
+//! with idiomatic Rust types derived from the binding's expressions. For
+//! example, a `utf8-str` becomes a `&str`, and a `view` of a `Uint8Array`
+//! becomes a `&[u8]`. Named Web IDL dictionaries and enumerations become Rust
+//! structs and enums.
+//!
+//! Alongside each trait method, we generate a marshalling shim that takes the
+//! raw Wasm arguments and linear memory, converts the arguments as the
+//! binding's outgoing binding map describes, calls the trait method, and
+//! converts its result back into Wasm values as the binding's incoming
+//! binding map describes.
+//!
+//! Bindings that use expressions the host can't perform on its own, like
+//! calling back into Wasm to allocate memory, are reported as errors.
+
+use crate::ast::*;
+use anyhow::{anyhow, bail, Context, Result};
+use id_arena::Id;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+/// Generate Rust traits and marshalling shims for every import of `module`
+/// that is bound to an import binding in `wb`.
+///
+/// Each import binding is checked with `ImportBinding::validate` first.
+pub fn generate(module: &walrus::Module, wb: &WebidlBindings) -> Result<String> {
+    let mut gen = Generator::new(wb);
+    // Import module name -> (trait methods, shims).
+    let mut modules: Vec<(String, String, String)> = vec![];
+    let mut seen = HashSet::new();
+
+    for (_, bind) in wb.binds.iter() {
+        let binding = match wb.bindings.arena.get(bind.binding) {
+            Some(b) => b,
+            None => bail!(
+                "bind refers to missing function binding {}",
+                bind.binding.index()
+            ),
+        };
+        let binding = match binding {
+            FunctionBinding::Import(b) => b,
+            FunctionBinding::Export(_) => continue,
+        };
+        let import = match &module.funcs.get(bind.func).kind {
+            walrus::FunctionKind::Import(i) => module.imports.get(i.import),
+            _ => continue,
+        };
+        let name = snake_case(&import.name);
+        if !seen.insert((import.module.clone(), name.clone())) {
+            bail!(
+                "import `{}` from `{}` is bound more than once",
+                import.name,
+                import.module
+            );
+        }
+
+        let index = match modules.iter().position(|(m, _, _)| *m == import.module) {
+            Some(i) => i,
+            None => {
+                modules.push((import.module.clone(), String::new(), String::new()));
+                modules.len() - 1
+            }
+        };
+        let (_, methods, shims) = &mut modules[index];
+        gen.import(module, &name, binding, methods, shims)
+            .with_context(|| {
+                format!(
+                    "cannot generate host code for import `{}` from `{}`",
+                    import.name, import.module
+                )
+            })?;
+    }
+
+    while let Some(id) = gen.pending.pop() {
+        let decl = gen.declaration(id)?;
+        gen.declarations.insert(id.index(), decl);
+    }
+
+    let mut out = String::new();
+    out.push_str(PRELUDE);
+    for decl in gen.declarations.values() {
+        out.push('\n');
+        out.push_str(decl);
+    }
+    for (module_name, methods, shims) in modules {
+        writeln!(out).unwrap();
+        writeln!(out, "pub mod {} {{", ident(&snake_case(&module_name))).unwrap();
+        writeln!(out, "    use super::*;").unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "    /// The functions imported from `{}`.",
+            module_name.escape_default()
+        )
+        .unwrap();
+        writeln!(out, "    pub trait Imports {{").unwrap();
+        out.push_str(&methods);
+        writeln!(out, "    }}").unwrap();
+        out.push_str(&shims);
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(out)
+}
+
+/// The error type and memory access helpers that the shims use.
+const PRELUDE: &str = r#"// Generated by wasm-webidl-bindings. Do not edit.
+
+/// An error converting between Wasm values and host values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarshalError {
+    /// A region of linear memory is out of bounds.
+    OutOfBounds,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// An integer is not the index of a value in an enumeration.
+    InvalidEnum,
+}
+
+#[allow(dead_code)]
+fn read(memory: &[u8], offset: i32, length: i32, size: usize) -> Result<&[u8], MarshalError> {
+    let length = (length as u32 as usize)
+        .checked_mul(size)
+        .ok_or(MarshalError::OutOfBounds)?;
+    memory
+        .get(offset as u32 as usize..)
+        .and_then(|m| m.get(..length))
+        .ok_or(MarshalError::OutOfBounds)
+}
+
+#[allow(dead_code)]
+fn read_cstr(memory: &[u8], offset: i32) -> Result<&[u8], MarshalError> {
+    let bytes = memory
+        .get(offset as u32 as usize..)
+        .ok_or(MarshalError::OutOfBounds)?;
+    let length = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(MarshalError::OutOfBounds)?;
+    Ok(&bytes[..length])
+}
+
+#[allow(dead_code)]
+fn utf8(bytes: &[u8]) -> Result<&str, MarshalError> {
+    std::str::from_utf8(bytes).map_err(|_| MarshalError::InvalidUtf8)
+}
+
+#[allow(dead_code)]
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[allow(dead_code)]
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+#[allow(dead_code)]
+fn write_bytes(
+    memory: &mut [u8],
+    offset: i32,
+    capacity: i32,
+    bytes: &[u8],
+    size: usize,
+) -> Result<i32, MarshalError> {
+    let n = bytes.len().min(capacity as u32 as usize) / size * size;
+    memory
+        .get_mut(offset as u32 as usize..)
+        .and_then(|m| m.get_mut(..n))
+        .ok_or(MarshalError::OutOfBounds)?
+        .copy_from_slice(&bytes[..n]);
+    Ok(n as i32)
+}
+
+#[allow(dead_code)]
+fn write_utf8(memory: &mut [u8], offset: i32, capacity: i32, s: &str) -> Result<i32, MarshalError> {
+    let mut n = s.len().min(capacity as u32 as usize);
+    while !s.is_char_boundary(n) {
+        n -= 1;
+    }
+    write_bytes(memory, offset, n as i32, s.as_bytes(), 1)
+}
+
+#[allow(dead_code)]
+fn write_utf16(memory: &mut [u8], offset: i32, capacity: i32, s: &str) -> Result<i32, MarshalError> {
+    let mut units = vec![];
+    for c in s.chars() {
+        let mut buf = [0; 2];
+        let encoded = c.encode_utf16(&mut buf);
+        if units.len() + encoded.len() > capacity as u32 as usize {
+            break;
+        }
+        units.extend_from_slice(encoded);
+    }
+    let bytes: Vec<u8> = units.iter().flat_map(|u| u.to_le_bytes().to_vec()).collect();
+    write_bytes(memory, offset, bytes.len() as i32, &bytes, 2)?;
+    Ok(units.len() as i32)
+}
+"#;
+
+/// The owned Rust type that a Web IDL type maps to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RustTy {
+    /// A primitive number or `bool`.
+    Scalar(&'static str),
+    String,
+    /// A `Vec` of primitive numbers.
+    Vec(&'static str),
+    /// A generated struct or enum.
+    Named(Id<WebidlCompoundType>),
+}
+
+/// A value computed by an incoming binding expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Incoming {
+    Webidl(RustTy),
+    Wasm(walrus::ValType),
+}
+
+/// The Rust code for an outgoing binding expression.
+struct Outgoing {
+    /// The type of the trait method's parameter.
+    param_ty: String,
+    code: String,
+    /// The owned type of the value, for use in a dictionary field.
+    owned: RustTy,
+    /// The method that converts `code` into an owned value, if it borrows
+    /// linear memory.
+    to_owned: &'static str,
+}
+
+struct Generator<'a> {
+    wb: &'a WebidlBindings,
+    names: HashMap<Id<WebidlCompoundType>, String>,
+    /// Named types whose declarations still need to be generated.
+    pending: Vec<Id<WebidlCompoundType>>,
+    /// Declarations of named types, in arena order. Pending types have an
+    /// empty placeholder.
+    declarations: BTreeMap<usize, String>,
+}
+
+/// The state of the shim currently being generated.
+struct Shim<'a> {
+    params: &'a [walrus::ValType],
+    used_params: HashSet<u32>,
+    uses_memory: bool,
+}
+
+impl Shim<'_> {
+    fn param(&mut self, idx: u32, expected: Option<walrus::ValType>) -> Result<String> {
+        let ty = match self.params.get(idx as usize) {
+            Some(ty) => *ty,
+            None => bail!("Wasm parameter index {} is out of bounds", idx),
+        };
+        if let Some(expected) = expected {
+            if ty != expected {
+                bail!("Wasm parameter {} is {}, expected {}", idx, ty, expected);
+            }
+        }
+        self.used_params.insert(idx);
+        Ok(format!("wasm{}", idx))
+    }
+
+    fn memory(&mut self) -> &'static str {
+        self.uses_memory = true;
+        "memory"
+    }
+}
+
+impl<'a> Generator<'a> {
+    fn new(wb: &'a WebidlBindings) -> Self {
+        Generator {
+            wb,
+            names: wb
+                .types
+                .names
+                .iter()
+                .map(|(name, id)| (*id, camel_case(name.trim_start_matches('$'))))
+                .collect(),
+            pending: vec![],
+            declarations: BTreeMap::new(),
+        }
+    }
+
+    /// Get the Web IDL type with the given id, which may be dangling.
+    fn webidl_type(&self, id: Id<WebidlCompoundType>) -> Result<&'a WebidlCompoundType> {
+        match self.wb.types.arena.get(id) {
+            Some(ty) => Ok(ty),
+            None => bail!("reference to missing Web IDL type {}", id.index()),
+        }
+    }
+
+    fn import(
+        &mut self,
+        module: &walrus::Module,
+        name: &str,
+        binding: &ImportBinding,
+        methods: &mut String,
+        shims: &mut String,
+    ) -> Result<()> {
+        binding.validate(module, self.wb)?;
+        let func = match binding.webidl_ty {
+            WebidlTypeRef::Id(id) => match self.webidl_type(id)? {
+                WebidlCompoundType::Function(f) => f,
+                _ => bail!("the import binding's Web IDL type is not a function"),
+            },
+            _ => bail!("the import binding's Web IDL type is not a function"),
+        };
+        let (params, results) = module.types.params_results(binding.wasm_ty);
+        for ty in params.iter().chain(results) {
+            match ty {
+                walrus::ValType::Anyref | walrus::ValType::V128 => {
+                    bail!("Wasm values of type {} cannot be passed to the host", ty)
+                }
+                _ => {}
+            }
+        }
+        let mut shim = Shim {
+            params,
+            used_params: HashSet::new(),
+            uses_memory: false,
+        };
+
+        let mut body = String::new();
+        let mut args = vec![];
+        let mut arg_tys = vec![];
+        for e in &binding.params.bindings {
+            let arg = match self.outgoing(&mut shim, e)? {
+                Some(arg) => arg,
+                None => continue,
+            };
+            let arg_name = match func.kind {
+                WebidlFunctionKind::Method(_) if args.is_empty() => "this".to_string(),
+                WebidlFunctionKind::Method(_) => format!("arg{}", args.len() - 1),
+                _ => format!("arg{}", args.len()),
+            };
+            writeln!(body, "        let {} = {};", arg_name, arg.code).unwrap();
+            arg_tys.push(format!("{}: {}", arg_name, arg.param_ty));
+            args.push(arg_name);
+        }
+
+        let result_ty = match func.result {
+            Some(ty) => Some(self.rust_ty(ty)?),
+            None => None,
+        };
+        let call = format!("host.{}({})", ident(name), args.join(", "));
+        let mut result_codes = vec![];
+        if binding.result.bindings.is_empty() {
+            writeln!(body, "        {};", call).unwrap();
+        } else {
+            writeln!(body, "        let result = {};", call).unwrap();
+            for (i, e) in binding.result.bindings.iter().enumerate() {
+                let (code, ty) = self.incoming(&mut shim, result_ty, e)?;
+                let expected = results
+                    .get(i)
+                    .ok_or_else(|| anyhow!("more results than the Wasm function type has"))?;
+                if ty != Incoming::Wasm(*expected) {
+                    bail!("result {} is not a Wasm {}", i, expected);
+                }
+                writeln!(body, "        let result{} = {};", i, code).unwrap();
+                result_codes.push(format!("result{}", i));
+            }
+        }
+        if result_codes.len() != results.len() {
+            bail!("fewer results than the Wasm function type has");
+        }
+
+        // Trait method.
+        let mut method = format!("fn {}(&mut self", ident(name));
+        for arg in &arg_tys {
+            write!(method, ", {}", arg).unwrap();
+        }
+        method.push(')');
+        if let Some(ty) = result_ty {
+            write!(method, " -> {}", self.ty_name(ty)).unwrap();
+        }
+        writeln!(methods, "        {};", method).unwrap();
+
+        // Marshalling shim.
+        writeln!(shims).unwrap();
+        writeln!(
+            shims,
+            "    /// Convert the Wasm arguments, call `Imports::{}`, and convert its result.",
+            name
+        )
+        .unwrap();
+        writeln!(shims, "    pub fn {}(", ident(name)).unwrap();
+        writeln!(shims, "        host: &mut impl Imports,").unwrap();
+        writeln!(
+            shims,
+            "        {}memory: &mut [u8],",
+            if shim.uses_memory { "" } else { "_" }
+        )
+        .unwrap();
+        for (i, ty) in params.iter().enumerate() {
+            let unused = if shim.used_params.contains(&(i as u32)) {
+                ""
+            } else {
+                "_"
+            };
+            writeln!(shims, "        {}wasm{}: {},", unused, i, ty).unwrap();
+        }
+        let (result_ty, result_value) = match results.len() {
+            1 => (results[0].to_string(), result_codes[0].clone()),
+            _ => (
+                format!(
+                    "({})",
+                    results
+                        .iter()
+                        .map(|t| t.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                format!("({})", result_codes.join(", ")),
+            ),
+        };
+        writeln!(shims, "    ) -> Result<{}, MarshalError> {{", result_ty).unwrap();
+        shims.push_str(&body);
+        writeln!(shims, "        Ok({})", result_value).unwrap();
+        writeln!(shims, "    }}").unwrap();
+        Ok(())
+    }
+
+    fn outgoing(
+        &mut self,
+        shim: &mut Shim,
+        e: &OutgoingBindingExpression,
+    ) -> Result<Option<Outgoing>> {
+        use OutgoingBindingExpression as O;
+        let owned = |code: String, ty: RustTy, this: &Self| Outgoing {
+            param_ty: this.ty_name(ty),
+            code,
+            owned: ty,
+            to_owned: "",
+        };
+        Ok(Some(match e {
+            O::As(e) => {
+                let ty = self.rust_ty(e.ty)?;
+                let wasm = shim.param(e.idx, None)?;
+                let code = match (ty, shim.params[e.idx as usize]) {
+                    (RustTy::Scalar("bool"), walrus::ValType::F32)
+                    | (RustTy::Scalar("bool"), walrus::ValType::F64) => {
+                        format!("{} != 0.0", wasm)
+                    }
+                    (RustTy::Scalar("bool"), _) => format!("{} != 0", wasm),
+                    (RustTy::Scalar(s), _) => format!("{} as {}", wasm, s),
+                    _ => bail!("cannot convert a Wasm value to {:?}", e.ty),
+                };
+                owned(code, ty, self)
+            }
+            O::Utf8Str(e) => {
+                self.expect_string(e.ty)?;
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(walrus::ValType::I32))?;
+                let length = shim.param(e.length, Some(walrus::ValType::I32))?;
+                Outgoing {
+                    param_ty: "&str".to_string(),
+                    code: format!("utf8(read({}, {}, {}, 1)?)?", memory, offset, length),
+                    owned: RustTy::String,
+                    to_owned: ".to_owned()",
+                }
+            }
+            O::Utf8CStr(e) => {
+                self.expect_string(e.ty)?;
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(walrus::ValType::I32))?;
+                Outgoing {
+                    param_ty: "&str".to_string(),
+                    code: format!("utf8(read_cstr({}, {})?)?", memory, offset),
+                    owned: RustTy::String,
+                    to_owned: ".to_owned()",
+                }
+            }
+            O::Utf16Str(e) => {
+                self.expect_string(e.ty)?;
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(walrus::ValType::I32))?;
+                let length = shim.param(e.length, Some(walrus::ValType::I32))?;
+                owned(
+                    format!("utf16(read({}, {}, {}, 2)?)", memory, offset, length),
+                    RustTy::String,
+                    self,
+                )
+            }
+            O::Latin1Str(e) => {
+                self.expect_string(e.ty)?;
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(walrus::ValType::I32))?;
+                let length = shim.param(e.length, Some(walrus::ValType::I32))?;
+                owned(
+                    format!("latin1(read({}, {}, {}, 1)?)", memory, offset, length),
+                    RustTy::String,
+                    self,
+                )
+            }
+            O::I32ToEnum(e) => {
+                let ty = self.rust_ty(e.ty)?;
+                if !self.is_enum(ty) {
+                    bail!("i32-to-enum of a non-enumeration type {:?}", e.ty);
+                }
+                let wasm = shim.param(e.idx, Some(walrus::ValType::I32))?;
+                owned(
+                    format!(
+                        "{}::from_i32({}).ok_or(MarshalError::InvalidEnum)?",
+                        self.ty_name(ty),
+                        wasm
+                    ),
+                    ty,
+                    self,
+                )
+            }
+            O::View(OutgoingBindingExpressionView {
+                ty,
+                offset,
+                length,
+                unit,
+            })
+            | O::Copy(OutgoingBindingExpressionCopy {
+                ty,
+                offset,
+                length,
+                unit,
+            }) => {
+                let rust_ty = self.rust_ty(*ty)?;
+                let elem = match rust_ty {
+                    RustTy::Vec(elem) => elem,
+                    _ => bail!("cannot view or copy a non-buffer type {:?}", ty),
+                };
+                let size = scalar_size(elem);
+                let unit_size = match unit {
+                    BufferLengthUnit::Elements => size,
+                    BufferLengthUnit::Bytes => 1,
+                };
+                let memory = shim.memory();
+                let offset = shim.param(*offset, Some(walrus::ValType::I32))?;
+                let length = shim.param(*length, Some(walrus::ValType::I32))?;
+                let bytes = format!("read({}, {}, {}, {})?", memory, offset, length, unit_size);
+                match (e, elem) {
+                    (O::View(_), "u8") => Outgoing {
+                        param_ty: "&[u8]".to_string(),
+                        code: bytes,
+                        owned: rust_ty,
+                        to_owned: ".to_vec()",
+                    },
+                    (_, "u8") => owned(format!("{}.to_vec()", bytes), rust_ty, self),
+                    _ => owned(from_le_bytes(&bytes, elem, size, elem), rust_ty, self),
+                }
+            }
+            O::Dict(e) => {
+                let ty = self.rust_ty(e.ty)?;
+                let dict = match ty {
+                    RustTy::Named(id) => match self.webidl_type(id)? {
+                        WebidlCompoundType::Dictionary(d) => d,
+                        _ => bail!("dict of a non-dictionary type {:?}", e.ty),
+                    },
+                    _ => bail!("dict of a non-dictionary type {:?}", e.ty),
+                };
+                if dict.fields.len() != e.fields.len() {
+                    bail!(
+                        "dict has {} fields, but its dictionary type has {}",
+                        e.fields.len(),
+                        dict.fields.len()
+                    );
+                }
+                let mut fields = vec![];
+                for (field, expr) in dict.fields.iter().zip(&e.fields) {
+                    let value = match self.outgoing(shim, expr)? {
+                        Some(v) => v,
+                        None => bail!("dictionary field `{}` cannot be undefined", field.name),
+                    };
+                    if value.owned != self.rust_ty(field.ty)? {
+                        bail!("dictionary field `{}` has the wrong type", field.name);
+                    }
+                    fields.push(format!(
+                        "{}: {}{}",
+                        ident(&snake_case(&field.name)),
+                        value.code,
+                        value.to_owned
+                    ));
+                }
+                owned(
+                    format!("{} {{ {} }}", self.ty_name(ty), fields.join(", ")),
+                    ty,
+                    self,
+                )
+            }
+            O::BindExport(_) => bail!("bind-export is not supported in host code"),
+            O::Undefined(_) => return Ok(None),
+            O::Variadic(e) => {
+                let elem = match self.rust_ty(e.ty)? {
+                    RustTy::Scalar(s) => s,
+                    _ => bail!("cannot convert a Wasm value to {:?}", e.ty),
+                };
+                let wasm_elem = match e.elem_ty {
+                    walrus::ValType::I32 => "i32",
+                    walrus::ValType::I64 => "i64",
+                    walrus::ValType::F32 => "f32",
+                    walrus::ValType::F64 => "f64",
+                    ty => bail!("cannot read {} values from linear memory", ty),
+                };
+                let size = scalar_size(wasm_elem);
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(walrus::ValType::I32))?;
+                let length = shim.param(e.length, Some(walrus::ValType::I32))?;
+                let bytes = format!("read({}, {}, {}, {})?", memory, offset, length, size);
+                owned(
+                    from_le_bytes(&bytes, wasm_elem, size, elem),
+                    RustTy::Vec(elem),
+                    self,
+                )
+            }
+        }))
+    }
+
+    fn incoming(
+        &mut self,
+        shim: &mut Shim,
+        result: Option<RustTy>,
+        e: &IncomingBindingExpression,
+    ) -> Result<(String, Incoming)> {
+        use walrus::ValType::I32;
+        use IncomingBindingExpression as I;
+        Ok(match e {
+            I::Get(e) => match result {
+                Some(ty) if e.idx == 0 => ("result".to_string(), Incoming::Webidl(ty)),
+                _ => bail!("get {} of a function without that many results", e.idx),
+            },
+            I::As(e) => {
+                let (code, ty) = self.incoming(shim, result, &e.expr)?;
+                let code = match ty {
+                    Incoming::Webidl(RustTy::Scalar("bool")) if e.ty != I32 => {
+                        format!("{} as i32 as {}", code, e.ty)
+                    }
+                    Incoming::Webidl(RustTy::Scalar(_)) => format!("{} as {}", code, e.ty),
+                    _ => bail!("cannot convert {:?} to a Wasm {}", ty, e.ty),
+                };
+                (code, Incoming::Wasm(e.ty))
+            }
+            I::EnumToI32(e) => {
+                let (code, ty) = self.incoming(shim, result, &e.expr)?;
+                match ty {
+                    Incoming::Webidl(ty) if self.is_enum(ty) => {}
+                    _ => bail!("enum-to-i32 of a non-enumeration value"),
+                }
+                (format!("{} as i32", code), Incoming::Wasm(I32))
+            }
+            I::Field(e) => {
+                let (code, ty) = self.incoming(shim, result, &e.expr)?;
+                let dict = match ty {
+                    Incoming::Webidl(RustTy::Named(id)) => match self.webidl_type(id)? {
+                        WebidlCompoundType::Dictionary(d) => d,
+                        _ => bail!("field of a non-dictionary value"),
+                    },
+                    _ => bail!("field of a non-dictionary value"),
+                };
+                let field = dict
+                    .fields
+                    .get(e.idx as usize)
+                    .ok_or_else(|| anyhow!("field index {} is out of bounds", e.idx))?;
+                (
+                    format!("{}.{}", code, ident(&snake_case(&field.name))),
+                    Incoming::Webidl(self.rust_ty(field.ty)?),
+                )
+            }
+            I::WriteUtf8Str(IncomingBindingExpressionWriteUtf8Str {
+                offset,
+                capacity,
+                expr,
+            })
+            | I::WriteUtf16Str(IncomingBindingExpressionWriteUtf16Str {
+                offset,
+                capacity,
+                expr,
+            }) => {
+                let (code, ty) = self.incoming(shim, result, expr)?;
+                if ty != Incoming::Webidl(RustTy::String) {
+                    bail!("cannot write a non-string value as a string");
+                }
+                let write = match e {
+                    I::WriteUtf8Str(_) => "write_utf8",
+                    _ => "write_utf16",
+                };
+                let memory = shim.memory();
+                let offset = shim.param(*offset, Some(I32))?;
+                let capacity = shim.param(*capacity, Some(I32))?;
+                (
+                    format!(
+                        "{}({}, {}, {}, &{})?",
+                        write, memory, offset, capacity, code
+                    ),
+                    Incoming::Wasm(I32),
+                )
+            }
+            I::WriteCopy(e) => {
+                let (code, ty) = self.incoming(shim, result, &e.expr)?;
+                let elem = match ty {
+                    Incoming::Webidl(RustTy::Vec(elem)) => elem,
+                    _ => bail!("cannot write-copy a non-buffer value"),
+                };
+                let bytes = if elem == "u8" {
+                    format!("&{}", code)
+                } else {
+                    format!(
+                        "&{}.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect::<Vec<u8>>()",
+                        code
+                    )
+                };
+                let memory = shim.memory();
+                let offset = shim.param(e.offset, Some(I32))?;
+                let capacity = shim.param(e.capacity, Some(I32))?;
+                (
+                    format!(
+                        "write_bytes({}, {}, {}, {}, {})?",
+                        memory,
+                        offset,
+                        capacity,
+                        bytes,
+                        scalar_size(elem)
+                    ),
+                    Incoming::Wasm(I32),
+                )
+            }
+            I::AllocUtf8Str(_) | I::AllocUtf16Str(_) | I::AllocLatin1Str(_) | I::AllocCopy(_) => {
+                bail!("allocating in linear memory is not supported in host code")
+            }
+            I::BindImport(_) => bail!("bind-import is not supported in host code"),
+        })
+    }
+
+    fn declaration(&mut self, id: Id<WebidlCompoundType>) -> Result<String> {
+        let name = self.names[&id].clone();
+        let mut out = String::new();
+        match self.webidl_type(id)? {
+            WebidlCompoundType::Dictionary(d) => {
+                writeln!(out, "#[derive(Clone, Debug, PartialEq)]").unwrap();
+                writeln!(out, "pub struct {} {{", name).unwrap();
+                for field in &d.fields {
+                    let ty = self.rust_ty(field.ty)?;
+                    writeln!(
+                        out,
+                        "    pub {}: {},",
+                        ident(&snake_case(&field.name)),
+                        self.ty_name(ty)
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "}}").unwrap();
+            }
+            WebidlCompoundType::Enumeration(e) => {
+                let variants: Vec<_> = e.values.iter().map(|v| camel_case(v)).collect();
+                writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq)]").unwrap();
+                writeln!(out, "pub enum {} {{", name).unwrap();
+                for (i, (value, variant)) in e.values.iter().zip(&variants).enumerate() {
+                    writeln!(out, "    /// `{:?}`", value).unwrap();
+                    writeln!(out, "    {} = {},", variant, i).unwrap();
+                }
+                writeln!(out, "}}").unwrap();
+                writeln!(out).unwrap();
+                writeln!(out, "impl {} {{", name).unwrap();
+                writeln!(
+                    out,
+                    "    /// Get the value at `index` in the enumeration, if any."
+                )
+                .unwrap();
+                writeln!(out, "    pub fn from_i32(index: i32) -> Option<Self> {{").unwrap();
+                writeln!(out, "        match index {{").unwrap();
+                for (i, variant) in variants.iter().enumerate() {
+                    writeln!(out, "            {} => Some({}::{}),", i, name, variant).unwrap();
+                }
+                writeln!(out, "            _ => None,").unwrap();
+                writeln!(out, "        }}").unwrap();
+                writeln!(out, "    }}").unwrap();
+                writeln!(out, "}}").unwrap();
+            }
+            _ => unreachable!("only dictionaries and enumerations are declared"),
+        }
+        Ok(out)
+    }
+
+    /// Get the owned Rust type for a Web IDL type.
+    fn rust_ty(&mut self, ty: WebidlTypeRef) -> Result<RustTy> {
+        let scalar = match ty {
+            WebidlTypeRef::Scalar(s) => s,
+            WebidlTypeRef::Annotated(a) => a.ty(),
+            WebidlTypeRef::Id(id) => {
+                let name = match self.names.get(&id) {
+                    Some(name) => name,
+                    None => bail!(
+                        "anonymous Web IDL type {} needs a name to become a Rust type",
+                        id.index()
+                    ),
+                };
+                match self.webidl_type(id)? {
+                    WebidlCompoundType::Dictionary(_) => {}
+                    WebidlCompoundType::Enumeration(e) if !e.values.is_empty() => {}
+                    _ => bail!("Web IDL type `{}` has no Rust equivalent", name),
+                }
+                if let Entry::Vacant(slot) = self.declarations.entry(id.index()) {
+                    // Reserve the slot, so that cyclic references to this
+                    // type aren't queued again.
+                    slot.insert(String::new());
+                    self.pending.push(id);
+                }
+                return Ok(RustTy::Named(id));
+            }
+        };
+        use WebidlScalarType::*;
+        Ok(match scalar {
+            Boolean => RustTy::Scalar("bool"),
+            Byte => RustTy::Scalar("i8"),
+            Octet => RustTy::Scalar("u8"),
+            Short => RustTy::Scalar("i16"),
+            UnsignedShort => RustTy::Scalar("u16"),
+            Long => RustTy::Scalar("i32"),
+            UnsignedLong => RustTy::Scalar("u32"),
+            LongLong => RustTy::Scalar("i64"),
+            UnsignedLongLong => RustTy::Scalar("u64"),
+            Float | UnrestrictedFloat => RustTy::Scalar("f32"),
+            Double | UnrestrictedDouble => RustTy::Scalar("f64"),
+            DomString | ByteString | UsvString => RustTy::String,
+            ArrayBuffer | DataView | Uint8Array | Uint8ClampedArray => RustTy::Vec("u8"),
+            Int8Array => RustTy::Vec("i8"),
+            Int16Array => RustTy::Vec("i16"),
+            Uint16Array => RustTy::Vec("u16"),
+            Int32Array => RustTy::Vec("i32"),
+            Uint32Array => RustTy::Vec("u32"),
+            Float32Array => RustTy::Vec("f32"),
+            Float64Array => RustTy::Vec("f64"),
+            Any | BigInt | Object | Symbol => bail!("Web IDL type {:?} has no Rust equivalent", ty),
+        })
+    }
+
+    fn ty_name(&self, ty: RustTy) -> String {
+        match ty {
+            RustTy::Scalar(s) => s.to_string(),
+            RustTy::String => "String".to_string(),
+            RustTy::Vec(elem) => format!("Vec<{}>", elem),
+            RustTy::Named(id) => self.names[&id].clone(),
+        }
+    }
+
+    fn is_enum(&self, ty: RustTy) -> bool {
+        match ty {
+            RustTy::Named(id) => {
+                matches!(
+                    self.wb.types.arena.get(id),
+                    Some(WebidlCompoundType::Enumeration(_))
+                )
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_string(&mut self, ty: WebidlTypeRef) -> Result<()> {
+        if self.rust_ty(ty)? != RustTy::String {
+            bail!("expected a string type, found {:?}", ty);
+        }
+        Ok(())
+    }
+}
+
+/// Code that decodes little endian `from` values out of `bytes` and converts
+/// them to `to`s.
+fn from_le_bytes(bytes: &str, from: &str, size: usize, to: &str) -> String {
+    let convert = match (from, to) {
+        (from, to) if from == to => String::new(),
+        ("f32", "bool") | ("f64", "bool") => " != 0.0".to_string(),
+        (_, "bool") => " != 0".to_string(),
+        (_, to) => format!(" as {}", to),
+    };
+    format!(
+        "{}.chunks_exact({}).map(|c| {}::from_le_bytes(std::convert::TryInto::try_into(c).unwrap()){}).collect::<Vec<{}>>()",
+        bytes, size, from, convert, to
+    )
+}
+
+fn scalar_size(scalar: &str) -> usize {
+    match scalar {
+        "u8" | "i8" | "bool" => 1,
+        "u16" | "i16" => 2,
+        "u32" | "i32" | "f32" => 4,
+        "u64" | "i64" | "f64" => 8,
+        _ => unreachable!("not a scalar: {}", scalar),
+    }
+}
+
+fn words(s: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn snake_case(s: &str) -> String {
+    let name = words(s)
+        .iter()
+        .map(|w| w.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    match name.chars().next() {
+        None => "_".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        Some(_) => name,
+    }
+}
+
+fn camel_case(s: &str) -> String {
+    let name: String = words(s)
+        .iter()
+        .map(|w| {
+            let mut chars = w.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect();
+    match name.chars().next() {
+        None => "Empty".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("V{}", name),
+        Some(_) => name,
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "try", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+/// Escape a snake case name that is a Rust keyword.
+fn ident(name: &str) -> String {
+    match name {
+        "self" | "super" | "crate" | "_" => format!("{}_", name),
+        name if KEYWORDS.contains(&name) => format!("r#{}", name),
+        name => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{incoming as inc, outgoing as out};
+    use std::path::Path;
+    use walrus::ValType::{F32, F64, I32, I64};
+    use WebidlScalarType::*;
+
+    fn import_binding(
+        m: &mut walrus::Module,
+        wb: &mut WebidlBindings,
+        module: &str,
+        name: &str,
+        (params, results): (&[walrus::ValType], &[walrus::ValType]),
+        binding: impl FnOnce(walrus::TypeId) -> ImportBinding,
+    ) {
+        let ty = m.types.add(params, results);
+        let (func, _) = m.add_import_func(module, name, ty);
+        let binding = wb.bindings.insert(binding(ty));
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+    }
+
+    /// Compare `actual` with the snapshot in `tests/snapshots/host`, or
+    /// overwrite the snapshot if `BLESS` is set.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots/host")
+            .join(name);
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            expected == actual,
+            "{} is out of date; rerun with BLESS=1 to update it. Generated:\n{}",
+            path.display(),
+            actual
+        );
+    }
+
+    #[test]
+    fn text_encoder() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+
+        let result = wb.types.insert(WebidlDictionary {
+            fields: vec![
+                WebidlDictionaryField {
+                    name: "read".into(),
+                    ty: UnsignedLongLong.into(),
+                },
+                WebidlDictionaryField {
+                    name: "written".into(),
+                    ty: UnsignedLongLong.into(),
+                },
+            ],
+        });
+        wb.types
+            .names
+            .insert("$TextEncoderEncodeIntoResult".into(), result.into());
+        let encode_into = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![UsvString.into(), Uint8Array.into()],
+            result: Some(result.into()),
+        });
+        import_binding(
+            &mut m,
+            &mut wb,
+            "TextEncoder",
+            "encodeInto",
+            (&[I32, I32, I32, I32], &[I64, I64]),
+            |wasm_ty| ImportBinding {
+                wasm_ty,
+                webidl_ty: encode_into.into(),
+                params: OutgoingBindingMap {
+                    bindings: vec![
+                        out::utf8_str(UsvString, 0, 1),
+                        out::view(Uint8Array, 2, 3, BufferLengthUnit::Elements),
+                    ],
+                },
+                result: IncomingBindingMap {
+                    bindings: vec![
+                        inc::as_(I64, inc::field(0, inc::get(0))),
+                        inc::as_(I64, inc::field(1, inc::get(0))),
+                    ],
+                },
+            },
+        );
+
+        assert_snapshot("text_encoder.rs", &generate(&m, &wb).unwrap());
+    }
+
+    #[test]
+    fn dictionaries_and_enumerations() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+
+        let mode = wb.types.insert(WebidlEnumeration {
+            values: vec!["fast".into(), "high-quality".into()],
+        });
+        wb.types.names.insert("$Mode".into(), mode.into());
+        let options = wb.types.insert(WebidlDictionary {
+            fields: vec![
+                WebidlDictionaryField {
+                    name: "label".into(),
+                    ty: DomString.into(),
+                },
+                WebidlDictionaryField {
+                    name: "mode".into(),
+                    ty: mode.into(),
+                },
+                WebidlDictionaryField {
+                    name: "weights".into(),
+                    ty: Float32Array.into(),
+                },
+            ],
+        });
+        wb.types.names.insert("$Options".into(), options.into());
+        let configure = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlTypeRef::from(options).into(), Boolean.into()],
+            result: Some(mode.into()),
+        });
+        import_binding(
+            &mut m,
+            &mut wb,
+            "renderer",
+            "configure",
+            (&[I32, I32, I32, I32, I32, I32], &[I32]),
+            |wasm_ty| ImportBinding {
+                wasm_ty,
+                webidl_ty: configure.into(),
+                params: OutgoingBindingMap {
+                    bindings: vec![
+                        out::dict(
+                            options,
+                            vec![
+                                out::utf16_str(DomString, 0, 1),
+                                out::i32_to_enum(mode, 2),
+                                out::copy(Float32Array, 3, 4, BufferLengthUnit::Elements),
+                            ],
+                        ),
+                        out::as_(Boolean, 5),
+                    ],
+                },
+                result: IncomingBindingMap {
+                    bindings: vec![inc::enum_to_i32(mode, inc::get(0))],
+                },
+            },
+        );
+
+        let name = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: Some(DomString.into()),
+        });
+        import_binding(
+            &mut m,
+            &mut wb,
+            "renderer",
+            "name",
+            (&[I32, I32], &[I32]),
+            |wasm_ty| ImportBinding {
+                wasm_ty,
+                webidl_ty: name.into(),
+                params: OutgoingBindingMap { bindings: vec![] },
+                result: IncomingBindingMap {
+                    bindings: vec![inc::write_utf8_str(0, 1, inc::get(0))],
+                },
+            },
+        );
+
+        assert_snapshot(
+            "dictionaries_and_enumerations.rs",
+            &generate(&m, &wb).unwrap(),
+        );
+    }
+
+    #[test]
+    fn booleans() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        let set_flags = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![Boolean.into(); 4],
+            result: None,
+        });
+        import_binding(
+            &mut m,
+            &mut wb,
+            "env",
+            "setFlags",
+            (&[I32, I64, F32, F64], &[]),
+            |wasm_ty| ImportBinding {
+                wasm_ty,
+                webidl_ty: set_flags.into(),
+                params: OutgoingBindingMap {
+                    bindings: (0..4).map(|i| out::as_(Boolean, i)).collect(),
+                },
+                result: IncomingBindingMap { bindings: vec![] },
+            },
+        );
+
+        assert_snapshot("booleans.rs", &generate(&m, &wb).unwrap());
+    }
+
+    #[test]
+    fn invalid_bindings() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        let f = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![Long.into()],
+            result: None,
+        });
+        import_binding(&mut m, &mut wb, "env", "f", (&[I32], &[]), |wasm_ty| {
+            ImportBinding {
+                wasm_ty,
+                webidl_ty: f.into(),
+                params: OutgoingBindingMap { bindings: vec![] },
+                result: IncomingBindingMap { bindings: vec![] },
+            }
+        });
+        let err = generate(&m, &wb).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "cannot generate host code for import `f` from `env`: invalid import binding \
+             params: expected 1 outgoing binding expressions, found 0"
+        );
+    }
+
+    #[test]
+    fn unsupported_bindings() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        let f = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: Some(DomString.into()),
+        });
+        import_binding(&mut m, &mut wb, "env", "f", (&[], &[I32, I32]), |wasm_ty| {
+            ImportBinding {
+                wasm_ty,
+                webidl_ty: f.into(),
+                params: OutgoingBindingMap { bindings: vec![] },
+                result: IncomingBindingMap {
+                    bindings: vec![inc::alloc_utf8_str("malloc", inc::get(0))],
+                },
+            }
+        });
+        let err = generate(&m, &wb).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "cannot generate host code for import `f` from `env`: allocating in linear \
+             memory is not supported in host code"
+        );
+    }
+
+    #[test]
+    fn dangling_references() {
+        let mut elsewhere = WebidlBindings::default();
+        let foreign = elsewhere.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: None,
+        });
+        let binding = |wasm_ty| ImportBinding {
+            wasm_ty,
+            webidl_ty: foreign.into(),
+            params: OutgoingBindingMap { bindings: vec![] },
+            result: IncomingBindingMap { bindings: vec![] },
+        };
+
+        // A binding whose Web IDL type isn't in the section.
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        import_binding(&mut m, &mut wb, "env", "f", (&[], &[]), binding);
+        assert!(generate(&m, &wb).is_err());
+
+        // A bind whose binding isn't in the section.
+        let mut m = walrus::Module::default();
+        import_binding(&mut m, &mut elsewhere, "env", "f", (&[], &[]), binding);
+        let (_, bind) = elsewhere.binds.iter().next().unwrap();
+        let mut wb = WebidlBindings::default();
+        wb.binds.insert(bind.clone());
+        let err = generate(&m, &wb).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing function binding 0"));
+    }
+
+    /// The snapshots must be valid Rust, so compile them too.
+    #[allow(dead_code, clippy::module_inception, clippy::too_many_arguments)]
+    mod snapshots {
+        mod booleans {
+            include!("../tests/snapshots/host/booleans.rs");
+        }
+
+        mod dictionaries_and_enumerations {
+            include!("../tests/snapshots/host/dictionaries_and_enumerations.rs");
+        }
+
+        mod text_encoder {
+            include!("../tests/snapshots/host/text_encoder.rs");
+        }
+    }
+
+    #[test]
+    fn case_conversion() {
+        assert_eq!(snake_case("encodeInto"), "encode_into");
+        assert_eq!(snake_case("TextEncoder"), "text_encoder");
+        assert_eq!(
+            snake_case("wasi_snapshot_preview1"),
+            "wasi_snapshot_preview1"
+        );
+        assert_eq!(camel_case("high-quality"), "HighQuality");
+        assert_eq!(camel_case("2d"), "V2d");
+        assert_eq!(camel_case(""), "Empty");
+        assert_eq!(ident("type"), "r#type");
+        assert_eq!(ident("self"), "self_");
+    }
+}
@@ -0,0 +1,1638 @@
+//! Serializing Web IDL bindings sections with `serde`, for example to JSON.
+//!
+//! This module is only available with the `serde` feature enabled.
+//!
+//! The AST refers to Web IDL types and function bindings with arena ids, and
+//! to Wasm functions and types with its `WasmRefs`, such as `walrus` ids, none
+//! of which need to mean anything outside of this process. The types in this module mirror the AST, but
+//! replace those ids with indices, just like the binary format does:
+//!
+//! * Web IDL types and function bindings are referenced by their index in the
+//!   section's `types` and `bindings` lists. Like in the binary format, they
+//!   may only reference types and bindings that come before them.
+//!
+//! * Wasm functions and types are referenced by their index in the Wasm
+//!   module, as given by the `ToIndices` and `FromIndices` that the section
+//!   is converted with, such as `walrus::IdsToIndices` and
+//!   `walrus::IndicesToIds`.
+//!
+//! ## Schema
+//!
+//! This is the stable JSON schema, described by example. Optional fields are
+//! omitted when they are empty.
+//!
+//! ```json
+//! {
+//!   "types": [
+//!     { "name": "MyDict", "kind": "dict", "fields": [{ "name": "x", "ty": "long" }] },
+//!     { "kind": "enum", "values": ["a", "b"] },
+//!     { "kind": "union", "members": [0, "DOMString"] },
+//!     {
+//!       "name": "MyFunc",
+//!       "kind": "func",
+//!       "function_kind": "static",
+//!       "params": [
+//!         { "ty": { "scalar": "long", "attributes": ["Clamp"] } },
+//!         { "ty": 0, "optional": { "default": { "integer": 1 } } },
+//!         { "ty": "any", "variadic": true }
+//!       ],
+//!       "result": 1
+//!     }
+//!   ],
+//!   "bindings": [
+//!     {
+//!       "name": "MyImport",
+//!       "kind": "import",
+//!       "wasm_ty": 0,
+//!       "webidl_ty": 3,
+//!       "params": [{ "op": "as", "ty": "long", "idx": 0 }],
+//!       "result": [{ "op": "as", "ty": "i32", "expr": { "op": "get", "idx": 0 } }]
+//!     }
+//!   ],
+//!   "binds": [{ "func": 0, "binding": 0 }]
+//! }
+//! ```
+//!
+//! Type references are a number for the index of a compound type, a string
+//! for a scalar type, using the same names as the text format, or an object
+//! for a scalar type annotated with extended attributes.
+//!
+//! A compound type's `kind` is one of `func`, `dict`, `enum` or `union`. A
+//! function's `function_kind` is `"static"`, `{ "method": <type ref> }`, or
+//! `{ "constructor": <type ref or null> }`, where `null` is the default new
+//! target. Optional parameters' defaults are `"null"`, `{ "boolean": <bool>
+//! }`, `{ "integer": <number> }` or `{ "string": <string> }`.
+//!
+//! A binding's `kind` is `import` or `export`. Each binding expression's `op`
+//! is the name of the expression in the text format, and its other fields
+//! are named like the fields of the corresponding AST node. Wasm value types
+//! are strings like `"i32"`, and `view` and `copy` have a `unit` of
+//! `"elements"` or `"bytes"`.
+
+use crate::ast;
+use crate::wasm::{self, FromIndices, ToIndices, WasmRefs};
+use anyhow::{anyhow, bail, Result};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serialize a Web IDL bindings section with the given serializer.
+///
+/// The section's Wasm references are serialized as the indices that `indices`
+/// gives for them, such as a `walrus::IdsToIndices` for a section that uses
+/// `walrus` IDs.
+pub fn serialize<R, I, S>(
+    section: &ast::WebidlBindings<R>,
+    indices: &I,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    R: WasmRefs,
+    I: ToIndices<R>,
+    S: Serializer,
+{
+    Section::new(section, indices)
+        .map_err(|e| ser::Error::custom(format!("{:#}", e)))?
+        .serialize(serializer)
+}
+
+/// Deserialize a Web IDL bindings section with the given deserializer.
+///
+/// The section's Wasm references are resolved from their indices with `ids`,
+/// such as a `walrus::IndicesToIds` for a section that uses `walrus` IDs.
+pub fn deserialize<'de, R, I, D>(
+    ids: &I,
+    deserializer: D,
+) -> std::result::Result<ast::WebidlBindings<R>, D::Error>
+where
+    R: WasmRefs,
+    I: FromIndices<R>,
+    D: Deserializer<'de>,
+{
+    Section::deserialize(deserializer)?
+        .to_webidl_bindings(ids)
+        .map_err(|e| de::Error::custom(format!("{:#}", e)))
+}
+
+/// A Web IDL bindings section.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Section {
+    #[serde(default)]
+    pub types: Vec<Type>,
+    #[serde(default)]
+    pub bindings: Vec<Binding>,
+    #[serde(default)]
+    pub binds: Vec<Bind>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Type {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub ty: CompoundType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum CompoundType {
+    Func {
+        function_kind: FunctionKind,
+        #[serde(default)]
+        params: Vec<Param>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        result: Option<TypeRef>,
+    },
+    Dict {
+        fields: Vec<DictField>,
+    },
+    Enum {
+        values: Vec<String>,
+    },
+    Union {
+        members: Vec<TypeRef>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FunctionKind {
+    Static,
+    Method(TypeRef),
+    Constructor(Option<TypeRef>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Param {
+    pub ty: TypeRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optional: Option<Optional>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub variadic: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Optional {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<DefaultValue>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DefaultValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DictField {
+    pub name: String,
+    pub ty: TypeRef,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TypeRef {
+    /// The index of a compound type.
+    Index(u32),
+    /// A scalar type, named like in the text format.
+    Scalar(String),
+    /// A scalar type annotated with extended attributes.
+    Annotated {
+        scalar: String,
+        attributes: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Binding {
+    Import {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        wasm_ty: u32,
+        webidl_ty: TypeRef,
+        #[serde(default)]
+        params: Vec<OutgoingExpression>,
+        #[serde(default)]
+        result: Vec<IncomingExpression>,
+    },
+    Export {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        wasm_ty: u32,
+        webidl_ty: TypeRef,
+        #[serde(default)]
+        params: Vec<IncomingExpression>,
+        #[serde(default)]
+        result: Vec<OutgoingExpression>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bind {
+    pub func: u32,
+    pub binding: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    Anyref,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BufferLengthUnit {
+    Elements,
+    Bytes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum OutgoingExpression {
+    As {
+        ty: TypeRef,
+        idx: u32,
+    },
+    Utf8Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Utf8Cstr {
+        ty: TypeRef,
+        offset: u32,
+    },
+    I32ToEnum {
+        ty: TypeRef,
+        idx: u32,
+    },
+    View {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    },
+    Copy {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+        unit: BufferLengthUnit,
+    },
+    Dict {
+        ty: TypeRef,
+        fields: Vec<OutgoingExpression>,
+    },
+    BindExport {
+        ty: TypeRef,
+        binding: u32,
+        idx: u32,
+    },
+    Undefined,
+    Variadic {
+        ty: TypeRef,
+        elem_ty: ValType,
+        offset: u32,
+        length: u32,
+    },
+    Utf16Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Latin1Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum IncomingExpression {
+    Get {
+        idx: u32,
+    },
+    As {
+        ty: ValType,
+        expr: Box<IncomingExpression>,
+    },
+    AllocUtf8Str {
+        alloc_func_name: String,
+        expr: Box<IncomingExpression>,
+    },
+    AllocCopy {
+        alloc_func_name: String,
+        expr: Box<IncomingExpression>,
+    },
+    EnumToI32 {
+        ty: TypeRef,
+        expr: Box<IncomingExpression>,
+    },
+    Field {
+        idx: u32,
+        expr: Box<IncomingExpression>,
+    },
+    BindImport {
+        ty: u32,
+        binding: u32,
+        expr: Box<IncomingExpression>,
+    },
+    AllocUtf16Str {
+        alloc_func_name: String,
+        expr: Box<IncomingExpression>,
+    },
+    AllocLatin1Str {
+        alloc_func_name: String,
+        expr: Box<IncomingExpression>,
+    },
+    WriteUtf8Str {
+        offset: u32,
+        capacity: u32,
+        expr: Box<IncomingExpression>,
+    },
+    WriteUtf16Str {
+        offset: u32,
+        capacity: u32,
+        expr: Box<IncomingExpression>,
+    },
+    WriteCopy {
+        offset: u32,
+        capacity: u32,
+        expr: Box<IncomingExpression>,
+    },
+}
+
+const SCALAR_NAMES: &[(ast::WebidlScalarType, &str)] = {
+    use ast::WebidlScalarType::*;
+    &[
+        (Any, "any"),
+        (Boolean, "boolean"),
+        (Byte, "byte"),
+        (Octet, "octet"),
+        (Long, "long"),
+        (UnsignedLong, "unsigned long"),
+        (Short, "short"),
+        (UnsignedShort, "unsigned short"),
+        (LongLong, "long long"),
+        (UnsignedLongLong, "unsigned long long"),
+        (BigInt, "bigint"),
+        (Float, "float"),
+        (UnrestrictedFloat, "unrestricted float"),
+        (Double, "double"),
+        (UnrestrictedDouble, "unrestricted double"),
+        (DomString, "DOMString"),
+        (ByteString, "ByteString"),
+        (UsvString, "USVString"),
+        (Object, "object"),
+        (Symbol, "symbol"),
+        (ArrayBuffer, "ArrayBuffer"),
+        (DataView, "DataView"),
+        (Int8Array, "Int8Array"),
+        (Int16Array, "Int16Array"),
+        (Int32Array, "Int32Array"),
+        (Uint8Array, "Uint8Array"),
+        (Uint16Array, "Uint16Array"),
+        (Uint32Array, "Uint32Array"),
+        (Uint8ClampedArray, "Uint8ClampedArray"),
+        (Float32Array, "Float32Array"),
+        (Float64Array, "Float64Array"),
+    ]
+};
+
+const EXTENDED_ATTRIBUTE_NAMES: &[(ast::WebidlExtendedAttribute, &str)] = {
+    use ast::WebidlExtendedAttribute::*;
+    &[
+        (Clamp, "Clamp"),
+        (EnforceRange, "EnforceRange"),
+        (AllowShared, "AllowShared"),
+        (LegacyNullToEmptyString, "LegacyNullToEmptyString"),
+    ]
+};
+
+fn scalar_name(ty: ast::WebidlScalarType) -> String {
+    SCALAR_NAMES
+        .iter()
+        .find(|(s, _)| *s == ty)
+        .map(|(_, name)| name.to_string())
+        .unwrap()
+}
+
+fn scalar_from_name(name: &str) -> Result<ast::WebidlScalarType> {
+    SCALAR_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(s, _)| *s)
+        .ok_or_else(|| anyhow!("unknown Web IDL scalar type `{}`", name))
+}
+
+impl Section {
+    /// Convert a Web IDL bindings section into its serializable form.
+    ///
+    /// Fails if the section refers to Web IDL types or function bindings that
+    /// aren't in it.
+    pub fn new<R, I>(section: &ast::WebidlBindings<R>, indices: &I) -> Result<Section>
+    where
+        R: WasmRefs,
+        I: ToIndices<R>,
+    {
+        ToJson::new(section, indices).section()
+    }
+
+    /// Convert this back into a Web IDL bindings section.
+    pub fn to_webidl_bindings<R, I>(&self, ids: &I) -> Result<ast::WebidlBindings<R>>
+    where
+        R: WasmRefs,
+        I: FromIndices<R>,
+    {
+        FromJson::new(ids).section(self)
+    }
+}
+
+struct ToJson<'a, R: WasmRefs> {
+    section: &'a ast::WebidlBindings<R>,
+    indices: &'a dyn ToIndices<R>,
+    type_indices: std::collections::HashMap<id_arena::Id<ast::WebidlCompoundType>, u32>,
+    binding_indices: std::collections::HashMap<id_arena::Id<ast::FunctionBinding<R>>, u32>,
+}
+
+impl<'a, R: WasmRefs> ToJson<'a, R> {
+    fn new(section: &'a ast::WebidlBindings<R>, indices: &'a dyn ToIndices<R>) -> Self {
+        ToJson {
+            section,
+            indices,
+            type_indices: section
+                .types
+                .arena
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| (id, i as u32))
+                .collect(),
+            binding_indices: section
+                .bindings
+                .arena
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| (id, i as u32))
+                .collect(),
+        }
+    }
+
+    /// Get the index of the Web IDL type with the given id, which may be
+    /// dangling.
+    fn type_index(&self, id: id_arena::Id<ast::WebidlCompoundType>) -> Result<u32> {
+        match self.type_indices.get(&id) {
+            Some(i) => Ok(*i),
+            None => bail!("reference to missing Web IDL type {}", id.index()),
+        }
+    }
+
+    /// Get the index of the function binding with the given id, which may be
+    /// dangling.
+    fn binding_index(&self, id: id_arena::Id<ast::FunctionBinding<R>>) -> Result<u32> {
+        match self.binding_indices.get(&id) {
+            Some(i) => Ok(*i),
+            None => bail!("reference to missing function binding {}", id.index()),
+        }
+    }
+
+    fn section(&self) -> Result<Section> {
+        let type_names: std::collections::HashMap<_, _> = self
+            .section
+            .types
+            .names
+            .iter()
+            .map(|(name, id)| (*id, name.clone()))
+            .collect();
+        let binding_names: std::collections::HashMap<_, _> = self
+            .section
+            .bindings
+            .names
+            .iter()
+            .map(|(name, id)| (*id, name.clone()))
+            .collect();
+        Ok(Section {
+            types: self
+                .section
+                .types
+                .arena
+                .iter()
+                .enumerate()
+                .map(|(i, (id, ty))| {
+                    Ok(Type {
+                        name: type_names.get(&id).cloned(),
+                        ty: self
+                            .compound_type(ty)
+                            .map_err(|e| e.context(format!("invalid Web IDL type {}", i)))?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            bindings: self
+                .section
+                .bindings
+                .arena
+                .iter()
+                .enumerate()
+                .map(|(i, (id, b))| {
+                    self.binding(binding_names.get(&id).cloned(), b)
+                        .map_err(|e| e.context(format!("invalid function binding {}", i)))
+                })
+                .collect::<Result<_>>()?,
+            binds: self
+                .section
+                .binds
+                .iter()
+                .map(|(_, b)| {
+                    Ok(Bind {
+                        func: self.indices.func_index(b.func),
+                        binding: self.binding_index(b.binding)?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn compound_type(&self, ty: &ast::WebidlCompoundType) -> Result<CompoundType> {
+        Ok(match ty {
+            ast::WebidlCompoundType::Function(f) => CompoundType::Func {
+                function_kind: match &f.kind {
+                    ast::WebidlFunctionKind::Static => FunctionKind::Static,
+                    ast::WebidlFunctionKind::Method(m) => FunctionKind::Method(self.ty(m.ty)?),
+                    ast::WebidlFunctionKind::Constructor(c) => {
+                        FunctionKind::Constructor(c.new_target.map(|t| self.ty(t)).transpose()?)
+                    }
+                },
+                params: f
+                    .params
+                    .iter()
+                    .map(|p| {
+                        Ok(Param {
+                            ty: self.ty(p.ty)?,
+                            optional: match &p.kind {
+                                ast::WebidlFunctionParamKind::Optional(o) => Some(Optional {
+                                    default: o.default.as_ref().map(|d| match d {
+                                        ast::WebidlDefaultValue::Null => DefaultValue::Null,
+                                        ast::WebidlDefaultValue::Boolean(b) => {
+                                            DefaultValue::Boolean(*b)
+                                        }
+                                        ast::WebidlDefaultValue::Integer(i) => {
+                                            DefaultValue::Integer(*i)
+                                        }
+                                        ast::WebidlDefaultValue::String(s) => {
+                                            DefaultValue::String(s.clone())
+                                        }
+                                    }),
+                                }),
+                                _ => None,
+                            },
+                            variadic: p.kind == ast::WebidlFunctionParamKind::Variadic,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+                result: f.result.map(|r| self.ty(r)).transpose()?,
+            },
+            ast::WebidlCompoundType::Dictionary(d) => CompoundType::Dict {
+                fields: d
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        Ok(DictField {
+                            name: f.name.clone(),
+                            ty: self.ty(f.ty)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            ast::WebidlCompoundType::Enumeration(e) => CompoundType::Enum {
+                values: e.values.clone(),
+            },
+            ast::WebidlCompoundType::Union(u) => CompoundType::Union {
+                members: u
+                    .members
+                    .iter()
+                    .map(|m| self.ty(*m))
+                    .collect::<Result<_>>()?,
+            },
+        })
+    }
+
+    fn ty(&self, ty: ast::WebidlTypeRef) -> Result<TypeRef> {
+        Ok(match ty {
+            ast::WebidlTypeRef::Id(id) => TypeRef::Index(self.type_index(id)?),
+            ast::WebidlTypeRef::Scalar(s) => TypeRef::Scalar(scalar_name(s)),
+            ast::WebidlTypeRef::Annotated(a) => TypeRef::Annotated {
+                scalar: scalar_name(a.ty()),
+                attributes: a
+                    .attributes()
+                    .iter()
+                    .map(|attr| {
+                        EXTENDED_ATTRIBUTE_NAMES
+                            .iter()
+                            .find(|(a, _)| *a == attr)
+                            .unwrap()
+                            .1
+                            .to_string()
+                    })
+                    .collect(),
+            },
+        })
+    }
+
+    fn binding(&self, name: Option<String>, b: &ast::FunctionBinding<R>) -> Result<Binding> {
+        Ok(match b {
+            ast::FunctionBinding::Import(i) => Binding::Import {
+                name,
+                wasm_ty: self.indices.func_type_index(i.wasm_ty),
+                webidl_ty: self.ty(i.webidl_ty)?,
+                params: i
+                    .params
+                    .bindings
+                    .iter()
+                    .map(|e| self.outgoing(e))
+                    .collect::<Result<_>>()?,
+                result: i
+                    .result
+                    .bindings
+                    .iter()
+                    .map(|e| self.incoming(e))
+                    .collect::<Result<_>>()?,
+            },
+            ast::FunctionBinding::Export(e) => Binding::Export {
+                name,
+                wasm_ty: self.indices.func_type_index(e.wasm_ty),
+                webidl_ty: self.ty(e.webidl_ty)?,
+                params: e
+                    .params
+                    .bindings
+                    .iter()
+                    .map(|e| self.incoming(e))
+                    .collect::<Result<_>>()?,
+                result: e
+                    .result
+                    .bindings
+                    .iter()
+                    .map(|e| self.outgoing(e))
+                    .collect::<Result<_>>()?,
+            },
+        })
+    }
+
+    fn outgoing(&self, e: &ast::OutgoingBindingExpression<R>) -> Result<OutgoingExpression> {
+        use ast::OutgoingBindingExpression as O;
+        Ok(match e {
+            O::As(e) => OutgoingExpression::As {
+                ty: self.ty(e.ty)?,
+                idx: e.idx,
+            },
+            O::Utf8Str(e) => OutgoingExpression::Utf8Str {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+                length: e.length,
+            },
+            O::Utf8CStr(e) => OutgoingExpression::Utf8Cstr {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+            },
+            O::I32ToEnum(e) => OutgoingExpression::I32ToEnum {
+                ty: self.ty(e.ty)?,
+                idx: e.idx,
+            },
+            O::View(e) => OutgoingExpression::View {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+                length: e.length,
+                unit: unit_to_json(e.unit),
+            },
+            O::Copy(e) => OutgoingExpression::Copy {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+                length: e.length,
+                unit: unit_to_json(e.unit),
+            },
+            O::Dict(e) => OutgoingExpression::Dict {
+                ty: self.ty(e.ty)?,
+                fields: e
+                    .fields
+                    .iter()
+                    .map(|f| self.outgoing(f))
+                    .collect::<Result<_>>()?,
+            },
+            O::BindExport(e) => OutgoingExpression::BindExport {
+                ty: self.ty(e.ty)?,
+                binding: self.binding_index(e.binding)?,
+                idx: e.idx,
+            },
+            O::Undefined(_) => OutgoingExpression::Undefined,
+            O::Variadic(e) => OutgoingExpression::Variadic {
+                ty: self.ty(e.ty)?,
+                elem_ty: val_type_to_json(e.elem_ty.into()),
+                offset: e.offset,
+                length: e.length,
+            },
+            O::Utf16Str(e) => OutgoingExpression::Utf16Str {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+                length: e.length,
+            },
+            O::Latin1Str(e) => OutgoingExpression::Latin1Str {
+                ty: self.ty(e.ty)?,
+                offset: e.offset,
+                length: e.length,
+            },
+        })
+    }
+
+    fn incoming(&self, e: &ast::IncomingBindingExpression<R>) -> Result<IncomingExpression> {
+        use ast::IncomingBindingExpression as I;
+        let expr = |e: &ast::IncomingBindingExpression<R>| self.incoming(e).map(Box::new);
+        Ok(match e {
+            I::Get(e) => IncomingExpression::Get { idx: e.idx },
+            I::As(e) => IncomingExpression::As {
+                ty: val_type_to_json(e.ty.into()),
+                expr: expr(&e.expr)?,
+            },
+            I::AllocUtf8Str(e) => IncomingExpression::AllocUtf8Str {
+                alloc_func_name: e.alloc_func_name.clone(),
+                expr: expr(&e.expr)?,
+            },
+            I::AllocCopy(e) => IncomingExpression::AllocCopy {
+                alloc_func_name: e.alloc_func_name.clone(),
+                expr: expr(&e.expr)?,
+            },
+            I::EnumToI32(e) => IncomingExpression::EnumToI32 {
+                ty: self.ty(e.ty)?,
+                expr: expr(&e.expr)?,
+            },
+            I::Field(e) => IncomingExpression::Field {
+                idx: e.idx,
+                expr: expr(&e.expr)?,
+            },
+            I::BindImport(e) => IncomingExpression::BindImport {
+                ty: self.indices.func_type_index(e.ty),
+                binding: self.binding_index(e.binding)?,
+                expr: expr(&e.expr)?,
+            },
+            I::AllocUtf16Str(e) => IncomingExpression::AllocUtf16Str {
+                alloc_func_name: e.alloc_func_name.clone(),
+                expr: expr(&e.expr)?,
+            },
+            I::AllocLatin1Str(e) => IncomingExpression::AllocLatin1Str {
+                alloc_func_name: e.alloc_func_name.clone(),
+                expr: expr(&e.expr)?,
+            },
+            I::WriteUtf8Str(e) => IncomingExpression::WriteUtf8Str {
+                offset: e.offset,
+                capacity: e.capacity,
+                expr: expr(&e.expr)?,
+            },
+            I::WriteUtf16Str(e) => IncomingExpression::WriteUtf16Str {
+                offset: e.offset,
+                capacity: e.capacity,
+                expr: expr(&e.expr)?,
+            },
+            I::WriteCopy(e) => IncomingExpression::WriteCopy {
+                offset: e.offset,
+                capacity: e.capacity,
+                expr: expr(&e.expr)?,
+            },
+        })
+    }
+}
+
+fn unit_to_json(unit: ast::BufferLengthUnit) -> BufferLengthUnit {
+    match unit {
+        ast::BufferLengthUnit::Elements => BufferLengthUnit::Elements,
+        ast::BufferLengthUnit::Bytes => BufferLengthUnit::Bytes,
+    }
+}
+
+fn unit_from_json(unit: BufferLengthUnit) -> ast::BufferLengthUnit {
+    match unit {
+        BufferLengthUnit::Elements => ast::BufferLengthUnit::Elements,
+        BufferLengthUnit::Bytes => ast::BufferLengthUnit::Bytes,
+    }
+}
+
+fn val_type_to_json(ty: wasm::ValType) -> ValType {
+    match ty {
+        wasm::ValType::I32 => ValType::I32,
+        wasm::ValType::I64 => ValType::I64,
+        wasm::ValType::F32 => ValType::F32,
+        wasm::ValType::F64 => ValType::F64,
+        wasm::ValType::V128 => ValType::V128,
+        wasm::ValType::Anyref => ValType::Anyref,
+    }
+}
+
+fn val_type_from_json(ty: ValType) -> wasm::ValType {
+    match ty {
+        ValType::I32 => wasm::ValType::I32,
+        ValType::I64 => wasm::ValType::I64,
+        ValType::F32 => wasm::ValType::F32,
+        ValType::F64 => wasm::ValType::F64,
+        ValType::V128 => wasm::ValType::V128,
+        ValType::Anyref => wasm::ValType::Anyref,
+    }
+}
+
+struct FromJson<'a, R: WasmRefs> {
+    ids: &'a dyn FromIndices<R>,
+    section: ast::WebidlBindings<R>,
+}
+
+impl<'a, R: WasmRefs> FromJson<'a, R> {
+    fn new(ids: &'a dyn FromIndices<R>) -> Self {
+        FromJson {
+            ids,
+            section: ast::WebidlBindings::default(),
+        }
+    }
+
+    fn section(mut self, json: &Section) -> Result<ast::WebidlBindings<R>> {
+        for (i, ty) in json.types.iter().enumerate() {
+            let compound = self
+                .compound_type(&ty.ty)
+                .map_err(|e| e.context(format!("invalid Web IDL type {}", i)))?;
+            let id = self.section.types.insert(compound);
+            if let Some(name) = &ty.name {
+                self.section.types.names.insert(name.clone(), id);
+            }
+        }
+        for (i, b) in json.bindings.iter().enumerate() {
+            let (name, id) = self
+                .binding(b)
+                .map_err(|e| e.context(format!("invalid function binding {}", i)))?;
+            if let Some(name) = name {
+                self.section.bindings.names.insert(name, id);
+            }
+        }
+        for b in &json.binds {
+            let bind = ast::Bind {
+                func: self.ids.func(b.func)?,
+                binding: self.binding_id(b.binding)?,
+            };
+            self.section.binds.insert(bind);
+        }
+        Ok(self.section)
+    }
+
+    fn binding_id(&self, index: u32) -> Result<id_arena::Id<ast::FunctionBinding<R>>> {
+        self.section
+            .bindings
+            .by_index(index)
+            .ok_or_else(|| anyhow!("no function binding for index {}", index))
+    }
+
+    fn compound_type(&self, ty: &CompoundType) -> Result<ast::WebidlCompoundType> {
+        Ok(match ty {
+            CompoundType::Func {
+                function_kind,
+                params,
+                result,
+            } => {
+                let params = params
+                    .iter()
+                    .map(|p| {
+                        let kind = match (&p.optional, p.variadic) {
+                            (Some(_), true) => bail!("a param cannot be optional and variadic"),
+                            (Some(o), false) => ast::WebidlFunctionParamOptional {
+                                default: o.default.as_ref().map(|d| match d {
+                                    DefaultValue::Null => ast::WebidlDefaultValue::Null,
+                                    DefaultValue::Boolean(b) => {
+                                        ast::WebidlDefaultValue::Boolean(*b)
+                                    }
+                                    DefaultValue::Integer(i) => {
+                                        ast::WebidlDefaultValue::Integer(*i)
+                                    }
+                                    DefaultValue::String(s) => {
+                                        ast::WebidlDefaultValue::String(s.clone())
+                                    }
+                                }),
+                            }
+                            .into(),
+                            (None, true) => ast::WebidlFunctionParamKind::Variadic,
+                            (None, false) => ast::WebidlFunctionParamKind::Required,
+                        };
+                        Ok(ast::WebidlFunctionParam {
+                            ty: self.ty(&p.ty)?,
+                            kind,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                crate::binary::validate_param_order(&params)?;
+                ast::WebidlFunction {
+                    kind: match function_kind {
+                        FunctionKind::Static => ast::WebidlFunctionKind::Static,
+                        FunctionKind::Method(ty) => {
+                            ast::WebidlFunctionKindMethod { ty: self.ty(ty)? }.into()
+                        }
+                        FunctionKind::Constructor(new_target) => {
+                            ast::WebidlFunctionKindConstructor {
+                                new_target: new_target.as_ref().map(|t| self.ty(t)).transpose()?,
+                            }
+                            .into()
+                        }
+                    },
+                    params,
+                    result: result.as_ref().map(|r| self.ty(r)).transpose()?,
+                }
+                .into()
+            }
+            CompoundType::Dict { fields } => ast::WebidlDictionary {
+                fields: fields
+                    .iter()
+                    .map(|f| {
+                        Ok(ast::WebidlDictionaryField {
+                            name: f.name.clone(),
+                            ty: self.ty(&f.ty)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            }
+            .into(),
+            CompoundType::Enum { values } => ast::WebidlEnumeration {
+                values: values.clone(),
+            }
+            .into(),
+            CompoundType::Union { members } => ast::WebidlUnion {
+                members: members.iter().map(|m| self.ty(m)).collect::<Result<_>>()?,
+            }
+            .into(),
+        })
+    }
+
+    fn ty(&self, ty: &TypeRef) -> Result<ast::WebidlTypeRef> {
+        match ty {
+            TypeRef::Index(i) => self
+                .section
+                .types
+                .by_index(*i)
+                .map(Into::into)
+                .ok_or_else(|| anyhow!("no Web IDL type for index {}", i)),
+            TypeRef::Scalar(name) => scalar_from_name(name).map(Into::into),
+            TypeRef::Annotated { scalar, attributes } => {
+                let ty = scalar_from_name(scalar)?;
+                let attributes = attributes
+                    .iter()
+                    .map(|name| {
+                        EXTENDED_ATTRIBUTE_NAMES
+                            .iter()
+                            .find(|(_, n)| n == name)
+                            .map(|(a, _)| *a)
+                            .ok_or_else(|| anyhow!("unknown extended attribute `{}`", name))
+                    })
+                    .collect::<Result<ast::WebidlExtendedAttributes>>()?;
+                ast::WebidlAnnotatedTypeRef::new(ty, attributes)
+                    .map(Into::into)
+                    .ok_or_else(|| {
+                        anyhow!("invalid extended attributes {:?} for {:?}", attributes, ty)
+                    })
+            }
+        }
+    }
+
+    fn buffer_ty(&self, ty: &TypeRef) -> Result<ast::WebidlTypeRef> {
+        let ty = self.ty(ty)?;
+        if ty.buffer_element_size().is_none() {
+            bail!("expected a buffer source type, found {:?}", ty);
+        }
+        Ok(ty)
+    }
+
+    fn binding(
+        &mut self,
+        b: &Binding,
+    ) -> Result<(Option<String>, id_arena::Id<ast::FunctionBinding<R>>)> {
+        Ok(match b {
+            Binding::Import {
+                name,
+                wasm_ty,
+                webidl_ty,
+                params,
+                result,
+            } => {
+                let binding = ast::ImportBinding {
+                    wasm_ty: self.ids.func_type(*wasm_ty)?,
+                    webidl_ty: self.ty(webidl_ty)?,
+                    params: ast::OutgoingBindingMap {
+                        bindings: params
+                            .iter()
+                            .map(|e| self.outgoing(e))
+                            .collect::<Result<_>>()?,
+                    },
+                    result: ast::IncomingBindingMap {
+                        bindings: result
+                            .iter()
+                            .map(|e| self.incoming(e))
+                            .collect::<Result<_>>()?,
+                    },
+                };
+                (name.clone(), self.section.bindings.insert(binding).into())
+            }
+            Binding::Export {
+                name,
+                wasm_ty,
+                webidl_ty,
+                params,
+                result,
+            } => {
+                let binding = ast::ExportBinding {
+                    wasm_ty: self.ids.func_type(*wasm_ty)?,
+                    webidl_ty: self.ty(webidl_ty)?,
+                    params: ast::IncomingBindingMap {
+                        bindings: params
+                            .iter()
+                            .map(|e| self.incoming(e))
+                            .collect::<Result<_>>()?,
+                    },
+                    result: ast::OutgoingBindingMap {
+                        bindings: result
+                            .iter()
+                            .map(|e| self.outgoing(e))
+                            .collect::<Result<_>>()?,
+                    },
+                };
+                (name.clone(), self.section.bindings.insert(binding).into())
+            }
+        })
+    }
+
+    fn outgoing(&self, e: &OutgoingExpression) -> Result<ast::OutgoingBindingExpression<R>> {
+        use ast::*;
+        Ok(match e {
+            OutgoingExpression::As { ty, idx } => OutgoingBindingExpressionAs {
+                ty: self.ty(ty)?,
+                idx: *idx,
+            }
+            .into(),
+            OutgoingExpression::Utf8Str { ty, offset, length } => {
+                OutgoingBindingExpressionUtf8Str {
+                    ty: self.ty(ty)?,
+                    offset: *offset,
+                    length: *length,
+                }
+                .into()
+            }
+            OutgoingExpression::Utf8Cstr { ty, offset } => OutgoingBindingExpressionUtf8CStr {
+                ty: self.ty(ty)?,
+                offset: *offset,
+            }
+            .into(),
+            OutgoingExpression::I32ToEnum { ty, idx } => OutgoingBindingExpressionI32ToEnum {
+                ty: self.ty(ty)?,
+                idx: *idx,
+            }
+            .into(),
+            OutgoingExpression::View {
+                ty,
+                offset,
+                length,
+                unit,
+            } => OutgoingBindingExpressionView {
+                ty: self.buffer_ty(ty)?,
+                offset: *offset,
+                length: *length,
+                unit: unit_from_json(*unit),
+            }
+            .into(),
+            OutgoingExpression::Copy {
+                ty,
+                offset,
+                length,
+                unit,
+            } => OutgoingBindingExpressionCopy {
+                ty: self.buffer_ty(ty)?,
+                offset: *offset,
+                length: *length,
+                unit: unit_from_json(*unit),
+            }
+            .into(),
+            OutgoingExpression::Dict { ty, fields } => OutgoingBindingExpressionDict {
+                ty: self.ty(ty)?,
+                fields: fields
+                    .iter()
+                    .map(|f| self.outgoing(f))
+                    .collect::<Result<_>>()?,
+            }
+            .into(),
+            OutgoingExpression::BindExport { ty, binding, idx } => {
+                OutgoingBindingExpressionBindExport {
+                    ty: self.ty(ty)?,
+                    binding: self.binding_id(*binding)?,
+                    idx: *idx,
+                }
+                .into()
+            }
+            OutgoingExpression::Undefined => OutgoingBindingExpressionUndefined.into(),
+            OutgoingExpression::Variadic {
+                ty,
+                elem_ty,
+                offset,
+                length,
+            } => OutgoingBindingExpressionVariadic {
+                ty: self.ty(ty)?,
+                elem_ty: val_type_from_json(*elem_ty).into(),
+                offset: *offset,
+                length: *length,
+            }
+            .into(),
+            OutgoingExpression::Utf16Str { ty, offset, length } => {
+                OutgoingBindingExpressionUtf16Str {
+                    ty: self.ty(ty)?,
+                    offset: *offset,
+                    length: *length,
+                }
+                .into()
+            }
+            OutgoingExpression::Latin1Str { ty, offset, length } => {
+                OutgoingBindingExpressionLatin1Str {
+                    ty: self.ty(ty)?,
+                    offset: *offset,
+                    length: *length,
+                }
+                .into()
+            }
+        })
+    }
+
+    fn incoming(&self, e: &IncomingExpression) -> Result<ast::IncomingBindingExpression<R>> {
+        use ast::*;
+        let expr = |e: &IncomingExpression| self.incoming(e).map(Box::new);
+        Ok(match e {
+            IncomingExpression::Get { idx } => IncomingBindingExpressionGet { idx: *idx }.into(),
+            IncomingExpression::As { ty, expr: e } => IncomingBindingExpressionAs {
+                ty: val_type_from_json(*ty).into(),
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::AllocUtf8Str {
+                alloc_func_name,
+                expr: e,
+            } => IncomingBindingExpressionAllocUtf8Str {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::AllocCopy {
+                alloc_func_name,
+                expr: e,
+            } => IncomingBindingExpressionAllocCopy {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::EnumToI32 { ty, expr: e } => IncomingBindingExpressionEnumToI32 {
+                ty: self.ty(ty)?,
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::Field { idx, expr: e } => IncomingBindingExpressionField {
+                idx: *idx,
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::BindImport {
+                ty,
+                binding,
+                expr: e,
+            } => IncomingBindingExpressionBindImport {
+                ty: self.ids.func_type(*ty)?,
+                binding: self.binding_id(*binding)?,
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::AllocUtf16Str {
+                alloc_func_name,
+                expr: e,
+            } => IncomingBindingExpressionAllocUtf16Str {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::AllocLatin1Str {
+                alloc_func_name,
+                expr: e,
+            } => IncomingBindingExpressionAllocLatin1Str {
+                alloc_func_name: alloc_func_name.clone(),
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::WriteUtf8Str {
+                offset,
+                capacity,
+                expr: e,
+            } => IncomingBindingExpressionWriteUtf8Str {
+                offset: *offset,
+                capacity: *capacity,
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::WriteUtf16Str {
+                offset,
+                capacity,
+                expr: e,
+            } => IncomingBindingExpressionWriteUtf16Str {
+                offset: *offset,
+                capacity: *capacity,
+                expr: expr(e)?,
+            }
+            .into(),
+            IncomingExpression::WriteCopy {
+                offset,
+                capacity,
+                expr: e,
+            } => IncomingBindingExpressionWriteCopy {
+                offset: *offset,
+                capacity: *capacity,
+                expr: expr(e)?,
+            }
+            .into(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "walrus"))]
+mod tests {
+    use super::*;
+    use crate::ast::*;
+    use crate::builder::{incoming as inc, outgoing as out};
+    use crate::merge::IdRemap;
+    use crate::wasm::{RawIndices, Walrus};
+    use std::borrow::Cow;
+    use walrus::ValType::I32;
+
+    /// Map `walrus` ids to and from their position in the module's arenas,
+    /// which is their index as long as the module has no imports.
+    struct ArenaIndices<'a>(&'a walrus::Module);
+
+    impl ToIndices<Walrus> for ArenaIndices<'_> {
+        fn func_index(&self, id: walrus::FunctionId) -> u32 {
+            self.0.funcs.iter().position(|f| f.id() == id).unwrap() as u32
+        }
+
+        fn func_type_index(&self, id: walrus::TypeId) -> u32 {
+            self.0.types.iter().position(|t| t.id() == id).unwrap() as u32
+        }
+    }
+
+    impl FromIndices<Walrus> for ArenaIndices<'_> {
+        fn func(&self, index: u32) -> Result<walrus::FunctionId> {
+            self.0
+                .funcs
+                .iter()
+                .nth(index as usize)
+                .map(|f| f.id())
+                .ok_or_else(|| anyhow!("no func {}", index))
+        }
+
+        fn func_type(&self, index: u32) -> Result<walrus::TypeId> {
+            self.0
+                .types
+                .iter()
+                .nth(index as usize)
+                .map(|t| t.id())
+                .ok_or_else(|| anyhow!("no type {}", index))
+        }
+    }
+
+    fn section() -> (walrus::Module, WebidlBindings) {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32, I32], &[I32]);
+        let mut builder = walrus::FunctionBuilder::new(&mut m.types, &[I32, I32], &[I32]);
+        builder.func_body().i32_const(0);
+        let func = builder.finish(vec![], &mut m.funcs);
+        m.exports.add("f", func);
+
+        let mut wb = WebidlBindings::default();
+        let e = wb.types.insert(WebidlEnumeration {
+            values: vec!["a".into(), "b".into()],
+        });
+        let clamped: WebidlTypeRef = WebidlAnnotatedTypeRef::new(
+            WebidlScalarType::Octet,
+            vec![WebidlExtendedAttribute::Clamp].into_iter().collect(),
+        )
+        .unwrap()
+        .into();
+        let f = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![
+                WebidlScalarType::DomString.into(),
+                WebidlFunctionParam {
+                    ty: clamped,
+                    kind: WebidlFunctionParamOptional {
+                        default: Some(WebidlDefaultValue::Integer(1)),
+                    }
+                    .into(),
+                },
+            ],
+            result: Some(e.into()),
+        });
+        wb.types.names.insert("MyFunc".into(), f.into());
+
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: f.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![
+                    out::view(
+                        WebidlScalarType::Uint8Array,
+                        0,
+                        1,
+                        ast::BufferLengthUnit::Bytes,
+                    ),
+                    out::as_(clamped, 1),
+                ],
+            },
+            result: IncomingBindingMap {
+                bindings: vec![inc::enum_to_i32(e, inc::get(0))],
+            },
+        });
+        wb.bindings.names.insert("MyImport".into(), binding.into());
+        wb.binds.insert(ast::Bind {
+            func,
+            binding: binding.into(),
+        });
+        (m, wb)
+    }
+
+    #[test]
+    fn stable_json() {
+        let (m, wb) = section();
+        let json =
+            serde_json::to_value(ToJson::new(&wb, &ArenaIndices(&m)).section().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "types": [
+                    { "kind": "enum", "values": ["a", "b"] },
+                    {
+                        "name": "MyFunc",
+                        "kind": "func",
+                        "function_kind": "static",
+                        "params": [
+                            { "ty": "DOMString" },
+                            {
+                                "ty": { "scalar": "octet", "attributes": ["Clamp"] },
+                                "optional": { "default": { "integer": 1 } }
+                            }
+                        ],
+                        "result": 0
+                    }
+                ],
+                "bindings": [
+                    {
+                        "name": "MyImport",
+                        "kind": "import",
+                        "wasm_ty": 0,
+                        "webidl_ty": 1,
+                        "params": [
+                            {
+                                "op": "view",
+                                "ty": "Uint8Array",
+                                "offset": 0,
+                                "length": 1,
+                                "unit": "bytes"
+                            },
+                            {
+                                "op": "as",
+                                "ty": { "scalar": "octet", "attributes": ["Clamp"] },
+                                "idx": 1
+                            }
+                        ],
+                        "result": [
+                            { "op": "enum-to-i32", "ty": 0, "expr": { "op": "get", "idx": 0 } }
+                        ]
+                    }
+                ],
+                "binds": [{ "func": 0, "binding": 0 }]
+            })
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let (m, wb) = section();
+        let indices = ArenaIndices(&m);
+        let json = serde_json::to_string(&ToJson::new(&wb, &indices).section().unwrap()).unwrap();
+
+        let parsed: Section = serde_json::from_str(&json).unwrap();
+        let wb2 = FromJson::new(&indices).section(&parsed).unwrap();
+        assert_eq!(ToJson::new(&wb2, &indices).section().unwrap(), parsed);
+        assert_eq!(wb2.types.by_name("MyFunc"), wb2.types.by_index(1));
+        assert_eq!(wb2.bindings.by_name("MyImport"), wb2.bindings.by_index(0));
+    }
+
+    /// A custom section with a Web IDL bindings section serialized as JSON.
+    #[derive(Debug)]
+    struct JsonSection(WebidlBindings);
+
+    impl walrus::CustomSection for JsonSection {
+        fn name(&self) -> &str {
+            "webidl-bindings-json"
+        }
+
+        fn data(&self, indices: &walrus::IdsToIndices) -> Cow<'_, [u8]> {
+            let mut json = vec![];
+            serialize(
+                &self.0,
+                indices,
+                &mut serde_json::Serializer::new(&mut json),
+            )
+            .unwrap();
+            json.into()
+        }
+    }
+
+    #[test]
+    fn round_trip_through_a_wasm_module() {
+        let (mut m, wb) = section();
+        m.customs.add(JsonSection(wb.clone()));
+        let buf = m.emit_wasm();
+
+        let mut config = walrus::ModuleConfig::new();
+        config.on_parse(|module, ids| {
+            let raw = module.customs.remove_raw("webidl-bindings-json").unwrap();
+            let wb = deserialize(ids, &mut serde_json::Deserializer::from_slice(&raw.data))?;
+            module.customs.add(wb);
+            Ok(())
+        });
+        let parsed = config.parse(&buf).unwrap();
+        let wb2 = parsed.customs.get_typed::<WebidlBindings>().unwrap();
+
+        let id_remap = IdRemap::rebind(&wb, &m, &parsed).unwrap();
+        assert!(wb.structurally_eq(wb2, &id_remap));
+    }
+
+    #[test]
+    fn round_trip_raw_indices() {
+        let mut wb = WebidlBindings::<RawIndices>::default();
+        let f = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlScalarType::Long.into()],
+            result: None,
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty: 3,
+            webidl_ty: f.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![OutgoingBindingExpressionAs {
+                    ty: WebidlScalarType::Long.into(),
+                    idx: 0,
+                }
+                .into()],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(ast::Bind {
+            func: 7,
+            binding: binding.into(),
+        });
+
+        let json = Section::new(&wb, &RawIndices).unwrap();
+        let value = serde_json::to_value(&json).unwrap();
+        assert_eq!(value["bindings"][0]["wasm_ty"], 3);
+        assert_eq!(value["binds"][0]["func"], 7);
+
+        let wb2: WebidlBindings<RawIndices> = json.to_webidl_bindings(&RawIndices).unwrap();
+        assert_eq!(Section::new(&wb2, &RawIndices).unwrap(), json);
+    }
+
+    #[test]
+    fn invalid_json() {
+        let (m, _) = section();
+        let indices = ArenaIndices(&m);
+        let from_json = |json: serde_json::Value| {
+            let section: Section = serde_json::from_value(json)?;
+            FromJson::new(&indices).section(&section)
+        };
+
+        // Unknown scalar type.
+        assert!(from_json(serde_json::json!({
+            "types": [{ "kind": "union", "members": ["DomString"] }]
+        }))
+        .is_err());
+
+        // Forward type reference.
+        assert!(from_json(serde_json::json!({
+            "types": [{ "kind": "union", "members": [1] }, { "kind": "enum", "values": [] }]
+        }))
+        .is_err());
+
+        // Extended attribute that doesn't apply.
+        assert!(from_json(serde_json::json!({
+            "types": [{
+                "kind": "union",
+                "members": [{ "scalar": "DOMString", "attributes": ["Clamp"] }]
+            }]
+        }))
+        .is_err());
+
+        // Viewing a non-buffer type.
+        assert!(from_json(serde_json::json!({
+            "types": [{ "kind": "func", "function_kind": "static" }],
+            "bindings": [{
+                "kind": "import",
+                "wasm_ty": 0,
+                "webidl_ty": 0,
+                "params": [
+                    { "op": "view", "ty": "long", "offset": 0, "length": 1, "unit": "elements" }
+                ]
+            }]
+        }))
+        .is_err());
+
+        // Out-of-bounds Wasm type.
+        assert!(from_json(serde_json::json!({
+            "types": [{ "kind": "func", "function_kind": "static" }],
+            "bindings": [{ "kind": "export", "wasm_ty": 9, "webidl_ty": 0 }]
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn dangling_references() {
+        let (m, mut elsewhere) = section();
+        let indices = ArenaIndices(&m);
+        let foreign = elsewhere.types.insert(WebidlDictionary { fields: vec![] });
+
+        // A type that refers to a Web IDL type that isn't in the section.
+        let mut wb = WebidlBindings::default();
+        wb.types.insert(WebidlUnion {
+            members: vec![foreign.into()],
+        });
+        let err = ToJson::new(&wb, &indices).section().unwrap_err();
+        assert!(format!("{:#}", err).contains("missing Web IDL type 2"));
+        let mut json = vec![];
+        let err = serialize(
+            &wb,
+            &walrus::IdsToIndices::default(),
+            &mut serde_json::Serializer::new(&mut json),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("missing Web IDL type 2"));
+
+        // A bind whose binding isn't in the section.
+        let (_, bind) = elsewhere.binds.iter().next().unwrap();
+        let mut wb = WebidlBindings::default();
+        wb.binds.insert(bind.clone());
+        let err = ToJson::new(&wb, &indices).section().unwrap_err();
+        assert!(format!("{:#}", err).contains("missing function binding 0"));
+    }
+
+    #[test]
+    fn param_order_is_checked_like_the_binary_format() {
+        use WebidlFunctionParamKind::{Required, Variadic};
+        let optional = || WebidlFunctionParamOptional { default: None }.into();
+        let param = |kind| WebidlFunctionParam {
+            ty: WebidlScalarType::Long.into(),
+            kind,
+        };
+        let decode_both = |params| {
+            let mut wb = WebidlBindings::default();
+            wb.types.insert(WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params,
+                result: None,
+            });
+
+            let mut binary = vec![];
+            crate::binary::encode(&wb, &walrus::IdsToIndices::default(), &mut binary).unwrap();
+            let from_binary: Result<WebidlBindings> =
+                crate::binary::decode(&walrus::IndicesToIds::default(), &binary);
+
+            let json = ToJson::new(&wb, &walrus::IdsToIndices::default())
+                .section()
+                .unwrap();
+            let from_json = FromJson::new(&walrus::IndicesToIds::default()).section(&json);
+            (from_binary, from_json)
+        };
+
+        let (from_binary, from_json) =
+            decode_both(vec![param(Required), param(optional()), param(Variadic)]);
+        from_binary.unwrap();
+        from_json.unwrap();
+
+        for params in [
+            vec![param(optional()), param(Required)],
+            vec![param(Variadic), param(Required)],
+            vec![param(Variadic), param(optional())],
+            vec![param(Variadic), param(Variadic)],
+        ] {
+            let (from_binary, from_json) = decode_both(params);
+            assert_eq!(
+                from_binary.unwrap_err().root_cause().to_string(),
+                from_json.unwrap_err().root_cause().to_string(),
+            );
+        }
+    }
+}
@@ -9,13 +9,25 @@
 ## What's Inside
 
 * A parser for the straw proposal text format. See `crates/text-parser/src/grammar.lalrpop`.
+  The text parser crate also has a lossless concrete syntax tree that keeps
+  comments, and a `webidl-bindings fmt` command that formats the text format.
 
 * A set of AST types for representing and manipulating WebIDL bindings. See
-  `src/ast.rs`.
+  `src/ast.rs`. The AST refers to Wasm functions and types with `walrus` IDs by
+  default, or with raw indices via `wasm::RawIndices`, so sections can be
+  decoded, read and encoded without a `walrus::Module`. See `src/wasm.rs`.
+
+* `walrus` support is behind the `walrus` feature, which is enabled by
+  default. Validation, the builders, the host and TypeScript generators and
+  the text format need it. With `default-features = false`, the AST, the
+  binary encoder and decoder, the section reader and the module helpers only
+  work with `RawIndices`.
 
 * An encoder and decoder for the straw proposal binary format. See the
   implementation at `src/binary/encode.rs` and details on the format at
-  `BINARY.md`.
+  `BINARY.md`. The section can also be found, stripped, or spliced into an
+  encoded Wasm module without parsing the rest of the module. See
+  `src/binary/module.rs`.
 
 ## Example
 
@@ -94,12 +106,25 @@ let new_raw_wasm = module.emit_wasm();
 
 pub mod ast;
 pub mod binary;
+#[cfg(feature = "walrus")]
+pub mod builder;
+#[cfg(feature = "walrus")]
+pub mod eq;
+#[cfg(feature = "walrus")]
+pub mod host;
+#[cfg(feature = "serde")]
+pub mod json;
+#[cfg(feature = "walrus")]
+pub mod merge;
+#[cfg(feature = "walrus")]
+pub mod rebind;
 #[cfg(feature = "text")]
 pub mod text;
+#[cfg(feature = "walrus")]
+pub mod typescript;
+#[cfg(feature = "walrus")]
+pub mod visit;
+pub mod wasm;
 
 #[cfg(feature = "quickcheck")]
 mod quickcheck;
-
-fn version() -> &'static str {
-    env!("CARGO_PKG_VERSION")
-}
@@ -0,0 +1,450 @@
+//! Merging Web IDL bindings sections, for example when linking multiple Wasm
+//! modules together.
+
+use crate::ast::*;
+use crate::visit::{walk, Visitor, VisitorMut};
+use id_arena::Id;
+use std::collections::HashMap;
+
+/// Maps from the Wasm functions and types of a module being merged in, to the
+/// corresponding functions and types of the merged module.
+#[derive(Clone, Debug, Default)]
+pub struct IdRemap {
+    pub funcs: HashMap<walrus::FunctionId, walrus::FunctionId>,
+    pub types: HashMap<walrus::TypeId, walrus::TypeId>,
+}
+
+/// The result of merging one Web IDL bindings section into another.
+#[derive(Clone, Debug, Default)]
+pub struct MergeReport {
+    /// Where each of the other section's types ended up in the merged
+    /// section. Structurally identical types are merged into one.
+    pub types: HashMap<Id<WebidlCompoundType>, Id<WebidlCompoundType>>,
+    /// Where each of the other section's function bindings ended up in the
+    /// merged section.
+    pub bindings: HashMap<Id<FunctionBinding>, Id<FunctionBinding>>,
+    /// Names of the other section's types that already named a different type
+    /// in the merged section. Those types are merged in without a name.
+    pub type_name_conflicts: Vec<String>,
+    /// Names of the other section's function bindings that already named a
+    /// function binding in the merged section. Those bindings are merged in
+    /// without a name.
+    pub binding_name_conflicts: Vec<String>,
+}
+
+impl WebidlBindings {
+    /// Merge all of the types, function bindings and binds from `other` into
+    /// this section.
+    ///
+    /// Wasm functions and types referenced by `other` are mapped to this
+    /// section's module through `id_remap`, and it is an error if any of them
+    /// are missing from it. It is also an error if `other` references a Web
+    /// IDL type or function binding that isn't in it. In either case, this
+    /// section is left unmodified.
+    pub fn merge(
+        &mut self,
+        other: &WebidlBindings,
+        id_remap: &IdRemap,
+    ) -> anyhow::Result<MergeReport> {
+        check_references(other, id_remap)?;
+
+        let mut remap = Remap {
+            id_remap,
+            types: HashMap::new(),
+            bindings: HashMap::new(),
+            missing: false,
+        };
+
+        // Map every type to a structurally identical existing type, if its
+        // references are already mapped and there is one, or else to a new
+        // copy. Copies with forward references are fixed up below.
+        let mut existing: HashMap<WebidlCompoundType, Id<WebidlCompoundType>> =
+            HashMap::with_capacity(self.types.arena.len());
+        for (id, ty) in self.types.arena.iter() {
+            existing.entry(ty.clone()).or_insert(id);
+        }
+        let mut unfixed_types = vec![];
+        for (id, ty) in other.types.arena.iter() {
+            let mut candidate = ty.clone();
+            remap.missing = false;
+            remap.visit_webidl_compound_type(id, &mut candidate);
+            let new_id = if remap.missing {
+                let new_id = self.types.insert(ty.clone());
+                unfixed_types.push(new_id);
+                new_id
+            } else {
+                match existing.get(&candidate) {
+                    Some(id) => *id,
+                    None => {
+                        let new_id = self.types.insert(candidate.clone());
+                        existing.insert(candidate, new_id);
+                        new_id
+                    }
+                }
+            };
+            remap.types.insert(id, new_id);
+        }
+
+        for (id, b) in other.bindings.arena.iter() {
+            let new_id = match b.clone() {
+                FunctionBinding::Import(i) => self.bindings.insert(i).into(),
+                FunctionBinding::Export(e) => self.bindings.insert(e).into(),
+            };
+            remap.bindings.insert(id, new_id);
+        }
+
+        for id in unfixed_types {
+            let ty = self.types.arena.get_mut(id).unwrap();
+            remap.visit_webidl_compound_type(id, ty);
+        }
+        let new_bindings: Vec<_> = remap.bindings.values().copied().collect();
+        for id in new_bindings {
+            let b = self.bindings.arena.get_mut(id).unwrap();
+            remap.visit_function_binding(id, b);
+        }
+        for (id, b) in other.binds.iter() {
+            let mut b = b.clone();
+            remap.visit_bind(id, &mut b);
+            self.binds.insert(b);
+        }
+
+        let mut report = MergeReport::default();
+
+        let mut type_names: Vec<_> = other.types.names.iter().collect();
+        type_names.sort();
+        for (name, id) in type_names {
+            let new_id = remap.types[id];
+            match self.types.names.get(name) {
+                Some(existing) if *existing != new_id => {
+                    report.type_name_conflicts.push(name.clone());
+                }
+                Some(_) => {}
+                None => {
+                    self.types.names.insert(name.clone(), new_id);
+                }
+            }
+        }
+
+        let mut binding_names: Vec<_> = other.bindings.names.iter().collect();
+        binding_names.sort();
+        for (name, id) in binding_names {
+            if self.bindings.names.contains_key(name) {
+                report.binding_name_conflicts.push(name.clone());
+            } else {
+                self.bindings.names.insert(name.clone(), remap.bindings[id]);
+            }
+        }
+
+        report.types = remap.types;
+        report.bindings = remap.bindings;
+        Ok(report)
+    }
+}
+
+/// Check that every Wasm function and type referenced by `wb` is in the remap,
+/// and that every Web IDL type and function binding it references is in it.
+fn check_references(wb: &WebidlBindings, id_remap: &IdRemap) -> anyhow::Result<()> {
+    struct Check<'a> {
+        wb: &'a WebidlBindings,
+        id_remap: &'a IdRemap,
+        result: anyhow::Result<()>,
+    }
+
+    impl Visitor<'_> for Check<'_> {
+        fn visit_function_id(&mut self, id: &walrus::FunctionId) {
+            if self.result.is_ok() && !self.id_remap.funcs.contains_key(id) {
+                self.result = Err(anyhow::anyhow!("no remapping for Wasm function {:?}", id));
+            }
+        }
+
+        fn visit_type_id(&mut self, id: &walrus::TypeId) {
+            if self.result.is_ok() && !self.id_remap.types.contains_key(id) {
+                self.result = Err(anyhow::anyhow!("no remapping for Wasm type {:?}", id));
+            }
+        }
+
+        fn visit_webidl_compound_type_id(&mut self, id: &Id<WebidlCompoundType>) {
+            if self.result.is_ok() && self.wb.types.arena.get(*id).is_none() {
+                self.result = Err(anyhow::anyhow!(
+                    "reference to missing Web IDL type {:?}",
+                    id
+                ));
+            }
+        }
+
+        fn visit_function_binding_id(&mut self, id: &Id<FunctionBinding>) {
+            if self.result.is_ok() && self.wb.bindings.arena.get(*id).is_none() {
+                self.result = Err(anyhow::anyhow!(
+                    "reference to missing function binding {:?}",
+                    id
+                ));
+            }
+        }
+    }
+
+    let mut check = Check {
+        wb,
+        id_remap,
+        result: Ok(()),
+    };
+    walk::webidl_bindings(&mut check, wb);
+    for id in wb.types.names.values() {
+        check.visit_webidl_compound_type_id(id);
+    }
+    for id in wb.bindings.names.values() {
+        check.visit_function_binding_id(id);
+    }
+    check.result
+}
+
+/// Rewrite references from the other section into references into the merged
+/// section.
+struct Remap<'a> {
+    id_remap: &'a IdRemap,
+    types: HashMap<Id<WebidlCompoundType>, Id<WebidlCompoundType>>,
+    bindings: HashMap<Id<FunctionBinding>, Id<FunctionBinding>>,
+    /// Whether a Web IDL type reference has been visited that isn't mapped
+    /// yet.
+    missing: bool,
+}
+
+impl VisitorMut for Remap<'_> {
+    fn visit_function_id(&mut self, id: &mut walrus::FunctionId) {
+        *id = self.id_remap.funcs[id];
+    }
+
+    fn visit_type_id(&mut self, id: &mut walrus::TypeId) {
+        *id = self.id_remap.types[id];
+    }
+
+    fn visit_webidl_compound_type_id(&mut self, id: &mut Id<WebidlCompoundType>) {
+        match self.types.get(id) {
+            Some(new_id) => *id = *new_id,
+            None => self.missing = true,
+        }
+    }
+
+    fn visit_function_binding_id(&mut self, id: &mut Id<FunctionBinding>) {
+        if let Some(new_id) = self.bindings.get(id) {
+            *id = *new_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{incoming as inc, outgoing as out};
+    use walrus::ValType::I32;
+    use WebidlScalarType::{DomString, Long};
+
+    /// A section with a named `(func (param DOMString))` type, a named import
+    /// binding, and a bind of a function to it.
+    fn section(
+        m: &mut walrus::Module,
+        type_name: &str,
+        binding_name: &str,
+        extra_param: bool,
+    ) -> (WebidlBindings, walrus::FunctionId) {
+        let wasm_ty = m.types.add(&[I32, I32], &[]);
+        let func = walrus::FunctionBuilder::new(&mut m.types, &[I32, I32], &[])
+            .finish(vec![], &mut m.funcs);
+
+        let mut wb = WebidlBindings::default();
+        let mut params = vec![DomString.into()];
+        if extra_param {
+            params.push(Long.into());
+        }
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params,
+            result: None,
+        });
+        wb.types.names.insert(type_name.into(), webidl_ty.into());
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![out::utf8_str(DomString, 0, 1)],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        wb.bindings
+            .names
+            .insert(binding_name.into(), binding.into());
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+        (wb, func)
+    }
+
+    fn identity_remap(m: &walrus::Module) -> IdRemap {
+        IdRemap {
+            funcs: m.funcs.iter().map(|f| (f.id(), f.id())).collect(),
+            types: m.types.iter().map(|t| (t.id(), t.id())).collect(),
+        }
+    }
+
+    #[test]
+    fn merge_dedups_identical_types() {
+        let mut m = walrus::Module::default();
+        let (mut a, _) = section(&mut m, "t", "a", false);
+        let (b, b_func) = section(&mut m, "t", "b", false);
+
+        let report = a.merge(&b, &identity_remap(&m)).unwrap();
+        assert!(report.type_name_conflicts.is_empty());
+        assert!(report.binding_name_conflicts.is_empty());
+        assert_eq!(a.types.arena.len(), 1);
+        assert_eq!(a.bindings.arena.len(), 2);
+        assert_eq!(a.binds.arena.len(), 2);
+
+        let t = a.types.by_name("t").unwrap();
+        let binding = a.bindings.by_name("b").unwrap();
+        assert_eq!(report.types.values().collect::<Vec<_>>(), vec![&t]);
+        match &a.bindings.arena[binding] {
+            FunctionBinding::Import(i) => assert_eq!(i.webidl_ty, t.into()),
+            _ => panic!("expected an import binding"),
+        }
+        assert!(a
+            .binds
+            .iter()
+            .any(|(_, bind)| bind.func == b_func && bind.binding == binding));
+    }
+
+    #[test]
+    fn merge_reports_name_conflicts() {
+        let mut m = walrus::Module::default();
+        let (mut a, _) = section(&mut m, "t", "f", false);
+        let (b, _) = section(&mut m, "t", "f", true);
+
+        let a_t = a.types.by_name("t").unwrap();
+        let a_f = a.bindings.by_name("f").unwrap();
+        let report = a.merge(&b, &identity_remap(&m)).unwrap();
+        assert_eq!(report.type_name_conflicts, vec!["t".to_string()]);
+        assert_eq!(report.binding_name_conflicts, vec!["f".to_string()]);
+        assert_eq!(a.types.arena.len(), 2);
+        assert_eq!(a.types.by_name("t"), Some(a_t));
+        assert_eq!(a.bindings.by_name("f"), Some(a_f));
+    }
+
+    #[test]
+    fn merge_remaps_wasm_ids() {
+        let mut m = walrus::Module::default();
+        let (mut a, _) = section(&mut m, "t", "a", false);
+        let other_ty = m.types.add(&[I32, I32, I32], &[]);
+
+        let mut other = walrus::Module::default();
+        let (b, b_func) = section(&mut other, "t", "b", false);
+
+        // Missing remappings are an error, and leave the section alone.
+        assert!(a.merge(&b, &IdRemap::default()).is_err());
+        assert_eq!(a.bindings.arena.len(), 1);
+
+        let new_func =
+            walrus::FunctionBuilder::new(&mut m.types, &[I32], &[]).finish(vec![], &mut m.funcs);
+        let remap = IdRemap {
+            funcs: vec![(b_func, new_func)].into_iter().collect(),
+            types: other.types.iter().map(|t| (t.id(), other_ty)).collect(),
+        };
+        let report = a.merge(&b, &remap).unwrap();
+
+        let binding = report.bindings.values().next().copied().unwrap();
+        match &a.bindings.arena[binding] {
+            FunctionBinding::Import(i) => assert_eq!(i.wasm_ty, other_ty),
+            _ => panic!("expected an import binding"),
+        }
+        assert!(a
+            .binds
+            .iter()
+            .any(|(_, bind)| bind.func == new_func && bind.binding == binding));
+    }
+
+    #[test]
+    fn merge_remaps_binding_references() {
+        let mut m = walrus::Module::default();
+        let wasm_ty = m.types.add(&[I32], &[]);
+        let mut a = WebidlBindings::default();
+
+        let mut b = WebidlBindings::default();
+        let callback = b.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![],
+            result: None,
+        });
+        let takes_callback = b.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlTypeRef::from(callback).into()],
+            result: None,
+        });
+        let callback_binding = b.bindings.insert(ExportBinding {
+            wasm_ty,
+            webidl_ty: callback.into(),
+            params: IncomingBindingMap { bindings: vec![] },
+            result: OutgoingBindingMap { bindings: vec![] },
+        });
+        b.bindings.insert(ImportBinding {
+            wasm_ty,
+            webidl_ty: takes_callback.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![out::bind_export(callback, callback_binding, 0)],
+            },
+            result: IncomingBindingMap {
+                bindings: vec![inc::bind_import(wasm_ty, callback_binding, inc::get(0))],
+            },
+        });
+
+        let report = a.merge(&b, &identity_remap(&m)).unwrap();
+        let new_callback = report.types[&callback.into()];
+        let new_takes_callback = report.types[&takes_callback.into()];
+        let new_callback_binding = report.bindings[&callback_binding.into()];
+
+        match &a.types.arena[new_takes_callback] {
+            WebidlCompoundType::Function(f) => {
+                assert_eq!(f.params[0].ty, new_callback.into())
+            }
+            _ => panic!("expected a function type"),
+        }
+        let (import_binding, _) = a
+            .bindings
+            .arena
+            .iter()
+            .find(|(_, b)| matches!(b, FunctionBinding::Import(_)))
+            .unwrap();
+        let refs = crate::visit::references(&a, import_binding);
+        assert_eq!(refs.bindings, vec![import_binding, new_callback_binding]);
+    }
+
+    #[test]
+    fn merge_rejects_dangling_references() {
+        let mut m = walrus::Module::default();
+        let (mut a, _) = section(&mut m, "t", "a", false);
+
+        // A type and a function binding that belong to some other section.
+        let (elsewhere, _) = section(&mut m, "u", "e", false);
+        let foreign_ty = elsewhere.types.by_name("u").unwrap();
+        let foreign_binding = elsewhere.bindings.by_name("e").unwrap();
+
+        let (mut b, _) = section(&mut m, "t", "b", false);
+        b.types.insert(WebidlUnion {
+            members: vec![foreign_ty.into()],
+        });
+        assert!(a.merge(&b, &identity_remap(&m)).is_err());
+
+        let (mut b, b_func) = section(&mut m, "t", "b", false);
+        b.binds.insert(Bind {
+            func: b_func,
+            binding: foreign_binding,
+        });
+        assert!(a.merge(&b, &identity_remap(&m)).is_err());
+
+        let (mut b, _) = section(&mut m, "t", "b", false);
+        b.types.names.insert("v".into(), foreign_ty);
+        assert!(a.merge(&b, &identity_remap(&m)).is_err());
+
+        // The section is left alone.
+        assert_eq!(a.types.arena.len(), 1);
+        assert_eq!(a.bindings.arena.len(), 1);
+        assert_eq!(a.binds.arena.len(), 1);
+    }
+}
@@ -1,13 +1,16 @@
 use crate::ast::*;
+#[cfg(test)]
+use id_arena::Id;
 use quickcheck::{Arbitrary, Gen};
 use rand::{seq::IteratorRandom, Rng};
 
 impl Arbitrary for WebidlBindings {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        // Function bindings and binds refer to a Wasm module's types and
+        // functions, so they are only generated along with a module, by
+        // `ModuleAndBindings`.
         let mut wb = WebidlBindings::default();
         wb.arbitrary_types(g);
-        wb.arbitrary_function_bindings(g);
-        wb.arbitrary_binds(g);
         wb
     }
 }
@@ -43,13 +46,43 @@ impl WebidlBindings {
             1 => WebidlFunctionKind::Method(WebidlFunctionKindMethod {
                 ty: WebidlScalarType::Any.into(),
             }),
-            2 => WebidlFunctionKind::Constructor,
+            2 => {
+                let new_target = if g.gen() {
+                    Some(self.arbitrary_webidl_type_ref(g))
+                } else {
+                    None
+                };
+                WebidlFunctionKindConstructor { new_target }.into()
+            }
             _ => unreachable!(),
         };
 
-        let params: Vec<_> = (0..g.size())
-            .map(|_| self.arbitrary_webidl_type_ref(g))
+        // Required parameters, then optional parameters, then at most one
+        // variadic parameter.
+        let num_required = g.gen_range(0, g.size() + 1);
+        let num_optional = g.gen_range(0, g.size() + 1);
+        let mut params: Vec<WebidlFunctionParam> = (0..num_required)
+            .map(|_| self.arbitrary_webidl_type_ref(g).into())
             .collect();
+        for _ in 0..num_optional {
+            let ty = self.arbitrary_webidl_type_ref(g);
+            let default = if g.gen() {
+                Some(arbitrary_webidl_default_value(g))
+            } else {
+                None
+            };
+            params.push(WebidlFunctionParam {
+                ty,
+                kind: WebidlFunctionParamOptional { default }.into(),
+            });
+        }
+        if g.gen() {
+            let ty = self.arbitrary_webidl_type_ref(g);
+            params.push(WebidlFunctionParam {
+                ty,
+                kind: WebidlFunctionParamKind::Variadic,
+            });
+        }
 
         let result = if g.gen() {
             Some(self.arbitrary_webidl_type_ref(g))
@@ -66,40 +99,7 @@ impl WebidlBindings {
 
     fn arbitrary_webidl_type_ref(&mut self, g: &mut impl Gen) -> WebidlTypeRef {
         if self.types.arena.len() == 0 || g.gen() {
-            // Scalar type.
-            match g.gen_range(0, 30) {
-                0 => WebidlScalarType::Any.into(),
-                1 => WebidlScalarType::Boolean.into(),
-                2 => WebidlScalarType::Byte.into(),
-                3 => WebidlScalarType::Octet.into(),
-                4 => WebidlScalarType::Long.into(),
-                5 => WebidlScalarType::UnsignedLong.into(),
-                6 => WebidlScalarType::Short.into(),
-                7 => WebidlScalarType::UnsignedShort.into(),
-                8 => WebidlScalarType::LongLong.into(),
-                9 => WebidlScalarType::UnsignedLongLong.into(),
-                10 => WebidlScalarType::Float.into(),
-                11 => WebidlScalarType::UnrestrictedFloat.into(),
-                12 => WebidlScalarType::Double.into(),
-                13 => WebidlScalarType::UnrestrictedDouble.into(),
-                14 => WebidlScalarType::DomString.into(),
-                15 => WebidlScalarType::ByteString.into(),
-                16 => WebidlScalarType::UsvString.into(),
-                17 => WebidlScalarType::Object.into(),
-                18 => WebidlScalarType::Symbol.into(),
-                19 => WebidlScalarType::ArrayBuffer.into(),
-                20 => WebidlScalarType::DataView.into(),
-                21 => WebidlScalarType::Int8Array.into(),
-                22 => WebidlScalarType::Int16Array.into(),
-                23 => WebidlScalarType::Int32Array.into(),
-                24 => WebidlScalarType::Uint8Array.into(),
-                25 => WebidlScalarType::Uint16Array.into(),
-                26 => WebidlScalarType::Uint32Array.into(),
-                27 => WebidlScalarType::Uint8ClampedArray.into(),
-                28 => WebidlScalarType::Float32Array.into(),
-                29 => WebidlScalarType::Float64Array.into(),
-                _ => unreachable!(),
-            }
+            arbitrary_webidl_scalar_type_ref(g)
         } else {
             // Reference to an existing compound type.
             self.types
@@ -112,7 +112,7 @@ impl WebidlBindings {
         }
     }
 
-    fn arbitrary_webidl_dictionary(&mut self, g: &mut impl Gen) {
+    fn arbitrary_webidl_dictionary(&mut self, g: &mut impl Gen) -> WebidlDictionaryId {
         let fields: Vec<_> = (0..g.size())
             .map(|_| {
                 let name = String::arbitrary(g);
@@ -121,12 +121,12 @@ impl WebidlBindings {
             })
             .collect();
 
-        self.types.insert(WebidlDictionary { fields });
+        self.types.insert(WebidlDictionary { fields })
     }
 
-    fn arbitrary_webidl_enumeration(&mut self, g: &mut impl Gen) {
+    fn arbitrary_webidl_enumeration(&mut self, g: &mut impl Gen) -> WebidlEnumerationId {
         let values: Vec<_> = (0..g.size()).map(|_| String::arbitrary(g)).collect();
-        self.types.insert(WebidlEnumeration { values });
+        self.types.insert(WebidlEnumeration { values })
     }
 
     fn arbitrary_webidl_union(&mut self, g: &mut impl Gen) {
@@ -135,38 +135,1032 @@ impl WebidlBindings {
             .collect();
         self.types.insert(WebidlUnion { members });
     }
+}
 
-    fn arbitrary_function_bindings(&mut self, _g: &mut impl Gen) {
-        // TODO: we don't actually generate any of these because we need to get
-        // `walrus::TypeId`s which means we need access to the `walrus::Module`.
+/// A Wasm function type, which may have multiple results.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub(crate) struct WasmFuncType {
+    pub(crate) params: Vec<walrus::ValType>,
+    pub(crate) results: Vec<walrus::ValType>,
+}
+
+#[cfg(test)]
+impl Arbitrary for WasmFuncType {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let valtypes = |g: &mut G| -> Vec<walrus::ValType> {
+            let n = g.gen_range(0, g.size() + 1);
+            (0..n).map(|_| arbitrary_wasm_valtype(g)).collect()
+        };
+        let params = valtypes(g);
+        let results = valtypes(g);
+        WasmFuncType { params, results }
     }
+}
 
-    fn arbitrary_binds(&mut self, _g: &mut impl Gen) {
-        // TODO: same story! We don't actually generate any bind statements here
-        // because we need access to a wasm module in order to get ahold of ids
-        // pointing to its functions.
+#[cfg(test)]
+fn arbitrary_wasm_valtype(g: &mut impl Gen) -> walrus::ValType {
+    match g.gen_range(0, 5) {
+        0 => walrus::ValType::I32,
+        1 => walrus::ValType::I64,
+        2 => walrus::ValType::F32,
+        3 => walrus::ValType::F64,
+        4 => walrus::ValType::Anyref,
+        _ => unreachable!(),
+    }
+}
+
+/// An arbitrary Wasm value type that `as` can convert to or from `ty`.
+#[cfg(test)]
+fn arbitrary_wasm_valtype_for(g: &mut impl Gen, ty: WebidlTypeRef) -> walrus::ValType {
+    [
+        walrus::ValType::I32,
+        walrus::ValType::I64,
+        walrus::ValType::F32,
+        walrus::ValType::F64,
+        walrus::ValType::Anyref,
+    ]
+    .iter()
+    .cloned()
+    .filter(|wasm_ty| ty.is_compatible_with(*wasm_ty))
+    .choose(g)
+    .unwrap()
+}
+
+/// A Wasm module, and a Web IDL bindings section for it with well-typed
+/// function bindings and binds that refer to the module's types and
+/// functions.
+///
+/// A `walrus::Module` can't be cloned, so this records how to build the
+/// module instead, along with the ids that the section was generated for.
+/// `build` creates a fresh module and remaps the section onto it.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub(crate) struct ModuleAndBindings {
+    types: Vec<(WasmFuncType, walrus::TypeId)>,
+    funcs: Vec<(WasmFunc, walrus::FunctionId)>,
+    section: WebidlBindings,
+}
+
+/// A function in a `ModuleAndBindings`' module, whose type is an index into
+/// its `types`.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+enum WasmFunc {
+    Import { ty: usize },
+    Local { ty: usize },
+}
+
+#[cfg(test)]
+impl ModuleAndBindings {
+    /// Build a new module and a copy of the section that refers to it.
+    pub(crate) fn build(&self) -> (walrus::Module, WebidlBindings) {
+        let mut module = walrus::Module::default();
+        let mut remap = crate::merge::IdRemap::default();
+        for (ty, id) in &self.types {
+            remap
+                .types
+                .insert(*id, module.types.add(&ty.params, &ty.results));
+        }
+        for (func, id) in &self.funcs {
+            remap
+                .funcs
+                .insert(*id, add_func(&mut module, &self.types, func));
+        }
+
+        struct Remap(crate::merge::IdRemap);
+
+        impl crate::visit::VisitorMut for Remap {
+            fn visit_function_id(&mut self, id: &mut walrus::FunctionId) {
+                *id = self.0.funcs[id];
+            }
+
+            fn visit_type_id(&mut self, id: &mut walrus::TypeId) {
+                *id = self.0.types[id];
+            }
+        }
+
+        let mut section = self.section.clone();
+        crate::visit::VisitorMut::visit_webidl_bindings(&mut Remap(remap), &mut section);
+        (module, section)
+    }
+
+    fn add_type(
+        &mut self,
+        module: &mut walrus::Module,
+        params: Vec<walrus::ValType>,
+        results: Vec<walrus::ValType>,
+    ) -> walrus::TypeId {
+        let id = module.types.add(&params, &results);
+        self.types.push((WasmFuncType { params, results }, id));
+        id
+    }
+
+    fn add_func(&mut self, module: &mut walrus::Module, func: WasmFunc) -> walrus::FunctionId {
+        let id = add_func(module, &self.types, &func);
+        self.funcs.push((func, id));
+        id
+    }
+
+    /// Generate a binding that is bound to nothing yet.
+    fn arbitrary_function_binding(
+        &mut self,
+        g: &mut impl Gen,
+        module: &mut walrus::Module,
+    ) -> Id<FunctionBinding> {
+        if g.gen() {
+            // The Wasm params are produced by the outgoing expressions for the
+            // Web IDL params, and the Wasm results by the incoming expressions
+            // for the Web IDL result. `write-*` expressions add more Wasm
+            // params for the caller's buffer.
+            let mut wasm_params = vec![];
+            let (kind, params, outgoing) = self.arbitrary_outgoing_params(g, &mut wasm_params);
+            let mut wasm_results = vec![];
+            let (result, incoming) =
+                self.arbitrary_incoming_result(g, &mut wasm_results, &mut wasm_params);
+            let webidl_ty = self.section.types.insert(WebidlFunction {
+                kind,
+                params,
+                result,
+            });
+            let wasm_ty = self.add_type(module, wasm_params, wasm_results);
+            self.section
+                .bindings
+                .insert(ImportBinding {
+                    wasm_ty,
+                    webidl_ty: webidl_ty.into(),
+                    params: OutgoingBindingMap { bindings: outgoing },
+                    result: IncomingBindingMap { bindings: incoming },
+                })
+                .into()
+        } else {
+            // The other way around: the Wasm params come from the incoming
+            // expressions for the Web IDL params, and the Wasm results are
+            // used by the outgoing expression for the Web IDL result.
+            let mut wasm_params = vec![];
+            let (kind, params, incoming) = self.arbitrary_incoming_params(g, &mut wasm_params);
+            let mut wasm_results = vec![];
+            let (result, outgoing) = if g.gen() {
+                let (expr, ty) = self.arbitrary_outgoing(g, &mut wasm_results, 2);
+                (Some(ty), vec![expr])
+            } else {
+                (None, vec![])
+            };
+            let webidl_ty = self.section.types.insert(WebidlFunction {
+                kind,
+                params,
+                result,
+            });
+            let wasm_ty = self.add_type(module, wasm_params, wasm_results);
+            self.section
+                .bindings
+                .insert(ExportBinding {
+                    wasm_ty,
+                    webidl_ty: webidl_ty.into(),
+                    params: IncomingBindingMap { bindings: incoming },
+                    result: OutgoingBindingMap { bindings: outgoing },
+                })
+                .into()
+        }
+    }
+
+    /// Bind a binding to zero or more new functions of the right kind and
+    /// type: imported functions for import bindings, and local functions for
+    /// export bindings.
+    fn arbitrary_binds(
+        &mut self,
+        g: &mut impl Gen,
+        module: &mut walrus::Module,
+        binding: Id<FunctionBinding>,
+    ) {
+        let (wasm_ty, is_import) = match &self.section.bindings.arena[binding] {
+            FunctionBinding::Import(b) => (b.wasm_ty, true),
+            FunctionBinding::Export(b) => (b.wasm_ty, false),
+        };
+        let ty = self
+            .types
+            .iter()
+            .position(|(_, id)| *id == wasm_ty)
+            .unwrap();
+        for _ in 0..g.gen_range(0, 3) {
+            let func = if is_import {
+                WasmFunc::Import { ty }
+            } else {
+                WasmFunc::Local { ty }
+            };
+            let func = self.add_func(module, func);
+            self.section.binds.insert(Bind { func, binding });
+        }
+    }
+
+    /// Generate the outgoing expressions for a Web IDL function's receiver and
+    /// params, which use the Wasm values in `wasm`.
+    fn arbitrary_outgoing_params(
+        &mut self,
+        g: &mut impl Gen,
+        wasm: &mut Vec<walrus::ValType>,
+    ) -> (
+        WebidlFunctionKind,
+        Vec<WebidlFunctionParam>,
+        Vec<OutgoingBindingExpression>,
+    ) {
+        let mut exprs = vec![];
+        let kind = match g.gen_range(0, 3) {
+            0 => WebidlFunctionKind::Static,
+            1 => {
+                let (expr, ty) = self.arbitrary_outgoing(g, wasm, 2);
+                exprs.push(expr);
+                WebidlFunctionKindMethod { ty }.into()
+            }
+            2 => {
+                let new_target = if g.gen() {
+                    Some(self.section.arbitrary_webidl_type_ref(g))
+                } else {
+                    None
+                };
+                WebidlFunctionKindConstructor { new_target }.into()
+            }
+            _ => unreachable!(),
+        };
+
+        // Required parameters, then optional parameters that may be
+        // `undefined`, then at most one variadic parameter.
+        let mut params = vec![];
+        for _ in 0..arbitrary_len(g) {
+            let (expr, ty) = self.arbitrary_outgoing(g, wasm, 2);
+            exprs.push(expr);
+            params.push(ty.into());
+        }
+        for _ in 0..arbitrary_len(g) {
+            let ty = if g.gen() {
+                exprs.push(OutgoingBindingExpressionUndefined.into());
+                self.section.arbitrary_webidl_type_ref(g)
+            } else {
+                let (expr, ty) = self.arbitrary_outgoing(g, wasm, 2);
+                exprs.push(expr);
+                ty
+            };
+            let default = if g.gen() {
+                Some(arbitrary_webidl_default_value(g))
+            } else {
+                None
+            };
+            params.push(WebidlFunctionParam {
+                ty,
+                kind: WebidlFunctionParamOptional { default }.into(),
+            });
+        }
+        if g.gen() {
+            let ty = arbitrary_numeric_scalar_type(g);
+            let ty = arbitrary_annotation(g, ty);
+            // Numeric types are never `anyref`s, so the elements fit in memory.
+            let elem_ty = arbitrary_wasm_valtype_for(g, ty);
+            exprs.push(
+                OutgoingBindingExpressionVariadic {
+                    ty,
+                    elem_ty,
+                    offset: push(wasm, walrus::ValType::I32),
+                    length: push(wasm, walrus::ValType::I32),
+                }
+                .into(),
+            );
+            params.push(WebidlFunctionParam {
+                ty,
+                kind: WebidlFunctionParamKind::Variadic,
+            });
+        }
+        (kind, params, exprs)
+    }
+
+    /// Generate an outgoing expression that uses Wasm values in `wasm`, and
+    /// the Web IDL type of the value it produces.
+    fn arbitrary_outgoing(
+        &mut self,
+        g: &mut impl Gen,
+        wasm: &mut Vec<walrus::ValType>,
+        depth: usize,
+    ) -> (OutgoingBindingExpression, WebidlTypeRef) {
+        let i32 = walrus::ValType::I32;
+        match g.gen_range(0, 10) {
+            1 => {
+                let ty = arbitrary_string_type_ref(g);
+                let offset = push(wasm, i32);
+                let length = push(wasm, i32);
+                let e = OutgoingBindingExpressionUtf8Str { ty, offset, length };
+                (e.into(), ty)
+            }
+            2 => {
+                let ty = arbitrary_string_type_ref(g);
+                let offset = push(wasm, i32);
+                (OutgoingBindingExpressionUtf8CStr { ty, offset }.into(), ty)
+            }
+            3 => {
+                let ty = self.section.arbitrary_webidl_enumeration(g).into();
+                let idx = push(wasm, i32);
+                (OutgoingBindingExpressionI32ToEnum { ty, idx }.into(), ty)
+            }
+            4 | 5 => {
+                let ty = arbitrary_buffer_type_ref(g);
+                let offset = push(wasm, i32);
+                let length = push(wasm, i32);
+                let unit = if g.gen() {
+                    BufferLengthUnit::Elements
+                } else {
+                    BufferLengthUnit::Bytes
+                };
+                let e = if g.gen() {
+                    OutgoingBindingExpressionView {
+                        ty,
+                        offset,
+                        length,
+                        unit,
+                    }
+                    .into()
+                } else {
+                    OutgoingBindingExpressionCopy {
+                        ty,
+                        offset,
+                        length,
+                        unit,
+                    }
+                    .into()
+                };
+                (e, ty)
+            }
+            6 if depth > 0 => {
+                let mut fields = vec![];
+                let mut exprs = vec![];
+                for _ in 0..arbitrary_len(g) {
+                    let (expr, ty) = self.arbitrary_outgoing(g, wasm, depth - 1);
+                    exprs.push(expr);
+                    fields.push(WebidlDictionaryField {
+                        name: String::arbitrary(g),
+                        ty,
+                    });
+                }
+                let ty = self
+                    .section
+                    .types
+                    .insert(WebidlDictionary { fields })
+                    .into();
+                let e = OutgoingBindingExpressionDict { ty, fields: exprs };
+                (e.into(), ty)
+            }
+            7 if self.has_binding(false) => {
+                let (binding, ty) = self.arbitrary_binding(g, false);
+                let idx = push(wasm, i32);
+                let e = OutgoingBindingExpressionBindExport { ty, binding, idx };
+                (e.into(), ty)
+            }
+            8 => {
+                let ty = arbitrary_string_type_ref(g);
+                let offset = push(wasm, i32);
+                let length = push(wasm, i32);
+                let e = OutgoingBindingExpressionUtf16Str { ty, offset, length };
+                (e.into(), ty)
+            }
+            9 => {
+                let ty = arbitrary_string_type_ref(g);
+                let offset = push(wasm, i32);
+                let length = push(wasm, i32);
+                let e = OutgoingBindingExpressionLatin1Str { ty, offset, length };
+                (e.into(), ty)
+            }
+            _ => {
+                let ty = arbitrary_webidl_scalar_type_ref(g);
+                let idx = push(wasm, arbitrary_wasm_valtype_for(g, ty));
+                (OutgoingBindingExpressionAs { ty, idx }.into(), ty)
+            }
+        }
+    }
+
+    /// Generate the incoming expressions for a Web IDL function's receiver and
+    /// params, which produce the Wasm values in `wasm`.
+    fn arbitrary_incoming_params(
+        &mut self,
+        g: &mut impl Gen,
+        wasm: &mut Vec<walrus::ValType>,
+    ) -> (
+        WebidlFunctionKind,
+        Vec<WebidlFunctionParam>,
+        Vec<IncomingBindingExpression>,
+    ) {
+        let mut exprs = vec![];
+        let mut get = |g: &mut _, this: &mut Self, wasm: &mut _| {
+            let idx = exprs.len() as u32;
+            let (expr, ty) = this.arbitrary_incoming_from(
+                g,
+                IncomingBindingExpressionGet { idx }.into(),
+                wasm,
+                None,
+            );
+            exprs.push(expr);
+            ty
+        };
+        let kind = if g.gen() {
+            WebidlFunctionKind::Static
+        } else {
+            let ty = get(g, self, wasm);
+            WebidlFunctionKindMethod { ty }.into()
+        };
+        let params = (0..arbitrary_len(g))
+            .map(|_| get(g, self, wasm).into())
+            .collect();
+        (kind, params, exprs)
+    }
+
+    /// Generate the incoming expressions that produce the Wasm values in
+    /// `wasm` from a Web IDL function's result, and the result's type.
+    fn arbitrary_incoming_result(
+        &mut self,
+        g: &mut impl Gen,
+        wasm: &mut Vec<walrus::ValType>,
+        caller: &mut Vec<walrus::ValType>,
+    ) -> (Option<WebidlTypeRef>, Vec<IncomingBindingExpression>) {
+        let get = || IncomingBindingExpression::from(IncomingBindingExpressionGet { idx: 0 });
+        match g.gen_range(0, 3) {
+            0 => (None, vec![]),
+            1 => {
+                let (expr, ty) = self.arbitrary_incoming_from(g, get(), wasm, Some(caller));
+                (Some(ty), vec![expr])
+            }
+            _ => {
+                // Produce each Wasm value from a field of a dictionary.
+                let mut exprs = vec![];
+                let mut fields = vec![];
+                for idx in 0..arbitrary_len(g) as u32 {
+                    let field = IncomingBindingExpressionField {
+                        idx,
+                        expr: Box::new(get()),
+                    };
+                    let (expr, ty) =
+                        self.arbitrary_incoming_from(g, field.into(), wasm, Some(&mut *caller));
+                    exprs.push(expr);
+                    fields.push(WebidlDictionaryField {
+                        name: String::arbitrary(g),
+                        ty,
+                    });
+                }
+                let ty = self.section.types.insert(WebidlDictionary { fields });
+                (Some(ty.into()), exprs)
+            }
+        }
+    }
+
+    /// Generate an incoming expression that converts the Web IDL value that
+    /// `source` gets into Wasm values in `wasm`, and the type that value must
+    /// have.
+    ///
+    /// `write-*` expressions are only generated when there is a `caller`,
+    /// whose params they add their buffer to.
+    fn arbitrary_incoming_from(
+        &mut self,
+        g: &mut impl Gen,
+        source: IncomingBindingExpression,
+        wasm: &mut Vec<walrus::ValType>,
+        caller: Option<&mut Vec<walrus::ValType>>,
+    ) -> (IncomingBindingExpression, WebidlTypeRef) {
+        let i32 = walrus::ValType::I32;
+
+        // Sometimes get the value out of a single field dictionary.
+        if g.gen_range(0, 4) == 0 {
+            let field = IncomingBindingExpressionField {
+                idx: 0,
+                expr: Box::new(source),
+            };
+            let (expr, ty) = self.arbitrary_incoming_from(g, field.into(), wasm, caller);
+            let dict = self.section.types.insert(WebidlDictionary {
+                fields: vec![WebidlDictionaryField {
+                    name: String::arbitrary(g),
+                    ty,
+                }],
+            });
+            return (expr, dict.into());
+        }
+
+        let expr = Box::new(source);
+        match (g.gen_range(0, 10), caller) {
+            (1, _) | (2, _) | (5, _) | (6, _) => {
+                let alloc_func_name = String::arbitrary(g);
+                wasm.push(i32);
+                wasm.push(i32);
+                let (e, ty) = match g.gen_range(0, 4) {
+                    0 => (
+                        IncomingBindingExpressionAllocUtf8Str {
+                            alloc_func_name,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_string_type_ref(g),
+                    ),
+                    1 => (
+                        IncomingBindingExpressionAllocUtf16Str {
+                            alloc_func_name,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_string_type_ref(g),
+                    ),
+                    2 => (
+                        IncomingBindingExpressionAllocLatin1Str {
+                            alloc_func_name,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_string_type_ref(g),
+                    ),
+                    _ => (
+                        IncomingBindingExpressionAllocCopy {
+                            alloc_func_name,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_buffer_type_ref(g),
+                    ),
+                };
+                (e, ty)
+            }
+            (3, _) => {
+                let ty = self.section.arbitrary_webidl_enumeration(g).into();
+                wasm.push(i32);
+                (IncomingBindingExpressionEnumToI32 { ty, expr }.into(), ty)
+            }
+            (4, _) if self.has_binding(true) => {
+                let (binding, ty) = self.arbitrary_binding(g, true);
+                let wasm_ty = match &self.section.bindings.arena[binding] {
+                    FunctionBinding::Import(b) => b.wasm_ty,
+                    FunctionBinding::Export(_) => unreachable!(),
+                };
+                wasm.push(walrus::ValType::Anyref);
+                let e = IncomingBindingExpressionBindImport {
+                    ty: wasm_ty,
+                    binding,
+                    expr,
+                };
+                (e.into(), ty)
+            }
+            (7, Some(caller)) | (8, Some(caller)) | (9, Some(caller)) => {
+                let offset = push(caller, i32);
+                let capacity = push(caller, i32);
+                wasm.push(i32);
+                match g.gen_range(0, 3) {
+                    0 => (
+                        IncomingBindingExpressionWriteUtf8Str {
+                            offset,
+                            capacity,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_string_type_ref(g),
+                    ),
+                    1 => (
+                        IncomingBindingExpressionWriteUtf16Str {
+                            offset,
+                            capacity,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_string_type_ref(g),
+                    ),
+                    _ => (
+                        IncomingBindingExpressionWriteCopy {
+                            offset,
+                            capacity,
+                            expr,
+                        }
+                        .into(),
+                        arbitrary_buffer_type_ref(g),
+                    ),
+                }
+            }
+            _ => {
+                let ty = arbitrary_webidl_scalar_type_ref(g);
+                let wasm_ty = arbitrary_wasm_valtype_for(g, ty);
+                wasm.push(wasm_ty);
+                (IncomingBindingExpressionAs { ty: wasm_ty, expr }.into(), ty)
+            }
+        }
+    }
+
+    fn has_binding(&self, import: bool) -> bool {
+        self.section
+            .bindings
+            .arena
+            .iter()
+            .any(|(_, b)| matches!(b, FunctionBinding::Import(_)) == import)
+    }
+
+    /// Choose an existing import or export binding, and get its Web IDL type.
+    fn arbitrary_binding(
+        &self,
+        g: &mut impl Gen,
+        import: bool,
+    ) -> (Id<FunctionBinding>, WebidlTypeRef) {
+        self.section
+            .bindings
+            .arena
+            .iter()
+            .filter_map(|(id, b)| match b {
+                FunctionBinding::Import(b) if import => Some((id, b.webidl_ty)),
+                FunctionBinding::Export(b) if !import => Some((id, b.webidl_ty)),
+                _ => None,
+            })
+            .choose(g)
+            .unwrap()
+    }
+
+    /// Make a copy of this section without the given binds and bindings.
+    ///
+    /// Returns `None` if a binding that is kept refers to one that is removed.
+    fn without(
+        &self,
+        remove_bind: impl Fn(Id<Bind>) -> bool,
+        remove_binding: impl Fn(Id<FunctionBinding>) -> bool,
+    ) -> Option<Self> {
+        let mut section = WebidlBindings {
+            types: self.section.types.clone(),
+            bindings: Default::default(),
+            binds: Default::default(),
+        };
+        let mut ids = std::collections::HashMap::new();
+        for (id, b) in self.section.bindings.arena.iter() {
+            if !remove_binding(id) {
+                ids.insert(id, section.bindings.insert(b.clone()));
+            }
+        }
+        for (name, id) in &self.section.bindings.names {
+            if let Some(new_id) = ids.get(id) {
+                section.bindings.names.insert(name.clone(), *new_id);
+            }
+        }
+        for (id, b) in self.section.binds.iter() {
+            if !remove_bind(id) && !remove_binding(b.binding) {
+                section.binds.insert(b.clone());
+            }
+        }
+
+        struct Remap(
+            std::collections::HashMap<Id<FunctionBinding>, Id<FunctionBinding>>,
+            bool,
+        );
+
+        impl crate::visit::VisitorMut for Remap {
+            fn visit_function_binding_id(&mut self, id: &mut Id<FunctionBinding>) {
+                match self.0.get(id) {
+                    Some(new_id) => *id = *new_id,
+                    None => self.1 = false,
+                }
+            }
+        }
+
+        let mut remap = Remap(ids, true);
+        crate::visit::VisitorMut::visit_webidl_bindings(&mut remap, &mut section);
+        if !remap.1 {
+            return None;
+        }
+        Some(ModuleAndBindings {
+            types: self.types.clone(),
+            funcs: self.funcs.clone(),
+            section,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Arbitrary for ModuleAndBindings {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let mut module = walrus::Module::default();
+        let mut mb = ModuleAndBindings {
+            types: vec![],
+            funcs: vec![],
+            section: WebidlBindings::default(),
+        };
+        mb.section.arbitrary_types(g);
+        for _ in 0..arbitrary_len(g) {
+            let binding = mb.arbitrary_function_binding(g, &mut module);
+            mb.arbitrary_binds(g, &mut module, binding);
+        }
+        mb
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        // Remove each bind, then each binding along with its binds. The
+        // module and the Web IDL types are left alone.
+        let binds = self.section.binds.iter().map(|(id, _)| id);
+        let bindings = self.section.bindings.arena.iter().map(|(id, _)| id);
+        let mut shrunk: Vec<_> = binds
+            .filter_map(|bind| self.without(|id| id == bind, |_| false))
+            .collect();
+        shrunk.extend(bindings.filter_map(|binding| self.without(|_| false, |id| id == binding)));
+        Box::new(shrunk.into_iter())
+    }
+}
+
+#[cfg(test)]
+fn add_func(
+    module: &mut walrus::Module,
+    types: &[(WasmFuncType, walrus::TypeId)],
+    func: &WasmFunc,
+) -> walrus::FunctionId {
+    // Import and export every function under a unique name, so that the
+    // section can be rebound onto a re-parsed copy of the module.
+    let name = format!("f{}", module.funcs.iter().count());
+    match func {
+        WasmFunc::Import { ty } => {
+            let ty = module
+                .types
+                .add(&types[*ty].0.params, &types[*ty].0.results);
+            module.add_import_func("env", &name, ty).0
+        }
+        WasmFunc::Local { ty } => {
+            // Return zero values of the result types.
+            let ty = &types[*ty].0;
+            let args = ty.params.iter().map(|t| module.locals.add(*t)).collect();
+            let mut builder =
+                walrus::FunctionBuilder::new(&mut module.types, &ty.params, &ty.results);
+            let mut body = builder.func_body();
+            for result in &ty.results {
+                match result {
+                    walrus::ValType::I32 => body.i32_const(0),
+                    walrus::ValType::I64 => body.i64_const(0),
+                    walrus::ValType::F32 => body.f32_const(0.0),
+                    walrus::ValType::F64 => body.f64_const(0.0),
+                    walrus::ValType::Anyref => body.instr(walrus::ir::RefNull {}),
+                    walrus::ValType::V128 => body.const_(walrus::ir::Value::V128(0)),
+                };
+            }
+            let id = builder.finish(args, &mut module.funcs);
+            module.exports.add(&name, id);
+            id
+        }
+    }
+}
+
+/// Push a Wasm value type and return its index.
+#[cfg(test)]
+fn push(tys: &mut Vec<walrus::ValType>, ty: walrus::ValType) -> u32 {
+    tys.push(ty);
+    tys.len() as u32 - 1
+}
+
+/// A small number of params, fields, bindings, etc.
+#[cfg(test)]
+fn arbitrary_len(g: &mut impl Gen) -> usize {
+    g.gen_range(0, g.size().min(4) + 1)
+}
+
+#[cfg(test)]
+fn arbitrary_string_type_ref(g: &mut impl Gen) -> WebidlTypeRef {
+    let ty = *[
+        WebidlScalarType::DomString,
+        WebidlScalarType::ByteString,
+        WebidlScalarType::UsvString,
+    ]
+    .iter()
+    .choose(g)
+    .unwrap();
+    arbitrary_annotation(g, ty)
+}
+
+#[cfg(test)]
+fn arbitrary_buffer_type_ref(g: &mut impl Gen) -> WebidlTypeRef {
+    loop {
+        let ty = arbitrary_webidl_scalar_type(g);
+        if ty.buffer_element_size().is_some() {
+            return arbitrary_annotation(g, ty);
+        }
+    }
+}
+
+#[cfg(test)]
+fn arbitrary_numeric_scalar_type(g: &mut impl Gen) -> WebidlScalarType {
+    use WebidlScalarType::*;
+    *[
+        Byte,
+        Octet,
+        Short,
+        UnsignedShort,
+        Long,
+        UnsignedLong,
+        LongLong,
+        UnsignedLongLong,
+        Float,
+        UnrestrictedFloat,
+        Double,
+        UnrestrictedDouble,
+    ]
+    .iter()
+    .choose(g)
+    .unwrap()
+}
+
+/// An arbitrary scalar type, sometimes annotated with an extended attribute
+/// that applies to it.
+fn arbitrary_webidl_scalar_type_ref(g: &mut impl Gen) -> WebidlTypeRef {
+    let ty = arbitrary_webidl_scalar_type(g);
+    arbitrary_annotation(g, ty)
+}
+
+fn arbitrary_webidl_scalar_type(g: &mut impl Gen) -> WebidlScalarType {
+    match g.gen_range(0, 31) {
+        0 => WebidlScalarType::Any,
+        1 => WebidlScalarType::Boolean,
+        2 => WebidlScalarType::Byte,
+        3 => WebidlScalarType::Octet,
+        4 => WebidlScalarType::Long,
+        5 => WebidlScalarType::UnsignedLong,
+        6 => WebidlScalarType::Short,
+        7 => WebidlScalarType::UnsignedShort,
+        8 => WebidlScalarType::LongLong,
+        9 => WebidlScalarType::UnsignedLongLong,
+        10 => WebidlScalarType::Float,
+        11 => WebidlScalarType::UnrestrictedFloat,
+        12 => WebidlScalarType::Double,
+        13 => WebidlScalarType::UnrestrictedDouble,
+        14 => WebidlScalarType::DomString,
+        15 => WebidlScalarType::ByteString,
+        16 => WebidlScalarType::UsvString,
+        17 => WebidlScalarType::Object,
+        18 => WebidlScalarType::Symbol,
+        19 => WebidlScalarType::ArrayBuffer,
+        20 => WebidlScalarType::DataView,
+        21 => WebidlScalarType::Int8Array,
+        22 => WebidlScalarType::Int16Array,
+        23 => WebidlScalarType::Int32Array,
+        24 => WebidlScalarType::Uint8Array,
+        25 => WebidlScalarType::Uint16Array,
+        26 => WebidlScalarType::Uint32Array,
+        27 => WebidlScalarType::Uint8ClampedArray,
+        28 => WebidlScalarType::Float32Array,
+        29 => WebidlScalarType::Float64Array,
+        30 => WebidlScalarType::BigInt,
+        _ => unreachable!(),
+    }
+}
+
+/// Sometimes annotate `ty` with an extended attribute that applies to it.
+fn arbitrary_annotation(g: &mut impl Gen, ty: WebidlScalarType) -> WebidlTypeRef {
+    let attr = [
+        WebidlExtendedAttribute::Clamp,
+        WebidlExtendedAttribute::EnforceRange,
+        WebidlExtendedAttribute::AllowShared,
+        WebidlExtendedAttribute::LegacyNullToEmptyString,
+    ]
+    .iter()
+    .cloned()
+    .filter(|a| a.applies_to(ty))
+    .choose(g);
+    match attr {
+        Some(attr) if g.gen() => WebidlAnnotatedTypeRef::new(ty, std::iter::once(attr).collect())
+            .unwrap()
+            .into(),
+        _ => ty.into(),
+    }
+}
+
+fn arbitrary_webidl_default_value(g: &mut impl Gen) -> WebidlDefaultValue {
+    match g.gen_range(0, 4) {
+        0 => WebidlDefaultValue::Null,
+        1 => WebidlDefaultValue::Boolean(g.gen()),
+        2 => WebidlDefaultValue::Integer(g.gen()),
+        3 => WebidlDefaultValue::String(String::arbitrary(g)),
+        _ => unreachable!(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ast::WebidlBindings;
+    use super::{ModuleAndBindings, WasmFuncType};
+    use crate::ast::*;
+    use crate::merge::IdRemap;
+
+    /// Make a Web IDL dictionary with an `any` field for each Wasm value, and
+    /// a static function returning it.
+    fn dict_returning_function(wb: &mut WebidlBindings, n: usize) -> WebidlTypeRef {
+        let fields = (0..n)
+            .map(|i| WebidlDictionaryField {
+                name: format!("field{}", i),
+                ty: WebidlScalarType::Any.into(),
+            })
+            .collect();
+        let dict = wb.types.insert(WebidlDictionary { fields });
+        wb.types
+            .insert(WebidlFunction {
+                kind: WebidlFunctionKind::Static,
+                params: vec![],
+                result: Some(dict.into()),
+            })
+            .into()
+    }
 
     quickcheck::quickcheck! {
-        fn can_encode_and_decode_arbitrary_webidl_bindings(section: WebidlBindings) -> () {
+        fn multi_value_import_results_validate(ty: WasmFuncType) -> () {
             let mut module = walrus::Module::default();
-            module.customs.add(section);
+            let wasm_ty = module.types.add(&ty.params, &ty.results);
+            let mut wb = WebidlBindings::default();
+            let webidl_ty = dict_returning_function(&mut wb, ty.results.len());
+
+            // Produce each Wasm result from one field of the Web IDL result.
+            let bindings = ty
+                .results
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    IncomingBindingExpressionAs {
+                        ty: *ty,
+                        expr: Box::new(
+                            IncomingBindingExpressionField {
+                                idx: i as u32,
+                                expr: Box::new(IncomingBindingExpressionGet { idx: 0 }.into()),
+                            }
+                            .into(),
+                        ),
+                    }
+                    .into()
+                })
+                .collect();
+            let binding = ImportBinding {
+                wasm_ty,
+                webidl_ty,
+                params: OutgoingBindingMap { bindings: vec![] },
+                result: IncomingBindingMap { bindings },
+            };
+
+            binding.validate(&module, &wb).expect("should validate OK");
+            if ty.results.len() > 1 {
+                assert!(!binding.is_expressible_in_js_without_webidl_bindings(&module, &wb));
+            }
+        }
+
+        fn multi_value_export_results_validate(ty: WasmFuncType) -> () {
+            let mut module = walrus::Module::default();
+            let wasm_ty = module.types.add(&ty.params, &ty.results);
+            let mut wb = WebidlBindings::default();
+            let webidl_ty = dict_returning_function(&mut wb, ty.results.len());
+            let dict = match wb.types.get::<WebidlCompoundType>(webidl_ty.id().unwrap()) {
+                Some(WebidlCompoundType::Function(f)) => f.result.unwrap(),
+                _ => unreachable!(),
+            };
+
+            // Combine all the Wasm results into a single Web IDL dictionary.
+            let fields = (0..ty.results.len())
+                .map(|i| {
+                    OutgoingBindingExpressionAs {
+                        ty: WebidlScalarType::Any.into(),
+                        idx: i as u32,
+                    }
+                    .into()
+                })
+                .collect();
+            let binding = ExportBinding {
+                wasm_ty,
+                webidl_ty,
+                params: IncomingBindingMap { bindings: vec![] },
+                result: OutgoingBindingMap {
+                    bindings: vec![OutgoingBindingExpressionDict { ty: dict, fields }.into()],
+                },
+            };
+
+            // The export's params are unbound, so it only validates when the
+            // Wasm function has none.
+            assert_eq!(binding.validate(&module, &wb).is_ok(), ty.params.is_empty());
+        }
+
+
+        fn arbitrary_function_bindings_validate(mb: ModuleAndBindings) -> () {
+            let (module, section) = mb.build();
+            for (_, binding) in section.bindings.arena.iter() {
+                binding
+                    .validate(&module, &section)
+                    .expect("generated bindings should validate");
+            }
+        }
+
+        fn can_encode_and_decode_arbitrary_webidl_bindings(mb: ModuleAndBindings) -> () {
+            let (mut module, section) = mb.build();
+            module.customs.add(section.clone());
             let buf = module.emit_wasm();
 
             let mut config = walrus::ModuleConfig::default();
             config.on_parse(|module, ids| {
                 let raw = module.customs.remove_raw("webidl-bindings")
                     .expect("the webidl-bindings custom section should have been emitted");
-                crate::binary::decode(ids, &raw.data)
+                let decoded = crate::binary::decode(ids, &raw.data)
                     .expect("should decode webidl-bindings section OK");
+                module.customs.add(decoded);
                 Ok(())
             });
-            config.parse(&buf).expect("should parse the wasm OK");
+            let new_module = config.parse(&buf).expect("should parse the wasm OK");
+            let decoded = new_module.customs.get_typed::<WebidlBindings>().unwrap();
+
+            let id_remap = IdRemap::rebind(&section, &module, &new_module)
+                .expect("should rebind onto the parsed module");
+            assert!(
+                section.structurally_eq(decoded, &id_remap),
+                "decoded section should equal the encoded one: {:#?} != {:#?}",
+                section,
+                decoded,
+            );
         }
 
         fn doesnt_panic_on_arbitrary_bytes(data: Vec<u8>) -> () {
@@ -189,4 +1183,83 @@ mod tests {
             config.parse(&wasm_buf).unwrap();
         }
     }
+
+    /// The text of the README example.
+    #[cfg(feature = "text")]
+    const README_TEXT: &str = r#"
+        type $TextEncoderEncodeIntoResult
+            (dict
+                (field "read" unsigned long long)
+                (field "written" unsigned long long))
+
+        type $EncodeIntoFuncWebIDL
+            (func (method any)
+                (param USVString Uint8Array)
+                (result $TextEncoderEncodeIntoResult))
+
+        func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL
+            (param
+                (as any 0)
+                (as any 1)
+                (view Int8Array 2 3))
+            (result
+                (as i64 (field 0 (get 0)))
+                (as i64 (field 1 (get 0))))
+
+        bind $encodeInto $encodeIntoBinding
+    "#;
+
+    /// Write the seed corpora for the fuzz targets in `fuzz/`: the README
+    /// example for `parse`, and arbitrary bindings sections, which `decode`,
+    /// `encode_decode` and `read` share.
+    ///
+    /// Run with `cargo test --all-features generate_fuzz_corpus -- --ignored`.
+    #[cfg(feature = "text")]
+    #[test]
+    #[ignore]
+    fn generate_fuzz_corpus() {
+        use quickcheck::Arbitrary;
+        use rand::SeedableRng;
+        use std::fs;
+        use std::path::Path;
+        use walrus::ValType::{Anyref, I32, I64};
+
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        let write = |target: &str, name: &str, data: &[u8]| {
+            let dir = corpus.join(target);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(name), data).unwrap();
+        };
+        // Write the encoded bindings section of `module`.
+        let write_section = |name: &str, module: &mut walrus::Module| {
+            let wasm = module.emit_wasm();
+            let mut module = walrus::Module::from_buffer(&wasm).unwrap();
+            let section = module.customs.remove_raw("webidl-bindings").unwrap();
+            write("sections", name, &section.data);
+        };
+
+        write("parse", "readme.txt", README_TEXT.as_bytes());
+        let mut module = walrus::Module::default();
+        let ty = module.types.add(&[Anyref, Anyref, I32, I32], &[I64, I64]);
+        let (func, _) = module.add_import_func("TextEncoder", "encodeInto", ty);
+        module.funcs.get_mut(func).name = Some("$encodeInto".into());
+        let wasm = module.emit_wasm();
+        let mut config = walrus::ModuleConfig::default();
+        config.on_parse(|module, ids| {
+            let ty = ids.get_type(0)?;
+            module.types.get_mut(ty).name = Some("$EncodeIntoFuncWasm".into());
+            let wb = crate::text::parse(module, ids, README_TEXT)?;
+            module.customs.add(wb);
+            Ok(())
+        });
+        write_section("readme", &mut config.parse(&wasm).unwrap());
+
+        for i in 0..32 {
+            let rng = rand::rngs::StdRng::seed_from_u64(i);
+            let mut g = quickcheck::StdGen::new(rng, 1 + i as usize % 8);
+            let (mut module, section) = ModuleAndBindings::arbitrary(&mut g).build();
+            module.customs.add(section);
+            write_section(&format!("arbitrary-{:02}", i), &mut module);
+        }
+    }
 }
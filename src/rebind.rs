@@ -0,0 +1,334 @@
+//! Transplanting a Web IDL bindings section from one `walrus::Module` onto
+//! another, for example after the Wasm has been re-emitted and re-parsed.
+
+use crate::ast::*;
+use crate::merge::IdRemap;
+use crate::visit::{walk, Visitor, VisitorMut};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A reference from a bindings section that could not be resolved in the new
+/// module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RebindError {
+    /// The function is neither imported nor exported from the old module, so
+    /// it has no stable identity to look up in the new module.
+    AnonymousFunction(walrus::FunctionId),
+    /// The new module has no function imported with this module and name.
+    MissingImport { module: String, name: String },
+    /// The new module has no function exported with any of these names.
+    MissingExport { names: Vec<String> },
+    /// The function was found in the new module, but has a different type.
+    FunctionTypeMismatch { name: String },
+    /// The new module has no type with these params and results.
+    MissingType {
+        params: Vec<walrus::ValType>,
+        results: Vec<walrus::ValType>,
+    },
+}
+
+impl fmt::Display for RebindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn val_types(tys: &[walrus::ValType]) -> String {
+            tys.iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+
+        match self {
+            RebindError::AnonymousFunction(id) => write!(
+                f,
+                "function {:?} is neither imported nor exported, so it cannot be found in the \
+                 new module",
+                id
+            ),
+            RebindError::MissingImport { module, name } => write!(
+                f,
+                "the new module does not import a function `{}` from `{}`",
+                name, module
+            ),
+            RebindError::MissingExport { names } => write!(
+                f,
+                "the new module does not export a function named {}",
+                names
+                    .iter()
+                    .map(|n| format!("`{}`", n))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+            RebindError::FunctionTypeMismatch { name } => write!(
+                f,
+                "function `{}` has a different type in the new module",
+                name
+            ),
+            RebindError::MissingType { params, results } => write!(
+                f,
+                "the new module has no type (param {}) (result {})",
+                val_types(params),
+                val_types(results)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RebindError {}
+
+impl IdRemap {
+    /// Map every Wasm function and type referenced by `wb` from `from` to the
+    /// same function or type in `to`.
+    ///
+    /// Imported functions are found by their import module and name, other
+    /// functions by their export names, and types structurally. Every
+    /// reference that cannot be resolved is reported.
+    pub fn rebind(
+        wb: &WebidlBindings,
+        from: &walrus::Module,
+        to: &walrus::Module,
+    ) -> Result<IdRemap, Vec<RebindError>> {
+        #[derive(Default)]
+        struct Collect {
+            funcs: Vec<walrus::FunctionId>,
+            types: Vec<walrus::TypeId>,
+        }
+
+        impl Visitor<'_> for Collect {
+            fn visit_function_id(&mut self, id: &walrus::FunctionId) {
+                if !self.funcs.contains(id) {
+                    self.funcs.push(*id);
+                }
+            }
+
+            fn visit_type_id(&mut self, id: &walrus::TypeId) {
+                if !self.types.contains(id) {
+                    self.types.push(*id);
+                }
+            }
+        }
+
+        let mut refs = Collect::default();
+        walk::webidl_bindings(&mut refs, wb);
+
+        let mut remap = IdRemap::default();
+        let mut errors = vec![];
+        for id in refs.types {
+            match rebind_type(id, from, to) {
+                Ok(new_id) => {
+                    remap.types.insert(id, new_id);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        for id in refs.funcs {
+            match rebind_func(id, from, to) {
+                Ok(new_id) => {
+                    remap.funcs.insert(id, new_id);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(remap)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl WebidlBindings {
+    /// Rewrite this section's references to Wasm functions and types in
+    /// `from` into references to the same functions and types in `to`.
+    ///
+    /// See `IdRemap::rebind` for how they are found. If any cannot be found,
+    /// the section is left unmodified.
+    pub fn rebind(
+        &mut self,
+        from: &walrus::Module,
+        to: &walrus::Module,
+    ) -> Result<(), Vec<RebindError>> {
+        struct Rewrite(IdRemap);
+
+        impl VisitorMut for Rewrite {
+            fn visit_function_id(&mut self, id: &mut walrus::FunctionId) {
+                *id = self.0.funcs[id];
+            }
+
+            fn visit_type_id(&mut self, id: &mut walrus::TypeId) {
+                *id = self.0.types[id];
+            }
+        }
+
+        let remap = IdRemap::rebind(self, from, to)?;
+        Rewrite(remap).visit_webidl_bindings(self);
+        Ok(())
+    }
+}
+
+fn rebind_type(
+    id: walrus::TypeId,
+    from: &walrus::Module,
+    to: &walrus::Module,
+) -> Result<walrus::TypeId, RebindError> {
+    let (params, results) = from.types.params_results(id);
+    to.types
+        .find(params, results)
+        .ok_or_else(|| RebindError::MissingType {
+            params: params.to_vec(),
+            results: results.to_vec(),
+        })
+}
+
+fn rebind_func(
+    id: walrus::FunctionId,
+    from: &walrus::Module,
+    to: &walrus::Module,
+) -> Result<walrus::FunctionId, RebindError> {
+    let (name, new_id) = match &from.funcs.get(id).kind {
+        walrus::FunctionKind::Import(i) => {
+            let import = from.imports.get(i.import);
+            let new_id = to
+                .imports
+                .find(&import.module, &import.name)
+                .and_then(|i| match to.imports.get(i).kind {
+                    walrus::ImportKind::Function(f) => Some(f),
+                    _ => None,
+                })
+                .ok_or_else(|| RebindError::MissingImport {
+                    module: import.module.clone(),
+                    name: import.name.clone(),
+                })?;
+            (import.name.clone(), new_id)
+        }
+        _ => {
+            let names: Vec<_> = from
+                .exports
+                .iter()
+                .filter(|e| matches!(e.item, walrus::ExportItem::Function(f) if f == id))
+                .map(|e| e.name.clone())
+                .collect();
+            if names.is_empty() {
+                return Err(RebindError::AnonymousFunction(id));
+            }
+            let names_set: HashSet<_> = names.iter().collect();
+            let found = to.exports.iter().find_map(|e| match e.item {
+                walrus::ExportItem::Function(f) if names_set.contains(&e.name) => {
+                    Some((e.name.clone(), f))
+                }
+                _ => None,
+            });
+            match found {
+                Some(found) => found,
+                None => return Err(RebindError::MissingExport { names }),
+            }
+        }
+    };
+
+    let (params, results) = from.types.params_results(from.funcs.get(id).ty());
+    let (new_params, new_results) = to.types.params_results(to.funcs.get(new_id).ty());
+    if params != new_params || results != new_results {
+        return Err(RebindError::FunctionTypeMismatch { name });
+    }
+    Ok(new_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use walrus::ValType::{I32, I64};
+    use WebidlScalarType::Long;
+
+    /// A module with an imported function and an exported function, and a
+    /// bindings section binding each of them.
+    fn module_and_section() -> (walrus::Module, WebidlBindings) {
+        let mut m = walrus::Module::default();
+        let ty = m.types.add(&[I32], &[]);
+        let (imported, _) = m.add_import_func("env", "log", ty);
+        let exported =
+            walrus::FunctionBuilder::new(&mut m.types, &[I32], &[]).finish(vec![], &mut m.funcs);
+        m.exports.add("run", exported);
+
+        let mut wb = WebidlBindings::default();
+        let webidl_ty = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![Long.into()],
+            result: None,
+        });
+        let binding = wb.bindings.insert(ImportBinding {
+            wasm_ty: ty,
+            webidl_ty: webidl_ty.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![OutgoingBindingExpressionAs {
+                    ty: Long.into(),
+                    idx: 0,
+                }
+                .into()],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        for &func in &[imported, exported] {
+            wb.binds.insert(Bind {
+                func,
+                binding: binding.into(),
+            });
+        }
+        (m, wb)
+    }
+
+    #[test]
+    fn rebind_onto_equivalent_module() {
+        let (old, mut wb) = module_and_section();
+
+        // The same functions and types, but with different ids.
+        let mut new = walrus::Module::default();
+        new.types.add(&[I64], &[]);
+        let exported = walrus::FunctionBuilder::new(&mut new.types, &[I32], &[])
+            .finish(vec![], &mut new.funcs);
+        new.exports.add("run", exported);
+        let ty = new.types.add(&[I32], &[]);
+        let (imported, _) = new.add_import_func("env", "log", ty);
+
+        wb.rebind(&old, &new).unwrap();
+
+        let (_, binding) = wb.bindings.arena.iter().next().unwrap();
+        match binding {
+            FunctionBinding::Import(i) => assert_eq!(i.wasm_ty, ty),
+            _ => panic!("expected an import binding"),
+        }
+        let funcs: Vec<_> = wb.binds.iter().map(|(_, b)| b.func).collect();
+        assert_eq!(funcs, vec![imported, exported]);
+    }
+
+    #[test]
+    fn rebind_reports_every_unresolved_reference() {
+        let (old, mut wb) = module_and_section();
+
+        let mut new = walrus::Module::default();
+        let ty = new.types.add(&[I64], &[]);
+        new.add_import_func("env", "log", ty);
+
+        let before = wb.clone();
+        let errors = wb.rebind(&old, &new).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                RebindError::MissingType {
+                    params: vec![I32],
+                    results: vec![],
+                },
+                RebindError::FunctionTypeMismatch { name: "log".into() },
+                RebindError::MissingExport {
+                    names: vec!["run".into()],
+                },
+            ]
+        );
+        assert_eq!(
+            errors[2].to_string(),
+            "the new module does not export a function named `run`"
+        );
+        assert_eq!(
+            wb.binds.iter().map(|(_, b)| b.func).collect::<Vec<_>>(),
+            before.binds.iter().map(|(_, b)| b.func).collect::<Vec<_>>()
+        );
+    }
+}
@@ -13,3 +13,82 @@ pub fn parse(
     parse_with_actions(&mut actions, input)?;
     Ok(bindings)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::WebidlBindings;
+    use crate::merge::IdRemap;
+    use walrus::ValType::I32;
+
+    /// Parse `input` into a bindings section for a module that imports two
+    /// functions and exports a third, all of type `(param i32 i32) (result
+    /// i32)`, and return the module with the section added to it.
+    fn parse_into_module(input: &'static str) -> walrus::Module {
+        let mut module = walrus::Module::default();
+        let ty = module.types.add(&[I32, I32], &[I32]);
+        module.add_import_func("env", "a", ty);
+        module.add_import_func("env", "b", ty);
+        let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[I32, I32], &[I32]);
+        builder.func_body().i32_const(0);
+        let c = builder.finish(vec![], &mut module.funcs);
+        module.exports.add("c", c);
+        let wasm = module.emit_wasm();
+
+        let mut config = walrus::ModuleConfig::default();
+        config.on_parse(move |module, ids| {
+            let wb = super::parse(module, ids, input)?;
+            module.customs.add(wb);
+            Ok(())
+        });
+        config.parse(&wasm).unwrap()
+    }
+
+    #[test]
+    fn text_to_binary_round_trip() {
+        let mut module = parse_into_module(
+            r#"
+            type $Dict (dict (field "x" long) (field "y" [Clamp] octet))
+            type $Enum (enum "a" "b")
+            type (union DOMString $Dict)
+            type $Func
+                (func (method any)
+                    (param DOMString (optional long -1) (variadic double))
+                    (result $Dict))
+            type (func (constructor (new-target $Func)) (param $Enum))
+
+            func-binding $imp import 0 $Func
+                (param
+                    (as any 0)
+                    (utf16-str DOMString 0 1)
+                    (undefined)
+                    (variadic double f64 0 1))
+                (result
+                    (as i32 (field 0 (get 0))))
+            func-binding $exp export 0 4
+                (param
+                    (enum-to-i32 $Enum (get 0))
+                    (alloc-copy $malloc (get 1)))
+                (result
+                    (view Uint8Array 0 1 bytes))
+
+            bind 0 $imp
+            bind 1 $imp
+            bind 2 $exp
+            "#,
+        );
+        let parsed = module
+            .customs
+            .get_typed::<WebidlBindings>()
+            .unwrap()
+            .clone();
+        let wasm = module.emit_wasm();
+
+        let mut config = walrus::ModuleConfig::default();
+        config.on_parse(crate::binary::on_parse);
+        let new_module = config.parse(&wasm).unwrap();
+        let decoded = new_module.customs.get_typed::<WebidlBindings>().unwrap();
+
+        let id_remap = IdRemap::rebind(&parsed, &module, &new_module).unwrap();
+        assert!(parsed.structurally_eq(decoded, &id_remap));
+    }
+}
@@ -0,0 +1,521 @@
+//! Generating TypeScript declarations (`.d.ts` files) for the Wasm exports
+//! that have Web IDL export bindings.
+//!
+//! Every exported Wasm function that is bound to an `ExportBinding` gets an
+//! `export function` declaration whose signature comes from the binding's
+//! Web IDL function type. Named Web IDL types that those signatures use get
+//! their own declarations:
+//!
+//! * dictionaries become interfaces,
+//! * enumerations become unions of string literal types,
+//! * unions become union types, and
+//! * functions become function types.
+//!
+//! Anonymous compound types are written inline.
+
+use crate::ast::*;
+use anyhow::{bail, Context, Result};
+use id_arena::Id;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Generate TypeScript declarations for every export of `module` that is
+/// bound to an export binding in `wb`.
+pub fn generate(module: &walrus::Module, wb: &WebidlBindings) -> Result<String> {
+    let mut gen = Generator::new(wb);
+    let mut funcs = String::new();
+
+    for export in module.exports.iter() {
+        let func = match export.item {
+            walrus::ExportItem::Function(f) => f,
+            _ => continue,
+        };
+        for (_, bind) in wb.binds.iter().filter(|(_, b)| b.func == func) {
+            let binding = match wb.bindings.arena.get(bind.binding) {
+                Some(b) => b,
+                None => bail!(
+                    "bind refers to missing function binding {}",
+                    bind.binding.index()
+                ),
+            };
+            let binding = match binding {
+                FunctionBinding::Export(b) => b,
+                FunctionBinding::Import(_) => continue,
+            };
+            gen.export_function(&mut funcs, &export.name, binding)
+                .with_context(|| format!("cannot declare export `{}`", export.name))?;
+        }
+    }
+
+    let mut out = String::new();
+    while let Some(id) = gen.pending.pop() {
+        let decl = gen.declaration(id)?;
+        gen.declarations.insert(id.index(), decl);
+    }
+    for decl in gen.declarations.values() {
+        out.push_str(decl);
+    }
+    if !out.is_empty() && !funcs.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&funcs);
+    Ok(out)
+}
+
+struct Generator<'a> {
+    wb: &'a WebidlBindings,
+    names: HashMap<Id<WebidlCompoundType>, String>,
+    /// Named types whose declarations still need to be generated.
+    pending: Vec<Id<WebidlCompoundType>>,
+    /// Declarations of named types, in arena order. Pending types have an
+    /// empty placeholder.
+    declarations: BTreeMap<usize, String>,
+    /// Anonymous types currently being written inline, to catch cycles.
+    inline_stack: Vec<Id<WebidlCompoundType>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(wb: &'a WebidlBindings) -> Self {
+        Generator {
+            wb,
+            names: wb
+                .types
+                .names
+                .iter()
+                .map(|(name, id)| (*id, ts_name(name)))
+                .collect(),
+            pending: vec![],
+            declarations: BTreeMap::new(),
+            inline_stack: vec![],
+        }
+    }
+
+    /// Get the Web IDL type with the given id, which may be dangling.
+    fn webidl_type(&self, id: Id<WebidlCompoundType>) -> Result<&'a WebidlCompoundType> {
+        match self.wb.types.arena.get(id) {
+            Some(ty) => Ok(ty),
+            None => bail!("reference to missing Web IDL type {}", id.index()),
+        }
+    }
+
+    fn export_function(
+        &mut self,
+        out: &mut String,
+        name: &str,
+        binding: &ExportBinding,
+    ) -> Result<()> {
+        if !is_identifier(name) {
+            bail!("`{}` is not a valid TypeScript identifier", name);
+        }
+        let func = match binding.webidl_ty {
+            WebidlTypeRef::Id(id) => match self.webidl_type(id)? {
+                WebidlCompoundType::Function(f) => f,
+                _ => bail!("the export binding's Web IDL type is not a function"),
+            },
+            _ => bail!("the export binding's Web IDL type is not a function"),
+        };
+        let params = self.params(func)?;
+        let result = self.result(func)?;
+        writeln!(out, "export function {}({}): {};", name, params, result).unwrap();
+        Ok(())
+    }
+
+    fn declaration(&mut self, id: Id<WebidlCompoundType>) -> Result<String> {
+        let name = self.names[&id].clone();
+        let mut out = String::new();
+        match self.webidl_type(id)? {
+            WebidlCompoundType::Dictionary(d) => {
+                writeln!(out, "export interface {} {{", name).unwrap();
+                for field in &d.fields {
+                    let ty = self.ty(field.ty)?;
+                    writeln!(out, "  {}: {};", property_name(&field.name), ty).unwrap();
+                }
+                writeln!(out, "}}").unwrap();
+            }
+            _ => {
+                let ty = self.compound_type(id)?;
+                writeln!(out, "export type {} = {};", name, ty).unwrap();
+            }
+        }
+        Ok(out)
+    }
+
+    fn params(&mut self, func: &WebidlFunction) -> Result<String> {
+        let mut params = vec![];
+        if let WebidlFunctionKind::Method(m) = &func.kind {
+            params.push(format!("this: {}", self.ty(m.ty)?));
+        }
+        for (i, param) in func.params.iter().enumerate() {
+            params.push(match param.kind {
+                WebidlFunctionParamKind::Required => format!("arg{}: {}", i, self.ty(param.ty)?),
+                WebidlFunctionParamKind::Optional(_) => {
+                    format!("arg{}?: {}", i, self.ty(param.ty)?)
+                }
+                WebidlFunctionParamKind::Variadic => {
+                    format!("...arg{}: {}[]", i, self.atom(param.ty)?)
+                }
+            });
+        }
+        Ok(params.join(", "))
+    }
+
+    fn result(&mut self, func: &WebidlFunction) -> Result<String> {
+        match func.result {
+            Some(ty) => self.ty(ty),
+            None => Ok("void".to_string()),
+        }
+    }
+
+    /// Write a type reference that may be used anywhere a type is expected.
+    fn ty(&mut self, ty: WebidlTypeRef) -> Result<String> {
+        match ty {
+            WebidlTypeRef::Id(id) => {
+                if let Some(name) = self.names.get(&id) {
+                    if let Entry::Vacant(slot) = self.declarations.entry(id.index()) {
+                        // Reserve the slot, so that cyclic references to
+                        // this type aren't queued again.
+                        slot.insert(String::new());
+                        self.pending.push(id);
+                    }
+                    return Ok(name.clone());
+                }
+                if self.inline_stack.contains(&id) {
+                    bail!("anonymous Web IDL type {} refers to itself", id.index());
+                }
+                self.inline_stack.push(id);
+                let result = self.compound_type(id);
+                self.inline_stack.pop();
+                result
+            }
+            WebidlTypeRef::Scalar(s) => Ok(scalar(s).to_string()),
+            WebidlTypeRef::Annotated(a) => Ok(scalar(a.ty()).to_string()),
+        }
+    }
+
+    /// Write a type reference that binds tighter than `|` and `=>`, for use
+    /// as an array element type or union member.
+    fn atom(&mut self, ty: WebidlTypeRef) -> Result<String> {
+        let needs_parens = match ty {
+            WebidlTypeRef::Id(id) if !self.names.contains_key(&id) => {
+                match self.webidl_type(id)? {
+                    WebidlCompoundType::Function(_) => true,
+                    WebidlCompoundType::Dictionary(_) => false,
+                    WebidlCompoundType::Enumeration(e) => e.values.len() > 1,
+                    WebidlCompoundType::Union(u) => u.members.len() > 1,
+                }
+            }
+            _ => false,
+        };
+        let ty = self.ty(ty)?;
+        Ok(if needs_parens {
+            format!("({})", ty)
+        } else {
+            ty
+        })
+    }
+
+    /// Write the structure of a compound type, ignoring its name.
+    fn compound_type(&mut self, id: Id<WebidlCompoundType>) -> Result<String> {
+        Ok(match self.webidl_type(id)? {
+            WebidlCompoundType::Function(f) => {
+                let params = self.params(f)?;
+                let result = self.result(f)?;
+                format!("({}) => {}", params, result)
+            }
+            WebidlCompoundType::Dictionary(d) => {
+                let mut fields = vec![];
+                for field in &d.fields {
+                    fields.push(format!(
+                        "{}: {}",
+                        property_name(&field.name),
+                        self.ty(field.ty)?
+                    ));
+                }
+                if fields.is_empty() {
+                    "{}".to_string()
+                } else {
+                    format!("{{ {} }}", fields.join("; "))
+                }
+            }
+            WebidlCompoundType::Enumeration(e) => {
+                if e.values.is_empty() {
+                    "never".to_string()
+                } else {
+                    e.values
+                        .iter()
+                        .map(|v| string_literal(v))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                }
+            }
+            WebidlCompoundType::Union(u) => {
+                if u.members.is_empty() {
+                    "never".to_string()
+                } else {
+                    let mut members = vec![];
+                    for m in &u.members {
+                        members.push(self.atom(*m)?);
+                    }
+                    members.join(" | ")
+                }
+            }
+        })
+    }
+}
+
+fn scalar(ty: WebidlScalarType) -> &'static str {
+    use WebidlScalarType::*;
+    match ty {
+        Any => "any",
+        Boolean => "boolean",
+        Byte | Octet | Long | UnsignedLong | Short | UnsignedShort | LongLong
+        | UnsignedLongLong | Float | UnrestrictedFloat | Double | UnrestrictedDouble => "number",
+        BigInt => "bigint",
+        DomString | ByteString | UsvString => "string",
+        Object => "object",
+        Symbol => "symbol",
+        ArrayBuffer => "ArrayBuffer",
+        DataView => "DataView",
+        Int8Array => "Int8Array",
+        Int16Array => "Int16Array",
+        Int32Array => "Int32Array",
+        Uint8Array => "Uint8Array",
+        Uint16Array => "Uint16Array",
+        Uint32Array => "Uint32Array",
+        Uint8ClampedArray => "Uint8ClampedArray",
+        Float32Array => "Float32Array",
+        Float64Array => "Float64Array",
+    }
+}
+
+/// Names in the text format conventionally start with `$`, which we drop.
+fn ts_name(name: &str) -> String {
+    let trimmed = name.trim_start_matches('$');
+    if is_identifier(trimmed) {
+        trimmed.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+const RESERVED_WORDS: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+];
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$') && !RESERVED_WORDS.contains(&s)
+}
+
+fn property_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        string_literal(name)
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use walrus::ValType::I32;
+    use WebidlScalarType::*;
+
+    fn export_binding(
+        m: &mut walrus::Module,
+        wb: &mut WebidlBindings,
+        name: &str,
+        webidl_ty: impl Into<WebidlTypeRef>,
+    ) {
+        let wasm_ty = m.types.add(&[I32], &[I32]);
+        let func =
+            walrus::FunctionBuilder::new(&mut m.types, &[I32], &[I32]).finish(vec![], &mut m.funcs);
+        m.exports.add(name, func);
+        let binding = wb.bindings.insert(ExportBinding {
+            wasm_ty,
+            webidl_ty: webidl_ty.into(),
+            params: IncomingBindingMap { bindings: vec![] },
+            result: OutgoingBindingMap { bindings: vec![] },
+        });
+        wb.binds.insert(Bind {
+            func,
+            binding: binding.into(),
+        });
+    }
+
+    #[test]
+    fn declarations() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+
+        let e = wb.types.insert(WebidlEnumeration {
+            values: vec!["red".into(), "green \"ish\"".into()],
+        });
+        wb.types.names.insert("$Color".into(), e.into());
+        let d = wb.types.insert(WebidlDictionary {
+            fields: vec![
+                WebidlDictionaryField {
+                    name: "color".into(),
+                    ty: e.into(),
+                },
+                WebidlDictionaryField {
+                    name: "pixel-data".into(),
+                    ty: Uint8ClampedArray.into(),
+                },
+            ],
+        });
+        wb.types.names.insert("$Options".into(), d.into());
+        let callback = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![Long.into()],
+            result: None,
+        });
+        let u = wb.types.insert(WebidlUnion {
+            members: vec![DomString.into(), callback.into()],
+        });
+        let f = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![
+                WebidlTypeRef::from(d).into(),
+                WebidlFunctionParam {
+                    ty: UnsignedLongLong.into(),
+                    kind: WebidlFunctionParamOptional { default: None }.into(),
+                },
+                WebidlFunctionParam {
+                    ty: u.into(),
+                    kind: WebidlFunctionParamKind::Variadic,
+                },
+            ],
+            result: Some(Float64Array.into()),
+        });
+        export_binding(&mut m, &mut wb, "draw", f);
+
+        let g = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Method(WebidlFunctionKindMethod { ty: Object.into() }),
+            params: vec![],
+            result: Some(e.into()),
+        });
+        export_binding(&mut m, &mut wb, "color", g);
+
+        // Not exported, so not declared.
+        let unexported = wb.bindings.insert(ExportBinding {
+            wasm_ty: m.types.add(&[], &[]),
+            webidl_ty: g.into(),
+            params: IncomingBindingMap { bindings: vec![] },
+            result: OutgoingBindingMap { bindings: vec![] },
+        });
+        let func =
+            walrus::FunctionBuilder::new(&mut m.types, &[], &[]).finish(vec![], &mut m.funcs);
+        wb.binds.insert(Bind {
+            func,
+            binding: unexported.into(),
+        });
+
+        assert_eq!(
+            generate(&m, &wb).unwrap(),
+            r#"export type Color = "red" | "green \"ish\"";
+export interface Options {
+  color: Color;
+  "pixel-data": Uint8ClampedArray;
+}
+
+export function draw(arg0: Options, arg1?: number, ...arg2: (string | ((arg0: number) => void))[]): Float64Array;
+export function color(this: object): Color;
+"#
+        );
+    }
+
+    #[test]
+    fn invalid_exports() {
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        export_binding(&mut m, &mut wb, "not-an-identifier", Long);
+        assert!(generate(&m, &wb).is_err());
+
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        let d = wb.types.insert(WebidlDictionary { fields: vec![] });
+        export_binding(&mut m, &mut wb, "f", d);
+        assert!(generate(&m, &wb).is_err());
+    }
+
+    #[test]
+    fn dangling_references() {
+        let mut elsewhere = WebidlBindings::default();
+        let foreign = elsewhere.types.insert(WebidlDictionary { fields: vec![] });
+
+        // A binding whose Web IDL type isn't in the section.
+        let mut m = walrus::Module::default();
+        let mut wb = WebidlBindings::default();
+        export_binding(&mut m, &mut wb, "f", foreign);
+        let err = generate(&m, &wb).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing Web IDL type 0"));
+
+        // A bind whose binding isn't in the section.
+        let mut m = walrus::Module::default();
+        export_binding(&mut m, &mut elsewhere, "f", Long);
+        let (_, bind) = elsewhere.binds.iter().next().unwrap();
+        let mut wb = WebidlBindings::default();
+        wb.binds.insert(bind.clone());
+        let err = generate(&m, &wb).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing function binding 0"));
+    }
+}
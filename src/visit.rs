@@ -0,0 +1,604 @@
+//! Visitors over the Web IDL bindings AST.
+//!
+//! `Visitor` and `VisitorMut` have a method for every kind of AST node. Each
+//! method's default implementation recursively visits the node's children by
+//! calling the function of the same name in the `walk` or `walk_mut` module,
+//! so implementations only need to override the methods for the nodes they
+//! care about, and can call back into `walk` or `walk_mut` to keep recursing.
+//!
+//! References to other things, such as Wasm functions and types, Web IDL
+//! compound types, and function bindings, are visited with leaf methods like
+//! `visit_function_id` that do nothing by default.
+
+use crate::ast::*;
+use id_arena::Id;
+use std::collections::HashSet;
+
+macro_rules! define_visitor {
+    (
+        $( #[$attr:meta] )*
+        $visitor:ident < $( $lt:lifetime )? >,
+        $walk:ident,
+        $iter:ident,
+        $( $mut:tt )?
+    ) => {
+        $( #[$attr] )*
+        pub trait $visitor<$( $lt )?>: Sized {
+            fn visit_webidl_bindings(&mut self, wb: &$($lt)? $($mut)? WebidlBindings) {
+                $walk::webidl_bindings(self, wb)
+            }
+
+            fn visit_webidl_compound_type(
+                &mut self,
+                id: Id<WebidlCompoundType>,
+                ty: &$($lt)? $($mut)? WebidlCompoundType,
+            ) {
+                $walk::webidl_compound_type(self, id, ty)
+            }
+
+            fn visit_webidl_function(&mut self, f: &$($lt)? $($mut)? WebidlFunction) {
+                $walk::webidl_function(self, f)
+            }
+
+            fn visit_webidl_function_kind(&mut self, k: &$($lt)? $($mut)? WebidlFunctionKind) {
+                $walk::webidl_function_kind(self, k)
+            }
+
+            fn visit_webidl_function_param(&mut self, p: &$($lt)? $($mut)? WebidlFunctionParam) {
+                $walk::webidl_function_param(self, p)
+            }
+
+            fn visit_webidl_dictionary(&mut self, d: &$($lt)? $($mut)? WebidlDictionary) {
+                $walk::webidl_dictionary(self, d)
+            }
+
+            fn visit_webidl_dictionary_field(
+                &mut self,
+                f: &$($lt)? $($mut)? WebidlDictionaryField,
+            ) {
+                $walk::webidl_dictionary_field(self, f)
+            }
+
+            fn visit_webidl_enumeration(&mut self, _e: &$($lt)? $($mut)? WebidlEnumeration) {}
+
+            fn visit_webidl_union(&mut self, u: &$($lt)? $($mut)? WebidlUnion) {
+                $walk::webidl_union(self, u)
+            }
+
+            fn visit_webidl_type_ref(&mut self, r: &$($lt)? $($mut)? WebidlTypeRef) {
+                $walk::webidl_type_ref(self, r)
+            }
+
+            fn visit_function_binding(
+                &mut self,
+                id: Id<FunctionBinding>,
+                b: &$($lt)? $($mut)? FunctionBinding,
+            ) {
+                $walk::function_binding(self, id, b)
+            }
+
+            fn visit_import_binding(&mut self, b: &$($lt)? $($mut)? ImportBinding) {
+                $walk::import_binding(self, b)
+            }
+
+            fn visit_export_binding(&mut self, b: &$($lt)? $($mut)? ExportBinding) {
+                $walk::export_binding(self, b)
+            }
+
+            fn visit_outgoing_binding_map(&mut self, m: &$($lt)? $($mut)? OutgoingBindingMap) {
+                $walk::outgoing_binding_map(self, m)
+            }
+
+            fn visit_incoming_binding_map(&mut self, m: &$($lt)? $($mut)? IncomingBindingMap) {
+                $walk::incoming_binding_map(self, m)
+            }
+
+            fn visit_outgoing_binding_expression(
+                &mut self,
+                e: &$($lt)? $($mut)? OutgoingBindingExpression,
+            ) {
+                $walk::outgoing_binding_expression(self, e)
+            }
+
+            fn visit_incoming_binding_expression(
+                &mut self,
+                e: &$($lt)? $($mut)? IncomingBindingExpression,
+            ) {
+                $walk::incoming_binding_expression(self, e)
+            }
+
+            fn visit_bind(&mut self, id: Id<Bind>, b: &$($lt)? $($mut)? Bind) {
+                $walk::bind(self, id, b)
+            }
+
+            fn visit_function_id(&mut self, _id: &$($lt)? $($mut)? walrus::FunctionId) {}
+
+            fn visit_type_id(&mut self, _id: &$($lt)? $($mut)? walrus::TypeId) {}
+
+            fn visit_webidl_compound_type_id(
+                &mut self,
+                _id: &$($lt)? $($mut)? Id<WebidlCompoundType>,
+            ) {
+            }
+
+            fn visit_function_binding_id(
+                &mut self,
+                _id: &$($lt)? $($mut)? Id<FunctionBinding>,
+            ) {
+            }
+        }
+
+        /// The default implementations of the visitor methods, which visit
+        /// each of a node's children in order.
+        pub mod $walk {
+            use super::$visitor;
+            use crate::ast::*;
+            use id_arena::Id;
+
+            pub fn webidl_bindings<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                wb: &$($lt)? $($mut)? WebidlBindings,
+            ) {
+                for (id, ty) in wb.types.arena.$iter() {
+                    v.visit_webidl_compound_type(id, ty);
+                }
+                for (id, b) in wb.bindings.arena.$iter() {
+                    v.visit_function_binding(id, b);
+                }
+                for (id, b) in wb.binds.arena.$iter() {
+                    v.visit_bind(id, b);
+                }
+            }
+
+            pub fn webidl_compound_type<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                _id: Id<WebidlCompoundType>,
+                ty: &$($lt)? $($mut)? WebidlCompoundType,
+            ) {
+                match ty {
+                    WebidlCompoundType::Function(f) => v.visit_webidl_function(f),
+                    WebidlCompoundType::Dictionary(d) => v.visit_webidl_dictionary(d),
+                    WebidlCompoundType::Enumeration(e) => v.visit_webidl_enumeration(e),
+                    WebidlCompoundType::Union(u) => v.visit_webidl_union(u),
+                }
+            }
+
+            pub fn webidl_function<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                f: &$($lt)? $($mut)? WebidlFunction,
+            ) {
+                v.visit_webidl_function_kind(&$($mut)? f.kind);
+                for p in f.params.$iter() {
+                    v.visit_webidl_function_param(p);
+                }
+                if let Some(r) = &$($mut)? f.result {
+                    v.visit_webidl_type_ref(r);
+                }
+            }
+
+            pub fn webidl_function_kind<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                k: &$($lt)? $($mut)? WebidlFunctionKind,
+            ) {
+                match k {
+                    WebidlFunctionKind::Static => {}
+                    WebidlFunctionKind::Method(m) => v.visit_webidl_type_ref(&$($mut)? m.ty),
+                    WebidlFunctionKind::Constructor(c) => {
+                        if let Some(r) = &$($mut)? c.new_target {
+                            v.visit_webidl_type_ref(r);
+                        }
+                    }
+                }
+            }
+
+            pub fn webidl_function_param<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                p: &$($lt)? $($mut)? WebidlFunctionParam,
+            ) {
+                v.visit_webidl_type_ref(&$($mut)? p.ty);
+            }
+
+            pub fn webidl_dictionary<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                d: &$($lt)? $($mut)? WebidlDictionary,
+            ) {
+                for f in d.fields.$iter() {
+                    v.visit_webidl_dictionary_field(f);
+                }
+            }
+
+            pub fn webidl_dictionary_field<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                f: &$($lt)? $($mut)? WebidlDictionaryField,
+            ) {
+                v.visit_webidl_type_ref(&$($mut)? f.ty);
+            }
+
+            pub fn webidl_union<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                u: &$($lt)? $($mut)? WebidlUnion,
+            ) {
+                for r in u.members.$iter() {
+                    v.visit_webidl_type_ref(r);
+                }
+            }
+
+            pub fn webidl_type_ref<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                r: &$($lt)? $($mut)? WebidlTypeRef,
+            ) {
+                if let WebidlTypeRef::Id(id) = r {
+                    v.visit_webidl_compound_type_id(id);
+                }
+            }
+
+            pub fn function_binding<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                _id: Id<FunctionBinding>,
+                b: &$($lt)? $($mut)? FunctionBinding,
+            ) {
+                match b {
+                    FunctionBinding::Import(i) => v.visit_import_binding(i),
+                    FunctionBinding::Export(e) => v.visit_export_binding(e),
+                }
+            }
+
+            pub fn import_binding<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                b: &$($lt)? $($mut)? ImportBinding,
+            ) {
+                v.visit_type_id(&$($mut)? b.wasm_ty);
+                v.visit_webidl_type_ref(&$($mut)? b.webidl_ty);
+                v.visit_outgoing_binding_map(&$($mut)? b.params);
+                v.visit_incoming_binding_map(&$($mut)? b.result);
+            }
+
+            pub fn export_binding<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                b: &$($lt)? $($mut)? ExportBinding,
+            ) {
+                v.visit_type_id(&$($mut)? b.wasm_ty);
+                v.visit_webidl_type_ref(&$($mut)? b.webidl_ty);
+                v.visit_incoming_binding_map(&$($mut)? b.params);
+                v.visit_outgoing_binding_map(&$($mut)? b.result);
+            }
+
+            pub fn outgoing_binding_map<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                m: &$($lt)? $($mut)? OutgoingBindingMap,
+            ) {
+                for e in m.bindings.$iter() {
+                    v.visit_outgoing_binding_expression(e);
+                }
+            }
+
+            pub fn incoming_binding_map<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                m: &$($lt)? $($mut)? IncomingBindingMap,
+            ) {
+                for e in m.bindings.$iter() {
+                    v.visit_incoming_binding_expression(e);
+                }
+            }
+
+            pub fn outgoing_binding_expression<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                e: &$($lt)? $($mut)? OutgoingBindingExpression,
+            ) {
+                match e {
+                    OutgoingBindingExpression::As(e) => v.visit_webidl_type_ref(&$($mut)? e.ty),
+                    OutgoingBindingExpression::Utf8Str(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                    OutgoingBindingExpression::Utf8CStr(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                    OutgoingBindingExpression::I32ToEnum(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                    OutgoingBindingExpression::View(e) => v.visit_webidl_type_ref(&$($mut)? e.ty),
+                    OutgoingBindingExpression::Copy(e) => v.visit_webidl_type_ref(&$($mut)? e.ty),
+                    OutgoingBindingExpression::Dict(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty);
+                        for f in e.fields.$iter() {
+                            v.visit_outgoing_binding_expression(f);
+                        }
+                    }
+                    OutgoingBindingExpression::BindExport(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty);
+                        v.visit_function_binding_id(&$($mut)? e.binding);
+                    }
+                    OutgoingBindingExpression::Undefined(_) => {}
+                    OutgoingBindingExpression::Variadic(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                    OutgoingBindingExpression::Utf16Str(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                    OutgoingBindingExpression::Latin1Str(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty)
+                    }
+                }
+            }
+
+            pub fn incoming_binding_expression<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                e: &$($lt)? $($mut)? IncomingBindingExpression,
+            ) {
+                match e {
+                    IncomingBindingExpression::Get(_) => {}
+                    IncomingBindingExpression::As(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::AllocUtf8Str(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::AllocCopy(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::EnumToI32(e) => {
+                        v.visit_webidl_type_ref(&$($mut)? e.ty);
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr);
+                    }
+                    IncomingBindingExpression::Field(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::BindImport(e) => {
+                        v.visit_type_id(&$($mut)? e.ty);
+                        v.visit_function_binding_id(&$($mut)? e.binding);
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr);
+                    }
+                    IncomingBindingExpression::AllocUtf16Str(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::AllocLatin1Str(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::WriteUtf8Str(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::WriteUtf16Str(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                    IncomingBindingExpression::WriteCopy(e) => {
+                        v.visit_incoming_binding_expression(&$($mut)? e.expr)
+                    }
+                }
+            }
+
+            pub fn bind<$($lt,)? V: $visitor<$($lt)?>>(
+                v: &mut V,
+                _id: Id<Bind>,
+                b: &$($lt)? $($mut)? Bind,
+            ) {
+                v.visit_function_id(&$($mut)? b.func);
+                v.visit_function_binding_id(&$($mut)? b.binding);
+            }
+        }
+    };
+}
+
+define_visitor! {
+    /// A visitor over a shared reference to the AST.
+    Visitor<'a>,
+    walk,
+    iter,
+}
+
+define_visitor! {
+    /// A visitor over a mutable reference to the AST, for rewriting it in
+    /// place.
+    VisitorMut<>,
+    walk_mut,
+    iter_mut,
+    mut
+}
+
+/// The Wasm functions and types, and other function bindings, that a function
+/// binding depends on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct References {
+    /// Wasm functions bound to the binding, or to any of `bindings`, by a
+    /// `Bind` in the section.
+    pub funcs: Vec<walrus::FunctionId>,
+    /// Wasm function types used by the binding or by any of `bindings`.
+    pub types: Vec<walrus::TypeId>,
+    /// The binding itself, and every binding transitively referenced from it
+    /// by `bind-export` and `bind-import` expressions.
+    pub bindings: Vec<Id<FunctionBinding>>,
+}
+
+/// Enumerate all of the references reachable from the given function binding,
+/// in the order they are first found.
+pub fn references(wb: &WebidlBindings, binding: Id<FunctionBinding>) -> References {
+    #[derive(Default)]
+    struct Collect {
+        refs: References,
+        seen_types: HashSet<walrus::TypeId>,
+        seen_bindings: HashSet<Id<FunctionBinding>>,
+    }
+
+    impl Visitor<'_> for Collect {
+        fn visit_type_id(&mut self, id: &walrus::TypeId) {
+            if self.seen_types.insert(*id) {
+                self.refs.types.push(*id);
+            }
+        }
+
+        fn visit_function_binding_id(&mut self, id: &Id<FunctionBinding>) {
+            if self.seen_bindings.insert(*id) {
+                self.refs.bindings.push(*id);
+            }
+        }
+    }
+
+    let mut cx = Collect::default();
+    cx.visit_function_binding_id(&binding);
+    let mut i = 0;
+    while let Some(&id) = cx.refs.bindings.get(i) {
+        if let Some(b) = wb.bindings.arena.get(id) {
+            cx.visit_function_binding(id, b);
+        }
+        i += 1;
+    }
+
+    for (_, b) in wb.binds.iter() {
+        if cx.seen_bindings.contains(&b.binding) && !cx.refs.funcs.contains(&b.func) {
+            cx.refs.funcs.push(b.func);
+        }
+    }
+    cx.refs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{incoming as inc, outgoing as out};
+    use walrus::ValType::{I32, I64};
+    use WebidlScalarType::Long;
+
+    fn callback_section() -> (walrus::Module, WebidlBindings, Vec<walrus::FunctionId>) {
+        let mut m = walrus::Module::default();
+        let callback_ty = m.types.add(&[I32], &[]);
+        let export_ty = m.types.add(&[I32], &[I32]);
+        let import_ty = m.types.add(&[I32, I32], &[]);
+        let funcs: Vec<_> = (0..2)
+            .map(|_| {
+                walrus::FunctionBuilder::new(&mut m.types, &[I32], &[]).finish(vec![], &mut m.funcs)
+            })
+            .collect();
+
+        let mut wb = WebidlBindings::default();
+        let callback = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![Long.into()],
+            result: None,
+        });
+        let takes_callback = wb.types.insert(WebidlFunction {
+            kind: WebidlFunctionKind::Static,
+            params: vec![WebidlTypeRef::from(callback).into()],
+            result: None,
+        });
+
+        let callback_binding = wb.bindings.insert(ExportBinding {
+            wasm_ty: callback_ty,
+            webidl_ty: callback.into(),
+            params: IncomingBindingMap {
+                bindings: vec![inc::as_(I32, inc::get(0))],
+            },
+            result: OutgoingBindingMap { bindings: vec![] },
+        });
+        let import_binding = wb.bindings.insert(ImportBinding {
+            wasm_ty: import_ty,
+            webidl_ty: takes_callback.into(),
+            params: OutgoingBindingMap {
+                bindings: vec![out::bind_export(callback, callback_binding, 0)],
+            },
+            result: IncomingBindingMap { bindings: vec![] },
+        });
+        let unrelated = wb.bindings.insert(ExportBinding {
+            wasm_ty: export_ty,
+            webidl_ty: callback.into(),
+            params: IncomingBindingMap {
+                bindings: vec![inc::as_(I32, inc::get(0))],
+            },
+            result: OutgoingBindingMap { bindings: vec![] },
+        });
+
+        wb.binds.insert(Bind {
+            func: funcs[0],
+            binding: import_binding.into(),
+        });
+        wb.binds.insert(Bind {
+            func: funcs[1],
+            binding: unrelated.into(),
+        });
+
+        (m, wb, funcs)
+    }
+
+    #[test]
+    fn visit_every_expression() {
+        #[derive(Default)]
+        struct Count {
+            outgoing: usize,
+            incoming: usize,
+            webidl_type_ids: usize,
+        }
+
+        impl Visitor<'_> for Count {
+            fn visit_outgoing_binding_expression(&mut self, e: &OutgoingBindingExpression) {
+                self.outgoing += 1;
+                walk::outgoing_binding_expression(self, e);
+            }
+
+            fn visit_incoming_binding_expression(&mut self, e: &IncomingBindingExpression) {
+                self.incoming += 1;
+                walk::incoming_binding_expression(self, e);
+            }
+
+            fn visit_webidl_compound_type_id(&mut self, _id: &Id<WebidlCompoundType>) {
+                self.webidl_type_ids += 1;
+            }
+        }
+
+        let (_, wb, _) = callback_section();
+        let mut count = Count::default();
+        count.visit_webidl_bindings(&wb);
+        assert_eq!(count.outgoing, 1);
+        // Both `as` expressions and their nested `get`s.
+        assert_eq!(count.incoming, 4);
+        // The callback param of `takes_callback`, the three bindings' Web IDL
+        // types and the `bind-export` expression's type.
+        assert_eq!(count.webidl_type_ids, 5);
+    }
+
+    #[test]
+    fn visit_mut_rewrites_in_place() {
+        struct Swap(walrus::TypeId, walrus::TypeId);
+
+        impl VisitorMut for Swap {
+            fn visit_type_id(&mut self, id: &mut walrus::TypeId) {
+                if *id == self.0 {
+                    *id = self.1;
+                }
+            }
+        }
+
+        let (mut m, mut wb, _) = callback_section();
+        let new_ty = m.types.add(&[I64], &[]);
+        let old_ty = wb.bindings.arena.iter().next().unwrap().1;
+        let old_ty = match old_ty {
+            FunctionBinding::Export(e) => e.wasm_ty,
+            FunctionBinding::Import(i) => i.wasm_ty,
+        };
+        Swap(old_ty, new_ty).visit_webidl_bindings(&mut wb);
+
+        let (id, _) = wb.bindings.arena.iter().next().unwrap();
+        assert_eq!(references(&wb, id).types, vec![new_ty]);
+    }
+
+    #[test]
+    fn references_are_transitive() {
+        let (_, wb, funcs) = callback_section();
+        let ids: Vec<_> = wb.bindings.arena.iter().map(|(id, _)| id).collect();
+        let types = |id| match &wb.bindings.arena[id] {
+            FunctionBinding::Export(e) => e.wasm_ty,
+            FunctionBinding::Import(i) => i.wasm_ty,
+        };
+
+        let refs = references(&wb, ids[1]);
+        assert_eq!(refs.bindings, vec![ids[1], ids[0]]);
+        assert_eq!(refs.types, vec![types(ids[1]), types(ids[0])]);
+        assert_eq!(refs.funcs, vec![funcs[0]]);
+
+        let refs = references(&wb, ids[0]);
+        assert_eq!(refs.bindings, vec![ids[0]]);
+        assert_eq!(refs.types, vec![types(ids[0])]);
+        assert!(refs.funcs.is_empty());
+
+        let refs = references(&wb, ids[2]);
+        assert_eq!(refs.bindings, vec![ids[2]]);
+        assert_eq!(refs.funcs, vec![funcs[1]]);
+    }
+}
@@ -0,0 +1,149 @@
+//! References to the Wasm functions, function types, and value types that a
+//! Web IDL bindings section refers to.
+//!
+//! The AST is generic over how these references are represented, so that it
+//! can be used with whichever library is used to work with the rest of the
+//! Wasm module. `Walrus` represents them with `walrus` IDs, and is the
+//! default when the `walrus` feature is enabled, as it is by default.
+//! `RawIndices` represents them with their indices in the module, and needs
+//! nothing else to resolve them.
+//!
+//! Encoding and decoding a section maps between these references and indices
+//! with `ToIndices` and `FromIndices` respectively.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// How the references to a Wasm module in a Web IDL bindings section are
+/// represented.
+pub trait WasmRefs: Copy + Debug + Default + Eq + Hash {
+    /// A reference to a function in the Wasm module.
+    type FuncRef: Copy + Debug + Eq + Hash;
+    /// A reference to a function type in the Wasm module.
+    type FuncTypeRef: Copy + Debug + Eq + Hash;
+    /// A Wasm value type.
+    type ValType: Copy + Debug + Eq + Hash + From<ValType> + Into<ValType>;
+}
+
+/// Wasm references that are `walrus` IDs.
+#[cfg(feature = "walrus")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Walrus;
+
+#[cfg(feature = "walrus")]
+impl WasmRefs for Walrus {
+    type FuncRef = walrus::FunctionId;
+    type FuncTypeRef = walrus::TypeId;
+    type ValType = walrus::ValType;
+}
+
+/// Wasm references that are the functions' and function types' indices in the
+/// module.
+///
+/// These are encoded as they are, and any index is accepted when decoding, so
+/// it is up to the user to check them against the module.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RawIndices;
+
+impl WasmRefs for RawIndices {
+    type FuncRef = u32;
+    type FuncTypeRef = u32;
+    type ValType = ValType;
+}
+
+/// A Wasm value type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    Anyref,
+}
+
+#[cfg(feature = "walrus")]
+impl From<walrus::ValType> for ValType {
+    fn from(ty: walrus::ValType) -> ValType {
+        match ty {
+            walrus::ValType::I32 => ValType::I32,
+            walrus::ValType::I64 => ValType::I64,
+            walrus::ValType::F32 => ValType::F32,
+            walrus::ValType::F64 => ValType::F64,
+            walrus::ValType::V128 => ValType::V128,
+            walrus::ValType::Anyref => ValType::Anyref,
+        }
+    }
+}
+
+#[cfg(feature = "walrus")]
+impl From<ValType> for walrus::ValType {
+    fn from(ty: ValType) -> walrus::ValType {
+        match ty {
+            ValType::I32 => walrus::ValType::I32,
+            ValType::I64 => walrus::ValType::I64,
+            ValType::F32 => walrus::ValType::F32,
+            ValType::F64 => walrus::ValType::F64,
+            ValType::V128 => walrus::ValType::V128,
+            ValType::Anyref => walrus::ValType::Anyref,
+        }
+    }
+}
+
+/// Maps Wasm references to the indices they are encoded as.
+pub trait ToIndices<R: WasmRefs> {
+    /// The index of the given function in the Wasm module.
+    fn func_index(&self, func: R::FuncRef) -> u32;
+    /// The index of the given function type in the Wasm module.
+    fn func_type_index(&self, ty: R::FuncTypeRef) -> u32;
+}
+
+#[cfg(feature = "walrus")]
+impl ToIndices<Walrus> for walrus::IdsToIndices {
+    fn func_index(&self, func: walrus::FunctionId) -> u32 {
+        self.get_func_index(func)
+    }
+
+    fn func_type_index(&self, ty: walrus::TypeId) -> u32 {
+        self.get_type_index(ty)
+    }
+}
+
+impl ToIndices<RawIndices> for RawIndices {
+    fn func_index(&self, func: u32) -> u32 {
+        func
+    }
+
+    fn func_type_index(&self, ty: u32) -> u32 {
+        ty
+    }
+}
+
+/// Maps encoded indices to Wasm references.
+pub trait FromIndices<R: WasmRefs> {
+    /// The function at the given index in the Wasm module.
+    fn func(&self, index: u32) -> anyhow::Result<R::FuncRef>;
+    /// The function type at the given index in the Wasm module.
+    fn func_type(&self, index: u32) -> anyhow::Result<R::FuncTypeRef>;
+}
+
+#[cfg(feature = "walrus")]
+impl FromIndices<Walrus> for walrus::IndicesToIds {
+    fn func(&self, index: u32) -> anyhow::Result<walrus::FunctionId> {
+        self.get_func(index)
+    }
+
+    fn func_type(&self, index: u32) -> anyhow::Result<walrus::TypeId> {
+        self.get_type(index)
+    }
+}
+
+impl FromIndices<RawIndices> for RawIndices {
+    fn func(&self, index: u32) -> anyhow::Result<u32> {
+        Ok(index)
+    }
+
+    fn func_type(&self, index: u32) -> anyhow::Result<u32> {
+        Ok(index)
+    }
+}
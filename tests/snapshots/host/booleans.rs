@@ -0,0 +1,122 @@
+// Generated by wasm-webidl-bindings. Do not edit.
+
+/// An error converting between Wasm values and host values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarshalError {
+    /// A region of linear memory is out of bounds.
+    OutOfBounds,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// An integer is not the index of a value in an enumeration.
+    InvalidEnum,
+}
+
+#[allow(dead_code)]
+fn read(memory: &[u8], offset: i32, length: i32, size: usize) -> Result<&[u8], MarshalError> {
+    let length = (length as u32 as usize)
+        .checked_mul(size)
+        .ok_or(MarshalError::OutOfBounds)?;
+    memory
+        .get(offset as u32 as usize..)
+        .and_then(|m| m.get(..length))
+        .ok_or(MarshalError::OutOfBounds)
+}
+
+#[allow(dead_code)]
+fn read_cstr(memory: &[u8], offset: i32) -> Result<&[u8], MarshalError> {
+    let bytes = memory
+        .get(offset as u32 as usize..)
+        .ok_or(MarshalError::OutOfBounds)?;
+    let length = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(MarshalError::OutOfBounds)?;
+    Ok(&bytes[..length])
+}
+
+#[allow(dead_code)]
+fn utf8(bytes: &[u8]) -> Result<&str, MarshalError> {
+    std::str::from_utf8(bytes).map_err(|_| MarshalError::InvalidUtf8)
+}
+
+#[allow(dead_code)]
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[allow(dead_code)]
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+#[allow(dead_code)]
+fn write_bytes(
+    memory: &mut [u8],
+    offset: i32,
+    capacity: i32,
+    bytes: &[u8],
+    size: usize,
+) -> Result<i32, MarshalError> {
+    let n = bytes.len().min(capacity as u32 as usize) / size * size;
+    memory
+        .get_mut(offset as u32 as usize..)
+        .and_then(|m| m.get_mut(..n))
+        .ok_or(MarshalError::OutOfBounds)?
+        .copy_from_slice(&bytes[..n]);
+    Ok(n as i32)
+}
+
+#[allow(dead_code)]
+fn write_utf8(memory: &mut [u8], offset: i32, capacity: i32, s: &str) -> Result<i32, MarshalError> {
+    let mut n = s.len().min(capacity as u32 as usize);
+    while !s.is_char_boundary(n) {
+        n -= 1;
+    }
+    write_bytes(memory, offset, n as i32, s.as_bytes(), 1)
+}
+
+#[allow(dead_code)]
+fn write_utf16(memory: &mut [u8], offset: i32, capacity: i32, s: &str) -> Result<i32, MarshalError> {
+    let mut units = vec![];
+    for c in s.chars() {
+        let mut buf = [0; 2];
+        let encoded = c.encode_utf16(&mut buf);
+        if units.len() + encoded.len() > capacity as u32 as usize {
+            break;
+        }
+        units.extend_from_slice(encoded);
+    }
+    let bytes: Vec<u8> = units.iter().flat_map(|u| u.to_le_bytes().to_vec()).collect();
+    write_bytes(memory, offset, bytes.len() as i32, &bytes, 2)?;
+    Ok(units.len() as i32)
+}
+
+pub mod env {
+    use super::*;
+
+    /// The functions imported from `env`.
+    pub trait Imports {
+        fn set_flags(&mut self, arg0: bool, arg1: bool, arg2: bool, arg3: bool);
+    }
+
+    /// Convert the Wasm arguments, call `Imports::set_flags`, and convert its result.
+    pub fn set_flags(
+        host: &mut impl Imports,
+        _memory: &mut [u8],
+        wasm0: i32,
+        wasm1: i64,
+        wasm2: f32,
+        wasm3: f64,
+    ) -> Result<(), MarshalError> {
+        let arg0 = wasm0 != 0;
+        let arg1 = wasm1 != 0;
+        let arg2 = wasm2 != 0.0;
+        let arg3 = wasm3 != 0.0;
+        host.set_flags(arg0, arg1, arg2, arg3);
+        Ok(())
+    }
+}